@@ -0,0 +1,56 @@
+//! A program that increments counters it is the authority of. Its PDA at
+//! `[b"actor"]` is the counters' authority, so only this program can move
+//! them, by signing as the actor in a CPI to the counter program.
+//!
+//! Instruction data is the amount, 8 bytes little-endian. Accounts: counter
+//! program, counter (writable), actor PDA.
+//!
+//! ```text
+//! cargo build --example cpi_caller --features cpi
+//! ```
+
+use counter_program::cpi;
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    entrypoint,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+pub const ACTOR_SEED: &[u8] = b"actor";
+
+entrypoint!(process_instruction);
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let amount = instruction_data
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let accounts_iter = &mut accounts.iter();
+    let counter_program = next_account_info(accounts_iter)?;
+    let counter = next_account_info(accounts_iter)?;
+    let actor = next_account_info(accounts_iter)?;
+
+    let (expected_actor, bump) = Pubkey::find_program_address(&[ACTOR_SEED], program_id);
+    if actor.key != &expected_actor {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    cpi::increment_by(
+        counter_program,
+        counter,
+        actor,
+        amount,
+        &[&[ACTOR_SEED, &[bump]]],
+    )?;
+
+    msg!("Incremented {} by {}", counter.key, amount);
+    Ok(())
+}
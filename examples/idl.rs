@@ -0,0 +1,10 @@
+//! Prints the program's JSON IDL. The features passed to the build decide
+//! which instructions and discriminators it lists.
+//!
+//! ```text
+//! cargo run --example idl > idl/counter_program.json
+//! ```
+
+fn main() {
+    println!("{}", counter_program::idl::idl_json());
+}
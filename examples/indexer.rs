@@ -0,0 +1,44 @@
+//! An indexer front end using only the decoding half of the client: no RPC,
+//! no signing. It reads lines from stdin and prints what it decodes:
+//!
+//! - transaction log lines (`Program data: ...`) as counter events;
+//! - account dumps, `<ADDRESS> <BASE64 DATA>`, as program accounts.
+//!
+//! ```text
+//! cargo run --example indexer --features client < logs.txt
+//! ```
+
+use std::io::{self, BufRead};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use counter_program::client::{PROGRAM_DATA_PREFIX, decode_account, parse_event_log};
+
+fn main() -> io::Result<()> {
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.starts_with(PROGRAM_DATA_PREFIX) {
+            match parse_event_log(line) {
+                Some(event) => println!("event: {:?}", event),
+                None => println!("skipped: not a counter event"),
+            }
+            continue;
+        }
+
+        let Some((address, data)) = line.split_once(' ') else {
+            continue;
+        };
+        match STANDARD
+            .decode(data)
+            .ok()
+            .as_deref()
+            .and_then(decode_account)
+        {
+            Some(account) => println!("{}: {:?}", address, account),
+            None => println!("{}: not a decodable program account", address),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! A keeper bot that reaps expired counters for the reward. Every interval
+//! it loads the program's counters over RPC and sends `Reap` for each one
+//! whose TTL has run out, a few per transaction.
+//!
+//! ```text
+//! cargo run --example keeper --features cli -- <PROGRAM_ID> [INTERVAL_SECS]
+//! ```
+//!
+//! The RPC URL and reaper keypair come from the Solana CLI config.
+
+use std::error::Error;
+use std::str::FromStr;
+use std::time::Duration;
+
+use counter_program::addresses::COUNTER_DISCRIMINATOR;
+use counter_program::client::{decode_counters, reap_expired_ixs};
+use solana_cli_config::{CONFIG_FILE, Config};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer, read_keypair_file},
+    transaction::Transaction,
+};
+
+type KeeperResult<T> = Result<T, Box<dyn Error>>;
+
+/// `Reap` instructions per transaction.
+const REAPS_PER_TRANSACTION: usize = 8;
+
+async fn reap_once(rpc: &RpcClient, program_id: &Pubkey, reaper: &Keypair) -> KeeperResult<()> {
+    let accounts = rpc
+        .get_program_accounts_with_config(
+            program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    COUNTER_DISCRIMINATOR.to_vec(),
+                ))]),
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .await?;
+    let counters = decode_counters(
+        accounts
+            .iter()
+            .map(|(address, account)| (*address, &account.data[..])),
+    );
+
+    let slot = rpc.get_slot().await?;
+    let reaps = reap_expired_ixs(program_id, &reaper.pubkey(), &counters, slot);
+    println!(
+        "slot {}: {} counters, {} to reap",
+        slot,
+        counters.len(),
+        reaps.len()
+    );
+
+    for chunk in reaps.chunks(REAPS_PER_TRANSACTION) {
+        let blockhash = rpc.get_latest_blockhash().await?;
+        let transaction =
+            Transaction::new_signed_with_payer(chunk, Some(&reaper.pubkey()), &[reaper], blockhash);
+        // another keeper may have got there first; carry on with the rest
+        match rpc.send_and_confirm_transaction(&transaction).await {
+            Ok(signature) => println!("Signature: {}", signature),
+            Err(err) => eprintln!("reap failed: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> KeeperResult<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let program_id = Pubkey::from_str(
+        args.first()
+            .ok_or("usage: keeper <PROGRAM_ID> [INTERVAL_SECS]")?,
+    )?;
+    let interval = match args.get(1) {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => Duration::from_secs(60),
+    };
+
+    let config = match CONFIG_FILE.as_ref() {
+        Some(path) => Config::load(path).unwrap_or_default(),
+        None => Config::default(),
+    };
+    let rpc = RpcClient::new_with_commitment(
+        config.json_rpc_url.clone(),
+        CommitmentConfig::from_str(&config.commitment)?,
+    );
+    let reaper = read_keypair_file(&config.keypair_path)?;
+
+    loop {
+        if let Err(err) = reap_once(&rpc, &program_id, &reaper).await {
+            eprintln!("keeper pass failed: {}", err);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,596 @@
+//! Typed account lists for each instruction. Every role, owner, signer and
+//! writability requirement is checked here once, so handlers can rely on the
+//! shapes they receive. Checks that need account data (such as matching the
+//! stored authority) stay in the handlers.
+
+use solana_program::{
+    account_info::AccountInfo, instruction::AccountMeta, msg, program_error::ProgramError,
+    pubkey::Pubkey, system_program,
+};
+
+use crate::dispatch::AccountSpec;
+use crate::error::CounterError;
+
+fn expect_signer(account: &AccountInfo, role: &str) -> Result<(), ProgramError> {
+    if !account.is_signer {
+        msg!("{} account must sign", role);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+fn expect_writable(account: &AccountInfo, role: &str) -> Result<(), ProgramError> {
+    if !account.is_writable {
+        msg!("{} account must be writable", role);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Multisig authorities cannot sign; the handler checks their approvals.
+fn expect_authority(account: &AccountInfo, program_id: &Pubkey) -> Result<(), ProgramError> {
+    if account.owner == program_id {
+        return Ok(());
+    }
+    expect_signer(account, "Authority")
+}
+
+/// A pre-funded address is still uninitialized as long as the system
+/// program owns it and it holds no data.
+fn expect_uninitialized(account: &AccountInfo) -> Result<(), ProgramError> {
+    if account.owner != &system_program::ID || !account.data_is_empty() {
+        msg!("Account {} is already initialized", account.key);
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+    Ok(())
+}
+
+fn expect_owned(account: &AccountInfo, program_id: &Pubkey) -> Result<(), ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Whether `check` is among an account's declared checks, at compile time.
+const fn has_check(checks: &[&str], check: &str) -> bool {
+    let mut index = 0;
+    while index < checks.len() {
+        let (declared, wanted) = (checks[index].as_bytes(), check.as_bytes());
+        if declared.len() == wanted.len() {
+            let mut byte = 0;
+            while byte < wanted.len() && declared[byte] == wanted[byte] {
+                byte += 1;
+            }
+            if byte == wanted.len() {
+                return true;
+            }
+        }
+        index += 1;
+    }
+    false
+}
+
+macro_rules! check_account {
+    ($account:ident, $program_id:ident, signer) => {
+        expect_signer($account, stringify!($account))?
+    };
+    ($account:ident, $program_id:ident, writable) => {
+        expect_writable($account, stringify!($account))?
+    };
+    ($account:ident, $program_id:ident, owned) => {
+        expect_owned($account, $program_id)?
+    };
+    ($account:ident, $program_id:ident, uninitialized) => {
+        expect_uninitialized($account)?
+    };
+    ($account:ident, $program_id:ident, system_program) => {
+        if $account.key != &system_program::ID {
+            msg!("{} is not the system program", $account.key);
+            return Err(CounterError::NotSystemProgram.into());
+        }
+    };
+    ($account:ident, $program_id:ident, signs) => {};
+}
+
+/// Declares an instruction's fixed accounts once, in order, and derives from
+/// that list the `AccountInfo` struct with its `parse`, the `SPEC` the
+/// dispatch table validates and publishes in the IDL, and a `Keys` struct
+/// whose `to_account_metas` the client builders use. The processor and the
+/// builders therefore cannot disagree on order or flags.
+///
+/// Checks per account: `signer`, `writable`, `owned` (by this program),
+/// `uninitialized` and `system_program` (the key). `signs` marks an account
+/// the builders sign with but whose signature the handler checks, such as an
+/// actor the counter's gates inspect. A trailing `..remaining` collects the
+/// accounts after the fixed ones.
+macro_rules! instruction_accounts {
+    (
+        $(#[$doc:meta])*
+        pub struct $name:ident, $keys:ident {
+            $($field:ident: [$($check:ident),*],)+
+            $(..$remaining:ident)?
+        }
+    ) => {
+        $(#[$doc])*
+        pub struct $name<'a, 'info> {
+            $(pub $field: &'a AccountInfo<'info>,)+
+            $(pub $remaining: &'a [AccountInfo<'info>],)?
+        }
+
+        impl<'a, 'info> $name<'a, 'info> {
+            pub const SPEC: &'static [AccountSpec] = &[$(AccountSpec {
+                name: stringify!($field),
+                writable: has_check(&[$(stringify!($check)),*], "writable"),
+                signer: has_check(&[$(stringify!($check)),*], "signer"),
+            }),+];
+
+            // `program_id` goes unused when no account is owned or uninitialized.
+            #[allow(unused_variables)]
+            pub fn parse(
+                program_id: &Pubkey,
+                accounts: &'a [AccountInfo<'info>],
+            ) -> Result<Self, ProgramError> {
+                let [$($field,)+ ..] = accounts else {
+                    return Err(ProgramError::NotEnoughAccountKeys);
+                };
+
+                $($(check_account!($field, program_id, $check);)*)+
+
+                Ok(Self {
+                    $($field,)+
+                    $($remaining: &accounts[Self::SPEC.len()..],)?
+                })
+            }
+        }
+
+        /// Addresses for the fixed accounts, in the order `parse` expects.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $keys {
+            $(pub $field: Pubkey,)+
+        }
+
+        impl $keys {
+            pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+                vec![$({
+                    let checks = [$(stringify!($check)),*];
+                    let signer = has_check(&checks, "signer") || has_check(&checks, "signs");
+                    if has_check(&checks, "writable") {
+                        AccountMeta::new(self.$field, signer)
+                    } else {
+                        AccountMeta::new_readonly(self.$field, signer)
+                    }
+                }),+]
+            }
+        }
+    };
+}
+
+instruction_accounts! {
+    /// Accounts for instructions that create a new program account:
+    /// new account (signer, writable), payer (signer, writable), system program.
+    pub struct CreateAccounts, CreateKeys {
+        new_account: [signer, writable, uninitialized],
+        payer: [signer, writable],
+        system_program: [system_program],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for instructions that create a program-derived account: new
+    /// account (writable, address checked by the handler), payer (signer,
+    /// writable), system program.
+    pub struct CreatePdaAccounts, CreatePdaKeys {
+        new_account: [writable, uninitialized],
+        payer: [signer, writable],
+        system_program: [system_program],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for count mutations: counter (writable), actor checked by the
+    /// counter's gates, then gate and trigger accounts in `remaining`.
+    pub struct MutateAccounts, MutateKeys {
+        counter: [owned, writable],
+        actor: [signs],
+        ..remaining
+    }
+}
+
+/// Accounts for authority-only instructions: counter (writable), authority
+/// (signer, unless it is a program-owned multisig), then any
+/// instruction-specific accounts in `remaining`.
+pub struct AuthorityAccounts<'a, 'info> {
+    pub counter: &'a AccountInfo<'info>,
+    pub authority: &'a AccountInfo<'info>,
+    pub remaining: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> AuthorityAccounts<'a, 'info> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let [counter, authority, remaining @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        expect_owned(counter, program_id)?;
+        expect_writable(counter, "Counter")?;
+        expect_authority(authority, program_id)?;
+
+        Ok(Self {
+            counter,
+            authority,
+            remaining,
+        })
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for permissionless counter updates: counter (writable), then
+    /// any instruction-specific accounts in `remaining`.
+    pub struct CounterAccounts, CounterKeys {
+        counter: [owned, writable],
+        ..remaining
+    }
+}
+
+/// Accounts for `CloseCounter` and `SkimExcess`: counter (writable), authority
+/// (signer, unless it is a program-owned multisig), lamport destination
+/// (writable, distinct from the counter).
+pub struct CloseAccounts<'a, 'info> {
+    pub counter: &'a AccountInfo<'info>,
+    pub authority: &'a AccountInfo<'info>,
+    pub destination: &'a AccountInfo<'info>,
+    /// Optional fourth account, where a strict counter's excess goes.
+    pub excess_destination: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> CloseAccounts<'a, 'info> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let [counter, authority, destination, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        expect_owned(counter, program_id)?;
+        expect_writable(counter, "Counter")?;
+        expect_authority(authority, program_id)?;
+        expect_writable(destination, "Destination")?;
+
+        if counter.key == destination.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(Self {
+            counter,
+            authority,
+            destination,
+            excess_destination: rest.first(),
+        })
+    }
+}
+
+/// Accounts for `Reap`: counter (writable), original payer (writable, checked
+/// against the counter by the handler), reaper (signer, writable).
+pub struct ReapAccounts<'a, 'info> {
+    pub counter: &'a AccountInfo<'info>,
+    pub payer: &'a AccountInfo<'info>,
+    pub reaper: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> ReapAccounts<'a, 'info> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let [counter, payer, reaper, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        expect_owned(counter, program_id)?;
+        expect_writable(counter, "Counter")?;
+        expect_writable(payer, "Payer")?;
+        expect_signer(reaper, "Reaper")?;
+        expect_writable(reaper, "Reaper")?;
+
+        if counter.key == payer.key || counter.key == reaper.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(Self {
+            counter,
+            payer,
+            reaper,
+        })
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `MigrateAccount`: counter (writable), payer for any extra
+    /// rent (signer, writable), system program.
+    pub struct MigrateAccounts, MigrateKeys {
+        counter: [owned, writable],
+        payer: [signer, writable],
+        system_program: [system_program],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for view key management: counter, authority (signer, writable,
+    /// pays or receives the rent), view key (writable, address checked by the
+    /// handler), then the system program in `remaining` when granting.
+    pub struct ViewKeyAccounts, ViewKeyKeys {
+        counter: [owned],
+        authority: [signer, writable],
+        view_key: [writable],
+        ..remaining
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `GetCount`: counter, view key (writable, owned), viewer
+    /// PDA (signer).
+    pub struct ViewAccounts, ViewKeys {
+        counter: [owned],
+        view_key: [writable],
+        viewer: [signer],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `MintCoupon`: counter, authority (signer, writable, pays
+    /// the rent), coupon (writable, address checked by the handler), system
+    /// program.
+    pub struct MintCouponAccounts, MintCouponKeys {
+        counter: [owned],
+        authority: [signer, writable],
+        coupon: [writable, uninitialized],
+        system_program: [system_program],
+    }
+}
+
+/// Accounts for `RedeemCoupon`: counter (writable), coupon (writable, address
+/// checked by the handler), the coupon's payer (writable), then trigger
+/// accounts in `remaining`.
+pub struct RedeemCouponAccounts<'a, 'info> {
+    pub counter: &'a AccountInfo<'info>,
+    pub coupon: &'a AccountInfo<'info>,
+    pub payer: &'a AccountInfo<'info>,
+    pub remaining: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> RedeemCouponAccounts<'a, 'info> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let [counter, coupon, payer, remaining @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        expect_owned(counter, program_id)?;
+        expect_writable(counter, "Counter")?;
+        expect_writable(coupon, "Coupon")?;
+        expect_writable(payer, "Payer")?;
+
+        if counter.key == payer.key || coupon.key == payer.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(Self {
+            counter,
+            coupon,
+            payer,
+            remaining,
+        })
+    }
+}
+
+/// Accounts for `IncrementMany`: actor checked by each counter's gates, then
+/// one or more counters (writable, owned).
+pub struct IncrementManyAccounts<'a, 'info> {
+    pub actor: &'a AccountInfo<'info>,
+    pub counters: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> IncrementManyAccounts<'a, 'info> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let [actor, counters @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if counters.is_empty() {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        for counter in counters {
+            expect_owned(counter, program_id)?;
+            expect_writable(counter, "Counter")?;
+        }
+
+        Ok(Self { actor, counters })
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `SetTemplate`: template (writable, address checked by the
+    /// handler, created on first use), admin (signer, writable, pays the
+    /// rent), system program.
+    pub struct SetTemplateAccounts, SetTemplateKeys {
+        template: [writable],
+        admin: [signer, writable],
+        system_program: [system_program],
+    }
+}
+
+/// Accounts for `InitializeFromTemplate`: the `CreateAccounts` list, then
+/// the template (owned).
+pub struct InitializeFromTemplateAccounts<'a, 'info> {
+    pub create: CreateAccounts<'a, 'info>,
+    pub template: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> InitializeFromTemplateAccounts<'a, 'info> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let create = CreateAccounts::parse(program_id, accounts)?;
+        let template = accounts.get(3).ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        expect_owned(template, program_id)?;
+
+        Ok(Self { create, template })
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `Snapshot`: counter (writable), authority (signer,
+    /// writable, pays the rent), snapshot (writable, address checked by the
+    /// handler), system program.
+    pub struct SnapshotAccounts, SnapshotKeys {
+        counter: [owned, writable],
+        authority: [signer, writable],
+        snapshot: [writable, uninitialized],
+        system_program: [system_program],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for read-only counter queries: counter.
+    pub struct ReadAccounts, ReadKeys {
+        counter: [owned],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for registry settings: registry (writable, owned), admin
+    /// (signer, checked against the registry by the handler).
+    pub struct RegistryAdminAccounts, RegistryAdminKeys {
+        registry: [owned, writable],
+        admin: [signer],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `WithdrawFees`: counter (writable), authority (signer),
+    /// fee vault (writable, owned, address checked by the handler),
+    /// destination (writable).
+    pub struct WithdrawFeesAccounts, WithdrawFeesKeys {
+        counter: [owned, writable],
+        authority: [signer],
+        fee_vault: [owned, writable],
+        destination: [writable],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `AdminFreeze`: registry (owned), admin (signer, checked
+    /// against the registry by the handler), counter (writable).
+    pub struct AdminFreezeAccounts, AdminFreezeKeys {
+        registry: [owned],
+        admin: [signer],
+        counter: [owned, writable],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `RequestUnfreeze`: counter (writable), authority
+    /// (signer), registry (owned), which sets the grace period.
+    pub struct RequestUnfreezeAccounts, RequestUnfreezeKeys {
+        counter: [owned, writable],
+        authority: [signer],
+        registry: [owned],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `BurnCounter`: registry (writable, owned, counts the
+    /// close), admin (signer), counter (writable), lamport destination
+    /// (writable).
+    pub struct BurnAccounts, BurnKeys {
+        registry: [owned, writable],
+        admin: [signer],
+        counter: [owned, writable],
+        destination: [writable],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `IncrementSigned` and `DecrementSigned`: signed counter
+    /// (writable), authority (signer).
+    pub struct SignedCounterAccounts, SignedCounterKeys {
+        counter: [owned, writable],
+        authority: [signer],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `TransferCount`: source counter (writable), destination
+    /// counter (writable), source authority (signer).
+    pub struct TransferCountAccounts, TransferCountKeys {
+        source: [owned, writable],
+        destination: [owned, writable],
+        authority: [signer],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `CloseExpired`: counter (writable), original payer
+    /// (writable, checked against the counter by the handler).
+    pub struct CloseExpiredAccounts, CloseExpiredKeys {
+        counter: [owned, writable],
+        payer: [writable],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `InitializeGlobalConfig`: global config PDA (writable),
+    /// upgrade authority (signer, writable), program data account (checked
+    /// by the handler), system program.
+    pub struct InitializeGlobalConfigAccounts, InitializeGlobalConfigKeys {
+        config: [writable, uninitialized],
+        authority: [signer, writable],
+        program_data: [],
+        system_program: [system_program],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `SetGlobalConfig`: global config (writable), upgrade
+    /// authority (signer), program data account (checked by the handler).
+    pub struct SetGlobalConfigAccounts, SetGlobalConfigKeys {
+        config: [owned, writable],
+        authority: [signer],
+        program_data: [],
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `IncrementWithProof`: counter (writable), actor (signer,
+    /// writable), claim bitmap (writable), system program, then gate and
+    /// trigger accounts in `remaining`.
+    pub struct IncrementWithProofAccounts, IncrementWithProofKeys {
+        counter: [owned, writable],
+        actor: [signer, writable],
+        claims: [writable],
+        system_program: [system_program],
+        ..remaining
+    }
+}
+
+instruction_accounts! {
+    /// Accounts for `IncrementSlot` and `DecrementSlot`: slot counter
+    /// (writable), authority (signer).
+    pub struct SlotCounterAccounts, SlotCounterKeys {
+        counter: [owned, writable],
+        authority: [signer],
+    }
+}
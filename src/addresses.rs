@@ -0,0 +1,230 @@
+//! Every account address the program derives or checks, and the
+//! discriminators that tell its account kinds apart. Other implementations
+//! re-derive addresses from these constants and `derive_*` functions rather
+//! than copying the literals.
+//!
+//! | Account        | Seeds                                       | Derived under      |
+//! |----------------|---------------------------------------------|--------------------|
+//! | named counter  | `["counter", owner, name]`                  | this program       |
+//! | external id    | `["external", owner, external_id]`          | this program       |
+//! | trigger signer | `["trigger", counter]`                      | this program       |
+//! | view key       | `["view", counter, viewer_program]`         | this program       |
+//! | viewer signer  | `["viewer"]`                                | the viewer program |
+//! | coupon         | `["coupon", counter, coupon_code_hash]`     | this program       |
+//! | template       | `["template", admin, name]`                 | this program       |
+//! | registry       | `["registry"]`                              | this program       |
+//! | fee vault      | `["fee_vault", counter]`                    | this program       |
+//! | owner stats    | `["owner_stats", owner]`                    | this program       |
+//! | snapshot       | `["snapshot", counter, index as u64 LE]`    | this program       |
+//! | owner index    | `["index", owner]`                          | this program       |
+//! | claim bitmap   | `["claims", counter, chunk as u32 LE]`      | this program       |
+//! | user record    | `["user", counter, user]`                   | this program       |
+//! | global config  | `["config"]`                                | this program       |
+//! | log page       | `["log", counter, page as u32 LE]`          | this program       |
+//! | program data   | `[program_id]`                              | upgradeable loader |
+//!
+//! Names are UTF-8 bytes of at most 32 bytes, hashes are 32 bytes. The scheme
+//! is versioned by `ADDRESS_SCHEME_VERSION`: a seed never changes meaning,
+//! and a changed derivation gets a new seed and a version bump instead.
+//!
+//! Builds with the `anchor-layout` feature use Anchor's account
+//! discriminators instead of the short ASCII tags.
+
+use solana_program::{bpf_loader_upgradeable, pubkey::Pubkey};
+
+/// Version of the seeds and derivations below.
+pub const ADDRESS_SCHEME_VERSION: u8 = 1;
+
+#[cfg(not(feature = "anchor-layout"))]
+mod discriminators {
+    /// Leading bytes of every counter account, so handlers can tell counters
+    /// apart from zeroed or other program-owned accounts. Versioned counters
+    /// follow it with a `version` byte.
+    pub const COUNTER_DISCRIMINATOR: [u8; 8] = *b"counterv";
+    pub const COMPOSITE_DISCRIMINATOR: [u8; 8] = *b"composit";
+    pub const VIEW_KEY_DISCRIMINATOR: [u8; 8] = *b"viewkey\0";
+    pub const COUPON_DISCRIMINATOR: [u8; 8] = *b"coupon\0\0";
+    pub const TEMPLATE_DISCRIMINATOR: [u8; 8] = *b"template";
+    pub const REGISTRY_DISCRIMINATOR: [u8; 8] = *b"registry";
+    pub const SNAPSHOT_DISCRIMINATOR: [u8; 8] = *b"snapshot";
+    pub const MULTISIG_DISCRIMINATOR: [u8; 8] = *b"multisig";
+    pub const OWNER_STATS_DISCRIMINATOR: [u8; 8] = *b"ownstats";
+    pub const SIGNED_COUNTER_DISCRIMINATOR: [u8; 8] = *b"sgncount";
+    pub const SLOT_COUNTER_DISCRIMINATOR: [u8; 8] = *b"slotcntr";
+    pub const OWNER_INDEX_DISCRIMINATOR: [u8; 8] = *b"ownindex";
+    pub const CLAIM_BITMAP_DISCRIMINATOR: [u8; 8] = *b"claimmap";
+    pub const USER_RECORD_DISCRIMINATOR: [u8; 8] = *b"userrcrd";
+    pub const GLOBAL_CONFIG_DISCRIMINATOR: [u8; 8] = *b"glbconfg";
+    pub const LOG_PAGE_DISCRIMINATOR: [u8; 8] = *b"logpage\0";
+}
+
+/// The first eight bytes of `sha256("account:<Type>")`, as Anchor derives
+/// them, so Anchor clients recognise the accounts without custom decoding.
+#[cfg(feature = "anchor-layout")]
+mod discriminators {
+    /// `account:CounterAccount`. Versioned counters follow it with a
+    /// `version` byte.
+    pub const COUNTER_DISCRIMINATOR: [u8; 8] = [164, 8, 153, 71, 8, 44, 93, 22];
+    pub const COMPOSITE_DISCRIMINATOR: [u8; 8] = [223, 142, 51, 23, 158, 25, 72, 77];
+    pub const VIEW_KEY_DISCRIMINATOR: [u8; 8] = [145, 25, 205, 72, 102, 237, 213, 2];
+    pub const COUPON_DISCRIMINATOR: [u8; 8] = [24, 230, 224, 210, 200, 206, 79, 57];
+    pub const TEMPLATE_DISCRIMINATOR: [u8; 8] = [43, 26, 88, 69, 69, 96, 9, 79];
+    pub const REGISTRY_DISCRIMINATOR: [u8; 8] = [47, 174, 110, 246, 184, 182, 252, 218];
+    pub const SNAPSHOT_DISCRIMINATOR: [u8; 8] = [137, 213, 28, 133, 224, 161, 48, 108];
+    pub const MULTISIG_DISCRIMINATOR: [u8; 8] = [224, 116, 121, 186, 68, 161, 79, 236];
+    pub const OWNER_STATS_DISCRIMINATOR: [u8; 8] = [91, 53, 236, 191, 162, 55, 15, 140];
+    pub const SIGNED_COUNTER_DISCRIMINATOR: [u8; 8] = [143, 110, 36, 99, 255, 15, 154, 110];
+    pub const SLOT_COUNTER_DISCRIMINATOR: [u8; 8] = [133, 160, 43, 104, 210, 62, 115, 41];
+    pub const OWNER_INDEX_DISCRIMINATOR: [u8; 8] = [28, 249, 139, 158, 18, 18, 173, 96];
+    pub const CLAIM_BITMAP_DISCRIMINATOR: [u8; 8] = [61, 25, 148, 196, 164, 208, 65, 169];
+    pub const USER_RECORD_DISCRIMINATOR: [u8; 8] = [210, 252, 132, 218, 191, 85, 173, 167];
+    pub const GLOBAL_CONFIG_DISCRIMINATOR: [u8; 8] = [149, 8, 156, 202, 160, 252, 176, 217];
+    pub const LOG_PAGE_DISCRIMINATOR: [u8; 8] = [222, 21, 1, 227, 238, 199, 126, 61];
+}
+
+pub use discriminators::*;
+
+/// Discriminator of the original, unversioned layout (`CounterAccountV0`).
+pub const COUNTER_DISCRIMINATOR_V0: [u8; 8] = *b"counter\0";
+
+pub const COUNTER_SEED: &[u8] = b"counter";
+pub const EXTERNAL_ID_SEED: &[u8] = b"external";
+pub const TRIGGER_SEED: &[u8] = b"trigger";
+pub const VIEW_KEY_SEED: &[u8] = b"view";
+/// Seed of the PDA a granted program signs `GetCount` with, derived under
+/// that program's id.
+pub const VIEWER_SEED: &[u8] = b"viewer";
+pub const COUPON_SEED: &[u8] = b"coupon";
+pub const TEMPLATE_SEED: &[u8] = b"template";
+pub const REGISTRY_SEED: &[u8] = b"registry";
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+pub const OWNER_STATS_SEED: &[u8] = b"owner_stats";
+pub const SNAPSHOT_SEED: &[u8] = b"snapshot";
+pub const OWNER_INDEX_SEED: &[u8] = b"index";
+pub const CLAIMS_SEED: &[u8] = b"claims";
+pub const USER_RECORD_SEED: &[u8] = b"user";
+pub const GLOBAL_CONFIG_SEED: &[u8] = b"config";
+pub const LOG_SEED: &[u8] = b"log";
+
+/// Deterministic address of `owner`'s counter called `name`. The empty name
+/// gives the owner's default `["counter", owner]` counter.
+pub fn derive_counter_address(program_id: &Pubkey, owner: &Pubkey, name: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUNTER_SEED, owner.as_ref(), name.as_bytes()], program_id)
+}
+
+/// Deterministic address of `owner`'s counter for an external entity, where
+/// `external_id` is a hash of the entity's own identifier, see
+/// `external_id_hash`.
+pub fn derive_external_counter_address(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    external_id: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EXTERNAL_ID_SEED, owner.as_ref(), external_id], program_id)
+}
+
+/// PDA that signs a counter's trigger CPI.
+pub fn derive_trigger_authority(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TRIGGER_SEED, counter.as_ref()], program_id)
+}
+
+pub fn derive_view_key_address(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    viewer: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VIEW_KEY_SEED, counter.as_ref(), viewer.as_ref()],
+        program_id,
+    )
+}
+
+/// Derived under the viewer program's id, not this program's.
+pub fn derive_viewer_authority(viewer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VIEWER_SEED], viewer)
+}
+
+pub fn derive_coupon_address(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    code_hash: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUPON_SEED, counter.as_ref(), code_hash], program_id)
+}
+
+pub fn derive_template_address(program_id: &Pubkey, admin: &Pubkey, name: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TEMPLATE_SEED, admin.as_ref(), name.as_bytes()],
+        program_id,
+    )
+}
+
+pub fn derive_registry_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED], program_id)
+}
+
+/// The program-owned, data-less account that collects a counter's increment
+/// fees when no recipient is configured.
+pub fn derive_fee_vault_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_VAULT_SEED, counter.as_ref()], program_id)
+}
+
+pub fn derive_owner_stats_address(program_id: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OWNER_STATS_SEED, owner.as_ref()], program_id)
+}
+
+pub fn derive_snapshot_address(program_id: &Pubkey, counter: &Pubkey, index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SNAPSHOT_SEED, counter.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// The list of counters `owner` has paid for, kept up to date by the
+/// instructions that create and close counters when it is passed.
+pub fn derive_owner_index_address(program_id: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OWNER_INDEX_SEED, owner.as_ref()], program_id)
+}
+
+/// The bitmap recording which merkle allowlist leaves of `counter` have
+/// claimed, for leaves `chunk * CLAIMS_PER_BITMAP` onwards.
+pub fn derive_claim_bitmap_address(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    chunk: u32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CLAIMS_SEED, counter.as_ref(), &chunk.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// `user`'s contributions to `counter`, updated by increments that pass it.
+pub fn derive_user_record_address(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    user: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USER_RECORD_SEED, counter.as_ref(), user.as_ref()],
+        program_id,
+    )
+}
+
+/// Page `page` of `counter`'s event log.
+pub fn derive_log_page_address(program_id: &Pubkey, counter: &Pubkey, page: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LOG_SEED, counter.as_ref(), &page.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// The program's global config, created by its upgrade authority.
+pub fn derive_global_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_CONFIG_SEED], program_id)
+}
+
+/// The program's data account, which records its upgrade authority.
+pub fn derive_program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
@@ -0,0 +1,108 @@
+//! Anchor-style instruction dispatch for builds with the `anchor-layout`
+//! feature. Anchor clients prefix an instruction with the first eight bytes
+//! of `sha256("global:<snake_case_name>")` instead of a tag byte; `translate`
+//! swaps that sighash for the tag so the usual decoding and checks apply.
+//!
+//! Arguments keep the program's own encoding, which is Borsh for every
+//! fixed-size field. The account discriminators live in `addresses`.
+
+/// Sighash of each instruction, indexed by tag like `dispatch::INSTRUCTIONS`.
+pub const INSTRUCTION_SIGHASHES: &[[u8; 8]] = &[
+    [67, 89, 100, 87, 231, 172, 35, 124],     // 0 initialize_counter
+    [16, 125, 2, 171, 73, 24, 207, 229],      // 1 increment_counter
+    [225, 48, 84, 136, 90, 146, 26, 149],     // 2 reset_circuit_breaker
+    [188, 2, 173, 81, 233, 197, 243, 208],    // 3 commit_increment
+    [87, 207, 67, 46, 65, 16, 177, 88],       // 4 reveal_increment
+    [131, 42, 254, 180, 121, 143, 72, 137],   // 5 increment_committed
+    [131, 147, 28, 108, 26, 59, 83, 82],      // 6 increment_metric
+    [218, 202, 89, 159, 180, 70, 9, 139],     // 7 initialize_composite
+    [31, 92, 169, 2, 203, 28, 47, 109],       // 8 refresh_composite
+    [169, 37, 242, 230, 169, 126, 245, 38],   // 9 decrement_counter
+    [86, 98, 157, 153, 144, 26, 54, 149],     // 10 sync_mint_supply
+    [103, 82, 124, 55, 231, 50, 146, 138],    // 11 increment_by
+    [103, 195, 73, 36, 174, 179, 60, 246],    // 12 decrement_by
+    [48, 169, 76, 72, 229, 180, 55, 161],     // 13 transfer_authority
+    [4, 236, 52, 248, 107, 146, 187, 49],     // 14 close_counter
+    [188, 143, 40, 40, 152, 72, 158, 106],    // 15 initialize_counter_pda
+    [245, 154, 57, 72, 251, 63, 131, 79],     // 16 reset_counter
+    [98, 68, 192, 166, 115, 7, 171, 39],      // 17 set_counter
+    [7, 137, 108, 216, 62, 5, 218, 156],      // 18 force_set
+    [74, 3, 132, 121, 30, 226, 185, 23],      // 19 fast_forward_cooldown
+    [92, 109, 130, 180, 54, 145, 202, 129],   // 20 reset_stats
+    [147, 15, 172, 179, 252, 146, 108, 91],   // 21 reap
+    [177, 228, 60, 125, 13, 116, 44, 84],     // 22 migrate_account
+    [60, 205, 100, 102, 71, 34, 13, 130],     // 23 lock_count
+    [254, 100, 75, 13, 49, 32, 67, 167],      // 24 unlock_count
+    [174, 183, 93, 50, 218, 28, 8, 54],       // 25 grant_view_key
+    [152, 55, 229, 152, 214, 239, 20, 244],   // 26 revoke_view_key
+    [224, 158, 79, 100, 65, 72, 197, 132],    // 27 get_count
+    [81, 24, 164, 131, 82, 18, 34, 31],       // 28 set_topics
+    [66, 127, 229, 150, 174, 89, 67, 31],     // 29 initialize_and_increment
+    [34, 98, 36, 122, 96, 133, 242, 181],     // 30 set_step
+    [190, 110, 73, 138, 8, 160, 244, 63],     // 31 mint_coupon
+    [66, 181, 163, 197, 244, 189, 153, 0],    // 32 redeem_coupon
+    [112, 78, 85, 141, 216, 214, 56, 5],      // 33 import_attestation
+    [138, 226, 160, 220, 47, 40, 184, 125],   // 34 increment_many
+    [244, 48, 98, 183, 185, 240, 231, 145],   // 35 skim_excess
+    [202, 47, 59, 7, 67, 160, 130, 7],        // 36 set_template
+    [204, 144, 64, 176, 23, 168, 183, 171],   // 37 initialize_from_template
+    [189, 181, 20, 17, 174, 57, 249, 59],     // 38 initialize_registry
+    [159, 47, 147, 247, 85, 53, 84, 230],     // 39 set_mode
+    [211, 22, 221, 251, 74, 121, 193, 47],    // 40 pause
+    [1, 166, 51, 170, 127, 32, 141, 206],     // 41 resume
+    [132, 3, 131, 35, 147, 24, 85, 159],      // 42 initialize_counter_by_id
+    [144, 236, 6, 133, 233, 160, 21, 94],     // 43 snapshot
+    [3, 67, 128, 218, 69, 139, 53, 88],       // 44 add_delegate
+    [94, 37, 16, 59, 7, 84, 97, 211],         // 45 remove_delegate
+    [148, 146, 240, 10, 226, 215, 167, 174],  // 46 create_multisig
+    [127, 61, 242, 73, 44, 82, 105, 163],     // 47 set_unlock_time
+    [190, 32, 49, 162, 120, 37, 132, 216],    // 48 set_owner_quota
+    [198, 212, 171, 109, 144, 215, 174, 89],  // 49 withdraw_fees
+    [74, 27, 74, 155, 56, 134, 175, 125],     // 50 resize
+    [21, 141, 206, 49, 76, 173, 174, 43],     // 51 admin_freeze
+    [110, 153, 44, 95, 222, 184, 155, 81],    // 52 request_unfreeze
+    [244, 184, 221, 192, 215, 228, 39, 50],   // 53 burn_counter
+    [33, 88, 186, 20, 205, 247, 117, 66],     // 54 set_unfreeze_grace_period
+    [171, 175, 8, 14, 215, 203, 33, 246],     // 55 initialize_signed_counter
+    [26, 130, 222, 240, 66, 104, 56, 35],     // 56 increment_signed
+    [81, 40, 1, 22, 54, 228, 38, 249],        // 57 decrement_signed
+    [254, 79, 138, 77, 246, 250, 227, 246],   // 58 initialize_slot_counter
+    [98, 178, 156, 135, 66, 10, 85, 215],     // 59 increment_slot
+    [38, 163, 115, 220, 110, 52, 178, 232],   // 60 decrement_slot
+    [169, 242, 196, 223, 142, 154, 238, 22],  // 61 get_slot
+    [253, 189, 252, 49, 253, 136, 39, 217],   // 62 conditional_increment
+    [74, 101, 44, 10, 47, 79, 83, 25],        // 63 transfer_count
+    [138, 186, 164, 245, 32, 116, 162, 62],   // 64 close_expired
+    [114, 106, 95, 137, 211, 129, 161, 148],  // 65 increment_with_proof
+    [113, 216, 122, 131, 225, 209, 22, 55],   // 66 initialize_global_config
+    [164, 165, 253, 109, 202, 216, 5, 75],    // 67 set_global_config
+    [231, 112, 202, 115, 176, 129, 223, 159], // 68 read_count
+];
+
+/// Tag of the instruction whose sighash is `discriminator`.
+pub fn sighash_tag(discriminator: &[u8; 8]) -> Option<u8> {
+    INSTRUCTION_SIGHASHES
+        .iter()
+        .position(|sighash| sighash == discriminator)
+        .map(|index| index as u8)
+}
+
+/// `input` with its sighash replaced by the tag byte, or `None` when it does
+/// not start with a known sighash.
+pub fn translate(input: &[u8]) -> Option<Vec<u8>> {
+    let (discriminator, rest) = input.split_first_chunk::<8>()?;
+    let tag = sighash_tag(discriminator)?;
+    let mut data = Vec::with_capacity(rest.len() + 1);
+    data.push(tag);
+    data.extend_from_slice(rest);
+    Some(data)
+}
+
+/// Prefixes the tagged `data` from `CounterInstruction::pack` with its
+/// sighash instead.
+pub fn to_anchor_data(data: &[u8]) -> Option<Vec<u8>> {
+    let (tag, rest) = data.split_first()?;
+    let mut anchor = INSTRUCTION_SIGHASHES.get(*tag as usize)?.to_vec();
+    anchor.extend_from_slice(rest);
+    Some(anchor)
+}
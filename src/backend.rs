@@ -0,0 +1,120 @@
+//! Storage backends for `CounterAccount`. Handlers load and save counters only
+//! through `load_counter`/`save_counter`, so a new on-chain layout (zero-copy,
+//! compressed) only needs a `CounterBackend` impl and a branch in
+//! `backend_for` rather than another copy of every handler.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, sysvar::Sysvar,
+};
+
+use crate::addresses::{COUNTER_DISCRIMINATOR, COUNTER_DISCRIMINATOR_V0};
+use crate::error::CounterError;
+use crate::events::{self, CounterEvent, LamportsDonated};
+use crate::state::{COUNTER_VERSION, CounterAccount, CounterAccountV0};
+
+pub trait CounterBackend {
+    /// Layout version this backend reads and writes.
+    fn version(&self) -> u8;
+    fn load(&self, data: &[u8]) -> Result<CounterAccount, ProgramError>;
+    fn save(&self, counter: &CounterAccount, data: &mut [u8]) -> ProgramResult;
+}
+
+/// The original layout: the whole account is the Borsh encoding of
+/// `CounterAccount`.
+pub struct BorshBackend;
+
+impl CounterBackend for BorshBackend {
+    fn version(&self) -> u8 {
+        COUNTER_VERSION
+    }
+
+    fn load(&self, data: &[u8]) -> Result<CounterAccount, ProgramError> {
+        Ok(CounterAccount::try_from_slice(data)?)
+    }
+
+    fn save(&self, counter: &CounterAccount, mut data: &mut [u8]) -> ProgramResult {
+        Ok(counter.serialize(&mut data)?)
+    }
+}
+
+/// The unversioned layout, read as the current `CounterAccount` so handlers
+/// work on old accounts before they are migrated.
+pub struct LegacyV0Backend;
+
+impl CounterBackend for LegacyV0Backend {
+    fn version(&self) -> u8 {
+        0
+    }
+
+    fn load(&self, data: &[u8]) -> Result<CounterAccount, ProgramError> {
+        Ok(CounterAccountV0::try_from_slice(data)?.into())
+    }
+
+    fn save(&self, counter: &CounterAccount, mut data: &mut [u8]) -> ProgramResult {
+        Ok(CounterAccountV0::try_from(counter.clone())?.serialize(&mut data)?)
+    }
+}
+
+/// Picks the backend that understands `data` from its discriminator. Zeroed
+/// accounts and other account types are rejected here, before any handler
+/// sees them.
+pub fn backend_for(data: &[u8]) -> Result<&'static dyn CounterBackend, ProgramError> {
+    match data.get(..COUNTER_DISCRIMINATOR.len()) {
+        Some(discriminator) if discriminator == COUNTER_DISCRIMINATOR => Ok(&BorshBackend),
+        Some(discriminator) if discriminator == COUNTER_DISCRIMINATOR_V0 => Ok(&LegacyV0Backend),
+        Some(discriminator) if discriminator.iter().any(|&byte| byte != 0) => {
+            msg!("Account is not a counter");
+            Err(CounterError::WrongAccountType.into())
+        }
+        _ => {
+            msg!("Counter account is not initialized");
+            Err(CounterError::AccountNotInitialized.into())
+        }
+    }
+}
+
+/// Loads the counter, raising a `LamportsDonated` alert if the account holds
+/// more than the program left in it.
+pub fn load_counter(account: &AccountInfo) -> Result<CounterAccount, ProgramError> {
+    let data = account.try_borrow_data()?;
+    let counter = backend_for(&data)?.load(&data)?;
+
+    // unversioned counters never recorded a balance
+    let observed = account.lamports();
+    if counter.min_observed_lamports != 0 && observed > counter.min_observed_lamports {
+        msg!(
+            "ALERT: counter holds {} lamports more than expected",
+            observed - counter.min_observed_lamports
+        );
+        let event = CounterEvent::LamportsDonated(LamportsDonated {
+            expected: counter.min_observed_lamports,
+            observed,
+        });
+        events::emit(account.key, &counter.topics, &event);
+    }
+
+    Ok(counter)
+}
+
+/// Writes `counter` back in the layout the account already uses, stamping
+/// the update time.
+pub fn save_counter(account: &AccountInfo, counter: &mut CounterAccount) -> ProgramResult {
+    counter.last_updated = Clock::get()?.unix_timestamp;
+
+    let mut data = account.try_borrow_mut_data()?;
+    let backend = backend_for(&data)?;
+    backend.save(counter, &mut data)
+}
+
+/// Writes `counter` in the current layout, for freshly created, migrated or
+/// resized accounts sized for it, and records the account's balance as the
+/// expected one and the update time.
+pub fn write_counter(account: &AccountInfo, counter: &mut CounterAccount) -> ProgramResult {
+    counter.min_observed_lamports = account.lamports();
+    counter.last_updated = Clock::get()?.unix_timestamp;
+
+    let mut data = account.try_borrow_mut_data()?;
+    BorshBackend.save(counter, &mut data)
+}
@@ -0,0 +1,177 @@
+//! Client-side buffering for high-frequency producers. Deltas are summed per
+//! counter in memory and flushed as `IncrementBy` messages, so a thousand
+//! events become one instruction per counter instead of a thousand
+//! transactions.
+//!
+//! Every flushed message carries an idempotency key, and the caller reports
+//! back what happened to it. In `DeliveryMode::ExactlyOnce` a failed message
+//! is handed back unchanged, blockhash included, so the resent transaction
+//! has the same signature and the runtime drops it if the first attempt did
+//! land. Its deltas only return to the buffer once the caller has seen the
+//! blockhash expire without the signature being processed.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use solana_program::{
+    hash::Hash, instruction::Instruction, message::Message, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::client::increment_by_ix;
+
+/// `IncrementBy` instructions per flushed message, comfortably inside the
+/// transaction size limit.
+pub const MAX_FLUSH_INSTRUCTIONS: usize = 16;
+
+/// What the batcher does with a flush whose outcome is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Returns its deltas to the buffer straight away. A flush that landed
+    /// but was not confirmed is applied twice.
+    AtLeastOnce,
+    /// Keeps it in flight for an identical resend until it is confirmed or
+    /// reported expired.
+    ExactlyOnce,
+}
+
+/// A message waiting for the caller to report its outcome.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flush {
+    pub key: u64,
+    pub message: Message,
+    pub deltas: Vec<(Pubkey, u64)>,
+}
+
+pub struct CounterBatcher {
+    program_id: Pubkey,
+    actor: Pubkey,
+    mode: DeliveryMode,
+    flush_interval: Duration,
+    max_pending: usize,
+    pending: BTreeMap<Pubkey, u64>,
+    in_flight: BTreeMap<u64, Flush>,
+    next_key: u64,
+    last_flush: Instant,
+}
+
+impl CounterBatcher {
+    /// Flushes are due every `flush_interval`, or sooner once `max_pending`
+    /// counters have deltas waiting. `actor` signs and pays for every flush.
+    pub fn new(
+        program_id: Pubkey,
+        actor: Pubkey,
+        mode: DeliveryMode,
+        flush_interval: Duration,
+        max_pending: usize,
+    ) -> Self {
+        Self {
+            program_id,
+            actor,
+            mode,
+            flush_interval,
+            max_pending,
+            pending: BTreeMap::new(),
+            in_flight: BTreeMap::new(),
+            next_key: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Adds `delta` to what `counter` will be incremented by at the next
+    /// flush.
+    pub fn record(&mut self, counter: Pubkey, delta: u64) -> Result<(), ProgramError> {
+        let pending = self.pending.entry(counter).or_default();
+        *pending = pending
+            .checked_add(delta)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// The delta buffered for `counter`, not counting flushes in flight.
+    pub fn pending(&self, counter: &Pubkey) -> u64 {
+        self.pending.get(counter).copied().unwrap_or(0)
+    }
+
+    pub fn should_flush(&self, now: Instant) -> bool {
+        !self.pending.is_empty()
+            && (self.pending.len() >= self.max_pending
+                || now.duration_since(self.last_flush) >= self.flush_interval)
+    }
+
+    /// Drains the buffer into messages built against `recent_blockhash`,
+    /// each tracked in flight under its own key until reported back.
+    pub fn flush(&mut self, recent_blockhash: Hash) -> Vec<Flush> {
+        self.last_flush = Instant::now();
+
+        let deltas: Vec<(Pubkey, u64)> = std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter(|&(_, delta)| delta != 0)
+            .collect();
+
+        deltas
+            .chunks(MAX_FLUSH_INSTRUCTIONS)
+            .map(|chunk| {
+                let instructions: Vec<Instruction> = chunk
+                    .iter()
+                    .map(|(counter, delta)| {
+                        increment_by_ix(&self.program_id, counter, &self.actor, *delta)
+                    })
+                    .collect();
+                let flush = Flush {
+                    key: self.next_key,
+                    message: Message::new_with_blockhash(
+                        &instructions,
+                        Some(&self.actor),
+                        &recent_blockhash,
+                    ),
+                    deltas: chunk.to_vec(),
+                };
+                self.next_key += 1;
+                self.in_flight.insert(flush.key, flush.clone());
+                flush
+            })
+            .collect()
+    }
+
+    pub fn in_flight(&self) -> impl Iterator<Item = &Flush> {
+        self.in_flight.values()
+    }
+
+    /// The flush's transaction was processed successfully.
+    pub fn confirm(&mut self, key: u64) {
+        self.in_flight.remove(&key);
+    }
+
+    /// The flush's transaction failed or its outcome is unknown. Returns the
+    /// message to resend unchanged in exactly-once mode; in at-least-once
+    /// mode its deltas go back into the buffer instead.
+    pub fn fail(&mut self, key: u64) -> Option<Message> {
+        match self.mode {
+            DeliveryMode::ExactlyOnce => {
+                self.in_flight.get(&key).map(|flush| flush.message.clone())
+            }
+            DeliveryMode::AtLeastOnce => {
+                self.requeue(key);
+                None
+            }
+        }
+    }
+
+    /// The flush's blockhash expired without its transaction being processed,
+    /// so it can no longer land and its deltas go back into the buffer.
+    pub fn expire(&mut self, key: u64) {
+        self.requeue(key);
+    }
+
+    fn requeue(&mut self, key: u64) {
+        let Some(flush) = self.in_flight.remove(&key) else {
+            return;
+        };
+
+        for (counter, delta) in flush.deltas {
+            let pending = self.pending.entry(counter).or_default();
+            *pending = pending.saturating_add(delta);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Command-line client for deployed counters. Reads the RPC URL, commitment
+//! and fee payer keypair from the Solana CLI config.
+//!
+//! ```text
+//! counter-cli --program-id <ID> [--config <PATH>] init [INITIAL_VALUE]
+//! counter-cli --program-id <ID> [--config <PATH>] increment <COUNTER> [AMOUNT]
+//! counter-cli --program-id <ID> [--config <PATH>] get <COUNTER>
+//! counter-cli --program-id <ID> [--config <PATH>] close <COUNTER> [DESTINATION]
+//! ```
+
+use std::error::Error;
+use std::process::exit;
+use std::str::FromStr;
+
+use counter_program::client::{
+    close_counter_ix, deserialize_counter, increment_by_ix, initialize_counter_ix,
+};
+use solana_cli_config::{CONFIG_FILE, Config};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer, read_keypair_file},
+    transaction::Transaction,
+};
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+const USAGE: &str = "usage: counter-cli --program-id <ID> [--config <PATH>] \
+                     <init [INITIAL_VALUE] | increment <COUNTER> [AMOUNT] | get <COUNTER> | \
+                     close <COUNTER> [DESTINATION]>";
+
+struct Cli {
+    rpc: RpcClient,
+    payer: Keypair,
+    program_id: Pubkey,
+}
+
+impl Cli {
+    fn send(&self, instructions: &[Instruction], extra_signers: &[&Keypair]) -> CliResult<()> {
+        let mut signers = vec![&self.payer];
+        signers.extend_from_slice(extra_signers);
+
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &signers,
+            blockhash,
+        );
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        println!("Signature: {}", signature);
+        Ok(())
+    }
+
+    fn print_count(&self, counter: &Pubkey) -> CliResult<()> {
+        let account = self.rpc.get_account(counter)?;
+        let state = deserialize_counter(&account.data)?;
+        println!("Count: {}", state.count);
+        Ok(())
+    }
+}
+
+fn parse<T: FromStr>(value: Option<&String>, name: &str) -> CliResult<T>
+where
+    T::Err: Error + 'static,
+{
+    let value = value.ok_or_else(|| format!("missing {}\n{}", name, USAGE))?;
+    Ok(value.parse()?)
+}
+
+fn parse_or<T: FromStr>(value: Option<&String>, default: T) -> CliResult<T>
+where
+    T::Err: Error + 'static,
+{
+    match value {
+        Some(value) => Ok(value.parse()?),
+        None => Ok(default),
+    }
+}
+
+fn run(args: Vec<String>) -> CliResult<()> {
+    let mut config_path = CONFIG_FILE.clone();
+    let mut program_id = None;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = args.next(),
+            "--program-id" => program_id = args.next(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let program_id: Pubkey = parse(program_id.as_ref(), "--program-id")?;
+    let config = match config_path {
+        Some(path) => Config::load(&path).unwrap_or_default(),
+        None => Config::default(),
+    };
+
+    let cli = Cli {
+        rpc: RpcClient::new_with_commitment(
+            config.json_rpc_url.clone(),
+            CommitmentConfig::from_str(&config.commitment).unwrap_or_default(),
+        ),
+        payer: read_keypair_file(&config.keypair_path)
+            .map_err(|err| format!("reading {}: {}", config.keypair_path, err))?,
+        program_id,
+    };
+    let payer = cli.payer.pubkey();
+
+    let command = positional.first().ok_or(USAGE)?;
+    match command.as_str() {
+        "init" => {
+            let initial_value = parse_or(positional.get(1), 0)?;
+            let counter = Keypair::new();
+            let instruction =
+                initialize_counter_ix(&cli.program_id, &counter.pubkey(), &payer, initial_value);
+            cli.send(&[instruction], &[&counter])?;
+            println!("Counter: {}", counter.pubkey());
+            cli.print_count(&counter.pubkey())
+        }
+        "increment" => {
+            let counter: Pubkey = parse(positional.get(1), "COUNTER")?;
+            let amount = parse_or(positional.get(2), 1)?;
+            cli.send(
+                &[increment_by_ix(&cli.program_id, &counter, &payer, amount)],
+                &[],
+            )?;
+            cli.print_count(&counter)
+        }
+        "get" => {
+            let counter: Pubkey = parse(positional.get(1), "COUNTER")?;
+            cli.print_count(&counter)
+        }
+        "close" => {
+            let counter: Pubkey = parse(positional.get(1), "COUNTER")?;
+            let destination = parse_or(positional.get(2), payer)?;
+            cli.send(
+                &[close_counter_ix(
+                    &cli.program_id,
+                    &counter,
+                    &payer,
+                    &destination,
+                )],
+                &[],
+            )?;
+            println!("Counter {} closed, rent sent to {}", counter, destination);
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() {
+    if let Err(err) = run(std::env::args().skip(1).collect()) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,189 @@
+//! Length-capped strings and vectors for state and instruction payloads.
+//! Their Borsh decoders reject an oversized length prefix before reading or
+//! allocating anything, so attacker-supplied data can never claim more
+//! memory or account space than the field allows. Both encode exactly like
+//! `String` and `Vec`, so swapping them in leaves account layouts unchanged.
+
+use std::fmt;
+use std::ops::Deref;
+
+use borsh::io::{self, Read, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::error::CounterError;
+
+/// Names used as a PDA seed, which caps them at 32 bytes.
+pub type Label32 = BoundedString<32>;
+
+/// A UTF-8 string of at most `N` bytes.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BoundedString<const N: usize>(String);
+
+/// A vector of at most `N` elements.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BoundedVec<T, const N: usize>(Vec<T>);
+
+fn check_len(len: usize, max: usize) -> io::Result<()> {
+    if len > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "length exceeds the field's maximum",
+        ));
+    }
+    Ok(())
+}
+
+impl<const N: usize> BoundedString<N> {
+    pub const MAX_LEN: usize = N;
+
+    pub fn new(value: impl Into<String>) -> Result<Self, CounterError> {
+        let value = value.into();
+        if value.len() > N {
+            return Err(CounterError::LengthExceeded);
+        }
+        Ok(Self(value))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl<const N: usize> Deref for BoundedString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> TryFrom<String> for BoundedString<N> {
+    type Error = CounterError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for BoundedString<N> {
+    type Error = CounterError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<const N: usize> PartialEq<str> for BoundedString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for BoundedString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl<const N: usize> fmt::Debug for BoundedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<const N: usize> fmt::Display for BoundedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<const N: usize> BorshSerialize for BoundedString<N> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.serialize(writer)
+    }
+}
+
+impl<const N: usize> BorshDeserialize for BoundedString<N> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let len = u32::deserialize_reader(reader)? as usize;
+        check_len(len, N)?;
+
+        let mut bytes = vec![0; len];
+        reader.read_exact(&mut bytes)?;
+        String::from_utf8(bytes)
+            .map(Self)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8"))
+    }
+}
+
+impl<T, const N: usize> BoundedVec<T, N> {
+    pub const MAX_LEN: usize = N;
+
+    pub fn new(items: Vec<T>) -> Result<Self, CounterError> {
+        if items.len() > N {
+            return Err(CounterError::LengthExceeded);
+        }
+        Ok(Self(items))
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T, const N: usize> Default for BoundedVec<T, N> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T, const N: usize> Deref for BoundedVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> AsRef<[T]> for BoundedVec<T, N> {
+    fn as_ref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> TryFrom<Vec<T>> for BoundedVec<T, N> {
+    type Error = CounterError;
+
+    fn try_from(items: Vec<T>) -> Result<Self, Self::Error> {
+        Self::new(items)
+    }
+}
+
+impl<T: PartialEq<U>, U, const N: usize> PartialEq<Vec<U>> for BoundedVec<T, N> {
+    fn eq(&self, other: &Vec<U>) -> bool {
+        self.0[..] == other[..]
+    }
+}
+
+impl<T: BorshSerialize, const N: usize> BorshSerialize for BoundedVec<T, N> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.serialize(writer)
+    }
+}
+
+impl<T: BorshDeserialize, const N: usize> BorshDeserialize for BoundedVec<T, N> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let len = u32::deserialize_reader(reader)? as usize;
+        check_len(len, N)?;
+
+        let mut items = Vec::with_capacity(len);
+        for _ in 0..len {
+            items.push(T::deserialize_reader(reader)?);
+        }
+        Ok(Self(items))
+    }
+}
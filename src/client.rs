@@ -0,0 +1,1618 @@
+//! Instruction builders, account decoders and event log parsing for off-chain
+//! callers, so they never assemble instruction bytes or account lists by hand. Account orders
+//! follow the `dispatch` table; where an instruction's accounts are declared
+//! with `instruction_accounts!`, builders take them from its `Keys` struct. Builders for count mutations only list the
+//! counter and actor; push gate and trigger accounts onto the returned
+//! instruction's `accounts` when the counter needs them.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use borsh::BorshDeserialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use thiserror::Error;
+
+use crate::accounts::{
+    AdminFreezeKeys, BurnKeys, CloseExpiredKeys, CounterKeys, CreateKeys, CreatePdaKeys,
+    IncrementWithProofKeys, InitializeGlobalConfigKeys, MigrateKeys, MintCouponKeys, MutateKeys,
+    ReadKeys, RegistryAdminKeys, RequestUnfreezeKeys, SetGlobalConfigKeys, SetTemplateKeys,
+    SignedCounterKeys, SlotCounterKeys, SnapshotKeys, TransferCountKeys, ViewKeyKeys, ViewKeys,
+    WithdrawFeesKeys,
+};
+use crate::addresses::{
+    CLAIM_BITMAP_DISCRIMINATOR, COMPOSITE_DISCRIMINATOR, COUNTER_DISCRIMINATOR,
+    COUNTER_DISCRIMINATOR_V0, GLOBAL_CONFIG_DISCRIMINATOR, LOG_PAGE_DISCRIMINATOR,
+    MULTISIG_DISCRIMINATOR, OWNER_INDEX_DISCRIMINATOR, OWNER_STATS_DISCRIMINATOR,
+    REGISTRY_DISCRIMINATOR, SIGNED_COUNTER_DISCRIMINATOR, SLOT_COUNTER_DISCRIMINATOR,
+    SNAPSHOT_DISCRIMINATOR, USER_RECORD_DISCRIMINATOR, derive_claim_bitmap_address,
+    derive_counter_address, derive_coupon_address, derive_external_counter_address,
+    derive_fee_vault_address, derive_global_config_address, derive_log_page_address,
+    derive_owner_index_address, derive_owner_stats_address, derive_program_data_address,
+    derive_registry_address, derive_snapshot_address, derive_template_address,
+    derive_trigger_authority, derive_user_record_address, derive_view_key_address,
+    derive_viewer_authority,
+};
+use crate::bounded::Label32;
+use crate::events::{LoggedEvent, decode_fields};
+use crate::instruction::{CounterInstruction, InitConfig, MetricRef, coupon_code_hash};
+use crate::state::{
+    AttestationSnapshot, CLAIMS_PER_BITMAP, ClaimBitmap, CompositeCounter, CounterAccount,
+    CouponCode, GlobalConfig, GlobalParams, IncrementMode, LogPage, MerkleProof, Multisig,
+    MultisigSigners, OwnerIndex, OwnerStats, Registry, SignedCounter, SlotCounter, Snapshot,
+    Topics, TriggerConfig, UserRecord, WeightedSource,
+};
+
+fn counter_instruction(
+    program_id: &Pubkey,
+    instruction: CounterInstruction,
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    Instruction::new_with_bytes(*program_id, &instruction.pack(), accounts)
+}
+
+fn mutate_accounts(counter: &Pubkey, actor: &Pubkey) -> Vec<AccountMeta> {
+    MutateKeys {
+        counter: *counter,
+        actor: *actor,
+    }
+    .to_account_metas()
+}
+
+/// Appends the global config, which every counter creation passes so the
+/// owner quota cannot be left out.
+fn push_global_config(accounts: &mut Vec<AccountMeta>, program_id: &Pubkey) {
+    let (config, _) = derive_global_config_address(program_id);
+    accounts.push(AccountMeta::new_readonly(config, false));
+}
+
+/// Appends the fee vault a counter created with `config` needs.
+fn push_fee_vault(
+    accounts: &mut Vec<AccountMeta>,
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    config: &InitConfig,
+) {
+    if config.increment_fee != 0 && config.fee_recipient.is_none() {
+        let (fee_vault, _) = derive_fee_vault_address(program_id, counter);
+        accounts.push(AccountMeta::new(fee_vault, false));
+    }
+}
+
+pub fn initialize_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    initial_value: u64,
+) -> Instruction {
+    initialize_counter_with_config_ix(
+        program_id,
+        counter,
+        payer,
+        initial_value,
+        InitConfig::default(),
+    )
+}
+
+pub fn initialize_counter_with_config_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    initial_value: u64,
+    config: InitConfig,
+) -> Instruction {
+    let mut accounts = CreateKeys {
+        new_account: *counter,
+        payer: *payer,
+        system_program: system_program::ID,
+    }
+    .to_account_metas();
+    push_fee_vault(&mut accounts, program_id, counter, &config);
+    push_global_config(&mut accounts, program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::InitializeCounter {
+            initial_value,
+            config: Box::new(config),
+        },
+        accounts,
+    )
+}
+
+pub fn initialize_and_increment_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    initial_value: u64,
+    delta: u64,
+) -> Instruction {
+    let mut accounts = CreateKeys {
+        new_account: *counter,
+        payer: *payer,
+        system_program: system_program::ID,
+    }
+    .to_account_metas();
+    push_global_config(&mut accounts, program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::InitializeAndIncrement {
+            initial_value,
+            delta,
+        },
+        accounts,
+    )
+}
+
+/// Creates the owner's `["external", owner, external_id]` counter, returning
+/// its address alongside the instruction.
+pub fn initialize_counter_by_id_ix(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    external_id: &[u8; 32],
+    initial_value: u64,
+    config: InitConfig,
+) -> (Pubkey, Instruction) {
+    let (counter, _) = derive_external_counter_address(program_id, owner, external_id);
+
+    let mut accounts = CreatePdaKeys {
+        new_account: counter,
+        payer: *owner,
+        system_program: system_program::ID,
+    }
+    .to_account_metas();
+    push_fee_vault(&mut accounts, program_id, &counter, &config);
+    push_global_config(&mut accounts, program_id);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::InitializeCounterById {
+            initial_value,
+            external_id: *external_id,
+            config: Box::new(config),
+        },
+        accounts,
+    );
+
+    (counter, instruction)
+}
+
+/// Creates the owner's `["counter", owner, name]` counter, returning its
+/// address alongside the instruction.
+pub fn initialize_counter_pda_ix(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    name: &Label32,
+    initial_value: u64,
+    config: InitConfig,
+) -> (Pubkey, Instruction) {
+    let (counter, _) = derive_counter_address(program_id, owner, name);
+
+    let mut accounts = CreatePdaKeys {
+        new_account: counter,
+        payer: *owner,
+        system_program: system_program::ID,
+    }
+    .to_account_metas();
+    push_fee_vault(&mut accounts, program_id, &counter, &config);
+    push_global_config(&mut accounts, program_id);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::InitializeCounterPda {
+            initial_value,
+            name: name.clone(),
+            config: Box::new(config),
+        },
+        accounts,
+    );
+
+    (counter, instruction)
+}
+
+pub fn increment_ix(program_id: &Pubkey, counter: &Pubkey, actor: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementCounter,
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn increment_by_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementBy { amount },
+        mutate_accounts(counter, actor),
+    )
+}
+
+/// Adds the counter's step, failing with `StaleValue` unless the counter is
+/// still at `expected`.
+pub fn conditional_increment_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    expected: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::ConditionalIncrement { expected },
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn transfer_count_ix(
+    program_id: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::TransferCount { amount },
+        TransferCountKeys {
+            source: *source,
+            destination: *destination,
+            authority: *authority,
+        }
+        .to_account_metas(),
+    )
+}
+
+/// Bumps every counter in `counters` by its step in one instruction.
+/// Adds the counter's step for `actor`, which must be leaf `index` of the
+/// counter's merkle allowlist; see `merkle::MerkleTree` for the proof.
+pub fn increment_with_proof_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    index: u32,
+    proof: MerkleProof,
+) -> Instruction {
+    let (claims, _) = derive_claim_bitmap_address(program_id, counter, index / CLAIMS_PER_BITMAP);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementWithProof { index, proof },
+        IncrementWithProofKeys {
+            counter: *counter,
+            actor: *actor,
+            claims,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn increment_many_ix(program_id: &Pubkey, actor: &Pubkey, counters: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(*actor, true)];
+    accounts.extend(
+        counters
+            .iter()
+            .map(|counter| AccountMeta::new(*counter, false)),
+    );
+
+    counter_instruction(program_id, CounterInstruction::IncrementMany, accounts)
+}
+
+pub fn decrement_ix(program_id: &Pubkey, counter: &Pubkey, actor: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::DecrementCounter,
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn decrement_by_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::DecrementBy { amount },
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn increment_metric_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    metric: MetricRef,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementMetric { metric, amount },
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn increment_committed_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    value: u64,
+    salt: [u8; 32],
+    amount: u64,
+    new_salt: [u8; 32],
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementCommitted {
+            value,
+            salt,
+            amount,
+            new_salt,
+        },
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn reset_counter_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::ResetCounter,
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn set_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    value: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetCounter { value },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn reset_circuit_breaker_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::ResetCircuitBreaker,
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn commit_increment_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    hash: [u8; 32],
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::CommitIncrement { hash },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn reveal_increment_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    amount: u64,
+    salt: [u8; 32],
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::RevealIncrement { amount, salt },
+        CounterKeys { counter: *counter }.to_account_metas(),
+    )
+}
+
+pub fn transfer_authority_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::TransferAuthority {
+            new_authority: *new_authority,
+        },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn close_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::CloseCounter,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
+
+/// `close_counter_ix` for strict counters, routing donated lamports to
+/// `excess_destination`.
+pub fn close_strict_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+    excess_destination: &Pubkey,
+) -> Instruction {
+    let mut instruction = close_counter_ix(program_id, counter, authority, destination);
+    instruction
+        .accounts
+        .push(AccountMeta::new(*excess_destination, false));
+    instruction
+}
+
+pub fn skim_excess_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    treasury: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SkimExcess,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*treasury, false),
+        ],
+    )
+}
+
+pub fn sync_mint_supply_ix(program_id: &Pubkey, counter: &Pubkey, mint: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SyncMintSupply,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+    )
+}
+
+pub fn initialize_composite_ix(
+    program_id: &Pubkey,
+    composite: &Pubkey,
+    payer: &Pubkey,
+    sources: Vec<WeightedSource>,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::InitializeComposite { sources },
+        vec![
+            AccountMeta::new(*composite, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// `sources` must be in the order the composite was configured with.
+pub fn refresh_composite_ix(
+    program_id: &Pubkey,
+    composite: &Pubkey,
+    sources: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*composite, false)];
+    accounts.extend(
+        sources
+            .iter()
+            .map(|source| AccountMeta::new_readonly(*source, false)),
+    );
+
+    counter_instruction(program_id, CounterInstruction::RefreshComposite, accounts)
+}
+
+pub fn reap_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    reaper: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::Reap,
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(*payer, false),
+            AccountMeta::new(*reaper, true),
+        ],
+    )
+}
+
+/// Closes `counter` once it has expired, refunding `payer`, its original
+/// payer. Strict counters also need their excess destination appended.
+pub fn close_expired_ix(program_id: &Pubkey, counter: &Pubkey, payer: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::CloseExpired,
+        CloseExpiredKeys {
+            counter: *counter,
+            payer: *payer,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn migrate_account_ix(program_id: &Pubkey, counter: &Pubkey, payer: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::MigrateAccount,
+        MigrateKeys {
+            counter: *counter,
+            payer: *payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    )
+}
+
+/// Also upgrades bare 8-byte counters, which `migrate_account_ix` cannot.
+/// The counter keypair signs, as bare counters need it to.
+pub fn resize_ix(program_id: &Pubkey, counter: &Pubkey, payer: &Pubkey) -> Instruction {
+    let mut accounts = MigrateKeys {
+        counter: *counter,
+        payer: *payer,
+        system_program: system_program::ID,
+    }
+    .to_account_metas();
+    accounts[0].is_signer = true;
+
+    counter_instruction(program_id, CounterInstruction::Resize, accounts)
+}
+
+pub fn lock_count_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    duration_slots: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::LockCount {
+            amount,
+            duration_slots,
+        },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn unlock_count_ix(program_id: &Pubkey, counter: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::UnlockCount,
+        CounterKeys { counter: *counter }.to_account_metas(),
+    )
+}
+
+pub fn grant_view_key_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    viewer: &Pubkey,
+) -> Instruction {
+    let (view_key, _) = derive_view_key_address(program_id, counter, viewer);
+    let mut accounts = ViewKeyKeys {
+        counter: *counter,
+        authority: *authority,
+        view_key,
+    }
+    .to_account_metas();
+    accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::GrantViewKey { program: *viewer },
+        accounts,
+    )
+}
+
+pub fn revoke_view_key_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    viewer: &Pubkey,
+) -> Instruction {
+    let (view_key, _) = derive_view_key_address(program_id, counter, viewer);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::RevokeViewKey,
+        ViewKeyKeys {
+            counter: *counter,
+            authority: *authority,
+            view_key,
+        }
+        .to_account_metas(),
+    )
+}
+
+/// The count comes back as return data; decode it with
+/// `interface::parse_value`.
+pub fn read_count_ix(program_id: &Pubkey, counter: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::ReadCount,
+        ReadKeys { counter: *counter }.to_account_metas(),
+    )
+}
+
+/// For the granted `viewer` program to CPI with its `["viewer"]` PDA as signer.
+pub fn get_count_ix(program_id: &Pubkey, counter: &Pubkey, viewer: &Pubkey) -> Instruction {
+    let (view_key, _) = derive_view_key_address(program_id, counter, viewer);
+    let (viewer_authority, _) = derive_viewer_authority(viewer);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::GetCount,
+        ViewKeys {
+            counter: *counter,
+            view_key,
+            viewer: viewer_authority,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn set_step_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    step: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetStep { step },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn set_mode_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    mode: IncrementMode,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetMode { mode },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn pause_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::Pause,
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn resume_ix(program_id: &Pubkey, counter: &Pubkey, authority: &Pubkey) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::Resume,
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn add_delegate_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    delegate: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::AddDelegate {
+            delegate: *delegate,
+        },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn remove_delegate_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    delegate: &Pubkey,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::RemoveDelegate {
+            delegate: *delegate,
+        },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn create_multisig_ix(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    payer: &Pubkey,
+    threshold: u8,
+    signers: MultisigSigners,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::CreateMultisig { threshold, signers },
+        CreateKeys {
+            new_account: *multisig,
+            payer: *payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    )
+}
+
+/// Turns a reset, set, close or authority transfer built for a multisig
+/// authority into one its `signers` approve: the multisig itself no longer
+/// signs and the approving signers are appended.
+pub fn with_multisig_signers(mut instruction: Instruction, signers: &[Pubkey]) -> Instruction {
+    instruction.accounts[1].is_signer = false;
+    instruction.accounts.extend(
+        signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(*signer, true)),
+    );
+    instruction
+}
+
+/// `None` clears the unlock time.
+pub fn set_unlock_time_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    unlock_timestamp: Option<i64>,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetUnlockTime { unlock_timestamp },
+        mutate_accounts(counter, authority),
+    )
+}
+
+pub fn set_topics_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    topics: Topics,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetTopics { topics },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Returns the coupon's address alongside the instruction; hand out `code`
+/// and keep it off-chain until redemption.
+pub fn mint_coupon_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    code: &CouponCode,
+    amount: u64,
+) -> (Pubkey, Instruction) {
+    let code_hash = coupon_code_hash(code);
+    let (coupon, _) = derive_coupon_address(program_id, counter, &code_hash);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::MintCoupon { code_hash, amount },
+        MintCouponKeys {
+            counter: *counter,
+            authority: *authority,
+            coupon,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    );
+
+    (coupon, instruction)
+}
+
+/// `payer` is whoever minted the coupon; it gets the rent back.
+pub fn redeem_coupon_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    code: &CouponCode,
+    payer: &Pubkey,
+) -> Instruction {
+    let (coupon, _) = derive_coupon_address(program_id, counter, &coupon_code_hash(code));
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::RedeemCoupon { code: code.clone() },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new(coupon, false),
+            AccountMeta::new(*payer, false),
+        ],
+    )
+}
+
+/// Must follow `ed25519::verify_instruction` for the attester's signature
+/// over `borsh::to_vec(&snapshot)` in the same transaction.
+pub fn import_attestation_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    snapshot: AttestationSnapshot,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::ImportAttestation { snapshot },
+        vec![
+            AccountMeta::new(*counter, false),
+            AccountMeta::new_readonly(sysvar::instructions::ID, false),
+        ],
+    )
+}
+
+pub fn set_template_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    name: &Label32,
+    config: InitConfig,
+) -> (Pubkey, Instruction) {
+    let (template, _) = derive_template_address(program_id, admin, name);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::SetTemplate {
+            name: name.clone(),
+            config: Box::new(config),
+        },
+        SetTemplateKeys {
+            template,
+            admin: *admin,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    );
+
+    (template, instruction)
+}
+
+pub fn initialize_from_template_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    template: &Pubkey,
+    initial_value: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::InitializeFromTemplate { initial_value },
+        vec![
+            AccountMeta::new(*counter, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(*template, false),
+            AccountMeta::new_readonly(derive_global_config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Records the count at snapshot `index`, which must be the counter's current
+/// `snapshot_count`. Returns the snapshot's address alongside the
+/// instruction.
+pub fn snapshot_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    index: u64,
+) -> (Pubkey, Instruction) {
+    let (snapshot, _) = derive_snapshot_address(program_id, counter, index);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::Snapshot,
+        SnapshotKeys {
+            counter: *counter,
+            authority: *authority,
+            snapshot,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    );
+
+    (snapshot, instruction)
+}
+
+/// Returns the registry's address alongside the instruction. Push it,
+/// writable, onto initialize, increment and close instructions to have them
+/// counted.
+pub fn initialize_registry_ix(program_id: &Pubkey, payer: &Pubkey) -> (Pubkey, Instruction) {
+    let (registry, _) = derive_registry_address(program_id);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::InitializeRegistry,
+        vec![
+            AccountMeta::new(registry, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    );
+
+    (registry, instruction)
+}
+
+/// Returns the global config's address alongside the instruction. Push it
+/// onto any instruction to have the config consulted; the counter creations
+/// built here already carry it. Increments under a protocol fee also need
+/// `with_increment_fee` for its recipient.
+pub fn initialize_global_config_ix(
+    program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+) -> (Pubkey, Instruction) {
+    let (config, _) = derive_global_config_address(program_id);
+
+    let instruction = counter_instruction(
+        program_id,
+        CounterInstruction::InitializeGlobalConfig,
+        InitializeGlobalConfigKeys {
+            config,
+            authority: *upgrade_authority,
+            program_data: derive_program_data_address(program_id),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    );
+
+    (config, instruction)
+}
+
+pub fn set_global_config_ix(
+    program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+    params: GlobalParams,
+) -> Instruction {
+    let (config, _) = derive_global_config_address(program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetGlobalConfig { params },
+        SetGlobalConfigKeys {
+            config,
+            authority: *upgrade_authority,
+            program_data: derive_program_data_address(program_id),
+        }
+        .to_account_metas(),
+    )
+}
+
+/// Turns an increment of a fee-charging counter into one that pays the fee:
+/// the actor becomes writable and `fee_recipient`, the counter's stored
+/// recipient or fee vault, and the system program are appended.
+pub fn with_increment_fee(mut instruction: Instruction, fee_recipient: &Pubkey) -> Instruction {
+    instruction.accounts[1].is_writable = true;
+    instruction.accounts.extend([
+        AccountMeta::new(*fee_recipient, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ]);
+    instruction
+}
+
+pub fn withdraw_fees_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let (fee_vault, _) = derive_fee_vault_address(program_id, counter);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::WithdrawFees,
+        WithdrawFeesKeys {
+            counter: *counter,
+            authority: *authority,
+            fee_vault,
+            destination: *destination,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn set_owner_quota_ix(
+    program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+    max_counters_per_owner: u32,
+) -> Instruction {
+    let (config, _) = derive_global_config_address(program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetOwnerQuota {
+            max_counters_per_owner,
+        },
+        SetGlobalConfigKeys {
+            config,
+            authority: *upgrade_authority,
+            program_data: derive_program_data_address(program_id),
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn admin_freeze_ix(program_id: &Pubkey, admin: &Pubkey, counter: &Pubkey) -> Instruction {
+    let (registry, _) = derive_registry_address(program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::AdminFreeze,
+        AdminFreezeKeys {
+            registry,
+            admin: *admin,
+            counter: *counter,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn request_unfreeze_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    let (registry, _) = derive_registry_address(program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::RequestUnfreeze,
+        RequestUnfreezeKeys {
+            counter: *counter,
+            authority: *authority,
+            registry,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn burn_counter_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    counter: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    let (registry, _) = derive_registry_address(program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::BurnCounter,
+        BurnKeys {
+            registry,
+            admin: *admin,
+            counter: *counter,
+            destination: *destination,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn set_unfreeze_grace_period_ix(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    grace_secs: i64,
+) -> Instruction {
+    let (registry, _) = derive_registry_address(program_id);
+
+    counter_instruction(
+        program_id,
+        CounterInstruction::SetUnfreezeGracePeriod { grace_secs },
+        RegistryAdminKeys {
+            registry,
+            admin: *admin,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn initialize_signed_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    initial_value: i64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::InitializeSignedCounter { initial_value },
+        CreateKeys {
+            new_account: *counter,
+            payer: *payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn increment_signed_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementSigned { amount },
+        signed_counter_accounts(counter, authority),
+    )
+}
+
+pub fn decrement_signed_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::DecrementSigned { amount },
+        signed_counter_accounts(counter, authority),
+    )
+}
+
+fn signed_counter_accounts(counter: &Pubkey, authority: &Pubkey) -> Vec<AccountMeta> {
+    SignedCounterKeys {
+        counter: *counter,
+        authority: *authority,
+    }
+    .to_account_metas()
+}
+
+pub fn initialize_slot_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    slots: u8,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::InitializeSlotCounter { slots },
+        CreateKeys {
+            new_account: *counter,
+            payer: *payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(),
+    )
+}
+
+pub fn increment_slot_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    index: u8,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::IncrementSlot { index },
+        slot_counter_accounts(counter, authority),
+    )
+}
+
+pub fn decrement_slot_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    authority: &Pubkey,
+    index: u8,
+) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::DecrementSlot { index },
+        slot_counter_accounts(counter, authority),
+    )
+}
+
+fn slot_counter_accounts(counter: &Pubkey, authority: &Pubkey) -> Vec<AccountMeta> {
+    SlotCounterKeys {
+        counter: *counter,
+        authority: *authority,
+    }
+    .to_account_metas()
+}
+
+/// The slot comes back as return data; decode it with
+/// `interface::parse_value`.
+pub fn get_slot_ix(program_id: &Pubkey, counter: &Pubkey, index: u8) -> Instruction {
+    counter_instruction(
+        program_id,
+        CounterInstruction::GetSlot { index },
+        ReadKeys { counter: *counter }.to_account_metas(),
+    )
+}
+
+/// Appends `user`'s record for `counter` to an increment, so the increment
+/// is credited to it. The first one creates the record, paid by the actor,
+/// which becomes writable, and the system program is appended for that.
+pub fn with_user_record(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    user: &Pubkey,
+) -> Instruction {
+    let (record, _) = derive_user_record_address(program_id, counter, user);
+    instruction.accounts[1].is_writable = true;
+    instruction.accounts.extend([
+        AccountMeta::new(record, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ]);
+    instruction
+}
+
+/// Appends the actor's `token_account` for the token balance gate. For an
+/// increment of a counter with a token treasury, pass the treasury: the
+/// token account becomes writable and the treasury and `token_program`, the
+/// one owning both, are appended for the transfer. Other gated instructions
+/// only check the balance and take `None`.
+pub fn with_token_gate(
+    mut instruction: Instruction,
+    token_account: &Pubkey,
+    treasury: Option<&Pubkey>,
+    token_program: &Pubkey,
+) -> Instruction {
+    let Some(treasury) = treasury else {
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(*token_account, false));
+        return instruction;
+    };
+    instruction.accounts.extend([
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new(*treasury, false),
+        AccountMeta::new_readonly(*token_program, false),
+    ]);
+    instruction
+}
+
+/// Appends the instructions sysvar to an increment of a counter that counts
+/// once per transaction, which checks the transaction's other instructions.
+pub fn with_instructions_sysvar(mut instruction: Instruction) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(sysvar::instructions::ID, false));
+    instruction
+}
+
+/// Appends the log pages an increment of an event-log counter may write to:
+/// the head page `log_head` and the one after it, which the increment that
+/// fills the head moves on to. New pages are paid for by the actor, which
+/// becomes writable, and the system program is appended for that.
+pub fn with_event_log(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    log_head: u32,
+) -> Instruction {
+    instruction.accounts[1].is_writable = true;
+    for page in [log_head, log_head.saturating_add(1)] {
+        let (address, _) = derive_log_page_address(program_id, counter, page);
+        instruction.accounts.push(AccountMeta::new(address, false));
+    }
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(system_program::ID, false));
+    instruction
+}
+
+/// Appends `owner`'s stats account to a counter creation, which it needs
+/// while the global config sets a quota.
+pub fn with_owner_quota(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    let (owner_stats, _) = derive_owner_stats_address(program_id, owner);
+    instruction
+        .accounts
+        .push(AccountMeta::new(owner_stats, false));
+    instruction
+}
+
+/// Appends what a count change needs once it crosses the counter's
+/// `trigger` threshold: the target program, then the trigger's accounts.
+/// The counter's trigger authority is signed for by the program, not the
+/// transaction.
+pub fn with_trigger_accounts(
+    mut instruction: Instruction,
+    counter: &Pubkey,
+    trigger: &TriggerConfig,
+) -> Instruction {
+    let (trigger_authority, _) = derive_trigger_authority(&instruction.program_id, counter);
+
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(trigger.program_id, false));
+    instruction
+        .accounts
+        .extend(trigger.accounts.iter().map(|account| AccountMeta {
+            pubkey: account.pubkey,
+            is_signer: account.is_signer && account.pubkey != trigger_authority,
+            is_writable: account.is_writable,
+        }));
+    instruction
+}
+
+/// Appends `owner`'s index so a counter-creating instruction lists the new
+/// counter in it, or a closing one drops it. For closes, `owner` is whoever
+/// paid for the counter. The index is only kept up to date by instructions
+/// that carry it, so pass it on every create and close.
+pub fn with_owner_index(mut instruction: Instruction, owner: &Pubkey) -> Instruction {
+    let (index, _) = derive_owner_index_address(&instruction.program_id, owner);
+    instruction.accounts.push(AccountMeta::new(index, false));
+    instruction
+}
+
+/// Decodes a counter account in any layout the program still reads.
+pub fn deserialize_counter(data: &[u8]) -> Result<CounterAccount, ProgramError> {
+    CounterAccount::from_account_data(data)
+}
+
+pub fn deserialize_composite(data: &[u8]) -> Result<CompositeCounter, ProgramError> {
+    if data.get(..COMPOSITE_DISCRIMINATOR.len()) != Some(&COMPOSITE_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(CompositeCounter::try_from_slice(data)?)
+}
+
+pub fn deserialize_snapshot(data: &[u8]) -> Result<Snapshot, ProgramError> {
+    if data.get(..SNAPSHOT_DISCRIMINATOR.len()) != Some(&SNAPSHOT_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(Snapshot::try_from_slice(data)?)
+}
+
+pub fn deserialize_multisig(data: &[u8]) -> Result<Multisig, ProgramError> {
+    if data.get(..MULTISIG_DISCRIMINATOR.len()) != Some(&MULTISIG_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(Multisig::try_from_slice(data)?)
+}
+
+pub fn deserialize_registry(data: &[u8]) -> Result<Registry, ProgramError> {
+    if data.get(..REGISTRY_DISCRIMINATOR.len()) != Some(&REGISTRY_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(Registry::try_from_slice(data)?)
+}
+
+pub fn deserialize_owner_stats(data: &[u8]) -> Result<OwnerStats, ProgramError> {
+    if data.get(..OWNER_STATS_DISCRIMINATOR.len()) != Some(&OWNER_STATS_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(OwnerStats::try_from_slice(data)?)
+}
+
+pub fn deserialize_owner_index(data: &[u8]) -> Result<OwnerIndex, ProgramError> {
+    if data.get(..OWNER_INDEX_DISCRIMINATOR.len()) != Some(&OWNER_INDEX_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(OwnerIndex::try_from_slice(data)?)
+}
+
+pub fn deserialize_claim_bitmap(data: &[u8]) -> Result<ClaimBitmap, ProgramError> {
+    if data.get(..CLAIM_BITMAP_DISCRIMINATOR.len()) != Some(&CLAIM_BITMAP_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(ClaimBitmap::try_from_slice(data)?)
+}
+
+pub fn deserialize_user_record(data: &[u8]) -> Result<UserRecord, ProgramError> {
+    if data.get(..USER_RECORD_DISCRIMINATOR.len()) != Some(&USER_RECORD_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(UserRecord::try_from_slice(data)?)
+}
+
+pub fn deserialize_log_page(data: &[u8]) -> Result<LogPage, ProgramError> {
+    if data.get(..LOG_PAGE_DISCRIMINATOR.len()) != Some(&LOG_PAGE_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(LogPage::try_from_slice(data)?)
+}
+
+/// Why `replay_log` could not account for a counter's count.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error("log page {0} is missing, out of order or not the counter's")]
+    Page(u32),
+    #[error("record {index} of page {page} does not follow from the count before it")]
+    Gap { page: u32, index: usize },
+}
+
+/// Folds an event-log counter's pages, in order from page 0, back into the
+/// count, starting from the `initial_value` it was created with. Each
+/// record's stored count is checked against the running total, so a count
+/// changed other than by a logged increment is reported as a gap.
+pub fn replay_log(
+    counter: &Pubkey,
+    initial_value: u64,
+    pages: &[LogPage],
+) -> Result<u64, ReplayError> {
+    let mut count = initial_value;
+    for (expected, page) in (0u32..).zip(pages) {
+        if page.page != expected || page.counter != *counter {
+            return Err(ReplayError::Page(expected));
+        }
+        for (index, record) in page.records().iter().enumerate() {
+            count = count
+                .checked_add(record.amount)
+                .filter(|&next| next == record.count)
+                .ok_or(ReplayError::Gap {
+                    page: page.page,
+                    index,
+                })?;
+        }
+    }
+    Ok(count)
+}
+
+pub fn deserialize_global_config(data: &[u8]) -> Result<GlobalConfig, ProgramError> {
+    if data.get(..GLOBAL_CONFIG_DISCRIMINATOR.len()) != Some(&GLOBAL_CONFIG_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(GlobalConfig::try_from_slice(data)?)
+}
+
+pub fn deserialize_signed_counter(data: &[u8]) -> Result<SignedCounter, ProgramError> {
+    if data.get(..SIGNED_COUNTER_DISCRIMINATOR.len()) != Some(&SIGNED_COUNTER_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(SignedCounter::try_from_slice(data)?)
+}
+
+pub fn deserialize_slot_counter(data: &[u8]) -> Result<SlotCounter, ProgramError> {
+    if data.get(..SLOT_COUNTER_DISCRIMINATOR.len()) != Some(&SLOT_COUNTER_DISCRIMINATOR[..]) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(SlotCounter::try_from_slice(data)?)
+}
+
+/// Any account the program owns that has a decoder here.
+#[derive(Debug)]
+pub enum ProgramAccount {
+    Counter(Box<CounterAccount>),
+    Composite(CompositeCounter),
+    Snapshot(Snapshot),
+    Multisig(Multisig),
+    Registry(Registry),
+    OwnerStats(OwnerStats),
+    OwnerIndex(OwnerIndex),
+    SignedCounter(SignedCounter),
+    SlotCounter(SlotCounter),
+    ClaimBitmap(Box<ClaimBitmap>),
+    UserRecord(UserRecord),
+    GlobalConfig(GlobalConfig),
+    LogPage(Box<LogPage>),
+}
+
+/// Decodes an account by its discriminator, `None` for account types without
+/// a decoder (templates, coupons, view keys, fee vaults) and bare counters.
+pub fn decode_account(data: &[u8]) -> Option<ProgramAccount> {
+    let discriminator: [u8; 8] = data.get(..8)?.try_into().ok()?;
+    let account = match discriminator {
+        COUNTER_DISCRIMINATOR | COUNTER_DISCRIMINATOR_V0 => {
+            ProgramAccount::Counter(Box::new(deserialize_counter(data).ok()?))
+        }
+        COMPOSITE_DISCRIMINATOR => ProgramAccount::Composite(deserialize_composite(data).ok()?),
+        SNAPSHOT_DISCRIMINATOR => ProgramAccount::Snapshot(deserialize_snapshot(data).ok()?),
+        MULTISIG_DISCRIMINATOR => ProgramAccount::Multisig(deserialize_multisig(data).ok()?),
+        REGISTRY_DISCRIMINATOR => ProgramAccount::Registry(deserialize_registry(data).ok()?),
+        OWNER_STATS_DISCRIMINATOR => {
+            ProgramAccount::OwnerStats(deserialize_owner_stats(data).ok()?)
+        }
+        OWNER_INDEX_DISCRIMINATOR => {
+            ProgramAccount::OwnerIndex(deserialize_owner_index(data).ok()?)
+        }
+        SIGNED_COUNTER_DISCRIMINATOR => {
+            ProgramAccount::SignedCounter(deserialize_signed_counter(data).ok()?)
+        }
+        SLOT_COUNTER_DISCRIMINATOR => {
+            ProgramAccount::SlotCounter(deserialize_slot_counter(data).ok()?)
+        }
+        CLAIM_BITMAP_DISCRIMINATOR => {
+            ProgramAccount::ClaimBitmap(Box::new(deserialize_claim_bitmap(data).ok()?))
+        }
+        USER_RECORD_DISCRIMINATOR => {
+            ProgramAccount::UserRecord(deserialize_user_record(data).ok()?)
+        }
+        GLOBAL_CONFIG_DISCRIMINATOR => {
+            ProgramAccount::GlobalConfig(deserialize_global_config(data).ok()?)
+        }
+        LOG_PAGE_DISCRIMINATOR => {
+            ProgramAccount::LogPage(Box::new(deserialize_log_page(data).ok()?))
+        }
+        _ => return None,
+    };
+    Some(account)
+}
+
+/// Picks the counters out of `accounts`, such as a `getProgramAccounts`
+/// response, skipping every other account type.
+pub fn decode_counters<'a>(
+    accounts: impl IntoIterator<Item = (Pubkey, &'a [u8])>,
+) -> Vec<(Pubkey, CounterAccount)> {
+    accounts
+        .into_iter()
+        .filter_map(|(address, data)| match decode_account(data)? {
+            ProgramAccount::Counter(counter) => Some((address, *counter)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `Reap` for each counter whose TTL ran out by `slot`, rewarding `reaper`.
+/// Paused, admin-frozen and finalized counters are left alone, the program
+/// rejects reaping them.
+pub fn reap_expired_ixs(
+    program_id: &Pubkey,
+    reaper: &Pubkey,
+    counters: &[(Pubkey, CounterAccount)],
+    slot: u64,
+) -> Vec<Instruction> {
+    counters
+        .iter()
+        .filter(|(_, counter)| {
+            counter.is_expired(slot)
+                && !counter.is_paused
+                && !counter.admin_frozen
+                && !counter.is_finalized
+        })
+        .map(|(address, counter)| reap_ix(program_id, address, &counter.payer, reaper))
+        .collect()
+}
+
+/// Prefix the runtime puts on `sol_log_data` output in transaction logs.
+pub const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Parses a counter event from one transaction log line, returning `None`
+/// for any other line.
+pub fn parse_event_log(line: &str) -> Option<LoggedEvent> {
+    let fields = line
+        .strip_prefix(PROGRAM_DATA_PREFIX)?
+        .split(' ')
+        .map(|field| STANDARD.decode(field).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    decode_fields(&fields)
+}
@@ -0,0 +1,136 @@
+//! Instruction builders and `invoke_signed` wrappers for programs that call
+//! the counter program. Depend on this crate with the `cpi` feature, which
+//! also leaves out its entrypoint, instead of copying the instruction
+//! encoding.
+//!
+//! `signer_seeds` may be empty when every signer already signed the outer
+//! transaction; pass the caller's PDA seeds when a PDA is the new counter or
+//! the acting authority.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::accounts::{CreateKeys, MutateKeys};
+use crate::addresses::derive_global_config_address;
+use crate::instruction::{CounterInstruction, InitConfig};
+
+/// Creations also pass the counter program's global config PDA, which need
+/// not be initialized.
+pub fn initialize_counter_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    payer: &Pubkey,
+    initial_value: u64,
+    config: InitConfig,
+) -> Instruction {
+    let mut accounts = CreateKeys {
+        new_account: *counter,
+        payer: *payer,
+        system_program: system_program::ID,
+    }
+    .to_account_metas();
+    let (global_config, _) = derive_global_config_address(program_id);
+    accounts.push(AccountMeta::new_readonly(global_config, false));
+
+    Instruction::new_with_bytes(
+        *program_id,
+        &CounterInstruction::InitializeCounter {
+            initial_value,
+            config: Box::new(config),
+        }
+        .pack(),
+        accounts,
+    )
+}
+
+/// Adds the counter's step.
+pub fn increment_ix(program_id: &Pubkey, counter: &Pubkey, actor: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &CounterInstruction::IncrementCounter.pack(),
+        mutate_accounts(counter, actor),
+    )
+}
+
+pub fn increment_by_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &CounterInstruction::IncrementBy { amount }.pack(),
+        mutate_accounts(counter, actor),
+    )
+}
+
+fn mutate_accounts(counter: &Pubkey, actor: &Pubkey) -> Vec<AccountMeta> {
+    MutateKeys {
+        counter: *counter,
+        actor: *actor,
+    }
+    .to_account_metas()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_counter<'info>(
+    counter_program: &AccountInfo<'info>,
+    counter: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    global_config: &AccountInfo<'info>,
+    initial_value: u64,
+    config: InitConfig,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &initialize_counter_ix(
+            counter_program.key,
+            counter.key,
+            payer.key,
+            initial_value,
+            config,
+        ),
+        &[
+            counter.clone(),
+            payer.clone(),
+            system_program.clone(),
+            global_config.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn increment<'info>(
+    counter_program: &AccountInfo<'info>,
+    counter: &AccountInfo<'info>,
+    actor: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &increment_ix(counter_program.key, counter.key, actor.key),
+        &[counter.clone(), actor.clone()],
+        signer_seeds,
+    )
+}
+
+pub fn increment_by<'info>(
+    counter_program: &AccountInfo<'info>,
+    counter: &AccountInfo<'info>,
+    actor: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &increment_by_ix(counter_program.key, counter.key, actor.key, amount),
+        &[counter.clone(), actor.clone()],
+        signer_seeds,
+    )
+}
@@ -0,0 +1,459 @@
+//! Static per-instruction metadata. The same table drives the account checks
+//! and handler `process_instruction` dispatches to and the IDL emitted by
+//! [`crate::idl::idl_json`], so the two cannot drift apart. Entries are
+//! indexed by the instruction's tag byte. Account lists declared with
+//! `instruction_accounts!` come straight from their `accounts` structs.
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::accounts::{
+    AdminFreezeAccounts, BurnAccounts, CloseExpiredAccounts, CounterAccounts, CreateAccounts,
+    CreatePdaAccounts, IncrementWithProofAccounts, InitializeGlobalConfigAccounts, MigrateAccounts,
+    MintCouponAccounts, MutateAccounts, ReadAccounts, RegistryAdminAccounts,
+    RequestUnfreezeAccounts, SetGlobalConfigAccounts, SetTemplateAccounts, SignedCounterAccounts,
+    SlotCounterAccounts, SnapshotAccounts, TransferCountAccounts, ViewAccounts, ViewKeyAccounts,
+    WithdrawFeesAccounts,
+};
+use crate::instruction::CounterInstruction;
+use crate::processor::handlers;
+
+/// Instructions that are always compiled in.
+pub const FEATURE_BASE: u32 = 0;
+
+/// State-manipulation instructions for QA on test clusters.
+pub const FEATURE_DEVNET_TOOLS: u32 = 1 << 0;
+
+/// Feature bits available in this build.
+pub const ENABLED_FEATURES: u32 = if cfg!(feature = "devnet-tools") {
+    FEATURE_DEVNET_TOOLS
+} else {
+    FEATURE_BASE
+};
+
+/// One fixed-position account an instruction requires. Optional trailing
+/// accounts (gate, trigger and composite source accounts) are not listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountSpec {
+    pub name: &'static str,
+    pub writable: bool,
+    pub signer: bool,
+}
+
+/// Runs a decoded instruction once its accounts pass `validate`.
+pub type Handler = fn(&Pubkey, &[AccountInfo], CounterInstruction) -> ProgramResult;
+
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionMeta {
+    pub tag: u8,
+    pub name: &'static str,
+    pub accounts: &'static [AccountSpec],
+    /// Feature bits that must all be enabled for the instruction to run.
+    pub feature: u32,
+    pub deprecated: bool,
+    /// Authority-only change to a live counter, recorded in its access log.
+    pub privileged: bool,
+    pub handler: Handler,
+}
+
+const fn account(name: &'static str, writable: bool, signer: bool) -> AccountSpec {
+    AccountSpec {
+        name,
+        writable,
+        signer,
+    }
+}
+
+const fn instruction(
+    tag: u8,
+    name: &'static str,
+    accounts: &'static [AccountSpec],
+    handler: Handler,
+) -> InstructionMeta {
+    InstructionMeta {
+        tag,
+        name,
+        accounts,
+        feature: FEATURE_BASE,
+        deprecated: false,
+        privileged: false,
+        handler,
+    }
+}
+
+const fn gated(meta: InstructionMeta, feature: u32) -> InstructionMeta {
+    InstructionMeta { feature, ..meta }
+}
+
+const fn privileged(meta: InstructionMeta) -> InstructionMeta {
+    InstructionMeta {
+        privileged: true,
+        ..meta
+    }
+}
+
+const CREATE: &[AccountSpec] = CreateAccounts::SPEC;
+const CREATE_PDA: &[AccountSpec] = CreatePdaAccounts::SPEC;
+const MUTATE: &[AccountSpec] = MutateAccounts::SPEC;
+const AUTHORITY: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("authority", false, true),
+];
+/// The authority either signs or is a `Multisig` whose signers follow.
+const MULTISIG_AUTHORITY: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("authority", false, false),
+];
+const COUNTER: &[AccountSpec] = CounterAccounts::SPEC;
+const SYNC: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("mint", false, false),
+];
+const CLOSE: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("authority", false, true),
+    account("destination", true, false),
+];
+const MULTISIG_CLOSE: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("authority", false, false),
+    account("destination", true, false),
+];
+const CREATE_MULTISIG: &[AccountSpec] = CreateAccounts::SPEC;
+const MIGRATE: &[AccountSpec] = MigrateAccounts::SPEC;
+const GRANT_VIEW: &[AccountSpec] = &[
+    account("counter", false, false),
+    account("authority", true, true),
+    account("view_key", true, false),
+    account("system_program", false, false),
+];
+const REVOKE_VIEW: &[AccountSpec] = ViewKeyAccounts::SPEC;
+const GET_COUNT: &[AccountSpec] = ViewAccounts::SPEC;
+const RESIZE: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("authority", true, true),
+    account("system_program", false, false),
+];
+const MINT_COUPON: &[AccountSpec] = MintCouponAccounts::SPEC;
+const REDEEM_COUPON: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("coupon", true, false),
+    account("payer", true, false),
+];
+const IMPORT: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("instructions_sysvar", false, false),
+];
+const BATCH: &[AccountSpec] = &[account("actor", false, false)];
+const TEMPLATE: &[AccountSpec] = SetTemplateAccounts::SPEC;
+const FROM_TEMPLATE: &[AccountSpec] = &[
+    account("counter", true, true),
+    account("payer", true, true),
+    account("system_program", false, false),
+    account("template", false, false),
+];
+const REGISTRY: &[AccountSpec] = &[
+    account("registry", true, false),
+    account("payer", true, true),
+    account("system_program", false, false),
+];
+const SNAPSHOT: &[AccountSpec] = SnapshotAccounts::SPEC;
+const REGISTRY_ADMIN: &[AccountSpec] = RegistryAdminAccounts::SPEC;
+const WITHDRAW_FEES: &[AccountSpec] = WithdrawFeesAccounts::SPEC;
+const ADMIN_FREEZE: &[AccountSpec] = AdminFreezeAccounts::SPEC;
+const REQUEST_UNFREEZE: &[AccountSpec] = RequestUnfreezeAccounts::SPEC;
+const BURN: &[AccountSpec] = BurnAccounts::SPEC;
+const SIGNED_COUNTER: &[AccountSpec] = SignedCounterAccounts::SPEC;
+const SLOT_COUNTER: &[AccountSpec] = SlotCounterAccounts::SPEC;
+const READ: &[AccountSpec] = ReadAccounts::SPEC;
+const TRANSFER_COUNT: &[AccountSpec] = TransferCountAccounts::SPEC;
+const CLOSE_EXPIRED: &[AccountSpec] = CloseExpiredAccounts::SPEC;
+const INCREMENT_WITH_PROOF: &[AccountSpec] = IncrementWithProofAccounts::SPEC;
+const INITIALIZE_GLOBAL_CONFIG: &[AccountSpec] = InitializeGlobalConfigAccounts::SPEC;
+const SET_GLOBAL_CONFIG: &[AccountSpec] = SetGlobalConfigAccounts::SPEC;
+const REAP: &[AccountSpec] = &[
+    account("counter", true, false),
+    account("payer", true, false),
+    account("reaper", true, true),
+];
+
+pub static INSTRUCTIONS: &[InstructionMeta] = &[
+    instruction(0, "InitializeCounter", CREATE, handlers::initialize_counter),
+    instruction(1, "IncrementCounter", MUTATE, handlers::increment_counter),
+    privileged(instruction(
+        2,
+        "ResetCircuitBreaker",
+        AUTHORITY,
+        handlers::reset_circuit_breaker,
+    )),
+    privileged(instruction(
+        3,
+        "CommitIncrement",
+        AUTHORITY,
+        handlers::commit_increment,
+    )),
+    instruction(4, "RevealIncrement", COUNTER, handlers::reveal_increment),
+    instruction(
+        5,
+        "IncrementCommitted",
+        MUTATE,
+        handlers::increment_committed,
+    ),
+    instruction(6, "IncrementMetric", MUTATE, handlers::increment_metric),
+    instruction(
+        7,
+        "InitializeComposite",
+        CREATE,
+        handlers::initialize_composite,
+    ),
+    instruction(8, "RefreshComposite", COUNTER, handlers::refresh_composite),
+    instruction(9, "DecrementCounter", MUTATE, handlers::decrement_counter),
+    instruction(10, "SyncMintSupply", SYNC, handlers::sync_mint_supply),
+    instruction(11, "IncrementBy", MUTATE, handlers::increment_by),
+    instruction(12, "DecrementBy", MUTATE, handlers::decrement_by),
+    privileged(instruction(
+        13,
+        "TransferAuthority",
+        MULTISIG_AUTHORITY,
+        handlers::transfer_authority,
+    )),
+    instruction(14, "CloseCounter", MULTISIG_CLOSE, handlers::close_counter),
+    instruction(
+        15,
+        "InitializeCounterPda",
+        CREATE_PDA,
+        handlers::initialize_counter_pda,
+    ),
+    privileged(instruction(
+        16,
+        "ResetCounter",
+        MULTISIG_AUTHORITY,
+        handlers::reset_counter,
+    )),
+    privileged(instruction(
+        17,
+        "SetCounter",
+        MULTISIG_AUTHORITY,
+        handlers::set_counter,
+    )),
+    privileged(gated(
+        instruction(18, "ForceSet", AUTHORITY, handlers::force_set),
+        FEATURE_DEVNET_TOOLS,
+    )),
+    privileged(gated(
+        instruction(
+            19,
+            "FastForwardCooldown",
+            AUTHORITY,
+            handlers::fast_forward_cooldown,
+        ),
+        FEATURE_DEVNET_TOOLS,
+    )),
+    privileged(gated(
+        instruction(20, "ResetStats", AUTHORITY, handlers::reset_stats),
+        FEATURE_DEVNET_TOOLS,
+    )),
+    instruction(21, "Reap", REAP, handlers::reap),
+    instruction(22, "MigrateAccount", MIGRATE, handlers::migrate_account),
+    privileged(instruction(
+        23,
+        "LockCount",
+        AUTHORITY,
+        handlers::lock_count,
+    )),
+    instruction(24, "UnlockCount", COUNTER, handlers::unlock_count),
+    instruction(25, "GrantViewKey", GRANT_VIEW, handlers::grant_view_key),
+    instruction(26, "RevokeViewKey", REVOKE_VIEW, handlers::revoke_view_key),
+    instruction(27, "GetCount", GET_COUNT, handlers::get_count),
+    privileged(instruction(28, "SetTopics", RESIZE, handlers::set_topics)),
+    instruction(
+        29,
+        "InitializeAndIncrement",
+        CREATE,
+        handlers::initialize_and_increment,
+    ),
+    privileged(instruction(30, "SetStep", AUTHORITY, handlers::set_step)),
+    instruction(31, "MintCoupon", MINT_COUPON, handlers::mint_coupon),
+    instruction(32, "RedeemCoupon", REDEEM_COUPON, handlers::redeem_coupon),
+    instruction(
+        33,
+        "ImportAttestation",
+        IMPORT,
+        handlers::import_attestation,
+    ),
+    instruction(34, "IncrementMany", BATCH, handlers::increment_many),
+    privileged(instruction(35, "SkimExcess", CLOSE, handlers::skim_excess)),
+    instruction(36, "SetTemplate", TEMPLATE, handlers::set_template),
+    instruction(
+        37,
+        "InitializeFromTemplate",
+        FROM_TEMPLATE,
+        handlers::initialize_from_template,
+    ),
+    instruction(
+        38,
+        "InitializeRegistry",
+        REGISTRY,
+        handlers::initialize_registry,
+    ),
+    privileged(instruction(39, "SetMode", AUTHORITY, handlers::set_mode)),
+    privileged(instruction(40, "Pause", AUTHORITY, handlers::pause)),
+    privileged(instruction(41, "Resume", AUTHORITY, handlers::resume)),
+    instruction(
+        42,
+        "InitializeCounterById",
+        CREATE_PDA,
+        handlers::initialize_counter_by_id,
+    ),
+    privileged(instruction(43, "Snapshot", SNAPSHOT, handlers::snapshot)),
+    privileged(instruction(
+        44,
+        "AddDelegate",
+        RESIZE,
+        handlers::add_delegate,
+    )),
+    privileged(instruction(
+        45,
+        "RemoveDelegate",
+        RESIZE,
+        handlers::remove_delegate,
+    )),
+    instruction(
+        46,
+        "CreateMultisig",
+        CREATE_MULTISIG,
+        handlers::create_multisig,
+    ),
+    privileged(instruction(
+        47,
+        "SetUnlockTime",
+        AUTHORITY,
+        handlers::set_unlock_time,
+    )),
+    instruction(
+        48,
+        "SetOwnerQuota",
+        SET_GLOBAL_CONFIG,
+        handlers::set_owner_quota,
+    ),
+    privileged(instruction(
+        49,
+        "WithdrawFees",
+        WITHDRAW_FEES,
+        handlers::withdraw_fees,
+    )),
+    instruction(50, "Resize", MIGRATE, handlers::resize),
+    instruction(51, "AdminFreeze", ADMIN_FREEZE, handlers::admin_freeze),
+    privileged(instruction(
+        52,
+        "RequestUnfreeze",
+        REQUEST_UNFREEZE,
+        handlers::request_unfreeze,
+    )),
+    instruction(53, "BurnCounter", BURN, handlers::burn_counter),
+    instruction(
+        54,
+        "SetUnfreezeGracePeriod",
+        REGISTRY_ADMIN,
+        handlers::set_unfreeze_grace_period,
+    ),
+    instruction(
+        55,
+        "InitializeSignedCounter",
+        CREATE,
+        handlers::initialize_signed_counter,
+    ),
+    instruction(
+        56,
+        "IncrementSigned",
+        SIGNED_COUNTER,
+        handlers::increment_signed,
+    ),
+    instruction(
+        57,
+        "DecrementSigned",
+        SIGNED_COUNTER,
+        handlers::decrement_signed,
+    ),
+    instruction(
+        58,
+        "InitializeSlotCounter",
+        CREATE,
+        handlers::initialize_slot_counter,
+    ),
+    instruction(59, "IncrementSlot", SLOT_COUNTER, handlers::increment_slot),
+    instruction(60, "DecrementSlot", SLOT_COUNTER, handlers::decrement_slot),
+    instruction(61, "GetSlot", READ, handlers::get_slot),
+    instruction(
+        62,
+        "ConditionalIncrement",
+        MUTATE,
+        handlers::conditional_increment,
+    ),
+    instruction(
+        63,
+        "TransferCount",
+        TRANSFER_COUNT,
+        handlers::transfer_count,
+    ),
+    instruction(64, "CloseExpired", CLOSE_EXPIRED, handlers::close_expired),
+    instruction(
+        65,
+        "IncrementWithProof",
+        INCREMENT_WITH_PROOF,
+        handlers::increment_with_proof,
+    ),
+    instruction(
+        66,
+        "InitializeGlobalConfig",
+        INITIALIZE_GLOBAL_CONFIG,
+        handlers::initialize_global_config,
+    ),
+    instruction(
+        67,
+        "SetGlobalConfig",
+        SET_GLOBAL_CONFIG,
+        handlers::set_global_config,
+    ),
+    instruction(68, "ReadCount", READ, handlers::read_count),
+];
+
+pub fn meta(tag: u8) -> Option<&'static InstructionMeta> {
+    INSTRUCTIONS.get(tag as usize)
+}
+
+impl InstructionMeta {
+    pub fn is_enabled(&self) -> bool {
+        self.feature & !ENABLED_FEATURES == 0
+    }
+
+    /// Checks the feature bits and the fixed accounts' count, signer and
+    /// writable flags. Ownership and data checks stay with the handlers.
+    pub fn validate(&self, accounts: &[AccountInfo]) -> ProgramResult {
+        if !self.is_enabled() {
+            msg!("{} is not available in this build", self.name);
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if self.deprecated {
+            msg!("{} is deprecated", self.name);
+        }
+        if accounts.len() < self.accounts.len() {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        for (spec, account) in self.accounts.iter().zip(accounts) {
+            if spec.signer && !account.is_signer {
+                msg!("{} account must sign", spec.name);
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if spec.writable && !account.is_writable {
+                msg!("{} account must be writable", spec.name);
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        Ok(())
+    }
+}
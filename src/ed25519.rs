@@ -0,0 +1,109 @@
+//! Signature checks through the Ed25519 precompile. The runtime verifies the
+//! precompile instruction before the program runs, so the program only has
+//! to confirm that the instruction right before it vouches for the expected
+//! key and message.
+
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    instruction::Instruction,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+
+use crate::error::CounterError;
+
+// layout of a single-signature precompile instruction with everything inline
+const OFFSETS_START: usize = 2;
+const OFFSETS_LEN: usize = 14;
+const PUBKEY_OFFSET: usize = OFFSETS_START + OFFSETS_LEN;
+const SIGNATURE_OFFSET: usize = PUBKEY_OFFSET + 32;
+const MESSAGE_OFFSET: usize = SIGNATURE_OFFSET + 64;
+/// Instruction index meaning "this instruction's own data".
+const INLINE: u16 = u16::MAX;
+
+/// Builds the precompile instruction `verify_preceding_signature` accepts.
+/// Place it immediately before the instruction that checks it.
+pub fn verify_instruction(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let mut data = Vec::with_capacity(MESSAGE_OFFSET + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for field in [
+        SIGNATURE_OFFSET as u16,
+        INLINE,
+        PUBKEY_OFFSET as u16,
+        INLINE,
+        MESSAGE_OFFSET as u16,
+        message.len() as u16,
+        INLINE,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction::new_with_bytes(ed25519_program::ID, &data, vec![])
+}
+
+/// Requires the instruction before the current one to be an Ed25519
+/// precompile check of `message` signed by `signer`.
+pub fn verify_preceding_signature(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<(), ProgramError> {
+    let current = load_current_index_checked(instructions_sysvar)?;
+    let Some(index) = current.checked_sub(1) else {
+        msg!("Expected an Ed25519 instruction before this one");
+        return Err(CounterError::InvalidAttestation.into());
+    };
+    let instruction = load_instruction_at_checked(index as usize, instructions_sysvar)?;
+
+    if instruction.program_id != ed25519_program::ID
+        || read_signed(&instruction.data) != Some((signer.as_ref(), message))
+    {
+        msg!("Preceding instruction does not verify this signer and message");
+        return Err(CounterError::InvalidAttestation.into());
+    }
+
+    Ok(())
+}
+
+/// The public key and message of a single inline signature check.
+fn read_signed(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if data.first() != Some(&1) {
+        return None;
+    }
+
+    let offsets: Vec<u16> = data
+        .get(OFFSETS_START..PUBKEY_OFFSET)?
+        .chunks_exact(2)
+        .map(|field| u16::from_le_bytes([field[0], field[1]]))
+        .collect();
+    let [
+        _,
+        signature_ix,
+        pubkey_offset,
+        pubkey_ix,
+        message_offset,
+        message_len,
+        message_ix,
+    ] = offsets[..]
+    else {
+        return None;
+    };
+
+    // data pulled from other instructions could say anything
+    if [signature_ix, pubkey_ix, message_ix] != [INLINE; 3] {
+        return None;
+    }
+
+    let pubkey_offset = pubkey_offset as usize;
+    let message_offset = message_offset as usize;
+    Some((
+        data.get(pubkey_offset..pubkey_offset + 32)?,
+        data.get(message_offset..message_offset + message_len as usize)?,
+    ))
+}
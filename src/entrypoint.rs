@@ -1,7 +1,4 @@
-use solana_program::{
-    entrypoint
-};
 use crate::processor::process_instruction;
+use solana_program::entrypoint;
 
 entrypoint!(process_instruction);
-
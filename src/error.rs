@@ -0,0 +1,122 @@
+#[cfg(feature = "client")]
+use num_derive::FromPrimitive;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use thiserror::Error;
+
+/// Program-specific failures, surfaced to clients as
+/// `ProgramError::Custom(code)` where `code` is the variant's discriminant.
+/// New variants go at the end so existing codes keep their meaning.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "client", derive(FromPrimitive))]
+pub enum CounterError {
+    #[error("Counter would overflow")]
+    Overflow,
+    #[error("Counter would go below zero")]
+    Underflow,
+    #[error("Account is not an initialized counter")]
+    AccountNotInitialized,
+    #[error("Account is already initialized")]
+    AlreadyInitialized,
+    #[error("Signer is not allowed to perform this instruction")]
+    Unauthorized,
+    #[error("Counter is frozen by its circuit breaker")]
+    CounterFrozen,
+    #[error("Counter is in commitment mode")]
+    CommitmentMode,
+    #[error("Counter is not in commitment mode")]
+    NotCommitted,
+    #[error("Commitment preimage does not match")]
+    CommitmentMismatch,
+    #[error("Counter mirrors a mint supply")]
+    MirroredCounter,
+    #[error("Counter configuration is invalid")]
+    InvalidConfig,
+    #[error("Metric does not exist")]
+    MetricNotFound,
+    #[error("Metric would exceed its maximum")]
+    MetricLimitExceeded,
+    #[error("Composite source account does not match its configuration")]
+    SourceMismatch,
+    #[error("Counter address does not match its seeds")]
+    InvalidCounterAddress,
+    #[error("Mint does not match the counter's mirror mint")]
+    MintMismatch,
+    #[error("Token account does not match the gated mint and actor")]
+    TokenAccountMismatch,
+    #[error("Token balance is below the gate minimum")]
+    InsufficientTokenBalance,
+    #[error("Counter is cooling down")]
+    CooldownActive,
+    #[error("Account holds a different account type")]
+    WrongAccountType,
+    #[error("Counter has not expired")]
+    NotExpired,
+    #[error("Counter must be migrated to the current layout first")]
+    MigrationRequired,
+    #[error("Change would spend locked points")]
+    InsufficientLiquid,
+    #[error("Lock has not expired")]
+    LockActive,
+    #[error("Program has no view key for this counter")]
+    ViewNotGranted,
+    #[error("Coupon does not exist or was already redeemed")]
+    InvalidCoupon,
+    #[error("Attestation is not signed by the mirror's attester or names another counter")]
+    InvalidAttestation,
+    #[error("Attestation is older than the mirror allows")]
+    StaleAttestation,
+    #[error("Counter would exceed its maximum value")]
+    CapExceeded,
+    #[error("Value is longer than its field allows")]
+    LengthExceeded,
+    #[error("Counter is paused")]
+    CounterPaused,
+    #[error("Not enough compute left to finish the instruction")]
+    InsufficientCompute,
+    #[error("Not enough multisig signers approved the instruction")]
+    MultisigThresholdNotMet,
+    #[error("Counter is locked until its unlock time")]
+    CounterLocked,
+    #[error("Owner has created as many counters as the registry allows")]
+    OwnerQuotaExceeded,
+    #[error("Counter is frozen by the registry admin")]
+    FrozenByAdmin,
+    #[error("Signed counter would overflow")]
+    SignedOverflow,
+    #[error("Signed counter would underflow")]
+    SignedUnderflow,
+    #[error("Slot index is out of range")]
+    SlotOutOfRange,
+    #[error("Counter no longer holds the expected value")]
+    StaleValue,
+    #[error("Expected the system program account")]
+    NotSystemProgram,
+    #[error("Payer cannot fund the new account's rent exemption")]
+    InsufficientRentFunds,
+    #[error("Counter has expired and is read-only")]
+    CounterExpired,
+    #[error("Merkle proof does not show the signer is on the allowlist")]
+    InvalidMerkleProof,
+    #[error("Allowlist entry has already been claimed")]
+    AlreadyClaimed,
+    #[error("Program is paused by its global config")]
+    ProgramPaused,
+    #[error("Instruction tag is not a known instruction")]
+    UnknownInstruction,
+    #[error("Counter may only be incremented once per transaction")]
+    DuplicateIncrement,
+    #[error("Counter has used all of its uses and can only be closed")]
+    CounterFinalized,
+}
+
+impl From<CounterError> for ProgramError {
+    fn from(e: CounterError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for CounterError {
+    fn type_of() -> &'static str {
+        "CounterError"
+    }
+}
@@ -0,0 +1,110 @@
+//! Structured events for indexers. Each event is logged through
+//! `sol_log_data` as one `Program data:` line whose fields are the counter
+//! address, the counter's topics, then the Borsh-encoded `CounterEvent`.
+//! Routers can match on the leading fields without decoding the last one.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CounterInitialized {
+    pub authority: Pubkey,
+    pub count: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CounterIncremented {
+    pub old: u64,
+    pub new: u64,
+}
+
+/// Any change that lowers the count, including sets and mint syncs.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CounterDecremented {
+    pub old: u64,
+    pub new: u64,
+}
+
+/// The counter was closed or reaped and its rent paid out.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CounterClosed {
+    pub count: u64,
+    pub lamports: u64,
+}
+
+/// Lamports were sent straight to the counter account.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LamportsDonated {
+    pub expected: u64,
+    pub observed: u64,
+}
+
+/// Donated lamports sent to a strict counter's excess destination on close.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExcessRouted {
+    pub destination: Pubkey,
+    pub lamports: u64,
+}
+
+/// New variants go at the end so existing tags keep their meaning.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CounterEvent {
+    Initialized(CounterInitialized),
+    Incremented(CounterIncremented),
+    Decremented(CounterDecremented),
+    Closed(CounterClosed),
+    LamportsDonated(LamportsDonated),
+    ExcessRouted(ExcessRouted),
+}
+
+impl CounterEvent {
+    /// The event for a count moving from `old` to `new`, if it moved.
+    pub fn count_changed(old: u64, new: u64) -> Option<Self> {
+        match new.cmp(&old) {
+            std::cmp::Ordering::Greater => Some(Self::Incremented(CounterIncremented { old, new })),
+            std::cmp::Ordering::Less => Some(Self::Decremented(CounterDecremented { old, new })),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+/// An event as read back from a log line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedEvent {
+    pub counter: Pubkey,
+    pub topics: Vec<Vec<u8>>,
+    pub event: CounterEvent,
+}
+
+/// The `sol_log_data` fields for `event`.
+pub fn event_fields<T: AsRef<[u8]>>(
+    counter: &Pubkey,
+    topics: &[T],
+    event: &CounterEvent,
+) -> Vec<Vec<u8>> {
+    let mut fields = Vec::with_capacity(topics.len() + 2);
+    fields.push(counter.to_bytes().to_vec());
+    fields.extend(topics.iter().map(|topic| topic.as_ref().to_vec()));
+    fields.push(borsh::to_vec(event).expect("events always serialize"));
+    fields
+}
+
+/// Reverses `event_fields`, returning `None` for data logged by anything
+/// else.
+pub fn decode_fields<T: AsRef<[u8]>>(fields: &[T]) -> Option<LoggedEvent> {
+    let (first, rest) = fields.split_first()?;
+    let (last, topics) = rest.split_last()?;
+
+    Some(LoggedEvent {
+        counter: Pubkey::try_from(first.as_ref()).ok()?,
+        topics: topics.iter().map(|topic| topic.as_ref().to_vec()).collect(),
+        event: CounterEvent::try_from_slice(last.as_ref()).ok()?,
+    })
+}
+
+pub fn emit<T: AsRef<[u8]>>(counter: &Pubkey, topics: &[T], event: &CounterEvent) {
+    let fields = event_fields(counter, topics, event);
+    let fields: Vec<&[u8]> = fields.iter().map(Vec::as_slice).collect();
+
+    sol_log_data(&fields);
+}
@@ -0,0 +1,431 @@
+//! Canonical JSON snapshots of a counter's configuration and value, for
+//! recreating it on another cluster or after a close. Keys are written in
+//! sorted order, pubkeys as base58 and byte strings as base64, so exporting
+//! the same counter always yields the same text. Runtime state such as
+//! locks, breaker windows, observations and a fired trigger is not carried
+//! over; metrics restart at their exported values.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Value, json};
+use solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey};
+use thiserror::Error;
+
+use crate::bounded::{BoundedVec, Label32};
+use crate::client::{
+    add_delegate_ix, initialize_counter_by_id_ix, initialize_counter_pda_ix,
+    initialize_counter_with_config_ix, set_topics_ix,
+};
+use crate::instruction::{CircuitBreakerConfig, InitConfig, MetricConfig};
+use crate::state::{
+    CapPolicy, CounterAccount, GateConfig, ImportSource, Topic, TriggerAccount, TriggerConfig,
+};
+
+/// `version` written by `export_counter_json`.
+pub const EXPORT_VERSION: u64 = 1;
+
+/// Why an export could not be turned back into instructions.
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Export is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Export field `{0}` is missing or invalid")]
+    Field(&'static str),
+    #[error("Export version {0} is not supported")]
+    Version(u64),
+}
+
+/// The `InitConfig` that recreates `counter`, with its authority made
+/// explicit.
+pub fn recreate_config(counter: &CounterAccount) -> InitConfig {
+    let breaker = &counter.circuit_breaker;
+
+    InitConfig {
+        authority: Some(counter.authority),
+        circuit_breaker: breaker.is_enabled().then_some(CircuitBreakerConfig {
+            max_delta: breaker.max_delta,
+            window_slots: breaker.window_slots,
+        }),
+        value_commitment: counter.is_committed().then_some(counter.value_commitment),
+        metrics: counter
+            .metrics
+            .iter()
+            .map(|metric| MetricConfig {
+                name: metric.name.clone(),
+                initial_value: metric.value,
+                max_value: metric.max_value,
+            })
+            .collect(),
+        trigger: counter.trigger.clone(),
+        mirror_mint: counter.mirror_mint,
+        observation_slots: counter.observations.samples.len() as u16,
+        gates: Some(counter.gates.clone()),
+        ttl_slots: counter.ttl_slots,
+        step: counter.step,
+        import_source: counter.import_source,
+        max_value: counter.max_value,
+        cap_policy: counter.cap_policy,
+        excess_destination: counter.excess_destination,
+        close_at_zero: counter.close_at_zero,
+        min_compute_units: counter.min_compute_units,
+        history_len: counter.history.entries.len() as u16,
+        access_log_len: counter.access_log.entries.len() as u16,
+        unlock_timestamp: (counter.unlock_timestamp != 0).then_some(counter.unlock_timestamp),
+        increment_fee: counter.increment_fee,
+        fee_recipient: (counter.increment_fee != 0).then_some(counter.fee_recipient),
+        expires_at: (counter.expires_at != 0).then_some(counter.expires_at),
+        merkle_root: (counter.merkle_root != [0; 32]).then_some(counter.merkle_root),
+        once_per_transaction: counter.once_per_transaction,
+        max_uses: counter.max_uses,
+        event_log: counter.event_log,
+        token_treasury: counter.token_treasury,
+    }
+}
+
+pub fn export_counter_json(counter: &CounterAccount) -> String {
+    let topics: Vec<String> = counter
+        .topics
+        .iter()
+        .map(|topic| STANDARD.encode(topic))
+        .collect();
+
+    let delegates: Vec<String> = counter.delegates.iter().map(Pubkey::to_string).collect();
+
+    json!({
+        "config": config_json(&recreate_config(counter)),
+        "delegates": delegates,
+        "external_id": counter.external_id.map(|id| STANDARD.encode(id)),
+        "name": counter.name.as_str(),
+        "topics": topics,
+        "value": counter.count,
+        "version": EXPORT_VERSION,
+    })
+    .to_string()
+}
+
+/// Builds the message that recreates an exported counter, returning the
+/// counter's address alongside it. Counters with an external ID or a name
+/// are recreated at `payer`'s PDA for it, others at `new_counter`, which
+/// must sign. Counters with topics or delegates also need their authority's
+/// signature.
+pub fn build_import_transaction(
+    program_id: &Pubkey,
+    json: &str,
+    new_counter: &Pubkey,
+    payer: &Pubkey,
+) -> Result<(Pubkey, Message), ImportError> {
+    let export: Value = serde_json::from_str(json)?;
+
+    let version = u64_field(&export, "version")?;
+    if version != EXPORT_VERSION {
+        return Err(ImportError::Version(version));
+    }
+
+    let value = u64_field(&export, "value")?;
+    let external_id = optional(&export, "external_id")?
+        .map(|id| bytes32(id, "external_id"))
+        .transpose()?;
+    let name = Label32::new(str_field(&export, "name")?).map_err(|_| ImportError::Field("name"))?;
+    let topics = array_field(&export, "topics")?
+        .iter()
+        .map(|topic| Topic::new(bytes(topic, "topics")?).map_err(|_| ImportError::Field("topics")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let topics = BoundedVec::new(topics).map_err(|_| ImportError::Field("topics"))?;
+    let delegates = array_field(&export, "delegates")?
+        .iter()
+        .map(|key| parse_pubkey(key, "delegates"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let config = parse_config(field(&export, "config")?)?;
+    let authority = config.authority.unwrap_or(*payer);
+
+    let (counter, initialize) = if let Some(external_id) = external_id {
+        initialize_counter_by_id_ix(program_id, payer, &external_id, value, config)
+    } else if name.is_empty() {
+        let instruction =
+            initialize_counter_with_config_ix(program_id, new_counter, payer, value, config);
+        (*new_counter, instruction)
+    } else {
+        initialize_counter_pda_ix(program_id, payer, &name, value, config)
+    };
+
+    let mut instructions: Vec<Instruction> = vec![initialize];
+    if !topics.is_empty() {
+        instructions.push(set_topics_ix(program_id, &counter, &authority, topics));
+    }
+    for delegate in &delegates {
+        instructions.push(add_delegate_ix(program_id, &counter, &authority, delegate));
+    }
+
+    Ok((counter, Message::new(&instructions, Some(payer))))
+}
+
+fn config_json(config: &InitConfig) -> Value {
+    let cap_policy = match config.cap_policy {
+        CapPolicy::Reject => "Reject",
+        CapPolicy::Saturate => "Saturate",
+    };
+    let metrics: Vec<Value> = config
+        .metrics
+        .iter()
+        .map(|metric| {
+            json!({
+                "initial_value": metric.initial_value,
+                "max_value": metric.max_value,
+                "name": metric.name.as_str(),
+            })
+        })
+        .collect();
+
+    json!({
+        "access_log_len": config.access_log_len,
+        "authority": config.authority.map(|key| key.to_string()),
+        "cap_policy": cap_policy,
+        "circuit_breaker": config.circuit_breaker.map(|breaker| json!({
+            "max_delta": breaker.max_delta,
+            "window_slots": breaker.window_slots,
+        })),
+        "close_at_zero": config.close_at_zero,
+        "event_log": config.event_log,
+        "excess_destination": config.excess_destination.map(|key| key.to_string()),
+        "expires_at": config.expires_at,
+        "fee_recipient": config.fee_recipient.map(|key| key.to_string()),
+        "gates": config.gates.as_ref().map(gates_json),
+        "history_len": config.history_len,
+        "import_source": config.import_source.map(|source| json!({
+            "attester": source.attester.to_string(),
+            "counter": source.counter.to_string(),
+            "max_age_secs": source.max_age_secs,
+        })),
+        "increment_fee": config.increment_fee,
+        "max_uses": config.max_uses,
+        "max_value": config.max_value,
+        "merkle_root": config.merkle_root.map(|root| STANDARD.encode(root)),
+        "metrics": metrics,
+        "min_compute_units": config.min_compute_units,
+        "mirror_mint": config.mirror_mint.map(|key| key.to_string()),
+        "observation_slots": config.observation_slots,
+        "once_per_transaction": config.once_per_transaction,
+        "step": config.step,
+        "token_treasury": config.token_treasury.map(|key| key.to_string()),
+        "trigger": config.trigger.as_ref().map(trigger_json),
+        "ttl_slots": config.ttl_slots,
+        "unlock_timestamp": config.unlock_timestamp,
+        "value_commitment": config.value_commitment.map(|hash| STANDARD.encode(hash)),
+    })
+}
+
+fn gates_json(gates: &GateConfig) -> Value {
+    let allowlist: Vec<String> = gates.allowlist.iter().map(Pubkey::to_string).collect();
+
+    json!({
+        "allowlist": allowlist,
+        "cooldown_slots": gates.cooldown_slots,
+        "cosigner": gates.cosigner.to_string(),
+        "flags": gates.flags,
+        "min_token_balance": gates.min_token_balance,
+        "token_mint": gates.token_mint.to_string(),
+    })
+}
+
+fn trigger_json(trigger: &TriggerConfig) -> Value {
+    let accounts: Vec<Value> = trigger
+        .accounts
+        .iter()
+        .map(|account| {
+            json!({
+                "is_signer": account.is_signer,
+                "is_writable": account.is_writable,
+                "pubkey": account.pubkey.to_string(),
+            })
+        })
+        .collect();
+
+    json!({
+        "accounts": accounts,
+        "data": STANDARD.encode(&trigger.data),
+        "program_id": trigger.program_id.to_string(),
+        "threshold": trigger.threshold,
+    })
+}
+
+fn parse_config(config: &Value) -> Result<InitConfig, ImportError> {
+    let cap_policy = match str_field(config, "cap_policy")? {
+        "Reject" => CapPolicy::Reject,
+        "Saturate" => CapPolicy::Saturate,
+        _ => return Err(ImportError::Field("cap_policy")),
+    };
+
+    let metrics = array_field(config, "metrics")?
+        .iter()
+        .map(|metric| {
+            Ok(MetricConfig {
+                name: Label32::new(str_field(metric, "name")?)
+                    .map_err(|_| ImportError::Field("name"))?,
+                initial_value: u64_field(metric, "initial_value")?,
+                max_value: u64_field(metric, "max_value")?,
+            })
+        })
+        .collect::<Result<_, ImportError>>()?;
+
+    let value_commitment = optional(config, "value_commitment")?
+        .map(|hash| bytes32(hash, "value_commitment"))
+        .transpose()?;
+
+    Ok(InitConfig {
+        authority: optional_pubkey(config, "authority")?,
+        circuit_breaker: optional(config, "circuit_breaker")?
+            .map(|breaker| {
+                Ok::<_, ImportError>(CircuitBreakerConfig {
+                    max_delta: u64_field(breaker, "max_delta")?,
+                    window_slots: u64_field(breaker, "window_slots")?,
+                })
+            })
+            .transpose()?,
+        value_commitment,
+        metrics,
+        trigger: optional(config, "trigger")?
+            .map(parse_trigger)
+            .transpose()?,
+        mirror_mint: optional_pubkey(config, "mirror_mint")?,
+        observation_slots: u64_field(config, "observation_slots")?
+            .try_into()
+            .map_err(|_| ImportError::Field("observation_slots"))?,
+        gates: optional(config, "gates")?.map(parse_gates).transpose()?,
+        ttl_slots: u64_field(config, "ttl_slots")?,
+        step: u64_field(config, "step")?,
+        import_source: optional(config, "import_source")?
+            .map(|source| {
+                Ok::<_, ImportError>(ImportSource {
+                    counter: pubkey_field(source, "counter")?,
+                    attester: pubkey_field(source, "attester")?,
+                    max_age_secs: field(source, "max_age_secs")?
+                        .as_i64()
+                        .ok_or(ImportError::Field("max_age_secs"))?,
+                })
+            })
+            .transpose()?,
+        max_value: optional(config, "max_value")?
+            .map(|max_value| max_value.as_u64().ok_or(ImportError::Field("max_value")))
+            .transpose()?,
+        cap_policy,
+        excess_destination: optional_pubkey(config, "excess_destination")?,
+        close_at_zero: bool_field(config, "close_at_zero")?,
+        min_compute_units: u64_field(config, "min_compute_units")?,
+        history_len: u64_field(config, "history_len")?
+            .try_into()
+            .map_err(|_| ImportError::Field("history_len"))?,
+        access_log_len: u64_field(config, "access_log_len")?
+            .try_into()
+            .map_err(|_| ImportError::Field("access_log_len"))?,
+        unlock_timestamp: optional(config, "unlock_timestamp")?
+            .map(|timestamp| {
+                timestamp
+                    .as_i64()
+                    .ok_or(ImportError::Field("unlock_timestamp"))
+            })
+            .transpose()?,
+        increment_fee: u64_field(config, "increment_fee")?,
+        fee_recipient: optional_pubkey(config, "fee_recipient")?,
+        expires_at: optional(config, "expires_at")?
+            .map(|timestamp| timestamp.as_i64().ok_or(ImportError::Field("expires_at")))
+            .transpose()?,
+        merkle_root: optional(config, "merkle_root")?
+            .map(|root| bytes32(root, "merkle_root"))
+            .transpose()?,
+        once_per_transaction: bool_field(config, "once_per_transaction")?,
+        max_uses: u64_field(config, "max_uses")?,
+        event_log: bool_field(config, "event_log")?,
+        token_treasury: optional_pubkey(config, "token_treasury")?,
+    })
+}
+
+fn parse_gates(gates: &Value) -> Result<GateConfig, ImportError> {
+    Ok(GateConfig {
+        flags: u64_field(gates, "flags")?
+            .try_into()
+            .map_err(|_| ImportError::Field("flags"))?,
+        allowlist: array_field(gates, "allowlist")?
+            .iter()
+            .map(|key| parse_pubkey(key, "allowlist"))
+            .collect::<Result<_, _>>()?,
+        token_mint: pubkey_field(gates, "token_mint")?,
+        min_token_balance: u64_field(gates, "min_token_balance")?,
+        cosigner: pubkey_field(gates, "cosigner")?,
+        cooldown_slots: u64_field(gates, "cooldown_slots")?,
+    })
+}
+
+fn parse_trigger(trigger: &Value) -> Result<TriggerConfig, ImportError> {
+    Ok(TriggerConfig {
+        threshold: u64_field(trigger, "threshold")?,
+        program_id: pubkey_field(trigger, "program_id")?,
+        accounts: array_field(trigger, "accounts")?
+            .iter()
+            .map(|account| {
+                Ok(TriggerAccount {
+                    pubkey: pubkey_field(account, "pubkey")?,
+                    is_signer: bool_field(account, "is_signer")?,
+                    is_writable: bool_field(account, "is_writable")?,
+                })
+            })
+            .collect::<Result<_, ImportError>>()?,
+        data: bytes(field(trigger, "data")?, "data")?,
+    })
+}
+
+fn field<'a>(object: &'a Value, key: &'static str) -> Result<&'a Value, ImportError> {
+    object.get(key).ok_or(ImportError::Field(key))
+}
+
+/// `None` for an explicit `null`.
+fn optional<'a>(object: &'a Value, key: &'static str) -> Result<Option<&'a Value>, ImportError> {
+    let value = field(object, key)?;
+    Ok((!value.is_null()).then_some(value))
+}
+
+fn u64_field(object: &Value, key: &'static str) -> Result<u64, ImportError> {
+    field(object, key)?.as_u64().ok_or(ImportError::Field(key))
+}
+
+fn bool_field(object: &Value, key: &'static str) -> Result<bool, ImportError> {
+    field(object, key)?.as_bool().ok_or(ImportError::Field(key))
+}
+
+fn str_field<'a>(object: &'a Value, key: &'static str) -> Result<&'a str, ImportError> {
+    field(object, key)?.as_str().ok_or(ImportError::Field(key))
+}
+
+fn array_field<'a>(object: &'a Value, key: &'static str) -> Result<&'a Vec<Value>, ImportError> {
+    field(object, key)?
+        .as_array()
+        .ok_or(ImportError::Field(key))
+}
+
+fn pubkey_field(object: &Value, key: &'static str) -> Result<Pubkey, ImportError> {
+    parse_pubkey(field(object, key)?, key)
+}
+
+fn optional_pubkey(object: &Value, key: &'static str) -> Result<Option<Pubkey>, ImportError> {
+    optional(object, key)?
+        .map(|value| parse_pubkey(value, key))
+        .transpose()
+}
+
+fn parse_pubkey(value: &Value, key: &'static str) -> Result<Pubkey, ImportError> {
+    value
+        .as_str()
+        .and_then(|text| text.parse().ok())
+        .ok_or(ImportError::Field(key))
+}
+
+fn bytes(value: &Value, key: &'static str) -> Result<Vec<u8>, ImportError> {
+    value
+        .as_str()
+        .and_then(|text| STANDARD.decode(text).ok())
+        .ok_or(ImportError::Field(key))
+}
+
+fn bytes32(value: &Value, key: &'static str) -> Result<[u8; 32], ImportError> {
+    bytes(value, key)?
+        .try_into()
+        .map_err(|_| ImportError::Field(key))
+}
@@ -0,0 +1,257 @@
+use std::slice::Iter;
+
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::error::CounterError;
+use crate::merkle;
+use crate::spl::read_token_account;
+use crate::state::{CounterAccount, GateConfig};
+
+pub const GATE_AUTHORITY: u8 = 1 << 0;
+pub const GATE_ALLOWLIST: u8 = 1 << 1;
+pub const GATE_TOKEN_BALANCE: u8 = 1 << 2;
+pub const GATE_SIGNATURE: u8 = 1 << 3;
+pub const GATE_COOLDOWN: u8 = 1 << 4;
+
+/// What a gate gets to look at when a mutation is attempted.
+pub struct GateContext<'a, 'b, 'info> {
+    /// Account in the authority position of the instruction.
+    pub actor: &'a AccountInfo<'info>,
+    /// Gate-specific accounts follow the actor in flag order: the cosigner
+    /// for `GATE_SIGNATURE`, then the token account for `GATE_TOKEN_BALANCE`.
+    pub accounts: &'b mut Iter<'a, AccountInfo<'info>>,
+    pub slot: u64,
+    /// The actor's merkle allowlist entry, on `IncrementWithProof` only.
+    pub merkle_claim: Option<MerkleClaim<'b>>,
+}
+
+/// An allowlist leaf index and the proof that the actor is that leaf.
+#[derive(Debug, Clone, Copy)]
+pub struct MerkleClaim<'a> {
+    pub index: u32,
+    pub proof: &'a [[u8; 32]],
+}
+
+/// A single access rule. A counter's enabled gates must all pass.
+pub trait Gate {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult;
+}
+
+pub struct AuthorityGate<'a> {
+    pub authority: &'a Pubkey,
+    /// Accepted in place of the authority.
+    pub delegates: &'a [Pubkey],
+}
+
+impl Gate for AuthorityGate<'_> {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult {
+        let permitted = ctx.actor.key == self.authority || self.delegates.contains(ctx.actor.key);
+        if !ctx.actor.is_signer || !permitted {
+            msg!("Instruction requires the signature of the counter authority or a delegate");
+            return Err(CounterError::Unauthorized.into());
+        }
+        Ok(())
+    }
+}
+
+pub struct AllowlistGate<'a> {
+    pub allowlist: &'a [Pubkey],
+}
+
+impl Gate for AllowlistGate<'_> {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult {
+        if !ctx.actor.is_signer || !self.allowlist.contains(ctx.actor.key) {
+            msg!("Signer is not on the counter allowlist");
+            return Err(CounterError::Unauthorized.into());
+        }
+        Ok(())
+    }
+}
+
+pub struct SignatureGate<'a> {
+    pub cosigner: &'a Pubkey,
+}
+
+impl Gate for SignatureGate<'_> {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult {
+        let cosigner = next_account_info(ctx.accounts)?;
+
+        if !cosigner.is_signer || cosigner.key != self.cosigner {
+            msg!("Instruction requires the counter cosigner's signature");
+            return Err(CounterError::Unauthorized.into());
+        }
+        Ok(())
+    }
+}
+
+pub struct TokenBalanceGate<'a> {
+    pub mint: &'a Pubkey,
+    pub min_amount: u64,
+}
+
+impl Gate for TokenBalanceGate<'_> {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult {
+        let token_account = next_account_info(ctx.accounts)?;
+        let token = read_token_account(token_account)?;
+
+        // otherwise anyone could pass a holder's key and token account
+        if !ctx.actor.is_signer {
+            msg!("Instruction requires the token holder's signature");
+            return Err(CounterError::Unauthorized.into());
+        }
+
+        if &token.mint != self.mint || &token.owner != ctx.actor.key {
+            msg!("Token account does not belong to the actor for the gated mint");
+            return Err(CounterError::TokenAccountMismatch.into());
+        }
+
+        if token.amount < self.min_amount {
+            msg!(
+                "Token balance {} is below the required {}",
+                token.amount,
+                self.min_amount
+            );
+            return Err(CounterError::InsufficientTokenBalance.into());
+        }
+
+        Ok(())
+    }
+}
+
+pub struct CooldownGate {
+    pub cooldown_slots: u64,
+    pub last_slot: u64,
+}
+
+impl Gate for CooldownGate {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult {
+        // a zero last slot means the counter was never gated before
+        let ends_at = self.last_slot.saturating_add(self.cooldown_slots);
+        if self.last_slot != 0 && ctx.slot < ends_at {
+            msg!("Counter is cooling down until slot {}", ends_at);
+            return Err(CounterError::CooldownActive.into());
+        }
+        Ok(())
+    }
+}
+
+/// Passes actors proven to be on the counter's merkle allowlist. Only
+/// `IncrementWithProof` carries a proof, so every other mutation fails it.
+pub struct MerkleGate<'a> {
+    pub root: &'a [u8; 32],
+}
+
+impl Gate for MerkleGate<'_> {
+    fn check(&self, ctx: &mut GateContext) -> ProgramResult {
+        let Some(claim) = ctx.merkle_claim else {
+            msg!("Counter has a merkle allowlist, use IncrementWithProof");
+            return Err(CounterError::Unauthorized.into());
+        };
+        if !ctx.actor.is_signer
+            || !merkle::verify(self.root, claim.index, ctx.actor.key, claim.proof)
+        {
+            msg!("Signer is not leaf {} of the merkle allowlist", claim.index);
+            return Err(CounterError::InvalidMerkleProof.into());
+        }
+        Ok(())
+    }
+}
+
+/// First slot at which the cooldown gate admits another mutation, or `None`
+/// when the gate is off or has not been passed yet. Lets rate-limited
+/// front-ends such as faucets show when the next claim opens.
+pub fn cooldown_ends_at(counter_data: &CounterAccount) -> Option<u64> {
+    if counter_data.gates.flags & GATE_COOLDOWN == 0 || counter_data.gate_last_slot == 0 {
+        return None;
+    }
+
+    Some(
+        counter_data
+            .gate_last_slot
+            .saturating_add(counter_data.gates.cooldown_slots),
+    )
+}
+
+/// Runs every gate enabled in the counter's flags against `actor`, consuming
+/// gate-specific accounts from `accounts`, and records the mutation slot for
+/// the cooldown gate once all of them pass.
+pub fn check_gates<'a, 'info>(
+    counter_data: &mut CounterAccount,
+    actor: &'a AccountInfo<'info>,
+    accounts: &mut Iter<'a, AccountInfo<'info>>,
+) -> ProgramResult {
+    check_gates_with_claim(counter_data, actor, accounts, None)
+}
+
+/// `check_gates` for an instruction carrying the actor's merkle allowlist
+/// entry, which only counters with an allowlist accept.
+pub fn check_gates_with_claim<'a, 'info>(
+    counter_data: &mut CounterAccount,
+    actor: &'a AccountInfo<'info>,
+    accounts: &mut Iter<'a, AccountInfo<'info>>,
+    merkle_claim: Option<MerkleClaim>,
+) -> ProgramResult {
+    if merkle_claim.is_some() && counter_data.merkle_root == [0; 32] {
+        msg!("Counter has no merkle allowlist");
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let slot = Clock::get()?.slot;
+    let config: &GateConfig = &counter_data.gates;
+
+    let mut gates: Vec<Box<dyn Gate + '_>> = Vec::new();
+    if config.flags & GATE_AUTHORITY != 0 {
+        gates.push(Box::new(AuthorityGate {
+            authority: &counter_data.authority,
+            delegates: &counter_data.delegates,
+        }));
+    }
+    if config.flags & GATE_ALLOWLIST != 0 {
+        gates.push(Box::new(AllowlistGate {
+            allowlist: &config.allowlist,
+        }));
+    }
+    if config.flags & GATE_SIGNATURE != 0 {
+        gates.push(Box::new(SignatureGate {
+            cosigner: &config.cosigner,
+        }));
+    }
+    if config.flags & GATE_TOKEN_BALANCE != 0 {
+        gates.push(Box::new(TokenBalanceGate {
+            mint: &config.token_mint,
+            min_amount: config.min_token_balance,
+        }));
+    }
+    if config.flags & GATE_COOLDOWN != 0 {
+        gates.push(Box::new(CooldownGate {
+            cooldown_slots: config.cooldown_slots,
+            last_slot: counter_data.gate_last_slot,
+        }));
+    }
+    if counter_data.merkle_root != [0; 32] {
+        gates.push(Box::new(MerkleGate {
+            root: &counter_data.merkle_root,
+        }));
+    }
+
+    let mut ctx = GateContext {
+        actor,
+        accounts,
+        slot,
+        merkle_claim,
+    };
+    for gate in &gates {
+        gate.check(&mut ctx)?;
+    }
+    drop(gates);
+
+    counter_data.gate_last_slot = slot;
+    Ok(())
+}
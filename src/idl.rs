@@ -0,0 +1,371 @@
+//! The program's JSON IDL, in the layout Anchor's TypeScript tooling reads:
+//! every enabled instruction with its tag, accounts and arguments, and the
+//! `CounterAccount` layout. Accounts come from the `dispatch` table and
+//! arguments from `INSTRUCTION_ARGS`, so the IDL tracks the handlers.
+//!
+//! Arguments are listed in the order `CounterInstruction::pack` writes them,
+//! and every encoding matches Borsh. Types the IDL does not spell out are
+//! referenced by their Rust name under `defined`.
+//!
+//! The shipped copy is `idl/counter_program.json`; regenerate it with
+//! `cargo run --example idl > idl/counter_program.json`.
+
+use crate::addresses::COUNTER_DISCRIMINATOR;
+use crate::dispatch::INSTRUCTIONS;
+
+/// The Borsh type of one argument or account field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdlType {
+    /// `u8` … `i64`, `bool`, `string`, `bytes` or `publicKey`.
+    Primitive(&'static str),
+    Array(&'static str, usize),
+    Option(&'static IdlType),
+    Vec(&'static IdlType),
+    Defined(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdlField {
+    pub name: &'static str,
+    pub ty: IdlType,
+}
+
+const U8: IdlType = IdlType::Primitive("u8");
+const U16: IdlType = IdlType::Primitive("u16");
+const U32: IdlType = IdlType::Primitive("u32");
+const U64: IdlType = IdlType::Primitive("u64");
+const I64: IdlType = IdlType::Primitive("i64");
+const BOOL: IdlType = IdlType::Primitive("bool");
+const STRING: IdlType = IdlType::Primitive("string");
+const BYTES: IdlType = IdlType::Primitive("bytes");
+const PUBKEY: IdlType = IdlType::Primitive("publicKey");
+const HASH: IdlType = IdlType::Array("u8", 32);
+
+const fn field(name: &'static str, ty: IdlType) -> IdlField {
+    IdlField { name, ty }
+}
+
+const fn defined(name: &'static str, type_name: &'static str) -> IdlField {
+    field(name, IdlType::Defined(type_name))
+}
+
+const NONE: &[IdlField] = &[];
+const AMOUNT: &[IdlField] = &[field("amount", U64)];
+const VALUE: &[IdlField] = &[field("value", U64)];
+const INITIAL_VALUE: &[IdlField] = &[field("initial_value", U64)];
+const DELEGATE: &[IdlField] = &[field("delegate", PUBKEY)];
+const INDEX: &[IdlField] = &[field("index", U8)];
+
+/// Arguments of each instruction, indexed by tag like `INSTRUCTIONS`.
+pub const INSTRUCTION_ARGS: &[&[IdlField]] = &[
+    &[field("initial_value", U64), defined("config", "InitConfig")],
+    NONE,
+    NONE,
+    &[field("hash", HASH)],
+    &[field("amount", U64), field("salt", HASH)],
+    &[
+        field("value", U64),
+        field("salt", HASH),
+        field("amount", U64),
+        field("new_salt", HASH),
+    ],
+    &[defined("metric", "MetricRef"), field("amount", U64)],
+    &[field(
+        "sources",
+        IdlType::Vec(&IdlType::Defined("WeightedSource")),
+    )],
+    NONE,
+    NONE,
+    NONE,
+    AMOUNT,
+    AMOUNT,
+    &[field("new_authority", PUBKEY)],
+    NONE,
+    &[
+        field("initial_value", U64),
+        field("name", STRING),
+        defined("config", "InitConfig"),
+    ],
+    NONE,
+    VALUE,
+    VALUE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    &[field("amount", U64), field("duration_slots", U64)],
+    NONE,
+    &[field("program", PUBKEY)],
+    NONE,
+    NONE,
+    &[field("topics", IdlType::Vec(&BYTES))],
+    &[field("initial_value", U64), field("delta", U64)],
+    &[field("step", U64)],
+    &[field("code_hash", HASH), field("amount", U64)],
+    &[field("code", BYTES)],
+    &[defined("snapshot", "AttestationSnapshot")],
+    NONE,
+    NONE,
+    &[field("name", STRING), defined("config", "InitConfig")],
+    INITIAL_VALUE,
+    NONE,
+    &[defined("mode", "IncrementMode")],
+    NONE,
+    NONE,
+    &[
+        field("initial_value", U64),
+        field("external_id", HASH),
+        defined("config", "InitConfig"),
+    ],
+    NONE,
+    DELEGATE,
+    DELEGATE,
+    &[
+        field("threshold", U8),
+        field("signers", IdlType::Vec(&PUBKEY)),
+    ],
+    &[field("unlock_timestamp", IdlType::Option(&I64))],
+    &[field("max_counters_per_owner", U32)],
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    &[field("grace_secs", I64)],
+    &[field("initial_value", I64)],
+    AMOUNT,
+    AMOUNT,
+    &[field("slots", U8)],
+    INDEX,
+    INDEX,
+    INDEX,
+    &[field("expected", U64)],
+    AMOUNT,
+    NONE,
+    &[field("index", U32), field("proof", IdlType::Vec(&HASH))],
+    NONE,
+    &[defined("params", "GlobalParams")],
+    NONE,
+];
+
+/// `CounterAccount` fields after its discriminator, in storage order.
+pub const COUNTER_ACCOUNT_FIELDS: &[IdlField] = &[
+    field("version", U8),
+    field("count", U64),
+    field("authority", PUBKEY),
+    field("is_frozen", BOOL),
+    defined("circuit_breaker", "CircuitBreaker"),
+    field("pending_commitment", HASH),
+    field("value_commitment", HASH),
+    field("metrics", IdlType::Vec(&IdlType::Defined("Metric"))),
+    field(
+        "trigger",
+        IdlType::Option(&IdlType::Defined("TriggerConfig")),
+    ),
+    field("trigger_fired", BOOL),
+    field("mirror_mint", IdlType::Option(&PUBKEY)),
+    field("mirror_synced_slot", U64),
+    defined("observations", "ObservationBuffer"),
+    defined("gates", "GateConfig"),
+    field("gate_last_slot", U64),
+    field("payer", PUBKEY),
+    field("ttl_slots", U64),
+    field("last_active_slot", U64),
+    field("locked_amount", U64),
+    field("locked_until_slot", U64),
+    field("name", STRING),
+    field("topics", IdlType::Vec(&BYTES)),
+    field("step", U64),
+    field(
+        "import_source",
+        IdlType::Option(&IdlType::Defined("ImportSource")),
+    ),
+    field("imported_slot", U64),
+    field("max_value", IdlType::Option(&U64)),
+    defined("cap_policy", "CapPolicy"),
+    field("min_observed_lamports", U64),
+    field("excess_destination", IdlType::Option(&PUBKEY)),
+    field("close_at_zero", BOOL),
+    field("is_paused", BOOL),
+    field("min_compute_units", U64),
+    field("external_id", IdlType::Option(&HASH)),
+    field("snapshot_count", U64),
+    defined("history", "HistoryBuffer"),
+    field("delegates", IdlType::Vec(&PUBKEY)),
+    defined("access_log", "AccessLog"),
+    field("created_at", I64),
+    field("last_updated", I64),
+    field("unlock_timestamp", I64),
+    field("increment_fee", U64),
+    field("fee_recipient", PUBKEY),
+    field("admin_frozen", BOOL),
+    field("unfreeze_requested_at", I64),
+    field("unfreeze_at", I64),
+    field("expires_at", I64),
+    field("merkle_root", HASH),
+    field("once_per_transaction", BOOL),
+    field("max_uses", U64),
+    field("uses", U64),
+    field("is_finalized", BOOL),
+    field("event_log", BOOL),
+    field("log_head", U32),
+    field("token_treasury", IdlType::Option(&PUBKEY)),
+];
+
+/// `InitConfig`, the argument most creation instructions share.
+pub const INIT_CONFIG_FIELDS: &[IdlField] = &[
+    field("authority", IdlType::Option(&PUBKEY)),
+    field(
+        "circuit_breaker",
+        IdlType::Option(&IdlType::Defined("CircuitBreakerConfig")),
+    ),
+    field("value_commitment", IdlType::Option(&HASH)),
+    field("metrics", IdlType::Vec(&IdlType::Defined("MetricConfig"))),
+    field(
+        "trigger",
+        IdlType::Option(&IdlType::Defined("TriggerConfig")),
+    ),
+    field("mirror_mint", IdlType::Option(&PUBKEY)),
+    field("observation_slots", U16),
+    field("gates", IdlType::Option(&IdlType::Defined("GateConfig"))),
+    field("ttl_slots", U64),
+    field("step", U64),
+    field(
+        "import_source",
+        IdlType::Option(&IdlType::Defined("ImportSource")),
+    ),
+    field("max_value", IdlType::Option(&U64)),
+    defined("cap_policy", "CapPolicy"),
+    field("excess_destination", IdlType::Option(&PUBKEY)),
+    field("close_at_zero", BOOL),
+    field("min_compute_units", U64),
+    field("history_len", U16),
+    field("access_log_len", U16),
+    field("unlock_timestamp", IdlType::Option(&I64)),
+    field("increment_fee", U64),
+    field("fee_recipient", IdlType::Option(&PUBKEY)),
+    field("expires_at", IdlType::Option(&I64)),
+    field("merkle_root", IdlType::Option(&HASH)),
+    field("once_per_transaction", BOOL),
+    field("max_uses", U64),
+    field("event_log", BOOL),
+    field("token_treasury", IdlType::Option(&PUBKEY)),
+];
+
+impl IdlType {
+    /// Encoded size, or `None` for variable-length types.
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self {
+            Self::Primitive(name) => primitive_size(name),
+            Self::Array(element, len) => primitive_size(element).map(|size| size * len),
+            Self::Option(_) | Self::Vec(_) | Self::Defined(_) => None,
+        }
+    }
+
+    fn to_json(self) -> String {
+        match self {
+            Self::Primitive(name) => format!(r#""{}""#, name),
+            Self::Array(element, len) => format!(r#"{{"array":["{}",{}]}}"#, element, len),
+            Self::Option(inner) => format!(r#"{{"option":{}}}"#, inner.to_json()),
+            Self::Vec(inner) => format!(r#"{{"vec":{}}}"#, inner.to_json()),
+            Self::Defined(name) => format!(r#"{{"defined":"{}"}}"#, name),
+        }
+    }
+}
+
+fn primitive_size(name: &str) -> Option<usize> {
+    match name {
+        "u8" | "bool" => Some(1),
+        "u16" => Some(2),
+        "u32" => Some(4),
+        "u64" | "i64" => Some(8),
+        "publicKey" => Some(32),
+        _ => None,
+    }
+}
+
+/// Arguments of the instruction tagged `tag`.
+pub fn instruction_args(tag: u8) -> &'static [IdlField] {
+    INSTRUCTION_ARGS.get(tag as usize).copied().unwrap_or(NONE)
+}
+
+fn fields_json(fields: &[IdlField]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            format!(
+                r#"{{"name":"{}","type":{}}}"#,
+                field.name,
+                field.ty.to_json()
+            )
+        })
+        .collect();
+    format!("[{}]", fields.join(","))
+}
+
+fn struct_json(fields: &[IdlField]) -> String {
+    format!(r#"{{"kind":"struct","fields":{}}}"#, fields_json(fields))
+}
+
+fn bytes_json(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(u8::to_string).collect();
+    format!("[{}]", bytes.join(","))
+}
+
+/// Renders the enabled instructions, their accounts and arguments, and the
+/// account layouts as JSON.
+pub fn idl_json() -> String {
+    let instructions: Vec<String> = INSTRUCTIONS
+        .iter()
+        .filter(|meta| meta.is_enabled())
+        .map(|meta| {
+            let accounts: Vec<String> = meta
+                .accounts
+                .iter()
+                .map(|spec| {
+                    format!(
+                        r#"{{"name":"{}","isMut":{},"isSigner":{}}}"#,
+                        spec.name, spec.writable, spec.signer
+                    )
+                })
+                .collect();
+            #[cfg(feature = "anchor-layout")]
+            let discriminator = format!(
+                r#","discriminator":{}"#,
+                bytes_json(&crate::anchor::INSTRUCTION_SIGHASHES[meta.tag as usize])
+            );
+            #[cfg(not(feature = "anchor-layout"))]
+            let discriminator = "";
+            format!(
+                r#"{{"name":"{}","discriminant":{}{},"deprecated":{},"accounts":[{}],"args":{}}}"#,
+                meta.name,
+                meta.tag,
+                discriminator,
+                meta.deprecated,
+                accounts.join(","),
+                fields_json(instruction_args(meta.tag))
+            )
+        })
+        .collect();
+
+    let accounts = format!(
+        r#"[{{"name":"CounterAccount","discriminator":{},"type":{}}}]"#,
+        bytes_json(&COUNTER_DISCRIMINATOR),
+        struct_json(COUNTER_ACCOUNT_FIELDS)
+    );
+    let types = format!(
+        r#"[{{"name":"InitConfig","type":{}}}]"#,
+        struct_json(INIT_CONFIG_FIELDS)
+    );
+
+    format!(
+        concat!(
+            r#"{{"version":"{}","name":"counter_program","instructions":[{}],"#,
+            r#""accounts":{},"types":{}}}"#
+        ),
+        env!("CARGO_PKG_VERSION"),
+        instructions.join(","),
+        accounts,
+        types
+    )
+}
@@ -1,30 +1,917 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::program_error::ProgramError;
+use solana_program::{hash::hashv, program_error::ProgramError, pubkey::Pubkey};
 
+use crate::bounded::Label32;
+use crate::error::CounterError;
+use crate::state::{
+    AttestationSnapshot, CapPolicy, CouponCode, GateConfig, GlobalParams, ImportSource,
+    IncrementMode, MerkleProof, MultisigSigners, Topics, TriggerConfig, WeightedSource,
+};
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum CounterInstruction {
-    InitializeCounter { initial_value: u64 },
+    InitializeCounter {
+        initial_value: u64,
+        config: Box<InitConfig>,
+    },
+    /// Adds the counter's `step`. Accounts: counter, actor (the authority
+    /// unless the counter's gates say otherwise), any gate accounts, then the
+    /// threshold trigger CPI accounts if any. Decrements and other counter
+    /// mutations take the same layout.
     IncrementCounter,
+    ResetCircuitBreaker,
+    CommitIncrement {
+        hash: [u8; 32],
+    },
+    RevealIncrement {
+        amount: u64,
+        salt: [u8; 32],
+    },
+    /// Proves the current committed `value` with its `salt` and replaces the
+    /// commitment with one to `value + amount` under `new_salt`.
+    IncrementCommitted {
+        value: u64,
+        salt: [u8; 32],
+        amount: u64,
+        new_salt: [u8; 32],
+    },
+    IncrementMetric {
+        metric: MetricRef,
+        amount: u64,
+    },
+    InitializeComposite {
+        sources: Vec<WeightedSource>,
+    },
+    /// Recomputes a composite counter; its sources follow it in the account list.
+    RefreshComposite,
+    /// Subtracts the counter's `step`. A `close_at_zero` counter that reaches
+    /// zero is closed, so its authority must also be passed writable (as the
+    /// actor or after the gate accounts) to receive the rent, along with a
+    /// strict counter's excess destination.
+    DecrementCounter,
+    /// Sets a mirror counter to the supply of the mint passed after it.
+    SyncMintSupply,
+    /// Same accounts as `IncrementCounter`.
+    IncrementBy {
+        amount: u64,
+    },
+    /// Same accounts as `DecrementCounter`.
+    DecrementBy {
+        amount: u64,
+    },
+    TransferAuthority {
+        new_authority: Pubkey,
+    },
+    /// Accounts: counter, authority (signer), rent destination, then for
+    /// strict counters holding donated lamports, their excess destination.
+    CloseCounter,
+    /// Accounts: counter PDA `["counter", owner, name]`, owner (signer,
+    /// payer), system program. Payload is `initial_value`, the Borsh-encoded
+    /// `name`, then the optional `InitConfig` as in `InitializeCounter`.
+    InitializeCounterPda {
+        initial_value: u64,
+        name: Label32,
+        config: Box<InitConfig>,
+    },
+    /// Accounts: counter, authority (signer), then trigger CPI accounts if any.
+    ResetCounter,
+    /// Same accounts as `ResetCounter`.
+    SetCounter {
+        value: u64,
+    },
+    /// Devnet only. Sets `count` ignoring freezes, breakers and modes.
+    /// Accounts: counter, authority (signer).
+    #[cfg(feature = "devnet-tools")]
+    ForceSet {
+        value: u64,
+    },
+    /// Devnet only. Clears the cooldown gate's last slot.
+    #[cfg(feature = "devnet-tools")]
+    FastForwardCooldown,
+    /// Devnet only. Zeroes metrics and observations and restarts the breaker
+    /// window and trigger.
+    #[cfg(feature = "devnet-tools")]
+    ResetStats,
+    /// Closes an expired counter. Accounts: counter, original payer
+    /// (writable), reaper (signer, writable). The reaper keeps
+    /// `REAP_REWARD_BPS` of the rent and the payer gets the rest.
+    Reap,
+    /// Upgrades a counter in an older layout to the current one.
+    /// Accounts: counter, payer for extra rent (signer), system program.
+    MigrateAccount,
+    /// Locks `amount` liquid points until at least `duration_slots` from now.
+    /// Accounts: counter, authority (signer).
+    LockCount {
+        amount: u64,
+        duration_slots: u64,
+    },
+    /// Releases an expired lock back to liquid. Accounts: counter.
+    UnlockCount,
+    /// Lets `program` read the counter through `GetCount`. Accounts: counter,
+    /// authority (signer, pays rent), view key PDA, system program.
+    GrantViewKey {
+        program: Pubkey,
+    },
+    /// Accounts: counter, authority (signer, receives the rent), view key PDA.
+    RevokeViewKey,
+    /// Sets a `CountAttestation` as return data. Accounts: counter, view key
+    /// PDA, the granted program's `["viewer"]` PDA (signer).
+    GetCount,
+    /// Replaces the counter's event topics, resizing the account. Accounts:
+    /// counter, authority (signer, writable, pays any extra rent), system
+    /// program.
+    SetTopics {
+        topics: Topics,
+    },
+    /// Creates a default-configured counter at `initial_value + delta` in a
+    /// single write. Accounts match `InitializeCounter`.
+    InitializeAndIncrement {
+        initial_value: u64,
+        delta: u64,
+    },
+    /// Accounts: counter, authority (signer).
+    SetStep {
+        step: u64,
+    },
+    /// Creates a coupon worth `amount` that the holder of the code hashing to
+    /// `code_hash` can redeem once. Accounts: counter, authority (signer,
+    /// pays rent), coupon PDA, system program.
+    MintCoupon {
+        code_hash: [u8; 32],
+        amount: u64,
+    },
+    /// Adds the coupon's amount and closes it, bypassing the counter's gates.
+    /// Accounts: counter, coupon PDA, the coupon's payer (receives the rent),
+    /// then any threshold trigger CPI accounts. Nobody signs to pay a
+    /// protocol fee, so it fails while the global config charges one.
+    RedeemCoupon {
+        code: CouponCode,
+    },
+    /// Sets a cross-cluster mirror to a signed snapshot of its source.
+    /// Accounts: counter, instructions sysvar, then any threshold trigger CPI
+    /// accounts. The preceding instruction must be the Ed25519 check of the
+    /// attester's signature over the Borsh-encoded snapshot.
+    ImportAttestation {
+        snapshot: AttestationSnapshot,
+    },
+    /// Adds each counter's `step`. Accounts: actor, then every counter to
+    /// bump. Counters whose gates need extra accounts, or whose trigger would
+    /// fire, must use `IncrementCounter` instead, as must every counter while
+    /// the global config charges a protocol fee.
+    IncrementMany,
+    /// Moves lamports sent straight to the counter out to a treasury.
+    /// Accounts: counter, authority (signer), treasury.
+    SkimExcess,
+    /// Creates or replaces the admin's template called `name`. Accounts:
+    /// template PDA `["template", admin, name]`, admin (signer, pays rent),
+    /// system program. Payload is the Borsh-encoded `name`, then the
+    /// optional `InitConfig` as in `InitializeCounter`.
+    SetTemplate {
+        name: Label32,
+        config: Box<InitConfig>,
+    },
+    /// Creates a counter configured by a template. Accounts match
+    /// `InitializeCounter`, followed by the template.
+    InitializeFromTemplate {
+        initial_value: u64,
+    },
+    /// Creates the program-wide registry. Accounts: registry PDA
+    /// `["registry"]`, payer (signer), system program. Initialize, increment
+    /// and close instructions update it when it is appended, writable, to
+    /// their accounts.
+    InitializeRegistry,
+    /// Switches between permissionless and authority-only mutations.
+    /// Accounts: counter, authority (signer).
+    SetMode {
+        mode: IncrementMode,
+    },
+    /// Rejects every mutation with `CounterPaused` until `Resume`.
+    /// Accounts: counter, authority (signer).
+    Pause,
+    /// Lifts a `Pause`. Accounts: counter, authority (signer).
+    Resume,
+    /// Accounts: counter PDA `["external", owner, external_id]`, owner
+    /// (signer, payer), system program. Payload is `initial_value`,
+    /// `external_id`, then the optional `InitConfig` as in
+    /// `InitializeCounter`.
+    InitializeCounterById {
+        initial_value: u64,
+        external_id: [u8; 32],
+        config: Box<InitConfig>,
+    },
+    /// Records the current count in the counter's next snapshot PDA.
+    /// Accounts: counter, authority (signer, pays rent), snapshot PDA
+    /// `["snapshot", counter, snapshot_count]`, system program.
+    Snapshot,
+    /// Lets `delegate` pass the authority gate, at most `MAX_DELEGATES`.
+    /// Accounts: counter, authority (signer, writable, pays any extra rent),
+    /// system program.
+    AddDelegate {
+        delegate: Pubkey,
+    },
+    /// Accounts match `AddDelegate`.
+    RemoveDelegate {
+        delegate: Pubkey,
+    },
+    /// Creates a `Multisig` to use as a counter authority. Accounts: new
+    /// multisig account (signer), payer (signer), system program.
+    CreateMultisig {
+        threshold: u8,
+        signers: MultisigSigners,
+    },
+    /// Sets or clears the time before which increments and decrements fail
+    /// with `CounterLocked`. Accounts: counter, authority (signer).
+    SetUnlockTime {
+        unlock_timestamp: Option<i64>,
+    },
+    /// Sets the global config's `max_counters_per_owner`, leaving its other
+    /// parameters alone. Accounts match `SetGlobalConfig`. Every counter
+    /// creation must pass the global config PDA, even before it is
+    /// initialized; while a quota is set it must also pass the paying
+    /// owner's `["owner_stats", owner]` account, writable, and fails with
+    /// `OwnerQuotaExceeded` past the cap.
+    SetOwnerQuota {
+        max_counters_per_owner: u32,
+    },
+    /// Moves the counter's collected fees, above the vault's rent-exempt
+    /// minimum, to a destination. Accounts: counter, authority (signer), fee
+    /// vault PDA, destination.
+    WithdrawFees,
+    /// Grows a counter in place to the current layout, topping its rent up
+    /// from the payer. A bare 8-byte counter keeps its count and gets the
+    /// payer as its authority, so the counter keypair has to sign; other
+    /// layouts migrate as `MigrateAccount` does. Accounts match
+    /// `MigrateAccount`.
+    Resize,
+    /// Freezes a counter for moderation: count changes, authority changes and
+    /// closing fail with `FrozenByAdmin`. Accounts: registry, registry admin
+    /// (signer), counter.
+    AdminFreeze,
+    /// Lets an admin freeze lapse after the registry's grace period unless
+    /// the admin burns the counter first. Accounts: counter, authority
+    /// (signer), registry.
+    RequestUnfreeze,
+    /// Closes an admin-frozen counter whose freeze has not lapsed, sending
+    /// its lamports to the destination. Accounts: registry, registry admin
+    /// (signer), counter, destination.
+    BurnCounter,
+    /// Sets the registry's unfreeze grace period. Accounts: registry,
+    /// registry admin (signer).
+    SetUnfreezeGracePeriod {
+        grace_secs: i64,
+    },
+    /// Creates a `SignedCounter`, whose count may go negative, with the payer
+    /// as its authority. Accounts match `InitializeCounter`.
+    InitializeSignedCounter {
+        initial_value: i64,
+    },
+    /// Adds `amount` to a signed counter, failing with `SignedOverflow` past
+    /// `i64::MAX`. Accounts: signed counter, authority (signer).
+    IncrementSigned {
+        amount: u64,
+    },
+    /// Subtracts `amount`, failing with `SignedUnderflow` past `i64::MIN`.
+    /// Accounts match `IncrementSigned`.
+    DecrementSigned {
+        amount: u64,
+    },
+    /// Creates a `SlotCounter` with `slots` tallies, all zero, and the payer
+    /// as its authority. Accounts match `InitializeCounter`.
+    InitializeSlotCounter {
+        slots: u8,
+    },
+    /// Adds one to slot `index`. Accounts: slot counter, authority (signer).
+    IncrementSlot {
+        index: u8,
+    },
+    /// Subtracts one from slot `index`. Accounts match `IncrementSlot`.
+    DecrementSlot {
+        index: u8,
+    },
+    /// Sets slot `index` as return data, 8 bytes little-endian. Accounts:
+    /// slot counter.
+    GetSlot {
+        index: u8,
+    },
+    /// Adds the counter's step only if the count equals `expected`, failing
+    /// with `StaleValue` otherwise. Accounts match `IncrementCounter`.
+    ConditionalIncrement {
+        expected: u64,
+    },
+    /// Moves `amount` from the source counter to the destination counter in
+    /// one step. Accounts: source (writable), destination (writable), source
+    /// authority (signer).
+    TransferCount {
+        amount: u64,
+    },
+    /// Closes a counter past its `expires_at`, refunding its original payer.
+    /// Anyone may send it. Accounts: counter (writable), original payer
+    /// (writable), then a strict counter's excess destination (writable).
+    CloseExpired,
+    /// Adds the counter's step for a signer proving it is leaf `index` of
+    /// the counter's merkle allowlist, once per leaf. Accounts: counter
+    /// (writable), actor (signer, writable, pays for a new claim bitmap),
+    /// the leaf's claim bitmap PDA (writable), system program, then gate and
+    /// trigger accounts.
+    IncrementWithProof {
+        index: u32,
+        proof: MerkleProof,
+    },
+    /// Creates the program's global config with default parameters.
+    /// Accounts: global config PDA `["config"]` (writable), the program's
+    /// upgrade authority (signer, writable, pays), the program's data
+    /// account, system program. Every instruction consults the config when
+    /// it is appended to its accounts; counter creations must append it.
+    InitializeGlobalConfig,
+    /// Replaces the global config's parameters. Accounts: global config
+    /// (writable), the program's upgrade authority (signer), the program's
+    /// data account.
+    SetGlobalConfig {
+        params: GlobalParams,
+    },
+    /// Sets the Borsh-encoded count as return data, the same 8 bytes as the
+    /// interface's `GetValue`. Unlike `GetCount` it needs no view key, so
+    /// any program or simulating client can read the count without knowing
+    /// the account layout. Accounts: counter.
+    ReadCount,
+}
+
+/// Addresses one of the counter's named metrics.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum MetricRef {
+    Index(u8),
+    Name(Label32),
+}
+
+/// Optional settings appended after `initial_value` in the initialize payload.
+/// An empty tail means every option is left at its default.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct InitConfig {
+    /// Defaults to the payer.
+    pub authority: Option<Pubkey>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Starts the counter in commitment mode with this `commitment_hash` of
+    /// the off-chain initial value. `initial_value` must then be zero.
+    pub value_commitment: Option<[u8; 32]>,
+    /// Named metrics created with the counter, at most `MAX_METRICS`.
+    pub metrics: Vec<MetricConfig>,
+    pub trigger: Option<TriggerConfig>,
+    /// Makes the counter a read-only mirror of this SPL mint's supply.
+    pub mirror_mint: Option<Pubkey>,
+    /// Capacity of the TWAP observation buffer, zero to disable it.
+    pub observation_slots: u16,
+    /// Defaults to the authority gate alone.
+    pub gates: Option<GateConfig>,
+    /// Inactive slots before the counter may be reaped, zero for no TTL.
+    pub ttl_slots: u64,
+    /// Amount `IncrementCounter` and `DecrementCounter` move by, zero for 1.
+    pub step: u64,
+    /// Makes the counter a read-only mirror of a counter on another cluster.
+    pub import_source: Option<ImportSource>,
+    /// Caps increments at this value; `initial_value` must not exceed it.
+    pub max_value: Option<u64>,
+    pub cap_policy: CapPolicy,
+    /// Enables strict mode: donated lamports go here on close and skim.
+    pub excess_destination: Option<Pubkey>,
+    /// Closes the counter when a decrement reaches zero, so it can serve as
+    /// a "remaining uses" ticket.
+    pub close_at_zero: bool,
+    /// Compute units count changes must have left before they start their
+    /// side effects, zero to skip the check.
+    pub min_compute_units: u64,
+    /// Increments kept in the counter's history, at most `MAX_HISTORY`, zero
+    /// to disable it.
+    pub history_len: u16,
+    /// Privileged operations kept in the counter's access log, at most
+    /// `MAX_ACCESS_LOG`, zero to disable it.
+    pub access_log_len: u16,
+    /// Unix timestamp before which increments and decrements fail.
+    pub unlock_timestamp: Option<i64>,
+    /// Lamports each increment charges the actor, which must then sign and
+    /// be writable.
+    pub increment_fee: u64,
+    /// Receives increment fees. Without one, fees go to the counter's
+    /// `["fee_vault", counter]` PDA, which must be passed, writable, to the
+    /// initializing instruction.
+    pub fee_recipient: Option<Pubkey>,
+    /// Unix timestamp from which the counter is read-only and anyone may
+    /// close it with `CloseExpired`. Must be in the future.
+    pub expires_at: Option<i64>,
+    /// Root of a merkle allowlist, see `merkle`. Only proven signers can
+    /// then change the count, and the authority gate is off by default.
+    pub merkle_root: Option<[u8; 32]>,
+    /// Allows one increment of the counter per transaction; see
+    /// `CounterAccount::once_per_transaction`.
+    pub once_per_transaction: bool,
+    /// Finalizes the counter after this many increments, zero for no limit;
+    /// see `CounterAccount::max_uses`.
+    pub max_uses: u64,
+    /// Logs every increment to log pages; see `CounterAccount::event_log`.
+    pub event_log: bool,
+    /// Transfers the token gate's minimum balance to this token account on
+    /// every gated mutation; see `CounterAccount::token_treasury`.
+    pub token_treasury: Option<Pubkey>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MetricConfig {
+    pub name: Label32,
+    pub initial_value: u64,
+    /// Zero leaves the metric unbounded.
+    pub max_value: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    pub max_delta: u64,
+    pub window_slots: u64,
+}
+
+/// Hash commitment to a counter value: `sha256(value_le || salt)`. Used both
+/// for pending `RevealIncrement` amounts and for counters in commitment mode.
+pub fn commitment_hash(value: u64, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[&value.to_le_bytes(), salt]).to_bytes()
+}
+
+/// `sha256(code)`. Coupons are minted against the hash so the code itself
+/// stays secret until it is redeemed.
+pub fn coupon_code_hash(code: &[u8]) -> [u8; 32] {
+    hashv(&[code]).to_bytes()
+}
+
+/// `sha256(id)`. Maps an entity's own identifier, such as an order ID, onto
+/// the `external_id` its counter's address is derived from.
+pub fn external_id_hash(id: &[u8]) -> [u8; 32] {
+    hashv(&[id]).to_bytes()
 }
 
 impl CounterInstruction {
+    /// Encodes the instruction in the wire format `unpack` reads.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        match self {
+            Self::InitializeCounter {
+                initial_value,
+                config,
+            } => {
+                data.push(0);
+                data.extend_from_slice(&initial_value.to_le_bytes());
+                push_borsh(&mut data, config.as_ref());
+            }
+            Self::IncrementCounter => data.push(1),
+            Self::ResetCircuitBreaker => data.push(2),
+            Self::CommitIncrement { hash } => {
+                data.push(3);
+                data.extend_from_slice(hash);
+            }
+            Self::RevealIncrement { amount, salt } => {
+                data.push(4);
+                data.extend_from_slice(&amount.to_le_bytes());
+                data.extend_from_slice(salt);
+            }
+            Self::IncrementCommitted {
+                value,
+                salt,
+                amount,
+                new_salt,
+            } => {
+                data.push(5);
+                data.extend_from_slice(&value.to_le_bytes());
+                data.extend_from_slice(salt);
+                data.extend_from_slice(&amount.to_le_bytes());
+                data.extend_from_slice(new_salt);
+            }
+            Self::IncrementMetric { metric, amount } => {
+                data.push(6);
+                push_borsh(&mut data, metric);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::InitializeComposite { sources } => {
+                data.push(7);
+                push_borsh(&mut data, sources);
+            }
+            Self::RefreshComposite => data.push(8),
+            Self::DecrementCounter => data.push(9),
+            Self::SyncMintSupply => data.push(10),
+            Self::IncrementBy { amount } => {
+                data.push(11);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::DecrementBy { amount } => {
+                data.push(12);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::TransferAuthority { new_authority } => {
+                data.push(13);
+                data.extend_from_slice(new_authority.as_ref());
+            }
+            Self::CloseCounter => data.push(14),
+            Self::InitializeCounterPda {
+                initial_value,
+                name,
+                config,
+            } => {
+                data.push(15);
+                data.extend_from_slice(&initial_value.to_le_bytes());
+                push_borsh(&mut data, name);
+                push_borsh(&mut data, config.as_ref());
+            }
+            Self::ResetCounter => data.push(16),
+            Self::SetCounter { value } => {
+                data.push(17);
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            #[cfg(feature = "devnet-tools")]
+            Self::ForceSet { value } => {
+                data.push(18);
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            #[cfg(feature = "devnet-tools")]
+            Self::FastForwardCooldown => data.push(19),
+            #[cfg(feature = "devnet-tools")]
+            Self::ResetStats => data.push(20),
+            Self::Reap => data.push(21),
+            Self::MigrateAccount => data.push(22),
+            Self::LockCount {
+                amount,
+                duration_slots,
+            } => {
+                data.push(23);
+                data.extend_from_slice(&amount.to_le_bytes());
+                data.extend_from_slice(&duration_slots.to_le_bytes());
+            }
+            Self::UnlockCount => data.push(24),
+            Self::GrantViewKey { program } => {
+                data.push(25);
+                data.extend_from_slice(program.as_ref());
+            }
+            Self::RevokeViewKey => data.push(26),
+            Self::GetCount => data.push(27),
+            Self::SetTopics { topics } => {
+                data.push(28);
+                push_borsh(&mut data, topics);
+            }
+            Self::InitializeAndIncrement {
+                initial_value,
+                delta,
+            } => {
+                data.push(29);
+                data.extend_from_slice(&initial_value.to_le_bytes());
+                data.extend_from_slice(&delta.to_le_bytes());
+            }
+            Self::SetStep { step } => {
+                data.push(30);
+                data.extend_from_slice(&step.to_le_bytes());
+            }
+            Self::MintCoupon { code_hash, amount } => {
+                data.push(31);
+                data.extend_from_slice(code_hash);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::RedeemCoupon { code } => {
+                data.push(32);
+                push_borsh(&mut data, code);
+            }
+            Self::ImportAttestation { snapshot } => {
+                data.push(33);
+                push_borsh(&mut data, snapshot);
+            }
+            Self::IncrementMany => data.push(34),
+            Self::SkimExcess => data.push(35),
+            Self::SetTemplate { name, config } => {
+                data.push(36);
+                push_borsh(&mut data, name);
+                push_borsh(&mut data, config.as_ref());
+            }
+            Self::InitializeFromTemplate { initial_value } => {
+                data.push(37);
+                data.extend_from_slice(&initial_value.to_le_bytes());
+            }
+            Self::InitializeRegistry => data.push(38),
+            Self::SetMode { mode } => {
+                data.push(39);
+                push_borsh(&mut data, mode);
+            }
+            Self::Pause => data.push(40),
+            Self::Resume => data.push(41),
+            Self::InitializeCounterById {
+                initial_value,
+                external_id,
+                config,
+            } => {
+                data.push(42);
+                data.extend_from_slice(&initial_value.to_le_bytes());
+                data.extend_from_slice(external_id);
+                push_borsh(&mut data, config);
+            }
+            Self::Snapshot => data.push(43),
+            Self::AddDelegate { delegate } => {
+                data.push(44);
+                data.extend_from_slice(delegate.as_ref());
+            }
+            Self::RemoveDelegate { delegate } => {
+                data.push(45);
+                data.extend_from_slice(delegate.as_ref());
+            }
+            Self::CreateMultisig { threshold, signers } => {
+                data.push(46);
+                data.push(*threshold);
+                push_borsh(&mut data, signers);
+            }
+            Self::SetUnlockTime { unlock_timestamp } => {
+                data.push(47);
+                push_borsh(&mut data, unlock_timestamp);
+            }
+            Self::SetOwnerQuota {
+                max_counters_per_owner,
+            } => {
+                data.push(48);
+                data.extend_from_slice(&max_counters_per_owner.to_le_bytes());
+            }
+            Self::WithdrawFees => data.push(49),
+            Self::Resize => data.push(50),
+            Self::AdminFreeze => data.push(51),
+            Self::RequestUnfreeze => data.push(52),
+            Self::BurnCounter => data.push(53),
+            Self::SetUnfreezeGracePeriod { grace_secs } => {
+                data.push(54);
+                data.extend_from_slice(&grace_secs.to_le_bytes());
+            }
+            Self::InitializeSignedCounter { initial_value } => {
+                data.push(55);
+                data.extend_from_slice(&initial_value.to_le_bytes());
+            }
+            Self::IncrementSigned { amount } => {
+                data.push(56);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::DecrementSigned { amount } => {
+                data.push(57);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::InitializeSlotCounter { slots } => {
+                data.push(58);
+                data.push(*slots);
+            }
+            Self::IncrementSlot { index } => {
+                data.push(59);
+                data.push(*index);
+            }
+            Self::DecrementSlot { index } => {
+                data.push(60);
+                data.push(*index);
+            }
+            Self::GetSlot { index } => {
+                data.push(61);
+                data.push(*index);
+            }
+            Self::ConditionalIncrement { expected } => {
+                data.push(62);
+                data.extend_from_slice(&expected.to_le_bytes());
+            }
+            Self::TransferCount { amount } => {
+                data.push(63);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::CloseExpired => data.push(64),
+            Self::IncrementWithProof { index, proof } => {
+                data.push(65);
+                data.extend_from_slice(&index.to_le_bytes());
+                push_borsh(&mut data, proof);
+            }
+            Self::InitializeGlobalConfig => data.push(66),
+            Self::SetGlobalConfig { params } => {
+                data.push(67);
+                push_borsh(&mut data, params);
+            }
+            Self::ReadCount => data.push(68),
+        }
+
+        data
+    }
+
+    /// Decodes instruction data, which must be exactly what `pack` produces
+    /// (or, for the `InitializeCounter*` variants, that with the `InitConfig`
+    /// left off). A tag no variant uses is `UnknownInstruction`; a payload
+    /// that is short, malformed or followed by extra bytes is
+    /// `InvalidInstructionData`.
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (&variant, rest) = input
             .split_first()
-            .ok_or(ProgramError::InvalidInstructionData)?;
+            .ok_or(CounterError::UnknownInstruction)?;
+        let mut payload = Payload { rest };
 
-        match variant {
-            0 => {
-                let initial_value = u64::from_le_bytes(
-                    rest.try_into()
-                        .map_err(|_| ProgramError::InvalidInstructionData)?,
-                );
+        let instruction = match variant {
+            0 => Self::InitializeCounter {
+                initial_value: payload.read()?,
+                config: payload.config()?,
+            },
+            1 => Self::IncrementCounter,
+            2 => Self::ResetCircuitBreaker,
+            3 => Self::CommitIncrement {
+                hash: payload.read()?,
+            },
+            4 => Self::RevealIncrement {
+                amount: payload.read()?,
+                salt: payload.read()?,
+            },
+            5 => Self::IncrementCommitted {
+                value: payload.read()?,
+                salt: payload.read()?,
+                amount: payload.read()?,
+                new_salt: payload.read()?,
+            },
+            6 => Self::IncrementMetric {
+                metric: payload.read()?,
+                amount: payload.read()?,
+            },
+            7 => Self::InitializeComposite {
+                sources: payload.read()?,
+            },
+            8 => Self::RefreshComposite,
+            9 => Self::DecrementCounter,
+            10 => Self::SyncMintSupply,
+            11 => Self::IncrementBy {
+                amount: payload.read()?,
+            },
+            12 => Self::DecrementBy {
+                amount: payload.read()?,
+            },
+            13 => Self::TransferAuthority {
+                new_authority: payload.read()?,
+            },
+            14 => Self::CloseCounter,
+            15 => Self::InitializeCounterPda {
+                initial_value: payload.read()?,
+                name: payload.read()?,
+                config: payload.config()?,
+            },
+            16 => Self::ResetCounter,
+            17 => Self::SetCounter {
+                value: payload.read()?,
+            },
+            #[cfg(feature = "devnet-tools")]
+            18 => Self::ForceSet {
+                value: payload.read()?,
+            },
+            #[cfg(feature = "devnet-tools")]
+            19 => Self::FastForwardCooldown,
+            #[cfg(feature = "devnet-tools")]
+            20 => Self::ResetStats,
+            21 => Self::Reap,
+            22 => Self::MigrateAccount,
+            23 => Self::LockCount {
+                amount: payload.read()?,
+                duration_slots: payload.read()?,
+            },
+            24 => Self::UnlockCount,
+            25 => Self::GrantViewKey {
+                program: payload.read()?,
+            },
+            26 => Self::RevokeViewKey,
+            27 => Self::GetCount,
+            28 => Self::SetTopics {
+                topics: payload.read()?,
+            },
+            29 => Self::InitializeAndIncrement {
+                initial_value: payload.read()?,
+                delta: payload.read()?,
+            },
+            30 => Self::SetStep {
+                step: payload.read()?,
+            },
+            31 => Self::MintCoupon {
+                code_hash: payload.read()?,
+                amount: payload.read()?,
+            },
+            32 => Self::RedeemCoupon {
+                code: payload.read()?,
+            },
+            33 => Self::ImportAttestation {
+                snapshot: payload.read()?,
+            },
+            34 => Self::IncrementMany,
+            35 => Self::SkimExcess,
+            36 => Self::SetTemplate {
+                name: payload.read()?,
+                config: payload.config()?,
+            },
+            37 => Self::InitializeFromTemplate {
+                initial_value: payload.read()?,
+            },
+            38 => Self::InitializeRegistry,
+            39 => Self::SetMode {
+                mode: payload.read()?,
+            },
+            40 => Self::Pause,
+            41 => Self::Resume,
+            42 => Self::InitializeCounterById {
+                initial_value: payload.read()?,
+                external_id: payload.read()?,
+                config: payload.config()?,
+            },
+            43 => Self::Snapshot,
+            44 => Self::AddDelegate {
+                delegate: payload.read()?,
+            },
+            45 => Self::RemoveDelegate {
+                delegate: payload.read()?,
+            },
+            46 => Self::CreateMultisig {
+                threshold: payload.read()?,
+                signers: payload.read()?,
+            },
+            47 => Self::SetUnlockTime {
+                unlock_timestamp: payload.read()?,
+            },
+            48 => Self::SetOwnerQuota {
+                max_counters_per_owner: payload.read()?,
+            },
+            49 => Self::WithdrawFees,
+            50 => Self::Resize,
+            51 => Self::AdminFreeze,
+            52 => Self::RequestUnfreeze,
+            53 => Self::BurnCounter,
+            54 => Self::SetUnfreezeGracePeriod {
+                grace_secs: payload.read()?,
+            },
+            55 => Self::InitializeSignedCounter {
+                initial_value: payload.read()?,
+            },
+            56 => Self::IncrementSigned {
+                amount: payload.read()?,
+            },
+            57 => Self::DecrementSigned {
+                amount: payload.read()?,
+            },
+            58 => Self::InitializeSlotCounter {
+                slots: payload.read()?,
+            },
+            59 => Self::IncrementSlot {
+                index: payload.read()?,
+            },
+            60 => Self::DecrementSlot {
+                index: payload.read()?,
+            },
+            61 => Self::GetSlot {
+                index: payload.read()?,
+            },
+            62 => Self::ConditionalIncrement {
+                expected: payload.read()?,
+            },
+            63 => Self::TransferCount {
+                amount: payload.read()?,
+            },
+            64 => Self::CloseExpired,
+            65 => Self::IncrementWithProof {
+                index: payload.read()?,
+                proof: payload.read()?,
+            },
+            66 => Self::InitializeGlobalConfig,
+            67 => Self::SetGlobalConfig {
+                params: payload.read()?,
+            },
+            68 => Self::ReadCount,
+            _ => return Err(CounterError::UnknownInstruction.into()),
+        };
 
-                Ok(Self::InitializeCounter { initial_value })
-            }
-            1 => Ok(Self::IncrementCounter),
-            _ => Err(ProgramError::InvalidInstructionData),
+        payload.finish()?;
+        Ok(instruction)
+    }
+}
+
+fn push_borsh(data: &mut Vec<u8>, value: &impl BorshSerialize) {
+    value
+        .serialize(data)
+        .expect("serializing into a Vec cannot fail");
+}
+
+/// The bytes after the tag, consumed field by field in Borsh encoding.
+struct Payload<'a> {
+    rest: &'a [u8],
+}
+
+impl Payload<'_> {
+    fn read<T: BorshDeserialize>(&mut self) -> Result<T, ProgramError> {
+        T::deserialize(&mut self.rest).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    /// An optional trailing `InitConfig`; nothing left means the default.
+    fn config(&mut self) -> Result<Box<InitConfig>, ProgramError> {
+        if self.rest.is_empty() {
+            return Ok(Box::default());
         }
+
+        self.read().map(Box::new)
     }
-}
\ No newline at end of file
+
+    /// Rejects anything left over once every field has been read.
+    fn finish(self) -> Result<(), ProgramError> {
+        if !self.rest.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(())
+    }
+}
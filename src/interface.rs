@@ -0,0 +1,150 @@
+//! A minimal counter interface other programs can implement, so callers can
+//! increment and read counters owned by any implementer the same way.
+//!
+//! Interface instructions start with an 8-byte discriminator, the first bytes
+//! of `sha256("counter-interface:<name>")`, which keeps them clear of an
+//! implementer's own instruction tags. An implementer checks incoming data
+//! with `CounterInterfaceInstruction::unpack` before its own decoding.
+
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::backend::backend_for;
+use crate::state::CounterAccount;
+
+pub const INCREMENT_DISCRIMINATOR: [u8; 8] = [248, 31, 29, 48, 53, 5, 30, 47];
+pub const GET_VALUE_DISCRIMINATOR: [u8; 8] = [80, 76, 143, 50, 226, 25, 46, 236];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterInterfaceInstruction {
+    /// Adds `amount` to the counter. Accounts: counter (writable), actor
+    /// (signer), then any accounts the implementer needs.
+    Increment { amount: u64 },
+    /// Sets the counter's value as return data, 8 bytes little-endian.
+    /// Accounts: counter.
+    GetValue,
+}
+
+impl CounterInterfaceInstruction {
+    pub fn pack(&self) -> Vec<u8> {
+        match self {
+            Self::Increment { amount } => {
+                let mut data = INCREMENT_DISCRIMINATOR.to_vec();
+                data.extend_from_slice(&amount.to_le_bytes());
+                data
+            }
+            Self::GetValue => GET_VALUE_DISCRIMINATOR.to_vec(),
+        }
+    }
+
+    /// `None` when `input` is not an interface instruction at all, so the
+    /// implementer can fall back to its own instructions.
+    pub fn unpack(input: &[u8]) -> Result<Option<Self>, ProgramError> {
+        let Some((discriminator, rest)) = input.split_first_chunk::<8>() else {
+            return Ok(None);
+        };
+
+        match *discriminator {
+            INCREMENT_DISCRIMINATOR => {
+                let amount = rest
+                    .try_into()
+                    .map(u64::from_le_bytes)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Ok(Some(Self::Increment { amount }))
+            }
+            GET_VALUE_DISCRIMINATOR if rest.is_empty() => Ok(Some(Self::GetValue)),
+            GET_VALUE_DISCRIMINATOR => Err(ProgramError::InvalidInstructionData),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Counter state every implementer exposes, whatever its account layout.
+pub trait CounterView {
+    fn count(&self) -> u64;
+    fn authority(&self) -> Pubkey;
+}
+
+/// Decodes an implementer's counter account from its data.
+pub trait CounterLayout: CounterView + Sized {
+    fn unpack_counter(data: &[u8]) -> Result<Self, ProgramError>;
+}
+
+impl CounterView for CounterAccount {
+    fn count(&self) -> u64 {
+        self.count
+    }
+
+    fn authority(&self) -> Pubkey {
+        self.authority
+    }
+}
+
+impl CounterLayout for CounterAccount {
+    fn unpack_counter(data: &[u8]) -> Result<Self, ProgramError> {
+        backend_for(data)?.load(data)
+    }
+}
+
+/// `Increment` for the implementer `program_id`. `extra` are appended after
+/// the counter and actor, for implementers that gate increments.
+pub fn increment_ix(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    actor: &Pubkey,
+    amount: u64,
+    extra: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*counter, false),
+        AccountMeta::new_readonly(*actor, true),
+    ];
+    accounts.extend_from_slice(extra);
+
+    Instruction::new_with_bytes(
+        *program_id,
+        &CounterInterfaceInstruction::Increment { amount }.pack(),
+        accounts,
+    )
+}
+
+pub fn get_value_ix(program_id: &Pubkey, counter: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(
+        *program_id,
+        &CounterInterfaceInstruction::GetValue.pack(),
+        vec![AccountMeta::new_readonly(*counter, false)],
+    )
+}
+
+/// Decodes the return data `GetValue` sets.
+pub fn parse_value(return_data: &[u8]) -> Result<u64, ProgramError> {
+    return_data
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// Reads `counter` through a CPI to the implementer that owns it.
+pub fn get_value(counter: &AccountInfo) -> Result<u64, ProgramError> {
+    let program_id = counter.owner;
+    invoke(
+        &get_value_ix(program_id, counter.key),
+        std::slice::from_ref(counter),
+    )?;
+
+    match get_return_data() {
+        Some((returned_by, data)) if &returned_by == program_id => parse_value(&data),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Reads an implementer's counter straight from account data, for callers
+/// that know its layout.
+pub fn read_count<L: CounterLayout>(data: &[u8]) -> Result<u64, ProgramError> {
+    Ok(L::unpack_counter(data)?.count())
+}
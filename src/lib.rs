@@ -1,7 +1,42 @@
+pub mod accounts;
+pub mod addresses;
+#[cfg(feature = "anchor-layout")]
+pub mod anchor;
+pub mod backend;
+#[cfg(feature = "client")]
+pub mod batcher;
+pub mod bounded;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "cpi")]
+pub mod cpi;
+pub mod dispatch;
+pub mod ed25519;
+// wasm builds are front-ends decoding accounts, never the program itself
+#[cfg(not(any(feature = "no-entrypoint", target_arch = "wasm32")))]
 pub mod entrypoint;
+pub mod error;
+pub mod events;
+#[cfg(feature = "client")]
+pub mod export;
+pub mod gate;
+#[cfg(not(target_os = "solana"))]
+pub mod idl;
 pub mod instruction;
+pub mod interface;
+pub mod merkle;
 pub mod processor;
+pub mod spl;
 pub mod state;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zero_copy;
+
+pub use instruction::CounterInstruction;
+pub use processor::process_instruction;
+pub use state::CounterAccount;
 
 #[cfg(test)]
 mod tests;
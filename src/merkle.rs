@@ -0,0 +1,85 @@
+//! Merkle allowlists for `IncrementWithProof`. A counter stores only the
+//! root; the allowlist is a list of keys, leaf `i` being
+//! `sha256(0x00 || i as u32 LE || key)` and each inner node
+//! `sha256(0x01 || left || right)`. The bits of the leaf index say which
+//! side each proof element goes on, lowest bit first. A level with an odd
+//! number of nodes pairs its last node with itself.
+//!
+//! Leaf indexes double as claim bits, so each allowlisted key can increment
+//! once; see `ClaimBitmap`.
+
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+pub fn leaf_hash(index: u32, key: &Pubkey) -> [u8; 32] {
+    hashv(&[LEAF_PREFIX, &index.to_le_bytes(), key.as_ref()]).to_bytes()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Whether `key` is leaf `index` of the tree with `root`.
+pub fn verify(root: &[u8; 32], index: u32, key: &Pubkey, proof: &[[u8; 32]]) -> bool {
+    let mut node = leaf_hash(index, key);
+    for (level, sibling) in (0u32..).zip(proof) {
+        node = if index.checked_shr(level).unwrap_or(0) & 1 == 1 {
+            node_hash(sibling, &node)
+        } else {
+            node_hash(&node, sibling)
+        };
+    }
+    node == *root
+}
+
+/// Builds the root and proofs for an allowlist off-chain.
+#[cfg(not(target_os = "solana"))]
+pub struct MerkleTree {
+    /// Leaves first, root last.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+#[cfg(not(target_os = "solana"))]
+impl MerkleTree {
+    pub fn new(keys: &[Pubkey]) -> Self {
+        let mut level: Vec<[u8; 32]> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| leaf_hash(index as u32, key))
+            .collect();
+        let mut levels = Vec::new();
+        while level.len() > 1 {
+            let next = level
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            levels.push(level);
+            level = next;
+        }
+        levels.push(level);
+        Self { levels }
+    }
+
+    /// All zeroes for an empty allowlist, which no proof satisfies.
+    pub fn root(&self) -> [u8; 32] {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Proof that the key at `index` is on the allowlist.
+    pub fn proof(&self, index: u32) -> Vec<[u8; 32]> {
+        let mut position = index as usize;
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+            proof.push(*sibling);
+            position /= 2;
+        }
+        proof
+    }
+}
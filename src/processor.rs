@@ -1,102 +1,4514 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    compute_units::sol_remaining_compute_units,
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT, get_stack_height},
     msg,
-    program::invoke,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction,
-    sysvar::{Sysvar, rent::Rent},
+    system_instruction, system_program,
+    sysvar::{self, Sysvar, instructions::load_instruction_at_checked, rent::Rent},
 };
 
-use crate::instruction::CounterInstruction;
-use crate::state::CounterAccount;
+use crate::accounts::{
+    AdminFreezeAccounts, AuthorityAccounts, BurnAccounts, CloseAccounts, CloseExpiredAccounts,
+    CounterAccounts, CreateAccounts, CreatePdaAccounts, IncrementManyAccounts,
+    IncrementWithProofAccounts, InitializeFromTemplateAccounts, InitializeGlobalConfigAccounts,
+    MigrateAccounts, MintCouponAccounts, MutateAccounts, ReadAccounts, ReapAccounts,
+    RedeemCouponAccounts, RegistryAdminAccounts, RequestUnfreezeAccounts, SetGlobalConfigAccounts,
+    SetTemplateAccounts, SignedCounterAccounts, SlotCounterAccounts, SnapshotAccounts,
+    TransferCountAccounts, ViewAccounts, ViewKeyAccounts, WithdrawFeesAccounts,
+};
+use crate::addresses::{
+    CLAIM_BITMAP_DISCRIMINATOR, CLAIMS_SEED, COMPOSITE_DISCRIMINATOR, COUNTER_DISCRIMINATOR,
+    COUNTER_SEED, COUPON_DISCRIMINATOR, COUPON_SEED, EXTERNAL_ID_SEED, FEE_VAULT_SEED,
+    GLOBAL_CONFIG_DISCRIMINATOR, GLOBAL_CONFIG_SEED, LOG_PAGE_DISCRIMINATOR, LOG_SEED,
+    MULTISIG_DISCRIMINATOR, OWNER_INDEX_DISCRIMINATOR, OWNER_INDEX_SEED, OWNER_STATS_DISCRIMINATOR,
+    OWNER_STATS_SEED, REGISTRY_DISCRIMINATOR, REGISTRY_SEED, SIGNED_COUNTER_DISCRIMINATOR,
+    SLOT_COUNTER_DISCRIMINATOR, SNAPSHOT_DISCRIMINATOR, SNAPSHOT_SEED, TEMPLATE_DISCRIMINATOR,
+    TEMPLATE_SEED, TRIGGER_SEED, USER_RECORD_DISCRIMINATOR, USER_RECORD_SEED,
+    VIEW_KEY_DISCRIMINATOR, VIEW_KEY_SEED, derive_claim_bitmap_address, derive_counter_address,
+    derive_coupon_address, derive_external_counter_address, derive_fee_vault_address,
+    derive_global_config_address, derive_log_page_address, derive_owner_index_address,
+    derive_owner_stats_address, derive_program_data_address, derive_registry_address,
+    derive_snapshot_address, derive_template_address, derive_trigger_authority,
+    derive_user_record_address, derive_view_key_address, derive_viewer_authority,
+};
+use crate::backend::{backend_for, load_counter, save_counter, write_counter};
+use crate::bounded::Label32;
+use crate::dispatch;
+use crate::ed25519::verify_preceding_signature;
+use crate::error::CounterError;
+use crate::events::{self, CounterClosed, CounterEvent, CounterInitialized, ExcessRouted};
+use crate::gate::{
+    GATE_AUTHORITY, GATE_TOKEN_BALANCE, MerkleClaim, check_gates, check_gates_with_claim,
+};
+use crate::instruction::{
+    CounterInstruction, InitConfig, MetricRef, commitment_hash, coupon_code_hash,
+};
+use crate::interface::CounterInterfaceInstruction;
+use crate::spl::{self, read_mint_supply, read_token_account};
+use crate::state::{
+    AccessLog, AccessLogEntry, AttestationSnapshot, BARE_COUNTER_LEN, CLAIMS_PER_BITMAP,
+    COUNTER_VERSION, CircuitBreaker, ClaimBitmap, CompositeCounter, CountAttestation,
+    CounterAccount, Coupon, DEFAULT_UNFREEZE_GRACE_SECS, Delegates, GateConfig, GlobalConfig,
+    GlobalParams, HistoryBuffer, HistoryEntry, IncrementMode, IndexedCounters, LogPage, LogRecord,
+    MAX_ACCESS_LOG, MAX_ALLOWLIST, MAX_COMPOSITE_SOURCES, MAX_COUNTER_SLOTS, MAX_HISTORY,
+    MAX_INDEXED_COUNTERS, MAX_METRICS, MAX_OBSERVATIONS, MAX_TRIGGER_ACCOUNTS,
+    MAX_TRIGGER_DATA_LEN, Metric, Multisig, MultisigSigners, ObservationBuffer, OwnerIndex,
+    OwnerStats, REAP_REWARD_BPS, Registry, SignedCounter, SlotCounter, Snapshot, Template, Topics,
+    TriggerConfig, UserRecord, ViewKey, WEIGHT_SCALE, WeightedSource,
+};
+use crate::zero_copy::PlainCounter;
 
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    if let Some(instruction) = CounterInterfaceInstruction::unpack(instruction_data)? {
+        check_not_globally_paused(program_id, accounts)?;
+        return process_interface_instruction(program_id, accounts, instruction);
+    }
+
+    #[cfg(feature = "anchor-layout")]
+    let translated = crate::anchor::translate(instruction_data);
+    #[cfg(feature = "anchor-layout")]
+    let instruction_data = translated.as_deref().unwrap_or(instruction_data);
+
     let instruction = CounterInstruction::unpack(instruction_data)?;
 
-    match instruction {
-        CounterInstruction::InitializeCounter { initial_value } => {
-            process_initialize_counter(program_id, accounts, initial_value)?;
-        }
-        CounterInstruction::IncrementCounter => process_increment_counter(program_id, accounts)?,
+    // `unpack` has rejected empty data and unknown tags by now.
+    let meta = dispatch::meta(instruction_data[0]).ok_or(ProgramError::InvalidInstructionData)?;
+    meta.validate(accounts)?;
+
+    // the upgrade authority has to be able to lift a pause
+    if !matches!(instruction, CounterInstruction::SetGlobalConfig { .. }) {
+        check_not_globally_paused(program_id, accounts)?;
+    }
+
+    (meta.handler)(program_id, accounts, instruction)?;
+
+    if meta.privileged {
+        record_access(program_id, accounts, meta.tag)?;
     }
 
     Ok(())
 }
 
+/// Declares each tag's `InstructionMeta::handler`, which takes its variant
+/// apart and calls the processor. A handler only ever sees the variant its
+/// tag decodes to, so any other is treated as malformed data.
+macro_rules! handlers {
+    (
+        |$program_id:ident, $accounts:ident|
+        $(
+            $(#[$attr:meta])*
+            $name:ident($variant:ident $({ $($field:ident),* })?) => $process:expr;
+        )*
+    ) => {
+        $(
+            $(#[$attr])*
+            pub(crate) fn $name(
+                $program_id: &Pubkey,
+                $accounts: &[AccountInfo],
+                instruction: CounterInstruction,
+            ) -> ProgramResult {
+                let CounterInstruction::$variant $({ $($field),* })? = instruction else {
+                    return Err(ProgramError::InvalidInstructionData);
+                };
+                $process
+            }
+        )*
+    };
+}
+
+/// Handlers in tag order, as `dispatch::INSTRUCTIONS` lists them.
+pub(crate) mod handlers {
+    use super::*;
+
+    handlers! {
+        |program_id, accounts|
+        initialize_counter(InitializeCounter { initial_value, config }) =>
+            process_initialize_counter(program_id, accounts, initial_value, *config);
+        increment_counter(IncrementCounter) =>
+            process_increment_counter(program_id, accounts, None, None);
+        reset_circuit_breaker(ResetCircuitBreaker) =>
+            process_reset_circuit_breaker(program_id, accounts);
+        commit_increment(CommitIncrement { hash }) =>
+            process_commit_increment(program_id, accounts, hash);
+        reveal_increment(RevealIncrement { amount, salt }) =>
+            process_reveal_increment(program_id, accounts, amount, salt);
+        increment_committed(IncrementCommitted { value, salt, amount, new_salt }) =>
+            process_increment_committed(program_id, accounts, value, salt, amount, new_salt);
+        increment_metric(IncrementMetric { metric, amount }) =>
+            process_increment_metric(program_id, accounts, metric, amount);
+        initialize_composite(InitializeComposite { sources }) =>
+            process_initialize_composite(program_id, accounts, sources);
+        refresh_composite(RefreshComposite) => process_refresh_composite(program_id, accounts);
+        decrement_counter(DecrementCounter) =>
+            process_decrement_counter(program_id, accounts, None);
+        sync_mint_supply(SyncMintSupply) => process_sync_mint_supply(program_id, accounts);
+        increment_by(IncrementBy { amount }) =>
+            process_increment_counter(program_id, accounts, Some(amount), None);
+        decrement_by(DecrementBy { amount }) =>
+            process_decrement_counter(program_id, accounts, Some(amount));
+        transfer_authority(TransferAuthority { new_authority }) =>
+            process_transfer_authority(program_id, accounts, new_authority);
+        close_counter(CloseCounter) => process_close_counter(program_id, accounts);
+        initialize_counter_pda(InitializeCounterPda { initial_value, name, config }) =>
+            process_initialize_counter_pda(program_id, accounts, initial_value, name, *config);
+        reset_counter(ResetCounter) => process_set_counter(program_id, accounts, 0);
+        set_counter(SetCounter { value }) => process_set_counter(program_id, accounts, value);
+        #[cfg(feature = "devnet-tools")]
+        force_set(ForceSet { value }) => process_force_set(program_id, accounts, value);
+        #[cfg(feature = "devnet-tools")]
+        fast_forward_cooldown(FastForwardCooldown) =>
+            process_fast_forward_cooldown(program_id, accounts);
+        #[cfg(feature = "devnet-tools")]
+        reset_stats(ResetStats) => process_reset_stats(program_id, accounts);
+        reap(Reap) => process_reap(program_id, accounts);
+        migrate_account(MigrateAccount) => process_migrate_account(program_id, accounts);
+        lock_count(LockCount { amount, duration_slots }) =>
+            process_lock_count(program_id, accounts, amount, duration_slots);
+        unlock_count(UnlockCount) => process_unlock_count(program_id, accounts);
+        grant_view_key(GrantViewKey { program }) =>
+            process_grant_view_key(program_id, accounts, program);
+        revoke_view_key(RevokeViewKey) => process_revoke_view_key(program_id, accounts);
+        get_count(GetCount) => process_get_count(program_id, accounts);
+        set_topics(SetTopics { topics }) => process_set_topics(program_id, accounts, topics);
+        initialize_and_increment(InitializeAndIncrement { initial_value, delta }) =>
+            process_initialize_and_increment(program_id, accounts, initial_value, delta);
+        set_step(SetStep { step }) => process_set_step(program_id, accounts, step);
+        mint_coupon(MintCoupon { code_hash, amount }) =>
+            process_mint_coupon(program_id, accounts, code_hash, amount);
+        redeem_coupon(RedeemCoupon { code }) => process_redeem_coupon(program_id, accounts, &code);
+        import_attestation(ImportAttestation { snapshot }) =>
+            process_import_attestation(program_id, accounts, snapshot);
+        increment_many(IncrementMany) => process_increment_many(program_id, accounts);
+        skim_excess(SkimExcess) => process_skim_excess(program_id, accounts);
+        set_template(SetTemplate { name, config }) =>
+            process_set_template(program_id, accounts, name, *config);
+        initialize_from_template(InitializeFromTemplate { initial_value }) =>
+            process_initialize_from_template(program_id, accounts, initial_value);
+        initialize_registry(InitializeRegistry) =>
+            process_initialize_registry(program_id, accounts);
+        set_mode(SetMode { mode }) => process_set_mode(program_id, accounts, mode);
+        pause(Pause) => process_set_paused(program_id, accounts, true);
+        resume(Resume) => process_set_paused(program_id, accounts, false);
+        initialize_counter_by_id(InitializeCounterById { initial_value, external_id, config }) =>
+            process_initialize_counter_by_id(
+                program_id,
+                accounts,
+                initial_value,
+                external_id,
+                *config,
+            );
+        snapshot(Snapshot) => process_snapshot(program_id, accounts);
+        add_delegate(AddDelegate { delegate }) =>
+            process_add_delegate(program_id, accounts, delegate);
+        remove_delegate(RemoveDelegate { delegate }) =>
+            process_remove_delegate(program_id, accounts, delegate);
+        create_multisig(CreateMultisig { threshold, signers }) =>
+            process_create_multisig(program_id, accounts, threshold, signers);
+        set_unlock_time(SetUnlockTime { unlock_timestamp }) =>
+            process_set_unlock_time(program_id, accounts, unlock_timestamp);
+        set_owner_quota(SetOwnerQuota { max_counters_per_owner }) =>
+            process_set_owner_quota(program_id, accounts, max_counters_per_owner);
+        withdraw_fees(WithdrawFees) => process_withdraw_fees(program_id, accounts);
+        resize(Resize) => process_resize(program_id, accounts);
+        admin_freeze(AdminFreeze) => process_admin_freeze(program_id, accounts);
+        request_unfreeze(RequestUnfreeze) => process_request_unfreeze(program_id, accounts);
+        burn_counter(BurnCounter) => process_burn_counter(program_id, accounts);
+        set_unfreeze_grace_period(SetUnfreezeGracePeriod { grace_secs }) =>
+            process_set_unfreeze_grace_period(program_id, accounts, grace_secs);
+        initialize_signed_counter(InitializeSignedCounter { initial_value }) =>
+            process_initialize_signed_counter(program_id, accounts, initial_value);
+        increment_signed(IncrementSigned { amount }) =>
+            process_change_signed_counter(program_id, accounts, |counter| {
+                counter.checked_add(amount)
+            });
+        decrement_signed(DecrementSigned { amount }) =>
+            process_change_signed_counter(program_id, accounts, |counter| {
+                counter.checked_sub(amount)
+            });
+        initialize_slot_counter(InitializeSlotCounter { slots }) =>
+            process_initialize_slot_counter(program_id, accounts, slots);
+        increment_slot(IncrementSlot { index }) =>
+            process_change_slot(program_id, accounts, index, |value| {
+                value.checked_add(1).ok_or(CounterError::Overflow)
+            });
+        decrement_slot(DecrementSlot { index }) =>
+            process_change_slot(program_id, accounts, index, |value| {
+                value.checked_sub(1).ok_or(CounterError::Underflow)
+            });
+        get_slot(GetSlot { index }) => process_get_slot(program_id, accounts, index);
+        conditional_increment(ConditionalIncrement { expected }) =>
+            process_increment_counter(program_id, accounts, None, Some(expected));
+        transfer_count(TransferCount { amount }) =>
+            process_transfer_count(program_id, accounts, amount);
+        close_expired(CloseExpired) => process_close_expired(program_id, accounts);
+        increment_with_proof(IncrementWithProof { index, proof }) =>
+            process_increment_with_proof(program_id, accounts, index, &proof);
+        initialize_global_config(InitializeGlobalConfig) =>
+            process_initialize_global_config(program_id, accounts);
+        set_global_config(SetGlobalConfig { params }) =>
+            process_set_global_config(program_id, accounts, params);
+        read_count(ReadCount) => process_read_count(program_id, accounts);
+    }
+
+    // `validate` rejects the tags of devnet tools left out of the build
+    // before their handler would run
+    #[cfg(not(feature = "devnet-tools"))]
+    pub(crate) fn unavailable(
+        _: &Pubkey,
+        _: &[AccountInfo],
+        _: CounterInstruction,
+    ) -> ProgramResult {
+        Err(ProgramError::InvalidInstructionData)
+    }
+    #[cfg(not(feature = "devnet-tools"))]
+    pub(crate) use {
+        unavailable as fast_forward_cooldown, unavailable as force_set, unavailable as reset_stats,
+    };
+}
+
 pub fn process_initialize_counter(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     initial_value: u64,
+    config: InitConfig,
 ) -> ProgramResult {
-    let accounts_iter = &mut accounts.iter();
+    let CreateAccounts {
+        new_account: counter_account,
+        payer: payer_account,
+        system_program,
+    } = CreateAccounts::parse(program_id, accounts)?;
 
-    let counter_account = next_account_info(accounts_iter)?;
-    let payer_account = next_account_info(accounts_iter)?;
-    let system_program = next_account_info(accounts_iter)?;
+    let config = with_global_defaults(program_id, accounts, config)?;
+    let account_space = CounterAccount::space_for(&config);
+    let mut counter_data = build_counter(program_id, payer_account.key, initial_value, config)?;
 
-    let account_space = 8;
+    charge_owner_quota(program_id, accounts, payer_account, system_program)?;
+    index_counter(
+        program_id,
+        accounts,
+        payer_account,
+        counter_account.key,
+        system_program,
+    )?;
+    setup_fee_vault(
+        program_id,
+        accounts,
+        counter_account.key,
+        &mut counter_data,
+        payer_account,
+        system_program,
+    )?;
 
-    let rent = Rent::get()?;
-    let required_lamports = rent.minimum_balance(account_space);
+    create_program_account(
+        program_id,
+        payer_account,
+        counter_account,
+        system_program,
+        account_space,
+        &[],
+    )?;
 
-    invoke(
-        &system_instruction::create_account(
-            payer_account.key,
-            counter_account.key,
-            required_lamports,
-            account_space as u64,
-            program_id,
-        ),
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter initialized with value {} ", initial_value);
+    emit_initialized(counter_account.key, &counter_data, initial_value);
+
+    update_registry(program_id, accounts, Registry::record_created)?;
+
+    Ok(())
+}
+
+/// Creates a default counter and applies its first increment before the
+/// account is written, so there is no window where it exists un-incremented.
+pub fn process_initialize_and_increment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+    delta: u64,
+) -> ProgramResult {
+    let CreateAccounts {
+        new_account: counter_account,
+        payer: payer_account,
+        system_program,
+    } = CreateAccounts::parse(program_id, accounts)?;
+
+    let config = with_global_defaults(program_id, accounts, InitConfig::default())?;
+    let account_space = CounterAccount::space_for(&config);
+    let mut counter_data = build_counter(program_id, payer_account.key, initial_value, config)?;
+
+    let new_count = counter_data.capped_add(delta)?;
+    apply_increment(&mut counter_data, new_count)?;
+
+    charge_owner_quota(program_id, accounts, payer_account, system_program)?;
+    index_counter(
+        program_id,
+        accounts,
+        payer_account,
+        counter_account.key,
+        system_program,
+    )?;
+    setup_fee_vault(
+        program_id,
+        accounts,
+        counter_account.key,
+        &mut counter_data,
+        payer_account,
+        system_program,
+    )?;
+
+    create_program_account(
+        program_id,
+        payer_account,
+        counter_account,
+        system_program,
+        account_space,
+        &[],
+    )?;
+
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Counter initialized with value {} and incremented to {}",
+        initial_value,
+        counter_data.count
+    );
+    emit_initialized(counter_account.key, &counter_data, initial_value);
+    emit_count_change(counter_account.key, &counter_data, initial_value);
+
+    let incremented = counter_data.count - initial_value;
+    update_registry(program_id, accounts, |registry| {
+        registry.record_created();
+        registry.record_incremented(incremented);
+    })?;
+
+    Ok(())
+}
+
+/// Same as `process_initialize_counter`, but the counter lives at the
+/// `["counter", owner, name]` PDA and the paying owner is its default
+/// authority.
+pub fn process_initialize_counter_pda(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+    name: Label32,
+    config: InitConfig,
+) -> ProgramResult {
+    let CreatePdaAccounts {
+        new_account: counter_account,
+        payer: owner_account,
+        system_program,
+    } = CreatePdaAccounts::parse(program_id, accounts)?;
+
+    let (counter_address, bump) = derive_counter_address(program_id, owner_account.key, &name);
+    if counter_account.key != &counter_address {
+        msg!("Counter account is not the owner's counter PDA for this name");
+        return Err(CounterError::InvalidCounterAddress.into());
+    }
+
+    let config = with_global_defaults(program_id, accounts, config)?;
+    let mut counter_data = build_counter(program_id, owner_account.key, initial_value, config)?;
+    counter_data.name = name;
+
+    let account_space = borsh::object_length(&counter_data)?;
+
+    charge_owner_quota(program_id, accounts, owner_account, system_program)?;
+    index_counter(
+        program_id,
+        accounts,
+        owner_account,
+        counter_account.key,
+        system_program,
+    )?;
+    setup_fee_vault(
+        program_id,
+        accounts,
+        counter_account.key,
+        &mut counter_data,
+        owner_account,
+        system_program,
+    )?;
+
+    create_program_account(
+        program_id,
+        owner_account,
+        counter_account,
+        system_program,
+        account_space,
         &[
-            payer_account.clone(),
-            counter_account.clone(),
-            system_program.clone(),
+            COUNTER_SEED,
+            owner_account.key.as_ref(),
+            counter_data.name.as_bytes(),
+            &[bump],
         ],
     )?;
 
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Counter PDA {} ({:?}) initialized with value {}",
+        counter_address,
+        counter_data.name,
+        initial_value
+    );
+    emit_initialized(counter_account.key, &counter_data, initial_value);
+
+    update_registry(program_id, accounts, Registry::record_created)?;
+
+    Ok(())
+}
+
+/// Validates `config` and builds the initial state for a new counter paid
+/// for by `payer`.
+fn build_counter(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    initial_value: u64,
+    config: InitConfig,
+) -> Result<CounterAccount, ProgramError> {
+    // committed counters never expose a value for the breaker or trigger to watch
+    if config.value_commitment.is_some()
+        && (initial_value != 0
+            || config.circuit_breaker.is_some()
+            || config.trigger.is_some()
+            || config.mirror_mint.is_some()
+            || config.import_source.is_some()
+            || config.max_value.is_some())
+    {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if let Some(max_value) = config.max_value
+        && initial_value > max_value
+    {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    // a counter can follow one source at most
+    if config.mirror_mint.is_some() && config.import_source.is_some() {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if let Some(trigger) = &config.trigger
+        && (trigger.accounts.len() > MAX_TRIGGER_ACCOUNTS
+            || trigger.data.len() > MAX_TRIGGER_DATA_LEN
+            || trigger.program_id == *program_id)
+    {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if config.metrics.len() > MAX_METRICS {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let mut metrics: Vec<Metric> = Vec::with_capacity(config.metrics.len());
+    for metric in config.metrics {
+        if metric.name.is_empty()
+            || metrics.iter().any(|existing| existing.name == metric.name)
+            || (metric.max_value != 0 && metric.initial_value > metric.max_value)
+        {
+            return Err(CounterError::InvalidConfig.into());
+        }
+
+        metrics.push(Metric {
+            name: metric.name,
+            value: metric.initial_value,
+            max_value: metric.max_value,
+        });
+    }
+
+    if config.observation_slots as usize > MAX_OBSERVATIONS {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if config.history_len as usize > MAX_HISTORY {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if config.access_log_len as usize > MAX_ACCESS_LOG {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if config.fee_recipient.is_some() && config.increment_fee == 0 {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let clock = Clock::get()?;
+    let slot = clock.slot;
+
+    if config
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= clock.unix_timestamp)
+    {
+        msg!("Expiry must be in the future");
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let observations = ObservationBuffer::new(config.observation_slots, slot);
+
+    let circuit_breaker = match config.circuit_breaker {
+        Some(breaker) => CircuitBreaker {
+            max_delta: breaker.max_delta,
+            window_slots: breaker.window_slots,
+            window_start_slot: slot,
+            window_start_value: initial_value,
+        },
+        None => CircuitBreaker::default(),
+    };
+
+    // an allowlist decides who may increment, so the authority gate is not
+    // wanted on top of it unless asked for
+    let gates = config.gates.unwrap_or(GateConfig {
+        flags: if config.merkle_root.is_some() {
+            0
+        } else {
+            GATE_AUTHORITY
+        },
+        ..GateConfig::default()
+    });
+
+    if gates.allowlist.len() > MAX_ALLOWLIST {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    if config.token_treasury.is_some() && gates.flags & GATE_TOKEN_BALANCE == 0 {
+        msg!("A token treasury needs the token balance gate");
+        return Err(CounterError::InvalidConfig.into());
+    }
+
     let counter_data = CounterAccount {
+        discriminator: COUNTER_DISCRIMINATOR,
+        version: COUNTER_VERSION,
         count: initial_value,
+        authority: config.authority.unwrap_or(*payer),
+        is_frozen: false,
+        circuit_breaker,
+        pending_commitment: [0; 32],
+        value_commitment: config.value_commitment.unwrap_or_default(),
+        metrics,
+        trigger: config.trigger,
+        trigger_fired: false,
+        mirror_mint: config.mirror_mint,
+        mirror_synced_slot: 0,
+        observations,
+        gates,
+        gate_last_slot: 0,
+        payer: *payer,
+        ttl_slots: config.ttl_slots,
+        last_active_slot: slot,
+        locked_amount: 0,
+        locked_until_slot: 0,
+        name: Label32::default(),
+        topics: Topics::default(),
+        step: config.step.max(1),
+        import_source: config.import_source,
+        imported_slot: 0,
+        max_value: config.max_value,
+        cap_policy: config.cap_policy,
+        min_observed_lamports: 0,
+        excess_destination: config.excess_destination,
+        close_at_zero: config.close_at_zero,
+        is_paused: false,
+        min_compute_units: config.min_compute_units,
+        external_id: None,
+        snapshot_count: 0,
+        history: HistoryBuffer::new(config.history_len),
+        access_log: AccessLog::new(config.access_log_len),
+        created_at: clock.unix_timestamp,
+        last_updated: clock.unix_timestamp,
+        unlock_timestamp: config.unlock_timestamp.unwrap_or(0),
+        delegates: Delegates::default(),
+        increment_fee: config.increment_fee,
+        fee_recipient: config.fee_recipient.unwrap_or_default(),
+        admin_frozen: false,
+        unfreeze_requested_at: 0,
+        unfreeze_at: 0,
+        expires_at: config.expires_at.unwrap_or(0),
+        merkle_root: config.merkle_root.unwrap_or_default(),
+        once_per_transaction: config.once_per_transaction,
+        max_uses: config.max_uses,
+        uses: 0,
+        is_finalized: false,
+        event_log: config.event_log,
+        log_head: 0,
+        token_treasury: config.token_treasury,
     };
 
-    let mut account_data = &mut counter_account.data.borrow_mut()[..];
+    Ok(counter_data)
+}
 
-    counter_data.serialize(&mut account_data)?;
+/// Adds `amount`, or the counter's `step` when no amount is given. With
+/// `expected`, only while the count still equals it.
+pub fn process_increment_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: Option<u64>,
+    expected: Option<u64>,
+) -> ProgramResult {
+    let mutate = MutateAccounts::parse(program_id, accounts)?;
 
-    msg!("Counter initialized with value {} ", initial_value);
+    if increment_in_place(
+        program_id,
+        mutate.counter,
+        mutate.actor,
+        accounts,
+        amount,
+        expected,
+    )? {
+        return Ok(());
+    }
 
-    Ok(())
+    increment(program_id, accounts, mutate, amount, expected, None)
 }
 
-pub fn process_increment_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let accounts_iter = &mut accounts.iter();
+/// The Borsh path of `process_increment_counter`, also taken by
+/// `IncrementWithProof` with the actor's allowlist entry.
+fn increment<'info>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'info>],
+    MutateAccounts {
+        counter: counter_account,
+        actor: actor_account,
+        remaining,
+    }: MutateAccounts<'_, 'info>,
+    amount: Option<u64>,
+    expected: Option<u64>,
+    merkle_claim: Option<MerkleClaim>,
+) -> ProgramResult {
+    let accounts_iter = &mut remaining.iter();
+
+    // deserialize the account data into out CounterAccount struct
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_gates_with_claim(
+        &mut counter_data,
+        actor_account,
+        accounts_iter,
+        merkle_claim,
+    )?;
 
-    let counter_account = next_account_info(accounts_iter)?;
+    check_count_writable(&counter_data)?;
+    check_unlocked(&counter_data)?;
+    check_once_per_transaction(program_id, counter_account, &counter_data, accounts)?;
 
-    // verify account ownership
-    if counter_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    if let Some(expected) = expected
+        && counter_data.count != expected
+    {
+        msg!(
+            "Counter is at {}, not the expected {}",
+            counter_data.count,
+            expected
+        );
+        return Err(CounterError::StaleValue.into());
     }
 
-    let mut data = counter_account.data.borrow_mut();
+    let amount = amount.unwrap_or(counter_data.step);
+    let new_count = counter_data.capped_add(amount)?;
 
-    // deserialize the account data into out CounterAccount struct
-    let mut counter_data: CounterAccount = CounterAccount::try_from_slice(&data)?;
+    check_compute_budget(&counter_data)?;
 
-    counter_data.count = counter_data
-        .count
-        .checked_add(1)
-        .ok_or(ProgramError::InvalidAccountData)?;
+    charge_increment_fee(&counter_data, actor_account, accounts)?;
+    charge_protocol_fee(program_id, actor_account, accounts)?;
+    charge_token_treasury(&counter_data, actor_account, accounts)?;
+
+    let old_count = counter_data.count;
+    if apply_increment(&mut counter_data, new_count)? {
+        msg!("Counter incremented to : {}", counter_data.count);
+        emit_count_change(counter_account.key, &counter_data, old_count);
+        record_increment(&mut counter_data, actor_account.key, old_count);
+    }
+    let trigger = counter_data.take_crossed_trigger(old_count);
+
+    let incremented = counter_data.count - old_count;
+    if counter_data.event_log && incremented != 0 {
+        append_log(
+            program_id,
+            accounts,
+            counter_account.key,
+            &mut counter_data,
+            actor_account,
+            incremented,
+        )?;
+    }
 
     // serialize the updated counter data back into the account
-    counter_data.serialize(&mut &mut data[..])?;
+    save_counter(counter_account, &mut counter_data)?;
+
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(incremented)
+    })?;
+    record_contribution(
+        program_id,
+        accounts,
+        counter_account.key,
+        actor_account,
+        incremented,
+    )?;
+
+    if let Some(trigger) = trigger {
+        invoke_trigger(
+            program_id,
+            counter_account.key,
+            &trigger,
+            accounts_iter.as_slice(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// `process_increment_counter` for counters `PlainCounter::scan` accepts:
+/// patches the changed fields in place instead of round-tripping the
+/// account through Borsh. Returns `false`, having changed nothing, whenever
+/// the full handler has to run instead, so every rejection still comes from
+/// one place.
+fn increment_in_place<'info>(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo,
+    actor_account: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    amount: Option<u64>,
+    expected: Option<u64>,
+) -> Result<bool, ProgramError> {
+    let mut data = counter_account.try_borrow_mut_data()?;
+    let Some(counter) = PlainCounter::scan(&data) else {
+        return Ok(false);
+    };
+
+    let authorized = !counter.authority_gated
+        || (actor_account.is_signer && *actor_account.key == counter.authority);
+    // the full path raises the donation alert
+    let donated = counter.min_observed_lamports != 0
+        && counter_account.lamports() > counter.min_observed_lamports;
+    if !authorized || donated || expected.is_some_and(|expected| expected != counter.count) {
+        return Ok(false);
+    }
+    let Some(new_count) = counter.count.checked_add(amount.unwrap_or(counter.step)) else {
+        return Ok(false);
+    };
+
+    let clock = Clock::get()?;
+    msg!("Counter incremented to : {}", new_count);
+    if let Some(event) = CounterEvent::count_changed(counter.count, new_count) {
+        events::emit(counter_account.key, &counter.topics(&data), &event);
+    }
+    counter.write_increment(&mut data, new_count, clock.slot, clock.unix_timestamp);
+    drop(data);
+
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(new_count - counter.count)
+    })?;
+    charge_protocol_fee(program_id, actor_account, accounts)?;
+    record_contribution(
+        program_id,
+        accounts,
+        counter_account.key,
+        actor_account,
+        new_count - counter.count,
+    )?;
+    Ok(true)
+}
+
+/// Subtracts `amount`, or the counter's `step` when no amount is given.
+pub fn process_decrement_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: Option<u64>,
+) -> ProgramResult {
+    let MutateAccounts {
+        counter: counter_account,
+        actor: actor_account,
+        remaining,
+    } = MutateAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_gates(&mut counter_data, actor_account, accounts_iter)?;
+
+    check_count_writable(&counter_data)?;
+    check_unlocked(&counter_data)?;
+
+    let amount = amount.unwrap_or(counter_data.step);
+    let new_count = counter_data.count.checked_sub(amount).ok_or_else(|| {
+        msg!("Counter cannot be decremented below zero");
+        CounterError::Underflow
+    })?;
+
+    let old_count = counter_data.count;
+    if apply_count(&mut counter_data, new_count)? {
+        msg!("Counter decremented to : {}", counter_data.count);
+        emit_count_change(counter_account.key, &counter_data, old_count);
+
+        if counter_data.close_at_zero && counter_data.count == 0 {
+            return close_used_up_counter(program_id, counter_account, &counter_data, accounts);
+        }
+    }
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    Ok(())
+}
+
+/// Closes a `close_at_zero` counter that just reached zero, refunding its
+/// authority from wherever it appears writable in `accounts`.
+fn close_used_up_counter(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo,
+    counter_data: &CounterAccount,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let find = |key: &Pubkey| {
+        accounts
+            .iter()
+            .find(|account| account.key == key && account.is_writable)
+    };
+
+    let Some(authority_account) = find(&counter_data.authority) else {
+        msg!(
+            "Counter closes at zero, pass its authority {} as a writable account",
+            counter_data.authority
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let excess_account = counter_data.excess_destination.as_ref().and_then(find);
+
+    let lamports = close_counter(
+        counter_account,
+        counter_data,
+        authority_account,
+        excess_account,
+    )?;
+
+    msg!("Counter used up and closed, {} lamports refunded", lamports);
+    emit_closed(counter_account.key, counter_data, lamports);
+    update_registry(program_id, accounts, Registry::record_closed)?;
+    unindex_counter(
+        program_id,
+        accounts,
+        counter_account.key,
+        &counter_data.payer,
+    )
+}
+
+/// Requires `authority_account` to be the counter's authority and to have
+/// signed the transaction.
+fn check_authority(
+    counter_data: &CounterAccount,
+    authority_account: &AccountInfo,
+) -> ProgramResult {
+    if !authority_account.is_signer || authority_account.key != &counter_data.authority {
+        msg!("Instruction requires the counter authority's signature");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    Ok(())
+}
+
+/// Like `check_authority`, but also accepts the approval of a `Multisig`
+/// authority: `threshold` of its signers signing anywhere in `accounts`.
+fn check_authority_or_multisig(
+    program_id: &Pubkey,
+    counter_data: &CounterAccount,
+    authority_account: &AccountInfo,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let is_multisig = authority_account.owner == program_id
+        && authority_account
+            .try_borrow_data()?
+            .starts_with(&MULTISIG_DISCRIMINATOR);
+    if !is_multisig || authority_account.key != &counter_data.authority {
+        return check_authority(counter_data, authority_account);
+    }
 
-    msg!("Counter incremented to : {}", counter_data.count);
+    let multisig = Multisig::try_from_slice(&authority_account.try_borrow_data()?)?;
+    let approvals = multisig
+        .signers
+        .iter()
+        .filter(|signer| {
+            accounts
+                .iter()
+                .any(|account| account.is_signer && account.key == *signer)
+        })
+        .count();
+    if approvals < multisig.threshold as usize {
+        msg!(
+            "Multisig approved by {} of the {} required signers",
+            approvals,
+            multisig.threshold
+        );
+        return Err(CounterError::MultisigThresholdNotMet.into());
+    }
+
+    Ok(())
+}
+
+/// Rejects direct changes to `count` on counters that are frozen or whose
+/// value is managed some other way.
+fn check_count_writable(counter_data: &CounterAccount) -> ProgramResult {
+    check_not_paused(counter_data)?;
+
+    if counter_data.is_frozen {
+        msg!("Counter is frozen by its circuit breaker");
+        return Err(CounterError::CounterFrozen.into());
+    }
+
+    if counter_data.is_committed() {
+        msg!("Counter is in commitment mode, use IncrementCommitted");
+        return Err(CounterError::CommitmentMode.into());
+    }
+
+    if counter_data.mirror_mint.is_some() {
+        msg!("Counter mirrors a mint supply, use SyncMintSupply");
+        return Err(CounterError::MirroredCounter.into());
+    }
+
+    if counter_data.import_source.is_some() {
+        msg!("Counter mirrors another cluster, use ImportAttestation");
+        return Err(CounterError::MirroredCounter.into());
+    }
+
+    Ok(())
+}
+
+fn check_not_paused(counter_data: &CounterAccount) -> ProgramResult {
+    check_closable(counter_data)?;
+
+    if counter_data.is_finalized {
+        msg!(
+            "Counter has used all {} of its uses and can only be closed",
+            counter_data.max_uses
+        );
+        return Err(CounterError::CounterFinalized.into());
+    }
+    Ok(())
+}
+
+/// What `check_not_paused` checks short of finalization, which still lets
+/// the authority close the counter.
+fn check_closable(counter_data: &CounterAccount) -> ProgramResult {
+    if counter_data.is_paused {
+        msg!("Counter is paused by its authority");
+        return Err(CounterError::CounterPaused.into());
+    }
+
+    check_not_admin_frozen(counter_data)?;
+
+    if counter_data.expires_at != 0 && counter_data.is_past_expiry(Clock::get()?.unix_timestamp) {
+        msg!("Counter expired at {}", counter_data.expires_at);
+        return Err(CounterError::CounterExpired.into());
+    }
+    Ok(())
+}
+
+fn check_not_admin_frozen(counter_data: &CounterAccount) -> ProgramResult {
+    if counter_data.admin_frozen && counter_data.is_admin_frozen(Clock::get()?.unix_timestamp) {
+        msg!("Counter is frozen by the registry admin");
+        return Err(CounterError::FrozenByAdmin.into());
+    }
+    Ok(())
+}
+
+/// Rejects increments and decrements before the counter's unlock time.
+fn check_unlocked(counter_data: &CounterAccount) -> ProgramResult {
+    if counter_data.unlock_timestamp != 0
+        && Clock::get()?.unix_timestamp < counter_data.unlock_timestamp
+    {
+        msg!("Counter is locked until {}", counter_data.unlock_timestamp);
+        return Err(CounterError::CounterLocked.into());
+    }
+    Ok(())
+}
+
+/// Rejects increments outside `IncrementCounter` on event log counters, as
+/// they take no log page to append the increment to.
+fn check_no_event_log(counter: &Pubkey, counter_data: &CounterAccount) -> ProgramResult {
+    if counter_data.event_log {
+        msg!(
+            "Counter {} keeps an event log, use IncrementCounter",
+            counter
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    Ok(())
+}
+
+/// Rejects increments outside `IncrementCounter` on counters that count once
+/// per transaction, as only that path checks the rest of the transaction.
+fn check_not_once_per_transaction(
+    counter: &Pubkey,
+    counter_data: &CounterAccount,
+) -> ProgramResult {
+    if counter_data.once_per_transaction {
+        msg!(
+            "Counter {} counts once per transaction, use IncrementCounter",
+            counter
+        );
+        return Err(CounterError::DuplicateIncrement.into());
+    }
+    Ok(())
+}
+
+/// Fails up front when the counter asks for a compute floor and less than that
+/// is left, so a write, its events and the trigger CPI are never cut off
+/// partway by the compute meter.
+fn check_compute_budget(counter_data: &CounterAccount) -> ProgramResult {
+    if counter_data.min_compute_units == 0 {
+        return Ok(());
+    }
+
+    let remaining = sol_remaining_compute_units();
+    if remaining < counter_data.min_compute_units {
+        msg!(
+            "{} compute units left, counter requires {}",
+            remaining,
+            counter_data.min_compute_units
+        );
+        return Err(CounterError::InsufficientCompute.into());
+    }
+    Ok(())
+}
+
+/// For counters that count once per transaction, fails unless this is the
+/// transaction's only top-level instruction incrementing the counter. The
+/// instructions sysvar has to be among the accounts. Increments through CPI
+/// are refused outright, as the caller could repeat them within a single
+/// top-level instruction.
+fn check_once_per_transaction(
+    program_id: &Pubkey,
+    counter_account: &AccountInfo,
+    counter_data: &CounterAccount,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if !counter_data.once_per_transaction {
+        return Ok(());
+    }
+
+    if get_stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT {
+        msg!("Counter counts once per transaction and cannot be incremented through CPI");
+        return Err(CounterError::DuplicateIncrement.into());
+    }
+
+    let Some(instructions_sysvar) = accounts
+        .iter()
+        .find(|account| *account.key == sysvar::instructions::ID)
+    else {
+        msg!("Counter counts once per transaction, pass the instructions sysvar");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let mut increments = 0;
+    for index in 0.. {
+        let instruction = match load_instruction_at_checked(index, instructions_sysvar) {
+            Ok(instruction) => instruction,
+            // past the last instruction
+            Err(ProgramError::InvalidArgument) => break,
+            Err(error) => return Err(error),
+        };
+
+        let targets_counter = instruction
+            .accounts
+            .first()
+            .is_some_and(|meta| meta.pubkey == *counter_account.key);
+        if instruction.program_id == *program_id
+            && targets_counter
+            && is_increment(&instruction.data)
+        {
+            increments += 1;
+        }
+    }
+
+    if increments > 1 {
+        msg!("Transaction increments the counter {} times", increments);
+        return Err(CounterError::DuplicateIncrement.into());
+    }
+    Ok(())
+}
+
+/// Whether instruction data for this program is handled by `increment`.
+fn is_increment(data: &[u8]) -> bool {
+    if let Ok(Some(instruction)) = CounterInterfaceInstruction::unpack(data) {
+        return matches!(instruction, CounterInterfaceInstruction::Increment { .. });
+    }
+
+    #[cfg(feature = "anchor-layout")]
+    let translated = crate::anchor::translate(data);
+    #[cfg(feature = "anchor-layout")]
+    let data = translated.as_deref().unwrap_or(data);
+
+    matches!(
+        CounterInstruction::unpack(data),
+        Ok(CounterInstruction::IncrementCounter
+            | CounterInstruction::IncrementBy { .. }
+            | CounterInstruction::ConditionalIncrement { .. }
+            | CounterInstruction::IncrementWithProof { .. })
+    )
+}
+
+/// CPIs into the trigger's target program, signing for the counter's trigger
+/// authority PDA. `remaining_accounts` must hold the target program and every
+/// account in the template.
+fn invoke_trigger(
+    program_id: &Pubkey,
+    counter_key: &Pubkey,
+    trigger: &TriggerConfig,
+    remaining_accounts: &[AccountInfo],
+) -> ProgramResult {
+    let (_, bump) = derive_trigger_authority(program_id, counter_key);
+
+    let instruction = Instruction {
+        program_id: trigger.program_id,
+        accounts: trigger
+            .accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: trigger.data.clone(),
+    };
+
+    msg!("Threshold {} crossed, invoking trigger", trigger.threshold);
+
+    invoke_signed(
+        &instruction,
+        remaining_accounts,
+        &[&[TRIGGER_SEED, counter_key.as_ref(), &[bump]]],
+    )
+}
+
+/// Creates `new_account` as a rent-exempt account of `space` bytes owned by
+/// this program, funded by `payer_account`. PDAs pass their `signer_seeds`,
+/// keypair accounts pass an empty slice and sign the transaction themselves.
+///
+/// `create_account` refuses an address that already holds lamports, so a
+/// pre-funded account is topped up, allocated and assigned instead. One
+/// holding more than the rent exemption looks like a wallet passed by
+/// mistake and is refused rather than locked into a counter.
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    payer_account: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(space);
+    if new_account.lamports() > required_lamports {
+        msg!(
+            "Account {} holds more lamports than a new account needs",
+            new_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+    let top_up = required_lamports.saturating_sub(new_account.lamports());
+    if payer_account.lamports() < top_up {
+        msg!(
+            "Payer has {} lamports, the account needs {} more to be rent exempt",
+            payer_account.lamports(),
+            top_up
+        );
+        return Err(CounterError::InsufficientRentFunds.into());
+    }
+
+    let account_infos = [
+        payer_account.clone(),
+        new_account.clone(),
+        system_program.clone(),
+    ];
+    let invoke_as_new_account = |instruction: &Instruction| {
+        if signer_seeds.is_empty() {
+            invoke(instruction, &account_infos)
+        } else {
+            invoke_signed(instruction, &account_infos, &[signer_seeds])
+        }
+    };
+
+    if new_account.lamports() == 0 {
+        return invoke_as_new_account(&system_instruction::create_account(
+            payer_account.key,
+            new_account.key,
+            required_lamports,
+            space as u64,
+            program_id,
+        ));
+    }
+
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account.key, new_account.key, top_up),
+            &account_infos,
+        )?;
+    }
+    invoke_as_new_account(&system_instruction::allocate(new_account.key, space as u64))?;
+    invoke_as_new_account(&system_instruction::assign(new_account.key, program_id))
+}
+
+/// Emits `CounterIncremented` or `CounterDecremented` for a count that moved
+/// from `old_count`, tagged with the counter's topics.
+fn emit_count_change(counter_key: &Pubkey, counter_data: &CounterAccount, old_count: u64) {
+    if let Some(event) = CounterEvent::count_changed(old_count, counter_data.count) {
+        events::emit(counter_key, &counter_data.topics, &event);
+    }
+}
+
+fn emit_initialized(counter_key: &Pubkey, counter_data: &CounterAccount, count: u64) {
+    let event = CounterEvent::Initialized(CounterInitialized {
+        authority: counter_data.authority,
+        count,
+    });
+    events::emit(counter_key, &counter_data.topics, &event);
+}
+
+fn emit_closed(counter_key: &Pubkey, counter_data: &CounterAccount, lamports: u64) {
+    let event = CounterEvent::Closed(CounterClosed {
+        count: counter_data.count,
+        lamports,
+    });
+    events::emit(counter_key, &counter_data.topics, &event);
+}
+
+/// Moves the counter to `new_count` unless the circuit breaker trips, in which
+/// case the counter is frozen at its current value instead. The caller still
+/// persists the account either way so a trip sticks. Returns whether the
+/// change was applied.
+fn apply_count(counter_data: &mut CounterAccount, new_count: u64) -> Result<bool, ProgramError> {
+    let slot = Clock::get()?.slot;
+
+    if new_count < counter_data.locked_amount {
+        msg!(
+            "Counter has {} points locked until slot {}",
+            counter_data.locked_amount,
+            counter_data.locked_until_slot
+        );
+        return Err(CounterError::InsufficientLiquid.into());
+    }
+
+    if counter_data
+        .circuit_breaker
+        .trips(slot, counter_data.count, new_count)
+    {
+        counter_data.is_frozen = true;
+
+        msg!(
+            "ALERT: circuit breaker tripped at slot {}, counter frozen at {}",
+            slot,
+            counter_data.count
+        );
+        return Ok(false);
+    }
+
+    // close out the time the old value was held before it changes
+    counter_data.observations.record(slot, counter_data.count);
+
+    counter_data.count = new_count;
+    counter_data.last_active_slot = slot;
+    Ok(true)
+}
+
+/// `apply_count` for increments, which count towards `max_uses` when they
+/// raise the count. Setting, syncing or transferring a count is no use.
+fn apply_increment(
+    counter_data: &mut CounterAccount,
+    new_count: u64,
+) -> Result<bool, ProgramError> {
+    let old_count = counter_data.count;
+    if !apply_count(counter_data, new_count)? {
+        return Ok(false);
+    }
+
+    if counter_data.max_uses != 0 && counter_data.count > old_count {
+        counter_data.uses += 1;
+        if counter_data.uses >= counter_data.max_uses {
+            counter_data.is_finalized = true;
+            msg!("Counter finalized after {} uses", counter_data.uses);
+        }
+    }
+    Ok(true)
+}
+
+/// Adds the increment `apply_count` just made to the counter's history.
+fn record_increment(counter_data: &mut CounterAccount, actor: &Pubkey, old_count: u64) {
+    let delta = counter_data.count - old_count;
+    if delta == 0 {
+        return;
+    }
+
+    counter_data.history.record(HistoryEntry {
+        slot: counter_data.last_active_slot,
+        delta,
+        actor: *actor,
+    });
+}
+
+/// Serves the shared counter interface from this program's own handlers.
+fn process_interface_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: CounterInterfaceInstruction,
+) -> ProgramResult {
+    match instruction {
+        CounterInterfaceInstruction::Increment { amount } => {
+            // takes the same accounts as `IncrementBy`
+            dispatch::meta(11)
+                .ok_or(ProgramError::InvalidInstructionData)?
+                .validate(accounts)?;
+            process_increment_counter(program_id, accounts, Some(amount), None)
+        }
+        CounterInterfaceInstruction::GetValue => process_read_count(program_id, accounts),
+    }
+}
+
+/// Returns the count for `ReadCount` and the interface's `GetValue`, which
+/// agree on the encoding: Borsh writes a `u64` as 8 bytes little-endian.
+pub fn process_read_count(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let ReadAccounts {
+        counter: counter_account,
+    } = ReadAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+    set_return_data(&borsh::to_vec(&counter_data.count)?);
+    Ok(())
+}
+
+/// Appends a privileged instruction to the access log of the counter it ran
+/// against. Every privileged instruction takes the counter first and its
+/// authority second.
+fn record_access(program_id: &Pubkey, accounts: &[AccountInfo], tag: u8) -> ProgramResult {
+    let [counter_account, authority_account, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // skimming may have closed the counter
+    if counter_account.owner != program_id || counter_account.data_is_empty() {
+        return Ok(());
+    }
+
+    let mut counter_data = load_counter(counter_account)?;
+    if counter_data.access_log.entries.is_empty() {
+        return Ok(());
+    }
+
+    counter_data.access_log.record(AccessLogEntry {
+        slot: Clock::get()?.slot,
+        actor: *authority_account.key,
+        instruction: tag,
+    });
+    save_counter(counter_account, &mut counter_data)
+}
+
+pub fn process_reset_circuit_breaker(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    // only the authority may clear a tripped breaker
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.is_frozen = false;
+    counter_data.circuit_breaker.window_start_slot = Clock::get()?.slot;
+    counter_data.circuit_breaker.window_start_value = counter_data.count;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Circuit breaker reset, counter unfrozen at {}",
+        counter_data.count
+    );
+    Ok(())
+}
+
+pub fn process_commit_increment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    hash: [u8; 32],
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    // only the authority may commit, otherwise anyone could overwrite a
+    // pending commitment before it is revealed
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.pending_commitment = hash;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Increment committed");
+    Ok(())
+}
+
+pub fn process_reveal_increment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    salt: [u8; 32],
+) -> ProgramResult {
+    let CounterAccounts {
+        counter: counter_account,
+        remaining,
+    } = CounterAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_count_writable(&counter_data)?;
+    check_unlocked(&counter_data)?;
+    check_no_event_log(counter_account.key, &counter_data)?;
+    check_not_once_per_transaction(counter_account.key, &counter_data)?;
+
+    if counter_data.pending_commitment == [0; 32]
+        || counter_data.pending_commitment != commitment_hash(amount, &salt)
+    {
+        msg!("Reveal does not match the pending commitment");
+        return Err(CounterError::CommitmentMismatch.into());
+    }
+
+    let new_count = counter_data.capped_add(amount)?;
+
+    // the commitment is spent whether or not the breaker lets the change through
+    counter_data.pending_commitment = [0; 32];
+
+    check_compute_budget(&counter_data)?;
+
+    let old_count = counter_data.count;
+    if apply_increment(&mut counter_data, new_count)? {
+        msg!(
+            "Revealed increment of {}, counter is now {}",
+            amount,
+            counter_data.count
+        );
+        emit_count_change(counter_account.key, &counter_data, old_count);
+    }
+    let trigger = counter_data.take_crossed_trigger(old_count);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    let incremented = counter_data.count - old_count;
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(incremented)
+    })?;
+
+    if let Some(trigger) = trigger {
+        invoke_trigger(
+            program_id,
+            counter_account.key,
+            &trigger,
+            accounts_iter.as_slice(),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn process_increment_committed(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    value: u64,
+    salt: [u8; 32],
+    amount: u64,
+    new_salt: [u8; 32],
+) -> ProgramResult {
+    let MutateAccounts {
+        counter: counter_account,
+        actor: actor_account,
+        remaining,
+    } = MutateAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_gates(&mut counter_data, actor_account, accounts_iter)?;
+
+    check_not_paused(&counter_data)?;
+    check_unlocked(&counter_data)?;
+    check_not_once_per_transaction(counter_account.key, &counter_data)?;
+
+    if counter_data.is_frozen {
+        msg!("Counter is frozen by its circuit breaker");
+        return Err(CounterError::CounterFrozen.into());
+    }
+
+    if !counter_data.is_committed() {
+        msg!("Counter is not in commitment mode");
+        return Err(CounterError::NotCommitted.into());
+    }
+
+    if counter_data.value_commitment != commitment_hash(value, &salt) {
+        msg!("Preimage does not match the stored commitment");
+        return Err(CounterError::CommitmentMismatch.into());
+    }
+
+    let new_value = value.checked_add(amount).ok_or(CounterError::Overflow)?;
+
+    charge_increment_fee(&counter_data, actor_account, accounts)?;
+
+    counter_data.value_commitment = commitment_hash(new_value, &new_salt);
+    counter_data.last_active_slot = Clock::get()?.slot;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(amount)
+    })?;
+
+    msg!("Committed counter advanced");
+    Ok(())
+}
+
+pub fn process_increment_metric(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    metric: MetricRef,
+    amount: u64,
+) -> ProgramResult {
+    let MutateAccounts {
+        counter: counter_account,
+        actor: actor_account,
+        remaining,
+    } = MutateAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_gates(&mut counter_data, actor_account, accounts_iter)?;
+
+    check_not_paused(&counter_data)?;
+    check_not_once_per_transaction(counter_account.key, &counter_data)?;
+
+    if counter_data.is_frozen {
+        msg!("Counter is frozen by its circuit breaker");
+        return Err(CounterError::CounterFrozen.into());
+    }
+
+    let index = match metric {
+        MetricRef::Index(index) => index as usize,
+        MetricRef::Name(name) => counter_data
+            .metric_index(&name)
+            .ok_or(CounterError::MetricNotFound)?,
+    };
+
+    let metric = counter_data
+        .metrics
+        .get_mut(index)
+        .ok_or(CounterError::MetricNotFound)?;
+
+    let new_value = metric
+        .value
+        .checked_add(amount)
+        .ok_or(CounterError::Overflow)?;
+
+    if metric.max_value != 0 && new_value > metric.max_value {
+        msg!(
+            "Metric {} would exceed its maximum of {}",
+            metric.name,
+            metric.max_value
+        );
+        return Err(CounterError::MetricLimitExceeded.into());
+    }
+
+    metric.value = new_value;
+    msg!("Metric {} incremented to : {}", metric.name, metric.value);
+    counter_data.last_active_slot = Clock::get()?.slot;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(amount)
+    })?;
+
+    Ok(())
+}
+
+pub fn process_initialize_composite(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    sources: Vec<WeightedSource>,
+) -> ProgramResult {
+    let CreateAccounts {
+        new_account: composite_account,
+        payer: payer_account,
+        system_program,
+    } = CreateAccounts::parse(program_id, accounts)?;
+
+    if sources.is_empty() || sources.len() > MAX_COMPOSITE_SOURCES {
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let composite_data = CompositeCounter {
+        discriminator: COMPOSITE_DISCRIMINATOR,
+        sources,
+        value: 0,
+        refreshed_slot: 0,
+    };
+
+    let account_space = borsh::object_length(&composite_data)?;
+
+    create_program_account(
+        program_id,
+        payer_account,
+        composite_account,
+        system_program,
+        account_space,
+        &[],
+    )?;
+
+    composite_data.serialize(&mut &mut composite_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "Composite counter initialized with {} sources",
+        composite_data.sources.len()
+    );
+
+    Ok(())
+}
+
+pub fn process_refresh_composite(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CounterAccounts {
+        counter: composite_account,
+        remaining,
+    } = CounterAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+
+    let mut data = composite_account.data.borrow_mut();
+    if data.get(..COMPOSITE_DISCRIMINATOR.len()) != Some(&COMPOSITE_DISCRIMINATOR[..]) {
+        msg!("Account is not a composite counter");
+        return Err(CounterError::WrongAccountType.into());
+    }
+    let mut composite_data: CompositeCounter = CompositeCounter::try_from_slice(&data)?;
+
+    // sources must be passed in the order they were configured
+    let mut total: u128 = 0;
+    for source in &composite_data.sources {
+        let source_account = next_account_info(accounts_iter)?;
+
+        if source_account.key != &source.counter {
+            return Err(CounterError::SourceMismatch.into());
+        }
+
+        if source_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let counter_data = load_counter(source_account)?;
+
+        if counter_data.is_committed() {
+            msg!("Committed counters cannot feed a composite");
+            return Err(CounterError::CommitmentMode.into());
+        }
+
+        total = (counter_data.count as u128)
+            .checked_mul(source.weight as u128)
+            .and_then(|weighted| total.checked_add(weighted))
+            .ok_or(CounterError::Overflow)?;
+    }
+
+    composite_data.value =
+        u64::try_from(total / WEIGHT_SCALE as u128).map_err(|_| CounterError::Overflow)?;
+    composite_data.refreshed_slot = Clock::get()?.slot;
+
+    composite_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Composite refreshed to {} at slot {}",
+        composite_data.value,
+        composite_data.refreshed_slot
+    );
+    Ok(())
+}
+
+pub fn process_sync_mint_supply(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CounterAccounts {
+        counter: counter_account,
+        remaining,
+    } = CounterAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+
+    let mint_account = next_account_info(accounts_iter)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_not_paused(&counter_data)?;
+
+    if counter_data.mirror_mint != Some(*mint_account.key) {
+        msg!("Counter does not mirror this mint");
+        return Err(CounterError::MintMismatch.into());
+    }
+
+    if counter_data.is_frozen {
+        msg!("Counter is frozen by its circuit breaker");
+        return Err(CounterError::CounterFrozen.into());
+    }
+
+    let supply = read_mint_supply(mint_account)?;
+
+    check_compute_budget(&counter_data)?;
+
+    let old_count = counter_data.count;
+    if apply_count(&mut counter_data, supply)? {
+        counter_data.mirror_synced_slot = Clock::get()?.slot;
+        msg!(
+            "Counter synced to mint supply {} at slot {}",
+            supply,
+            counter_data.mirror_synced_slot
+        );
+        emit_count_change(counter_account.key, &counter_data, old_count);
+    }
+    let trigger = counter_data.take_crossed_trigger(old_count);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    if let Some(trigger) = trigger {
+        invoke_trigger(
+            program_id,
+            counter_account.key,
+            &trigger,
+            accounts_iter.as_slice(),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn process_transfer_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Pubkey,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority_or_multisig(program_id, &counter_data, authority_account, accounts)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.authority = new_authority;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter authority transferred to {}", new_authority);
+    Ok(())
+}
+
+pub fn process_close_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CloseAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        destination: destination_account,
+        excess_destination: excess_account,
+    } = CloseAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_authority_or_multisig(program_id, &counter_data, authority_account, accounts)?;
+    check_closable(&counter_data)?;
+
+    let lamports = close_counter(
+        counter_account,
+        &counter_data,
+        destination_account,
+        excess_account,
+    )?;
+
+    msg!("Counter closed, {} lamports refunded", lamports);
+    emit_closed(counter_account.key, &counter_data, lamports);
+    update_registry(program_id, accounts, Registry::record_closed)?;
+    unindex_counter(
+        program_id,
+        accounts,
+        counter_account.key,
+        &counter_data.payer,
+    )
+}
+
+/// Closes an expired counter, splitting its rent between the original payer
+/// and the reaper.
+pub fn process_reap(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let ReapAccounts {
+        counter: counter_account,
+        payer: payer_account,
+        reaper: reaper_account,
+    } = ReapAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_not_paused(&counter_data)?;
+
+    if payer_account.key != &counter_data.payer {
+        msg!("Rent must go back to the counter's original payer");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let slot = Clock::get()?.slot;
+    if !counter_data.is_expired(slot) {
+        msg!(
+            "Counter is not reapable before slot {}",
+            counter_data
+                .last_active_slot
+                .saturating_add(counter_data.ttl_slots)
+        );
+        return Err(CounterError::NotExpired.into());
+    }
+
+    let lamports = counter_account.lamports();
+    let reward = (lamports as u128 * REAP_REWARD_BPS as u128 / 10_000) as u64;
+    credit_lamports(reaper_account, reward)?;
+    credit_lamports(payer_account, lamports - reward)?;
+    close_program_account(counter_account)?;
+
+    msg!(
+        "Counter reaped at slot {}, {} lamports to reaper, {} to payer",
+        slot,
+        reward,
+        lamports - reward
+    );
+    emit_closed(counter_account.key, &counter_data, lamports);
+    update_registry(program_id, accounts, Registry::record_closed)?;
+    unindex_counter(
+        program_id,
+        accounts,
+        counter_account.key,
+        &counter_data.payer,
+    )
+}
+
+fn credit_lamports(account: &AccountInfo, lamports: u64) -> ProgramResult {
+    **account.try_borrow_mut_lamports()? = account
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(CounterError::Overflow)?;
+    Ok(())
+}
+
+/// Drains and frees a program account once its lamports have been credited
+/// elsewhere.
+/// Pays out the counter's lamports and closes it. A strict counter's donated
+/// lamports go to `excess_account`, which must be its excess destination, and
+/// the rest to `destination_account`. Returns the amount the latter received.
+fn close_counter(
+    counter_account: &AccountInfo,
+    counter_data: &CounterAccount,
+    destination_account: &AccountInfo,
+    excess_account: Option<&AccountInfo>,
+) -> Result<u64, ProgramError> {
+    let mut lamports = counter_account.lamports();
+    if let Some(excess_destination) = counter_data.excess_destination {
+        let excess = lamports.saturating_sub(counter_data.min_observed_lamports);
+        if excess > 0 {
+            let excess_account = match excess_account {
+                Some(account) if account.key == &excess_destination => account,
+                _ => {
+                    msg!("Excess lamports must go to {}", excess_destination);
+                    return Err(ProgramError::InvalidArgument);
+                }
+            };
+
+            credit_lamports(excess_account, excess)?;
+            lamports -= excess;
+
+            msg!(
+                "Routed {} excess lamports to {}",
+                excess,
+                excess_destination
+            );
+            let event = CounterEvent::ExcessRouted(ExcessRouted {
+                destination: excess_destination,
+                lamports: excess,
+            });
+            events::emit(counter_account.key, &counter_data.topics, &event);
+        }
+    }
+
+    credit_lamports(destination_account, lamports)?;
+    close_program_account(counter_account)?;
+
+    Ok(lamports)
+}
+
+fn close_program_account(account: &AccountInfo) -> ProgramResult {
+    **account.try_borrow_mut_lamports()? = 0;
+
+    account.realloc(0, false)?;
+    account.assign(&system_program::ID);
+    Ok(())
+}
+
+/// Sets the count to an arbitrary `value`; `ResetCounter` is the zero case.
+pub fn process_set_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    value: u64,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        remaining,
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority_or_multisig(program_id, &counter_data, authority_account, accounts)?;
+
+    check_count_writable(&counter_data)?;
+
+    if let Some(max_value) = counter_data.max_value
+        && value > max_value
+    {
+        msg!("Counter is capped at {}", max_value);
+        return Err(CounterError::CapExceeded.into());
+    }
+
+    check_compute_budget(&counter_data)?;
+
+    let old_count = counter_data.count;
+    if apply_count(&mut counter_data, value)? {
+        msg!("Counter set from {} to {}", old_count, counter_data.count);
+        emit_count_change(counter_account.key, &counter_data, old_count);
+    }
+    let trigger = counter_data.take_crossed_trigger(old_count);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    if let Some(trigger) = trigger {
+        invoke_trigger(program_id, counter_account.key, &trigger, remaining)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "devnet-tools")]
+pub fn process_force_set(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    value: u64,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.count = value;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter force-set to {}", value);
+    Ok(())
+}
+
+#[cfg(feature = "devnet-tools")]
+pub fn process_fast_forward_cooldown(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.gate_last_slot = 0;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter cooldown cleared");
+    Ok(())
+}
+
+#[cfg(feature = "devnet-tools")]
+pub fn process_reset_stats(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    let slot = Clock::get()?.slot;
+    for metric in &mut counter_data.metrics {
+        metric.value = 0;
+    }
+    counter_data.observations =
+        ObservationBuffer::new(counter_data.observations.samples.len() as u16, slot);
+    counter_data.circuit_breaker.window_start_slot = slot;
+    counter_data.circuit_breaker.window_start_value = counter_data.count;
+    counter_data.trigger_fired = false;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter stats reset at slot {}", slot);
+    Ok(())
+}
+
+/// Rewrites a counter stored in an older layout in the current one, growing
+/// the account and topping up its rent from the payer as needed.
+pub fn process_migrate_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let MigrateAccounts {
+        counter: counter_account,
+        payer: payer_account,
+        system_program,
+    } = MigrateAccounts::parse(program_id, accounts)?;
+
+    let (version, mut counter_data) = {
+        let data = counter_account.try_borrow_data()?;
+        let backend = backend_for(&data)?;
+        (backend.version(), backend.load(&data)?)
+    };
+
+    if version == COUNTER_VERSION {
+        msg!("Counter already uses layout version {}", version);
+        return Ok(());
+    }
+
+    resize_program_account(
+        counter_account,
+        payer_account,
+        system_program,
+        borsh::object_length(&counter_data)?,
+    )?;
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Counter migrated from layout version {} to {}",
+        version,
+        COUNTER_VERSION
+    );
+    Ok(())
+}
+
+/// Resizes a counter account to fit `counter_data` in the current layout,
+/// topping its rent up from `payer_account` when it grows.
+fn resize_program_account<'a>(
+    account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    account_space: usize,
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(account_space);
+    let top_up = required_lamports.saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account.key, account.key, top_up),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+
+    account.realloc(account_space, false)
+}
+
+pub fn process_lock_count(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    duration_slots: u64,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    if amount == 0 || amount > counter_data.liquid() {
+        msg!(
+            "Cannot lock {} points with {} liquid",
+            amount,
+            counter_data.liquid()
+        );
+        return Err(CounterError::InsufficientLiquid.into());
+    }
+
+    // adding to a lock never shortens it
+    let unlock_slot = Clock::get()?.slot.saturating_add(duration_slots);
+    counter_data.locked_amount += amount;
+    counter_data.locked_until_slot = counter_data.locked_until_slot.max(unlock_slot);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Locked {} points, {} locked until slot {}",
+        amount,
+        counter_data.locked_amount,
+        counter_data.locked_until_slot
+    );
+    Ok(())
+}
+
+pub fn process_unlock_count(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CounterAccounts {
+        counter: counter_account,
+        ..
+    } = CounterAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_not_paused(&counter_data)?;
+
+    if Clock::get()?.slot < counter_data.locked_until_slot {
+        msg!(
+            "Points are locked until slot {}",
+            counter_data.locked_until_slot
+        );
+        return Err(CounterError::LockActive.into());
+    }
+
+    let unlocked = counter_data.locked_amount;
+    counter_data.locked_amount = 0;
+    counter_data.locked_until_slot = 0;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Unlocked {} points", unlocked);
+    Ok(())
+}
+
+pub fn process_grant_view_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    viewer: Pubkey,
+) -> ProgramResult {
+    let ViewKeyAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        view_key: view_key_account,
+        remaining,
+    } = ViewKeyAccounts::parse(program_id, accounts)?;
+    let system_program = next_account_info(&mut remaining.iter())?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+
+    let (view_key_address, bump) =
+        derive_view_key_address(program_id, counter_account.key, &viewer);
+    if view_key_account.key != &view_key_address {
+        msg!("View key account is not the grant PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let view_key = ViewKey {
+        discriminator: VIEW_KEY_DISCRIMINATOR,
+        counter: *counter_account.key,
+        program: viewer,
+        nonce: 0,
+    };
+
+    create_program_account(
+        program_id,
+        authority_account,
+        view_key_account,
+        system_program,
+        borsh::object_length(&view_key)?,
+        &[
+            VIEW_KEY_SEED,
+            counter_account.key.as_ref(),
+            viewer.as_ref(),
+            &[bump],
+        ],
+    )?;
+
+    view_key.serialize(&mut &mut view_key_account.data.borrow_mut()[..])?;
+
+    msg!("Program {} granted read access", viewer);
+    Ok(())
+}
+
+pub fn process_revoke_view_key(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let ViewKeyAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        view_key: view_key_account,
+        ..
+    } = ViewKeyAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+
+    let view_key = load_view_key(program_id, counter_account.key, view_key_account)?;
+
+    credit_lamports(authority_account, view_key_account.lamports())?;
+    close_program_account(view_key_account)?;
+
+    msg!("Program {} read access revoked", view_key.program);
+    Ok(())
+}
+
+/// Returns the count to a granted program as a nonce-stamped attestation.
+pub fn process_get_count(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let ViewAccounts {
+        counter: counter_account,
+        view_key: view_key_account,
+        viewer: viewer_account,
+    } = ViewAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+    let mut view_key = load_view_key(program_id, counter_account.key, view_key_account)?;
+
+    let (viewer_authority, _) = derive_viewer_authority(&view_key.program);
+    if viewer_account.key != &viewer_authority {
+        msg!("Signer is not the granted program's viewer PDA");
+        return Err(CounterError::ViewNotGranted.into());
+    }
+
+    view_key.nonce += 1;
+    view_key.serialize(&mut &mut view_key_account.data.borrow_mut()[..])?;
+
+    let attestation = CountAttestation {
+        counter: *counter_account.key,
+        count: counter_data.count,
+        slot: Clock::get()?.slot,
+        nonce: view_key.nonce,
+    };
+    set_return_data(&borsh::to_vec(&attestation)?);
+
+    Ok(())
+}
+
+/// Loads the view key for `counter`, treating anything else as no grant.
+fn load_view_key(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    view_key_account: &AccountInfo,
+) -> Result<ViewKey, ProgramError> {
+    let data = view_key_account.try_borrow_data()?;
+    if view_key_account.owner != program_id
+        || data.get(..VIEW_KEY_DISCRIMINATOR.len()) != Some(&VIEW_KEY_DISCRIMINATOR[..])
+    {
+        msg!("Account is not a view key");
+        return Err(CounterError::ViewNotGranted.into());
+    }
+
+    let view_key = ViewKey::try_from_slice(&data)?;
+    if &view_key.counter != counter {
+        msg!("View key belongs to another counter");
+        return Err(CounterError::ViewNotGranted.into());
+    }
+
+    Ok(view_key)
+}
+
+pub fn process_set_topics(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    topics: Topics,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        remaining,
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+    let system_program = next_account_info(&mut remaining.iter())?;
+
+    let version = backend_for(&counter_account.try_borrow_data()?)?.version();
+    if version != COUNTER_VERSION {
+        msg!("Counter must be migrated before setting topics");
+        return Err(CounterError::MigrationRequired.into());
+    }
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    if topics.iter().any(|topic| topic.is_empty()) {
+        msg!("Topics must not be empty");
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    counter_data.topics = topics;
+
+    resize_program_account(
+        counter_account,
+        authority_account,
+        system_program,
+        borsh::object_length(&counter_data)?,
+    )?;
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Counter topics set, {} registered",
+        counter_data.topics.len()
+    );
+    Ok(())
+}
+
+pub fn process_set_step(program_id: &Pubkey, accounts: &[AccountInfo], step: u64) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    if step == 0 {
+        msg!("Step must be at least 1");
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    counter_data.step = step;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter step set to {}", step);
+    Ok(())
+}
+
+pub fn process_mint_coupon(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    code_hash: [u8; 32],
+    amount: u64,
+) -> ProgramResult {
+    let MintCouponAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        coupon: coupon_account,
+        system_program,
+    } = MintCouponAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+
+    if amount == 0 {
+        msg!("Coupons must be worth at least 1");
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let (coupon_address, bump) = derive_coupon_address(program_id, counter_account.key, &code_hash);
+    if coupon_account.key != &coupon_address {
+        msg!("Coupon account is not the PDA for this code");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let coupon = Coupon {
+        discriminator: COUPON_DISCRIMINATOR,
+        counter: *counter_account.key,
+        amount,
+        payer: *authority_account.key,
+    };
+
+    create_program_account(
+        program_id,
+        authority_account,
+        coupon_account,
+        system_program,
+        borsh::object_length(&coupon)?,
+        &[
+            COUPON_SEED,
+            counter_account.key.as_ref(),
+            &code_hash,
+            &[bump],
+        ],
+    )?;
+
+    coupon.serialize(&mut &mut coupon_account.data.borrow_mut()[..])?;
+
+    msg!("Coupon {} minted for {} points", coupon_address, amount);
+    Ok(())
+}
+
+/// Spends a coupon. Its address is derived from the code, so only a holder
+/// of the code can find and redeem it, and closing it makes the redemption
+/// exactly-once.
+pub fn process_redeem_coupon(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    code: &[u8],
+) -> ProgramResult {
+    let RedeemCouponAccounts {
+        counter: counter_account,
+        coupon: coupon_account,
+        payer: payer_account,
+        remaining,
+    } = RedeemCouponAccounts::parse(program_id, accounts)?;
+
+    reject_protocol_fee(program_id, accounts)?;
+
+    let (coupon_address, _) =
+        derive_coupon_address(program_id, counter_account.key, &coupon_code_hash(code));
+    if coupon_account.key != &coupon_address {
+        msg!("Coupon account is not the PDA for this code");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let coupon = {
+        let data = coupon_account.try_borrow_data()?;
+        if coupon_account.owner != program_id
+            || data.get(..COUPON_DISCRIMINATOR.len()) != Some(&COUPON_DISCRIMINATOR[..])
+        {
+            msg!("Coupon does not exist or was already redeemed");
+            return Err(CounterError::InvalidCoupon.into());
+        }
+        Coupon::try_from_slice(&data)?
+    };
+
+    if payer_account.key != &coupon.payer {
+        msg!("Rent must go back to the coupon's payer");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_count_writable(&counter_data)?;
+    check_unlocked(&counter_data)?;
+    check_no_event_log(counter_account.key, &counter_data)?;
+    check_not_once_per_transaction(counter_account.key, &counter_data)?;
+
+    let new_count = counter_data.capped_add(coupon.amount)?;
+
+    // a tripped breaker keeps the coupon so it can be redeemed after a reset
+    check_compute_budget(&counter_data)?;
+
+    let old_count = counter_data.count;
+    let applied = apply_increment(&mut counter_data, new_count)?;
+    let trigger = counter_data.take_crossed_trigger(old_count);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    let incremented = counter_data.count - old_count;
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(incremented)
+    })?;
+
+    if applied {
+        credit_lamports(payer_account, coupon_account.lamports())?;
+        close_program_account(coupon_account)?;
+
+        msg!(
+            "Coupon redeemed for {}, counter is now {}",
+            coupon.amount,
+            counter_data.count
+        );
+        emit_count_change(counter_account.key, &counter_data, old_count);
+    }
+
+    if let Some(trigger) = trigger {
+        invoke_trigger(program_id, counter_account.key, &trigger, remaining)?;
+    }
+
+    Ok(())
+}
+
+/// Moves a cross-cluster mirror to the count in a signed snapshot of its
+/// source. Snapshots must be newer than the last one imported and younger
+/// than the source's `max_age_secs`.
+pub fn process_import_attestation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    snapshot: AttestationSnapshot,
+) -> ProgramResult {
+    let CounterAccounts {
+        counter: counter_account,
+        remaining,
+    } = CounterAccounts::parse(program_id, accounts)?;
+    let accounts_iter = &mut remaining.iter();
+    let instructions_sysvar = next_account_info(accounts_iter)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_not_paused(&counter_data)?;
+
+    let Some(source) = counter_data.import_source else {
+        msg!("Counter does not mirror another cluster");
+        return Err(CounterError::InvalidAttestation.into());
+    };
+
+    let attestation = snapshot.attestation;
+    if attestation.counter != source.counter {
+        msg!(
+            "Snapshot is of {}, not the mirrored counter",
+            attestation.counter
+        );
+        return Err(CounterError::InvalidAttestation.into());
+    }
+
+    verify_preceding_signature(
+        instructions_sysvar,
+        &source.attester,
+        &borsh::to_vec(&snapshot)?,
+    )?;
+
+    let age = Clock::get()?
+        .unix_timestamp
+        .saturating_sub(snapshot.unix_timestamp);
+    if age > source.max_age_secs || attestation.slot <= counter_data.imported_slot {
+        msg!(
+            "Snapshot from source slot {} is {}s old",
+            attestation.slot,
+            age
+        );
+        return Err(CounterError::StaleAttestation.into());
+    }
+
+    if counter_data.is_frozen {
+        msg!("Counter is frozen by its circuit breaker");
+        return Err(CounterError::CounterFrozen.into());
+    }
+
+    check_compute_budget(&counter_data)?;
+
+    let old_count = counter_data.count;
+    if apply_count(&mut counter_data, attestation.count)? {
+        counter_data.imported_slot = attestation.slot;
+        msg!(
+            "Counter imported {} from source slot {}",
+            attestation.count,
+            attestation.slot
+        );
+        emit_count_change(counter_account.key, &counter_data, old_count);
+    }
+    let trigger = counter_data.take_crossed_trigger(old_count);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    if let Some(trigger) = trigger {
+        invoke_trigger(
+            program_id,
+            counter_account.key,
+            &trigger,
+            accounts_iter.as_slice(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Adds each counter's step with the same checks as `IncrementCounter`. Any
+/// failing counter fails the whole batch.
+pub fn process_increment_many(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    reject_protocol_fee(program_id, accounts)?;
+
+    let IncrementManyAccounts {
+        actor: actor_account,
+        counters,
+    } = IncrementManyAccounts::parse(program_id, accounts)?;
+
+    // the registry is owned and writable too, so it passes as a counter
+    let registry = find_registry_account(program_id, counters);
+    let counters: Vec<_> = counters
+        .iter()
+        .filter(|account| registry.is_none_or(|registry| registry.key != account.key))
+        .collect();
+
+    let mut incremented = 0u64;
+    for counter_account in &counters {
+        let mut counter_data = load_counter(counter_account)?;
+
+        // gate accounts cannot be told apart from counters here
+        check_gates(&mut counter_data, actor_account, &mut [].iter())?;
+
+        check_count_writable(&counter_data)?;
+        check_unlocked(&counter_data)?;
+
+        if counter_data.increment_fee != 0 {
+            msg!(
+                "Counter {} charges an increment fee, use IncrementCounter",
+                counter_account.key
+            );
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        check_no_event_log(counter_account.key, &counter_data)?;
+
+        // the same counter could be listed twice
+        check_not_once_per_transaction(counter_account.key, &counter_data)?;
+
+        check_compute_budget(&counter_data)?;
+
+        let new_count = counter_data.capped_add(counter_data.step)?;
+
+        let old_count = counter_data.count;
+        if apply_increment(&mut counter_data, new_count)? {
+            emit_count_change(counter_account.key, &counter_data, old_count);
+            record_increment(&mut counter_data, actor_account.key, old_count);
+        }
+
+        if counter_data.take_crossed_trigger(old_count).is_some() {
+            msg!(
+                "Counter {} would fire its trigger, use IncrementCounter",
+                counter_account.key
+            );
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        save_counter(counter_account, &mut counter_data)?;
+        incremented = incremented.saturating_add(counter_data.count - old_count);
+    }
+
+    update_registry(program_id, accounts, |registry| {
+        registry.record_incremented(incremented)
+    })?;
+
+    msg!("Incremented {} counters", counters.len());
+    Ok(())
+}
+
+/// Sweeps lamports sent straight to the counter, keeping whichever is higher
+/// of its rent-exempt minimum and the balance the program last left it with.
+pub fn process_skim_excess(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CloseAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        destination: treasury_account,
+        ..
+    } = CloseAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    if let Some(excess_destination) = counter_data.excess_destination
+        && treasury_account.key != &excess_destination
+    {
+        msg!("Excess lamports must go to {}", excess_destination);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let floor = Rent::get()?
+        .minimum_balance(counter_account.data_len())
+        .max(counter_data.min_observed_lamports);
+    let excess = counter_account.lamports().saturating_sub(floor);
+    if excess == 0 {
+        msg!("Counter holds no excess lamports");
+        return Ok(());
+    }
+
+    **counter_account.try_borrow_mut_lamports()? -= excess;
+    credit_lamports(treasury_account, excess)?;
+    counter_data.min_observed_lamports = counter_account.lamports();
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Skimmed {} excess lamports to {}",
+        excess,
+        treasury_account.key
+    );
+    Ok(())
+}
+
+/// Creates or replaces the admin's template called `name`. The config is
+/// validated as `InitializeCounter` would, so a template cannot hold settings
+/// no counter could be created with.
+pub fn process_set_template(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: Label32,
+    config: InitConfig,
+) -> ProgramResult {
+    let SetTemplateAccounts {
+        template: template_account,
+        admin: admin_account,
+        system_program,
+    } = SetTemplateAccounts::parse(program_id, accounts)?;
+
+    let (template_address, bump) = derive_template_address(program_id, admin_account.key, &name);
+    if template_account.key != &template_address {
+        msg!("Template account is not the admin's template PDA for this name");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    build_counter(program_id, admin_account.key, 0, config.clone())?;
+
+    let template = Template {
+        discriminator: TEMPLATE_DISCRIMINATOR,
+        admin: *admin_account.key,
+        name,
+        config,
+    };
+    let account_space = borsh::object_length(&template)?;
+
+    // only this program can have created an owned account at the PDA
+    if template_account.owner == program_id {
+        resize_program_account(
+            template_account,
+            admin_account,
+            system_program,
+            account_space,
+        )?;
+    } else {
+        create_program_account(
+            program_id,
+            admin_account,
+            template_account,
+            system_program,
+            account_space,
+            &[
+                TEMPLATE_SEED,
+                admin_account.key.as_ref(),
+                template.name.as_bytes(),
+                &[bump],
+            ],
+        )?;
+    }
+
+    template.serialize(&mut &mut template_account.data.borrow_mut()[..])?;
+
+    msg!("Template {} ({:?}) saved", template_address, template.name);
+    Ok(())
+}
+
+pub fn process_initialize_from_template(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+) -> ProgramResult {
+    let InitializeFromTemplateAccounts {
+        create:
+            CreateAccounts {
+                new_account: counter_account,
+                payer: payer_account,
+                system_program,
+            },
+        template: template_account,
+    } = InitializeFromTemplateAccounts::parse(program_id, accounts)?;
+
+    let template = {
+        let data = template_account.try_borrow_data()?;
+        if data.get(..TEMPLATE_DISCRIMINATOR.len()) != Some(&TEMPLATE_DISCRIMINATOR[..]) {
+            msg!("Account is not a template");
+            return Err(CounterError::WrongAccountType.into());
+        }
+        Template::try_from_slice(&data)?
+    };
+
+    let config = with_global_defaults(program_id, accounts, template.config)?;
+    let mut counter_data = build_counter(program_id, payer_account.key, initial_value, config)?;
+
+    charge_owner_quota(program_id, accounts, payer_account, system_program)?;
+    index_counter(
+        program_id,
+        accounts,
+        payer_account,
+        counter_account.key,
+        system_program,
+    )?;
+    setup_fee_vault(
+        program_id,
+        accounts,
+        counter_account.key,
+        &mut counter_data,
+        payer_account,
+        system_program,
+    )?;
+
+    create_program_account(
+        program_id,
+        payer_account,
+        counter_account,
+        system_program,
+        borsh::object_length(&counter_data)?,
+        &[],
+    )?;
+
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Counter initialized with value {} from template {:?}",
+        initial_value,
+        template.name
+    );
+    emit_initialized(counter_account.key, &counter_data, initial_value);
+
+    update_registry(program_id, accounts, Registry::record_created)?;
+
+    Ok(())
+}
+
+/// Creates the program-wide `["registry"]` account. Handlers that create,
+/// increment or close counters update it whenever it is passed in.
+pub fn process_initialize_registry(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CreatePdaAccounts {
+        new_account: registry_account,
+        payer: payer_account,
+        system_program,
+    } = CreatePdaAccounts::parse(program_id, accounts)?;
+
+    let (registry_address, bump) = derive_registry_address(program_id);
+    if registry_account.key != &registry_address {
+        msg!("Registry account is not the program's registry PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let registry = Registry {
+        discriminator: REGISTRY_DISCRIMINATOR,
+        counters_created: 0,
+        counters_closed: 0,
+        total_incremented: 0,
+        admin: *payer_account.key,
+        unfreeze_grace_secs: DEFAULT_UNFREEZE_GRACE_SECS,
+    };
+
+    create_program_account(
+        program_id,
+        payer_account,
+        registry_account,
+        system_program,
+        borsh::object_length(&registry)?,
+        &[REGISTRY_SEED, &[bump]],
+    )?;
+
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+
+    msg!("Registry {} initialized", registry_address);
+    Ok(())
+}
+
+/// Creates the global config, with every parameter off, for the program's
+/// upgrade authority.
+pub fn process_initialize_global_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let InitializeGlobalConfigAccounts {
+        config: config_account,
+        authority: authority_account,
+        program_data,
+        system_program,
+    } = InitializeGlobalConfigAccounts::parse(program_id, accounts)?;
+
+    check_upgrade_authority(program_id, program_data, authority_account)?;
+
+    let (config_address, bump) = derive_global_config_address(program_id);
+    if config_account.key != &config_address {
+        msg!("Config account is not the program's global config PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let config = GlobalConfig {
+        discriminator: GLOBAL_CONFIG_DISCRIMINATOR,
+        params: GlobalParams::default(),
+    };
+    create_program_account(
+        program_id,
+        authority_account,
+        config_account,
+        system_program,
+        borsh::object_length(&config)?,
+        &[GLOBAL_CONFIG_SEED, &[bump]],
+    )?;
+    config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
+
+    msg!("Global config {} initialized", config_address);
+    Ok(())
+}
+
+pub fn process_set_global_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    params: GlobalParams,
+) -> ProgramResult {
+    let SetGlobalConfigAccounts {
+        config: config_account,
+        authority: authority_account,
+        program_data,
+    } = SetGlobalConfigAccounts::parse(program_id, accounts)?;
+
+    check_upgrade_authority(program_id, program_data, authority_account)?;
+
+    let mut config = load_global_config(config_account)?;
+    config.params = params;
+    config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
+
+    msg!("Global config updated: {:?}", config.params);
+    Ok(())
+}
+
+/// Fails unless `authority` is the upgrade authority recorded in the
+/// program's data account, so only whoever can redeploy the program can
+/// change its global config.
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: &AccountInfo,
+    authority: &AccountInfo,
+) -> ProgramResult {
+    if program_data.key != &derive_program_data_address(program_id)
+        || program_data.owner != &bpf_loader_upgradeable::id()
+    {
+        msg!("Program data account does not belong to this program");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // bincode `UpgradeableLoaderState::ProgramData`: variant 3, the deploy
+    // slot, then the optional upgrade authority
+    let data = program_data.try_borrow_data()?;
+    let metadata = data.get(..UpgradeableLoaderState::size_of_programdata_metadata());
+    let upgrade_authority = match metadata {
+        Some([3, 0, 0, 0, _, _, _, _, _, _, _, _, 1, key @ ..]) => Pubkey::try_from(key).ok(),
+        _ => None,
+    };
+    if upgrade_authority != Some(*authority.key) {
+        msg!("Signer is not the program's upgrade authority");
+        return Err(CounterError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+fn load_global_config(config_account: &AccountInfo) -> Result<GlobalConfig, ProgramError> {
+    let data = config_account.try_borrow_data()?;
+    if !data.starts_with(&GLOBAL_CONFIG_DISCRIMINATOR) {
+        return Err(CounterError::WrongAccountType.into());
+    }
+    Ok(GlobalConfig::try_from_slice(&data)?)
+}
+
+/// The global config's parameters if it was passed anywhere in `accounts`.
+/// Only `InitializeGlobalConfig` writes its discriminator, and only at the
+/// config PDA, so the discriminator is enough to find it.
+fn find_global_params(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Result<Option<GlobalParams>, ProgramError> {
+    let Some(config_account) = accounts.iter().find(|account| {
+        account.owner == program_id
+            && account
+                .try_borrow_data()
+                .is_ok_and(|data| data.starts_with(&GLOBAL_CONFIG_DISCRIMINATOR))
+    }) else {
+        return Ok(None);
+    };
+    Ok(Some(load_global_config(config_account)?.params))
+}
+
+fn check_not_globally_paused(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if find_global_params(program_id, accounts)?.is_some_and(|params| params.paused) {
+        msg!("Program is paused by its global config");
+        return Err(CounterError::ProgramPaused.into());
+    }
+    Ok(())
+}
+
+/// Fills in what `config` leaves to the global config, when it is passed.
+fn with_global_defaults(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mut config: InitConfig,
+) -> Result<InitConfig, ProgramError> {
+    // committed counters cannot have a cap
+    if let Some(params) = find_global_params(program_id, accounts)?
+        && params.default_max_value != 0
+        && config.value_commitment.is_none()
+    {
+        config.max_value.get_or_insert(params.default_max_value);
+    }
+    Ok(config)
+}
+
+/// Transfers the global config's protocol fee from `actor` to its
+/// recipient, when the config is passed and sets a fee.
+fn charge_protocol_fee<'a>(
+    program_id: &Pubkey,
+    actor: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    let Some(params) = find_global_params(program_id, accounts)? else {
+        return Ok(());
+    };
+    if params.protocol_fee == 0 {
+        return Ok(());
+    }
+
+    if !actor.is_signer || !actor.is_writable {
+        msg!("Actor must sign and be writable to pay the protocol fee");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let find = |key: &Pubkey| {
+        accounts
+            .iter()
+            .find(|account| account.key == key)
+            .ok_or(ProgramError::NotEnoughAccountKeys)
+    };
+    let recipient = find(&params.fee_recipient)?;
+    let system_program = find(&system_program::ID)?;
+
+    invoke(
+        &system_instruction::transfer(actor.key, recipient.key, params.protocol_fee),
+        &[actor.clone(), recipient.clone(), system_program.clone()],
+    )
+}
+
+/// For increments without an actor able to pay the protocol fee, which must
+/// not run while the passed global config sets one.
+fn reject_protocol_fee(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if find_global_params(program_id, accounts)?.is_some_and(|params| params.protocol_fee != 0) {
+        msg!("Global config charges a protocol fee, use IncrementCounter");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Applies `update` to the registry if it was passed writable anywhere in
+/// `accounts`. Only `InitializeRegistry` writes the registry discriminator,
+/// and only at the registry PDA, so the discriminator is enough to find it.
+fn update_registry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut Registry),
+) -> ProgramResult {
+    let Some(registry_account) =
+        find_registry_account(program_id, accounts).filter(|account| account.is_writable)
+    else {
+        return Ok(());
+    };
+
+    let mut registry = Registry::try_from_slice(&registry_account.try_borrow_data()?)?;
+    update(&mut registry);
+    registry.serialize(&mut &mut registry_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Points a fee-charging counter without a recipient at its fee vault and
+/// creates the vault, paid by `payer`.
+fn setup_fee_vault<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    counter: &Pubkey,
+    counter_data: &mut CounterAccount,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    if counter_data.increment_fee == 0 || counter_data.fee_recipient != Pubkey::default() {
+        return Ok(());
+    }
+
+    let (vault_address, bump) = derive_fee_vault_address(program_id, counter);
+    let Some(vault_account) = accounts
+        .iter()
+        .find(|account| account.key == &vault_address)
+    else {
+        msg!(
+            "Fee vault {} is required without a fee recipient",
+            vault_address
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    create_program_account(
+        program_id,
+        payer,
+        vault_account,
+        system_program,
+        0,
+        &[FEE_VAULT_SEED, counter.as_ref(), &[bump]],
+    )?;
+
+    counter_data.fee_recipient = vault_address;
+    Ok(())
+}
+
+/// Transfers the counter's increment fee from `actor`, which must sign and
+/// be writable, to its fee recipient. The recipient and the system program
+/// may sit anywhere in `accounts`.
+fn charge_increment_fee<'a>(
+    counter_data: &CounterAccount,
+    actor: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    if counter_data.increment_fee == 0 {
+        return Ok(());
+    }
+
+    if !actor.is_signer || !actor.is_writable {
+        msg!("Actor must sign and be writable to pay the increment fee");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let find = |key: &Pubkey| {
+        accounts
+            .iter()
+            .find(|account| account.key == key)
+            .ok_or(ProgramError::NotEnoughAccountKeys)
+    };
+    let recipient = find(&counter_data.fee_recipient)?;
+    let system_program = find(&system_program::ID)?;
+
+    invoke(
+        &system_instruction::transfer(actor.key, recipient.key, counter_data.increment_fee),
+        &[actor.clone(), recipient.clone(), system_program.clone()],
+    )
+}
+
+/// Transfers the token gate's minimum balance from the actor's token account
+/// to the counter's token treasury, when it has one. Only increments pay it;
+/// the gate itself just checks the balance. The actor's token account, which
+/// must be writable, the treasury and the token program owning them may sit
+/// anywhere in `accounts`.
+fn charge_token_treasury<'a>(
+    counter_data: &CounterAccount,
+    actor: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    let Some(treasury) = counter_data.token_treasury else {
+        return Ok(());
+    };
+
+    let find = |key: &Pubkey| {
+        accounts
+            .iter()
+            .find(|account| account.key == key)
+            .ok_or(ProgramError::NotEnoughAccountKeys)
+    };
+    let treasury_account = find(&treasury)?;
+    let Some(source) = accounts.iter().find(|account| {
+        account.is_writable
+            && read_token_account(account).is_ok_and(|token| {
+                token.mint == counter_data.gates.token_mint && token.owner == *actor.key
+            })
+    }) else {
+        msg!("Pass the actor's token account writable to pay the token treasury");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let token_program = find(source.owner)?;
+
+    invoke(
+        &spl::transfer(
+            token_program.key,
+            source.key,
+            treasury_account.key,
+            actor.key,
+            counter_data.gates.min_token_balance,
+        ),
+        &[
+            source.clone(),
+            treasury_account.clone(),
+            actor.clone(),
+            token_program.clone(),
+        ],
+    )
+}
+
+fn find_registry_account<'a, 'info>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+) -> Option<&'a AccountInfo<'info>> {
+    accounts.iter().find(|account| {
+        account.owner == program_id
+            && account
+                .try_borrow_data()
+                .is_ok_and(|data| data.starts_with(&REGISTRY_DISCRIMINATOR))
+    })
+}
+
+fn load_registry(registry_account: &AccountInfo) -> Result<Registry, ProgramError> {
+    let registry = Registry::try_from_slice(&registry_account.try_borrow_data()?)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if registry.discriminator != REGISTRY_DISCRIMINATOR {
+        return Err(CounterError::WrongAccountType.into());
+    }
+    Ok(registry)
+}
+
+fn check_registry_admin(registry: &Registry, admin_account: &AccountInfo) -> ProgramResult {
+    if registry.admin != *admin_account.key {
+        msg!("Only the registry admin can do this");
+        return Err(CounterError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+/// Counts a new counter against `owner`'s quota from the global config,
+/// creating the owner's stats account, paid by `owner`, on first use. The
+/// config PDA must be passed whether or not it is initialized, so leaving it
+/// out cannot dodge the quota. Runs before the counter is created so a
+/// refused creation leaves nothing behind.
+fn charge_owner_quota<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    owner: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let (config_address, _) = derive_global_config_address(program_id);
+    if !accounts
+        .iter()
+        .any(|account| account.key == &config_address)
+    {
+        msg!(
+            "Global config account {} is required to create a counter",
+            config_address
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let max_counters_per_owner =
+        find_global_params(program_id, accounts)?.map_or(0, |params| params.max_counters_per_owner);
+    if max_counters_per_owner == 0 {
+        return Ok(());
+    }
+
+    let (stats_address, bump) = derive_owner_stats_address(program_id, owner.key);
+    let Some(stats_account) = accounts
+        .iter()
+        .find(|account| account.key == &stats_address)
+    else {
+        msg!(
+            "Owner stats account {} is required while a quota is set",
+            stats_address
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !stats_account.is_writable {
+        msg!("Owner stats account must be writable");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let mut stats = if stats_account.data_is_empty() {
+        let stats = OwnerStats {
+            discriminator: OWNER_STATS_DISCRIMINATOR,
+            owner: *owner.key,
+            counters_created: 0,
+        };
+        create_program_account(
+            program_id,
+            owner,
+            stats_account,
+            system_program,
+            borsh::object_length(&stats)?,
+            &[OWNER_STATS_SEED, owner.key.as_ref(), &[bump]],
+        )?;
+        stats
+    } else {
+        if stats_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        OwnerStats::try_from_slice(&stats_account.try_borrow_data()?)?
+    };
+
+    if stats.counters_created >= max_counters_per_owner {
+        msg!(
+            "Owner {} has already created {} counters",
+            owner.key,
+            stats.counters_created
+        );
+        return Err(CounterError::OwnerQuotaExceeded.into());
+    }
+
+    stats.counters_created += 1;
+    stats.serialize(&mut &mut stats_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// `owner`'s index account and bump, when the caller passed it.
+fn find_owner_index<'a, 'info>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    owner: &Pubkey,
+) -> Result<Option<(&'a AccountInfo<'info>, u8)>, ProgramError> {
+    let (index_address, bump) = derive_owner_index_address(program_id, owner);
+    let Some(index_account) = accounts
+        .iter()
+        .find(|account| account.key == &index_address)
+    else {
+        return Ok(None);
+    };
+    if !index_account.is_writable {
+        msg!("Owner index account must be writable");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(Some((index_account, bump)))
+}
+
+fn load_owner_index(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<OwnerIndex, ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account.try_borrow_data()?;
+    if !data.starts_with(&OWNER_INDEX_DISCRIMINATOR) {
+        return Err(CounterError::WrongAccountType.into());
+    }
+    Ok(OwnerIndex::try_from_slice(&data)?)
+}
+
+/// Appends an increment of `amount` to an event-log counter's head page,
+/// moving `log_head` on to the next page once the head is full. Pages are
+/// created at the actor's expense.
+fn append_log<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    counter: &Pubkey,
+    counter_data: &mut CounterAccount,
+    actor: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    let (mut page_account, mut page) =
+        load_log_page(program_id, accounts, counter, counter_data.log_head, actor)?;
+    if page.is_full() {
+        counter_data.log_head = counter_data
+            .log_head
+            .checked_add(1)
+            .ok_or(CounterError::Overflow)?;
+        (page_account, page) =
+            load_log_page(program_id, accounts, counter, counter_data.log_head, actor)?;
+    }
+
+    page.push(LogRecord {
+        slot: Clock::get()?.slot,
+        actor: *actor.key,
+        amount,
+        count: counter_data.count,
+    });
+    page.serialize(&mut &mut page_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Finds log page `page` of `counter` among `accounts`, creating it empty,
+/// paid by `payer`, if it does not exist yet.
+fn load_log_page<'a, 'b>(
+    program_id: &Pubkey,
+    accounts: &'b [AccountInfo<'a>],
+    counter: &Pubkey,
+    page: u32,
+    payer: &AccountInfo<'a>,
+) -> Result<(&'b AccountInfo<'a>, LogPage), ProgramError> {
+    let (page_address, bump) = derive_log_page_address(program_id, counter, page);
+    let Some(page_account) = accounts.iter().find(|account| account.key == &page_address) else {
+        msg!("Counter keeps an event log, pass its log page {}", page);
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !page_account.is_writable {
+        msg!("Log page account must be writable");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if !page_account.data_is_empty() {
+        if page_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data = page_account.try_borrow_data()?;
+        if !data.starts_with(&LOG_PAGE_DISCRIMINATOR) {
+            return Err(CounterError::WrongAccountType.into());
+        }
+        return Ok((page_account, LogPage::try_from_slice(&data)?));
+    }
+
+    if !payer.is_signer || !payer.is_writable {
+        msg!("Actor must sign and be writable to pay for a new log page");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let system_program = accounts
+        .iter()
+        .find(|account| account.key == &system_program::ID)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let log_page = LogPage::new(*counter, page);
+    create_program_account(
+        program_id,
+        payer,
+        page_account,
+        system_program,
+        borsh::object_length(&log_page)?,
+        &[LOG_SEED, counter.as_ref(), &page.to_le_bytes(), &[bump]],
+    )?;
+    Ok((page_account, log_page))
+}
+
+/// Adds `amount` to `user`'s record for `counter` when the record account is
+/// passed, creating it at `user`'s expense on first use.
+fn record_contribution<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    counter: &Pubkey,
+    user: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    let (record_address, bump) = derive_user_record_address(program_id, counter, user.key);
+    let Some(record_account) = accounts
+        .iter()
+        .find(|account| account.key == &record_address)
+    else {
+        return Ok(());
+    };
+    if !record_account.is_writable {
+        msg!("User record account must be writable");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let mut record = if record_account.data_is_empty() {
+        if !user.is_signer || !user.is_writable {
+            msg!("Actor must sign and be writable to pay for its user record");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let system_program = accounts
+            .iter()
+            .find(|account| account.key == &system_program::ID)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let record = UserRecord::new(*counter, *user.key);
+        create_program_account(
+            program_id,
+            user,
+            record_account,
+            system_program,
+            borsh::object_length(&record)?,
+            &[
+                USER_RECORD_SEED,
+                counter.as_ref(),
+                user.key.as_ref(),
+                &[bump],
+            ],
+        )?;
+        record
+    } else {
+        if record_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data = record_account.try_borrow_data()?;
+        if !data.starts_with(&USER_RECORD_DISCRIMINATOR) {
+            return Err(CounterError::WrongAccountType.into());
+        }
+        UserRecord::try_from_slice(&data)?
+    };
+
+    record.count = record
+        .count
+        .checked_add(amount)
+        .ok_or(CounterError::Overflow)?;
+    record.last_updated = Clock::get()?.unix_timestamp;
+    record.serialize(&mut &mut record_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Adds `counter` to `owner`'s index when the index account is passed,
+/// creating the index or growing it by one address at `owner`'s expense.
+/// Without it the counter is simply not listed; see `OwnerIndex`.
+fn index_counter<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    owner: &AccountInfo<'a>,
+    counter: &Pubkey,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let Some((index_account, bump)) = find_owner_index(program_id, accounts, owner.key)? else {
+        return Ok(());
+    };
+
+    let mut index = if index_account.data_is_empty() {
+        OwnerIndex {
+            discriminator: OWNER_INDEX_DISCRIMINATOR,
+            owner: *owner.key,
+            counters: IndexedCounters::default(),
+        }
+    } else {
+        load_owner_index(program_id, index_account)?
+    };
+    let mut counters = index.counters.to_vec();
+    counters.push(*counter);
+    index.counters = IndexedCounters::new(counters).inspect_err(|_| {
+        msg!(
+            "Owner index already lists {} counters",
+            MAX_INDEXED_COUNTERS
+        );
+    })?;
+    let space = borsh::object_length(&index)?;
+
+    if index_account.data_is_empty() {
+        create_program_account(
+            program_id,
+            owner,
+            index_account,
+            system_program,
+            space,
+            &[OWNER_INDEX_SEED, owner.key.as_ref(), &[bump]],
+        )?;
+    } else {
+        resize_program_account(index_account, owner, system_program, space)?;
+    }
+    index.serialize(&mut &mut index_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Drops a closed `counter` from its creator's index when the index account
+/// is passed, leaving the address listed otherwise. The freed rent stays
+/// with the index for the next counter.
+fn unindex_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    counter: &Pubkey,
+    creator: &Pubkey,
+) -> ProgramResult {
+    let Some((index_account, _)) = find_owner_index(program_id, accounts, creator)? else {
+        return Ok(());
+    };
+
+    let mut index = load_owner_index(program_id, index_account)?;
+    let counters = index
+        .counters
+        .iter()
+        .copied()
+        .filter(|key| key != counter)
+        .collect();
+    index.counters = IndexedCounters::new(counters)?;
+    index_account.realloc(borsh::object_length(&index)?, false)?;
+    index.serialize(&mut &mut index_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+pub fn process_set_mode(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mode: IncrementMode,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.set_increment_mode(mode);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter mode set to {:?}", mode);
+    Ok(())
+}
+
+/// Shared by `Pause` and `Resume`.
+pub fn process_set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+
+    counter_data.is_paused = paused;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    if paused {
+        msg!("Counter paused");
+    } else {
+        msg!("Counter resumed");
+    }
+    Ok(())
+}
+
+pub fn process_initialize_counter_by_id(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+    external_id: [u8; 32],
+    config: InitConfig,
+) -> ProgramResult {
+    let CreatePdaAccounts {
+        new_account: counter_account,
+        payer: owner_account,
+        system_program,
+    } = CreatePdaAccounts::parse(program_id, accounts)?;
+
+    let (counter_address, bump) =
+        derive_external_counter_address(program_id, owner_account.key, &external_id);
+    if counter_account.key != &counter_address {
+        msg!("Counter account is not the owner's counter PDA for this external ID");
+        return Err(CounterError::InvalidCounterAddress.into());
+    }
+
+    let config = with_global_defaults(program_id, accounts, config)?;
+    let mut counter_data = build_counter(program_id, owner_account.key, initial_value, config)?;
+    counter_data.external_id = Some(external_id);
+
+    let account_space = borsh::object_length(&counter_data)?;
+
+    charge_owner_quota(program_id, accounts, owner_account, system_program)?;
+    index_counter(
+        program_id,
+        accounts,
+        owner_account,
+        counter_account.key,
+        system_program,
+    )?;
+    setup_fee_vault(
+        program_id,
+        accounts,
+        counter_account.key,
+        &mut counter_data,
+        owner_account,
+        system_program,
+    )?;
+
+    create_program_account(
+        program_id,
+        owner_account,
+        counter_account,
+        system_program,
+        account_space,
+        &[
+            EXTERNAL_ID_SEED,
+            owner_account.key.as_ref(),
+            &external_id,
+            &[bump],
+        ],
+    )?;
+
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Counter PDA {} initialized with value {}",
+        counter_address,
+        initial_value
+    );
+    emit_initialized(counter_account.key, &counter_data, initial_value);
+
+    update_registry(program_id, accounts, Registry::record_created)?;
+
+    Ok(())
+}
+
+/// Copies the count into a new, write-once snapshot PDA and advances the
+/// counter's snapshot index.
+pub fn process_snapshot(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let SnapshotAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        snapshot: snapshot_account,
+        system_program,
+    } = SnapshotAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    let index = counter_data.snapshot_count;
+    let (snapshot_address, bump) = derive_snapshot_address(program_id, counter_account.key, index);
+    if snapshot_account.key != &snapshot_address {
+        msg!("Snapshot account is not the PDA for index {}", index);
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let clock = Clock::get()?;
+    let snapshot = Snapshot {
+        discriminator: SNAPSHOT_DISCRIMINATOR,
+        counter: *counter_account.key,
+        index,
+        count: counter_data.count,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+    };
+
+    create_program_account(
+        program_id,
+        authority_account,
+        snapshot_account,
+        system_program,
+        borsh::object_length(&snapshot)?,
+        &[
+            SNAPSHOT_SEED,
+            counter_account.key.as_ref(),
+            &index.to_le_bytes(),
+            &[bump],
+        ],
+    )?;
+
+    snapshot.serialize(&mut &mut snapshot_account.data.borrow_mut()[..])?;
+
+    counter_data.snapshot_count = index.checked_add(1).ok_or(CounterError::Overflow)?;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Snapshot {} recorded count {} at slot {}",
+        index,
+        snapshot.count,
+        snapshot.slot
+    );
+    Ok(())
+}
+
+pub fn process_add_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+) -> ProgramResult {
+    update_delegates(program_id, accounts, |delegates| {
+        if delegates.contains(&delegate) {
+            msg!("{} is already a delegate", delegate);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut updated = delegates.to_vec();
+        updated.push(delegate);
+        *delegates = Delegates::new(updated)?;
+
+        msg!("Delegate {} added", delegate);
+        Ok(())
+    })
+}
+
+pub fn process_remove_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+) -> ProgramResult {
+    update_delegates(program_id, accounts, |delegates| {
+        if !delegates.contains(&delegate) {
+            msg!("{} is not a delegate", delegate);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let updated = delegates
+            .iter()
+            .copied()
+            .filter(|key| key != &delegate)
+            .collect();
+        *delegates = Delegates::new(updated)?;
+
+        msg!("Delegate {} removed", delegate);
+        Ok(())
+    })
+}
+
+/// Applies `update` to the counter's delegates and resizes the account to
+/// fit, the authority paying any extra rent.
+fn update_delegates(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut Delegates) -> ProgramResult,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        remaining,
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+    let system_program = next_account_info(&mut remaining.iter())?;
+
+    let version = backend_for(&counter_account.try_borrow_data()?)?.version();
+    if version != COUNTER_VERSION {
+        msg!("Counter must be migrated before changing delegates");
+        return Err(CounterError::MigrationRequired.into());
+    }
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    update(&mut counter_data.delegates)?;
+
+    resize_program_account(
+        counter_account,
+        authority_account,
+        system_program,
+        borsh::object_length(&counter_data)?,
+    )?;
+    write_counter(counter_account, &mut counter_data)
+}
+
+pub fn process_create_multisig(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    threshold: u8,
+    signers: MultisigSigners,
+) -> ProgramResult {
+    let CreateAccounts {
+        new_account: multisig_account,
+        payer: payer_account,
+        system_program,
+    } = CreateAccounts::parse(program_id, accounts)?;
+
+    if threshold == 0 || threshold as usize > signers.len() {
+        msg!("Threshold must be between 1 and the number of signers");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if signers
+        .iter()
+        .enumerate()
+        .any(|(index, signer)| signers[..index].contains(signer))
+    {
+        msg!("Multisig signers must be distinct");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let multisig = Multisig {
+        discriminator: MULTISIG_DISCRIMINATOR,
+        threshold,
+        signers,
+    };
+
+    create_program_account(
+        program_id,
+        payer_account,
+        multisig_account,
+        system_program,
+        borsh::object_length(&multisig)?,
+        &[],
+    )?;
+
+    multisig.serialize(&mut &mut multisig_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "Multisig {} created, {} of {} signers required",
+        multisig_account.key,
+        threshold,
+        multisig.signers.len()
+    );
+    Ok(())
+}
+
+pub fn process_set_unlock_time(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    unlock_timestamp: Option<i64>,
+) -> ProgramResult {
+    let AuthorityAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        ..
+    } = AuthorityAccounts::parse(program_id, accounts)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    counter_data.unlock_timestamp = unlock_timestamp.unwrap_or(0);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    match unlock_timestamp {
+        Some(unlock_timestamp) => msg!("Counter locked until {}", unlock_timestamp),
+        None => msg!("Counter unlock time cleared"),
+    }
+    Ok(())
+}
+
+pub fn process_set_owner_quota(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_counters_per_owner: u32,
+) -> ProgramResult {
+    let SetGlobalConfigAccounts {
+        config: config_account,
+        authority: authority_account,
+        program_data,
+    } = SetGlobalConfigAccounts::parse(program_id, accounts)?;
+
+    check_upgrade_authority(program_id, program_data, authority_account)?;
+
+    let mut config = load_global_config(config_account)?;
+    config.params.max_counters_per_owner = max_counters_per_owner;
+    config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
+
+    msg!("Owner quota set to {}", max_counters_per_owner);
+    Ok(())
+}
+
+/// Sweeps the fee vault down to its rent-exempt minimum.
+pub fn process_withdraw_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let WithdrawFeesAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        fee_vault: vault_account,
+        destination: destination_account,
+    } = WithdrawFeesAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+    check_not_paused(&counter_data)?;
+
+    let (vault_address, _) = derive_fee_vault_address(program_id, counter_account.key);
+    if vault_account.key != &vault_address {
+        msg!("Fee vault is not the counter's fee vault PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let floor = Rent::get()?.minimum_balance(vault_account.data_len());
+    let fees = vault_account.lamports().saturating_sub(floor);
+
+    **vault_account.try_borrow_mut_lamports()? -= fees;
+    credit_lamports(destination_account, fees)?;
+
+    msg!(
+        "Withdrew {} lamports of fees to {}",
+        fees,
+        destination_account.key
+    );
+    Ok(())
+}
+
+pub fn process_resize(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let MigrateAccounts {
+        counter: counter_account,
+        payer: payer_account,
+        system_program,
+    } = MigrateAccounts::parse(program_id, accounts)?;
+
+    if counter_account.data_len() != BARE_COUNTER_LEN {
+        return process_migrate_account(program_id, accounts);
+    }
+
+    // a bare counter records no authority, so only its keypair can say who
+    // takes it over
+    if !counter_account.is_signer {
+        msg!("Resizing a bare counter needs the counter keypair's signature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let count = counter_account.try_borrow_data()?[..]
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut counter_data =
+        build_counter(program_id, payer_account.key, count, InitConfig::default())?;
+
+    resize_program_account(
+        counter_account,
+        payer_account,
+        system_program,
+        borsh::object_length(&counter_data)?,
+    )?;
+    write_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Bare counter resized with value {} and authority {}",
+        count,
+        payer_account.key
+    );
+    Ok(())
+}
+
+pub fn process_admin_freeze(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let AdminFreezeAccounts {
+        registry: registry_account,
+        admin: admin_account,
+        counter: counter_account,
+    } = AdminFreezeAccounts::parse(program_id, accounts)?;
+
+    let registry = load_registry(registry_account)?;
+    check_registry_admin(&registry, admin_account)?;
+
+    let mut counter_data = load_counter(counter_account)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    if counter_data.is_admin_frozen(now) {
+        msg!("Counter is already frozen by the registry admin");
+        return Err(CounterError::FrozenByAdmin.into());
+    }
+
+    counter_data.admin_frozen = true;
+    counter_data.unfreeze_requested_at = 0;
+    counter_data.unfreeze_at = 0;
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!("Counter frozen by the registry admin");
+    Ok(())
+}
+
+/// Starts the grace period after which the admin freeze lapses.
+pub fn process_request_unfreeze(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let RequestUnfreezeAccounts {
+        counter: counter_account,
+        authority: authority_account,
+        registry: registry_account,
+    } = RequestUnfreezeAccounts::parse(program_id, accounts)?;
+
+    let registry = load_registry(registry_account)?;
+    let mut counter_data = load_counter(counter_account)?;
+
+    check_authority(&counter_data, authority_account)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    if !counter_data.is_admin_frozen(now) {
+        msg!("Counter is not frozen by the registry admin");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if counter_data.unfreeze_requested_at != 0 {
+        msg!(
+            "Unfreeze already requested, it lapses at {}",
+            counter_data.unfreeze_at
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    counter_data.unfreeze_requested_at = now;
+    counter_data.unfreeze_at = now.saturating_add(registry.unfreeze_grace_secs);
+
+    save_counter(counter_account, &mut counter_data)?;
+
+    msg!(
+        "Unfreeze requested, the freeze lapses at {}",
+        counter_data.unfreeze_at
+    );
+    Ok(())
+}
+
+/// The admin's escalation during the grace period: closes the frozen
+/// counter for good.
+pub fn process_burn_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let BurnAccounts {
+        registry: registry_account,
+        admin: admin_account,
+        counter: counter_account,
+        destination: destination_account,
+    } = BurnAccounts::parse(program_id, accounts)?;
+
+    let registry = load_registry(registry_account)?;
+    check_registry_admin(&registry, admin_account)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    if !counter_data.is_admin_frozen(Clock::get()?.unix_timestamp) {
+        msg!("Only a counter under an admin freeze can be burned");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if counter_account.key == destination_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let lamports = counter_account.lamports();
+    credit_lamports(destination_account, lamports)?;
+    close_program_account(counter_account)?;
+
+    msg!(
+        "Counter burned, {} lamports to {}",
+        lamports,
+        destination_account.key
+    );
+    emit_closed(counter_account.key, &counter_data, lamports);
+    update_registry(program_id, accounts, Registry::record_closed)?;
+    unindex_counter(
+        program_id,
+        accounts,
+        counter_account.key,
+        &counter_data.payer,
+    )
+}
+
+pub fn process_set_unfreeze_grace_period(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    grace_secs: i64,
+) -> ProgramResult {
+    let RegistryAdminAccounts {
+        registry: registry_account,
+        admin: admin_account,
+    } = RegistryAdminAccounts::parse(program_id, accounts)?;
+
+    let mut registry = load_registry(registry_account)?;
+    check_registry_admin(&registry, admin_account)?;
+
+    if grace_secs < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    registry.unfreeze_grace_secs = grace_secs;
+    registry.serialize(&mut &mut registry_account.try_borrow_mut_data()?[..])?;
+
+    msg!("Unfreeze grace period set to {} seconds", grace_secs);
+    Ok(())
+}
+
+pub fn process_initialize_signed_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: i64,
+) -> ProgramResult {
+    let CreateAccounts {
+        new_account: counter_account,
+        payer: payer_account,
+        system_program,
+    } = CreateAccounts::parse(program_id, accounts)?;
+
+    let counter = SignedCounter {
+        discriminator: SIGNED_COUNTER_DISCRIMINATOR,
+        authority: *payer_account.key,
+        count: initial_value,
+    };
+
+    create_program_account(
+        program_id,
+        payer_account,
+        counter_account,
+        system_program,
+        borsh::object_length(&counter)?,
+        &[],
+    )?;
+
+    counter.serialize(&mut &mut counter_account.data.borrow_mut()[..])?;
+
+    msg!("Signed counter initialized with value : {}", initial_value);
+    Ok(())
+}
+
+/// Moves a signed counter to the count `change` computes from it.
+fn process_change_signed_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    change: impl FnOnce(&SignedCounter) -> Result<i64, ProgramError>,
+) -> ProgramResult {
+    let SignedCounterAccounts {
+        counter: counter_account,
+        authority: authority_account,
+    } = SignedCounterAccounts::parse(program_id, accounts)?;
+
+    if !counter_account
+        .try_borrow_data()?
+        .starts_with(&SIGNED_COUNTER_DISCRIMINATOR)
+    {
+        return Err(CounterError::WrongAccountType.into());
+    }
+    let mut counter = SignedCounter::try_from_slice(&counter_account.try_borrow_data()?)?;
+
+    if authority_account.key != &counter.authority {
+        msg!("Instruction requires the signed counter authority's signature");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    counter.count = change(&counter)?;
+    counter.serialize(&mut &mut counter_account.try_borrow_mut_data()?[..])?;
+
+    msg!("Signed counter set to : {}", counter.count);
+    Ok(())
+}
+
+pub fn process_initialize_slot_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    slots: u8,
+) -> ProgramResult {
+    let CreateAccounts {
+        new_account: counter_account,
+        payer: payer_account,
+        system_program,
+    } = CreateAccounts::parse(program_id, accounts)?;
+
+    if slots == 0 || slots as usize > MAX_COUNTER_SLOTS {
+        msg!(
+            "A slot counter has between 1 and {} slots",
+            MAX_COUNTER_SLOTS
+        );
+        return Err(CounterError::InvalidConfig.into());
+    }
+
+    let counter = SlotCounter {
+        discriminator: SLOT_COUNTER_DISCRIMINATOR,
+        authority: *payer_account.key,
+        slots: vec![0; slots as usize],
+    };
+
+    create_program_account(
+        program_id,
+        payer_account,
+        counter_account,
+        system_program,
+        borsh::object_length(&counter)?,
+        &[],
+    )?;
+
+    counter.serialize(&mut &mut counter_account.data.borrow_mut()[..])?;
+
+    msg!("Slot counter initialized with {} slots", slots);
+    Ok(())
+}
+
+fn load_slot_counter(counter_account: &AccountInfo) -> Result<SlotCounter, ProgramError> {
+    let data = counter_account.try_borrow_data()?;
+    if !data.starts_with(&SLOT_COUNTER_DISCRIMINATOR) {
+        return Err(CounterError::WrongAccountType.into());
+    }
+    Ok(SlotCounter::try_from_slice(&data)?)
+}
+
+/// Moves slot `index` to the value `change` computes from it.
+fn process_change_slot(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u8,
+    change: impl FnOnce(u64) -> Result<u64, CounterError>,
+) -> ProgramResult {
+    let SlotCounterAccounts {
+        counter: counter_account,
+        authority: authority_account,
+    } = SlotCounterAccounts::parse(program_id, accounts)?;
+
+    let mut counter = load_slot_counter(counter_account)?;
+
+    if authority_account.key != &counter.authority {
+        msg!("Instruction requires the slot counter authority's signature");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    let slot = counter.slot_mut(index)?;
+    *slot = change(*slot)?;
+
+    msg!("Slot {} set to : {}", index, slot);
+    counter.serialize(&mut &mut counter_account.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+pub fn process_get_slot(program_id: &Pubkey, accounts: &[AccountInfo], index: u8) -> ProgramResult {
+    let ReadAccounts {
+        counter: counter_account,
+    } = ReadAccounts::parse(program_id, accounts)?;
+
+    let counter = load_slot_counter(counter_account)?;
+    set_return_data(&counter.slot(index)?.to_le_bytes());
+    Ok(())
+}
+
+/// Moves `amount` between two counters. Both have to accept the change the
+/// way a decrement and an increment would, and a circuit breaker tripping on
+/// either side fails the whole transfer rather than moving half of it.
+pub fn process_transfer_count(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let TransferCountAccounts {
+        source: source_account,
+        destination: destination_account,
+        authority: authority_account,
+    } = TransferCountAccounts::parse(program_id, accounts)?;
+
+    if source_account.key == destination_account.key {
+        msg!("Source and destination must be different counters");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut source = load_counter(source_account)?;
+    let mut destination = load_counter(destination_account)?;
+
+    check_authority(&source, authority_account)?;
+    for counter_data in [&source, &destination] {
+        check_count_writable(counter_data)?;
+        check_unlocked(counter_data)?;
+    }
+    check_no_event_log(destination_account.key, &destination)?;
+    check_not_once_per_transaction(destination_account.key, &destination)?;
+
+    let new_source_count = source.count.checked_sub(amount).ok_or_else(|| {
+        msg!("Source counter holds only {}", source.count);
+        CounterError::Underflow
+    })?;
+    let new_destination_count = destination.capped_add(amount)?;
+
+    let old_source_count = source.count;
+    let old_destination_count = destination.count;
+    if !apply_count(&mut source, new_source_count)?
+        || !apply_count(&mut destination, new_destination_count)?
+    {
+        return Err(CounterError::CounterFrozen.into());
+    }
+
+    save_counter(source_account, &mut source)?;
+    save_counter(destination_account, &mut destination)?;
+
+    msg!(
+        "Moved {} from {} to {}",
+        amount,
+        source_account.key,
+        destination_account.key
+    );
+    emit_count_change(source_account.key, &source, old_source_count);
+    emit_count_change(destination_account.key, &destination, old_destination_count);
+    Ok(())
+}
+
+/// Closes a counter past its expiry for anyone, refunding its original
+/// payer. A strict counter's donated lamports still go to its excess
+/// destination, and an admin freeze still holds the counter for the admin.
+pub fn process_close_expired(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let CloseExpiredAccounts {
+        counter: counter_account,
+        payer: payer_account,
+    } = CloseExpiredAccounts::parse(program_id, accounts)?;
+
+    let counter_data = load_counter(counter_account)?;
+
+    check_not_admin_frozen(&counter_data)?;
+
+    if payer_account.key != &counter_data.payer {
+        msg!("Rent must go back to the counter's original payer");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if !counter_data.is_past_expiry(Clock::get()?.unix_timestamp) {
+        msg!("Counter does not expire before {}", counter_data.expires_at);
+        return Err(CounterError::NotExpired.into());
+    }
+
+    let excess_account = counter_data.excess_destination.and_then(|destination| {
+        accounts
+            .iter()
+            .find(|account| account.key == &destination && account.is_writable)
+    });
+    let lamports = close_counter(
+        counter_account,
+        &counter_data,
+        payer_account,
+        excess_account,
+    )?;
+
+    msg!("Expired counter closed, {} lamports to payer", lamports);
+    emit_closed(counter_account.key, &counter_data, lamports);
+    update_registry(program_id, accounts, Registry::record_closed)?;
+    unindex_counter(
+        program_id,
+        accounts,
+        counter_account.key,
+        &counter_data.payer,
+    )
+}
+
+/// Adds the counter's step for an actor on its merkle allowlist, marking
+/// the actor's leaf claimed so it cannot be counted twice.
+pub fn process_increment_with_proof(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u32,
+    proof: &[[u8; 32]],
+) -> ProgramResult {
+    let IncrementWithProofAccounts {
+        counter: counter_account,
+        actor: actor_account,
+        claims: claims_account,
+        system_program,
+        remaining,
+    } = IncrementWithProofAccounts::parse(program_id, accounts)?;
+
+    let mutate = MutateAccounts {
+        counter: counter_account,
+        actor: actor_account,
+        remaining,
+    };
+    increment(
+        program_id,
+        accounts,
+        mutate,
+        None,
+        None,
+        Some(MerkleClaim { index, proof }),
+    )?;
+
+    claim_leaf(
+        program_id,
+        counter_account.key,
+        index,
+        actor_account,
+        claims_account,
+        system_program,
+    )
+}
+
+/// Sets leaf `index`'s bit in the counter's claim bitmap, creating the
+/// bitmap paid by `payer` if this is the first claim in its range.
+fn claim_leaf<'a>(
+    program_id: &Pubkey,
+    counter: &Pubkey,
+    index: u32,
+    payer: &AccountInfo<'a>,
+    claims_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let chunk = index / CLAIMS_PER_BITMAP;
+    let (claims_address, bump) = derive_claim_bitmap_address(program_id, counter, chunk);
+    if claims_account.key != &claims_address {
+        msg!("Claim bitmap is not the PDA for leaf {}", index);
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let mut bitmap = if claims_account.data_is_empty() {
+        let bitmap = ClaimBitmap::new(*counter, chunk);
+        create_program_account(
+            program_id,
+            payer,
+            claims_account,
+            system_program,
+            borsh::object_length(&bitmap)?,
+            &[CLAIMS_SEED, counter.as_ref(), &chunk.to_le_bytes(), &[bump]],
+        )?;
+        bitmap
+    } else {
+        if claims_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data = claims_account.try_borrow_data()?;
+        if data.get(..CLAIM_BITMAP_DISCRIMINATOR.len()) != Some(&CLAIM_BITMAP_DISCRIMINATOR[..]) {
+            return Err(CounterError::WrongAccountType.into());
+        }
+        ClaimBitmap::try_from_slice(&data)?
+    };
+
+    if !bitmap.claim(index) {
+        msg!("Allowlist leaf {} has already been claimed", index);
+        return Err(CounterError::AlreadyClaimed.into());
+    }
+    bitmap.serialize(&mut &mut claims_account.try_borrow_mut_data()?[..])?;
     Ok(())
 }
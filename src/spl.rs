@@ -0,0 +1,102 @@
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey,
+    pubkey::Pubkey,
+};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+// offsets into the SPL Token `Mint` layout, shared by Token-2022
+pub const MINT_LEN: usize = 82;
+const MINT_SUPPLY_OFFSET: usize = 36;
+const MINT_IS_INITIALIZED_OFFSET: usize = 45;
+
+/// Reads the supply of an initialized SPL Token or Token-2022 mint without
+/// pulling in the token program crates.
+pub fn read_mint_supply(mint: &AccountInfo) -> Result<u64, ProgramError> {
+    if mint.owner != &TOKEN_PROGRAM_ID && mint.owner != &TOKEN_2022_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let data = mint.data.borrow();
+
+    // token-2022 mints carry extensions past the base layout
+    if data.len() < MINT_LEN || data[MINT_IS_INITIALIZED_OFFSET] != 1 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let supply = data[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8]
+        .try_into()
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    Ok(u64::from_le_bytes(supply))
+}
+
+// offsets into the SPL Token `Account` layout
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+const TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
+
+pub struct TokenAccount {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// Reads the mint, owner and balance of an initialized SPL Token or
+/// Token-2022 token account.
+pub fn read_token_account(account: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    if account.owner != &TOKEN_PROGRAM_ID && account.owner != &TOKEN_2022_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let data = account.data.borrow();
+
+    // 1 = initialized, 2 = frozen
+    if data.len() < TOKEN_ACCOUNT_LEN || data[TOKEN_ACCOUNT_STATE_OFFSET] == 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let pubkey_at = |offset: usize| {
+        <[u8; 32]>::try_from(&data[offset..offset + 32])
+            .map(Pubkey::new_from_array)
+            .map_err(|_| ProgramError::InvalidAccountData)
+    };
+    let amount = data[64..72]
+        .try_into()
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    Ok(TokenAccount {
+        mint: pubkey_at(0)?,
+        owner: pubkey_at(32)?,
+        amount: u64::from_le_bytes(amount),
+    })
+}
+
+const TRANSFER_TAG: u8 = 3;
+
+/// SPL Token `Transfer` of `amount` from `source`, signed by its `owner`.
+/// Token-2022 accepts the same instruction for mints without transfer
+/// extensions.
+pub fn transfer(
+    token_program: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut data = vec![TRANSFER_TAG];
+    data.extend_from_slice(&amount.to_le_bytes());
+
+    Instruction::new_with_bytes(
+        *token_program,
+        &data,
+        vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
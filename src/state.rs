@@ -1,6 +1,1193 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+use crate::addresses::{
+    CLAIM_BITMAP_DISCRIMINATOR, COUNTER_DISCRIMINATOR, COUNTER_DISCRIMINATOR_V0,
+    LOG_PAGE_DISCRIMINATOR, USER_RECORD_DISCRIMINATOR,
+};
+use crate::backend::backend_for;
+use crate::bounded::{BoundedVec, Label32};
+use crate::error::CounterError;
+use crate::gate::GATE_AUTHORITY;
+use crate::instruction::InitConfig;
+
+/// Size of the program's first counters: a bare little-endian `u64` count,
+/// with no discriminator or authority. Only `Resize` reads them.
+pub const BARE_COUNTER_LEN: usize = 8;
+
+/// Layout version written by this program. Bump it, and keep a backend for
+/// the previous layout, whenever a deployed `CounterAccount` layout changes
+/// shape.
+pub const COUNTER_VERSION: u8 = 1;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct CounterAccount {
+    /// Always `COUNTER_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub count: u64,
+    /// Signs for increments, decrements, resets, closes and authority transfers.
+    pub authority: Pubkey,
+    pub is_frozen: bool,
+    pub circuit_breaker: CircuitBreaker,
+    /// Hash of a pending `RevealIncrement`, all zeroes when nothing is committed.
+    pub pending_commitment: [u8; 32],
+    /// Commitment to the off-chain count in commitment mode, all zeroes for
+    /// plain counters. `count` stays at zero while this is set.
+    pub value_commitment: [u8; 32],
+    /// Named metrics that live alongside `count`, fixed at initialization.
+    pub metrics: Vec<Metric>,
+    /// CPI fired once when `count` first reaches `trigger.threshold`.
+    pub trigger: Option<TriggerConfig>,
+    pub trigger_fired: bool,
+    /// SPL mint whose supply this counter mirrors via `SyncMintSupply`.
+    pub mirror_mint: Option<Pubkey>,
+    /// Slot of the last successful sync, so readers can judge staleness.
+    pub mirror_synced_slot: u64,
+    pub observations: ObservationBuffer,
+    /// Access rules for count mutations, see `gate`.
+    pub gates: GateConfig,
+    /// Slot of the last gated mutation, read by the cooldown gate.
+    pub gate_last_slot: u64,
+    /// Paid the rent at creation and gets most of it back if the counter is
+    /// reaped.
+    pub payer: Pubkey,
+    /// Slots of inactivity after which anyone may `Reap` the counter, zero
+    /// to keep it forever.
+    pub ttl_slots: u64,
+    /// Slot of the last change to the count, metrics or commitment.
+    pub last_active_slot: u64,
+    /// Part of `count` locked until `locked_until_slot`. Locked points cannot
+    /// be spent, so other programs can treat them as a commitment.
+    pub locked_amount: u64,
+    pub locked_until_slot: u64,
+    /// Label of a `["counter", owner, name]` counter, empty otherwise.
+    pub name: Label32,
+    /// Byte strings logged with every count change so log-based routers can
+    /// filter events without decoding them. Set with `SetTopics`.
+    pub topics: Topics,
+    /// Amount `IncrementCounter` and `DecrementCounter` move by, never zero.
+    pub step: u64,
+    /// Makes the counter a read-only mirror of a counter on another cluster,
+    /// updated by `ImportAttestation`.
+    pub import_source: Option<ImportSource>,
+    /// Source-cluster slot of the last imported snapshot.
+    pub imported_slot: u64,
+    /// Highest value increments may reach, enforced per `cap_policy`.
+    pub max_value: Option<u64>,
+    pub cap_policy: CapPolicy,
+    /// Balance the program last left the account with. Anything above it was
+    /// sent in directly and can be swept with `SkimExcess`.
+    pub min_observed_lamports: u64,
+    /// Strict mode: where lamports above `min_observed_lamports` go when the
+    /// counter is closed or skimmed, instead of to the closer.
+    pub excess_destination: Option<Pubkey>,
+    /// Closes the account, refunding the authority, once a decrement brings
+    /// `count` to zero.
+    pub close_at_zero: bool,
+    /// Set by `Pause`; every mutation except `Resume` fails while it is.
+    pub is_paused: bool,
+    /// Compute units a count change must have left before it writes, emits
+    /// events or fires the trigger. Zero skips the check.
+    pub min_compute_units: u64,
+    /// Hash the counter's address was derived from by
+    /// `InitializeCounterById`.
+    pub external_id: Option<[u8; 32]>,
+    /// Snapshots taken so far, and so the index of the next one.
+    pub snapshot_count: u64,
+    /// The most recent increments, empty when history is disabled.
+    pub history: HistoryBuffer,
+    /// Keys that pass the authority gate alongside `authority`, so a shared
+    /// counter can be bumped without sharing the authority's key.
+    pub delegates: Delegates,
+    /// The most recent privileged operations, empty when the log is
+    /// disabled.
+    pub access_log: AccessLog,
+    /// Unix timestamp of initialization, zero for counters migrated from the
+    /// unversioned layout.
+    pub created_at: i64,
+    /// Unix timestamp of the last write to the account.
+    pub last_updated: i64,
+    /// Unix timestamp before which increments and decrements fail, zero for
+    /// none. Not an `Option`, so setting it never resizes the account.
+    pub unlock_timestamp: i64,
+    /// Lamports `IncrementCounter`, `IncrementBy` and `IncrementCommitted`
+    /// charge the actor, zero for none.
+    pub increment_fee: u64,
+    /// Where increment fees go: the configured recipient or the counter's
+    /// fee vault. The default key when there is no fee.
+    pub fee_recipient: Pubkey,
+    /// Frozen by the registry admin with `AdminFreeze`; see
+    /// `is_admin_frozen` for whether the freeze is still in force.
+    pub admin_frozen: bool,
+    /// Unix timestamp of the authority's `RequestUnfreeze`, zero for none.
+    pub unfreeze_requested_at: i64,
+    /// Unix timestamp the admin freeze lapses at unless the admin burns the
+    /// counter first, zero until an unfreeze is requested.
+    pub unfreeze_at: i64,
+    /// Unix timestamp from which the counter is read-only and anyone may
+    /// close it with `CloseExpired`, zero for never.
+    pub expires_at: i64,
+    /// Root of the merkle allowlist `IncrementWithProof` checks signers
+    /// against, all zeroes for none. While set, only proven signers can
+    /// change the count; see `merkle`.
+    pub merkle_root: [u8; 32],
+    /// Rejects transactions that increment the counter more than once, for
+    /// counts such as check-ins where each transaction must count exactly
+    /// once. Increments then need the instructions sysvar and have to go
+    /// through `IncrementCounter` or its variants; every other path raising
+    /// the count is rejected.
+    pub once_per_transaction: bool,
+    /// Increments the counter takes before it is finalized, zero for no
+    /// limit.
+    pub max_uses: u64,
+    /// Increments counted towards `max_uses`, which stays zero without one.
+    pub uses: u64,
+    /// Set by the increment that reaches `max_uses`. A finalized counter
+    /// rejects everything but `CloseCounter`.
+    pub is_finalized: bool,
+    /// Event-sourced mode: every increment through `IncrementCounter` and
+    /// its variants also appends a `LogRecord` to the counter's log pages,
+    /// so its history can be audited and replayed from chain. Increments
+    /// that take no log page (batches, reveals, coupons, transfers in) are
+    /// rejected; other count changes are not logged, which a replay reports.
+    pub event_log: bool,
+    /// Log page the next record goes into.
+    pub log_head: u32,
+    /// Token account the token gate's `min_token_balance` is transferred
+    /// to on every gated mutation, making the gate a charge rather than a
+    /// holding requirement. Only set together with `GATE_TOKEN_BALANCE`.
+    pub token_treasury: Option<Pubkey>,
+}
+
+/// What an increment past `max_value` does.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CapPolicy {
+    /// Fail with `CapExceeded`.
+    #[default]
+    Reject,
+    /// Stop at `max_value`.
+    Saturate,
+}
+
+/// Who may change the count, stored as the `GATE_AUTHORITY` bit of the
+/// counter's gate flags. Other enabled gates apply in either mode.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IncrementMode {
+    /// Any actor that passes the other gates.
+    Permissionless,
+    /// Only the stored authority, signing. New counters start here unless
+    /// their config supplies gates.
+    #[default]
+    Permissioned,
+}
+
+/// The layout before `version` was added. Still readable and writable in
+/// place, and upgraded by `MigrateAccount`.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct CounterAccountV0 {
+    pub discriminator: [u8; 8],
+    pub count: u64,
+    pub authority: Pubkey,
+    pub is_frozen: bool,
+    pub circuit_breaker: CircuitBreaker,
+    pub pending_commitment: [u8; 32],
+    pub value_commitment: [u8; 32],
+    pub metrics: Vec<Metric>,
+    pub trigger: Option<TriggerConfig>,
+    pub trigger_fired: bool,
+    pub mirror_mint: Option<Pubkey>,
+    pub mirror_synced_slot: u64,
+    pub observations: ObservationBuffer,
+    pub gates: GateConfig,
+    pub gate_last_slot: u64,
+    pub payer: Pubkey,
+    pub ttl_slots: u64,
+    pub last_active_slot: u64,
+}
+
+impl From<CounterAccountV0> for CounterAccount {
+    fn from(old: CounterAccountV0) -> Self {
+        Self {
+            discriminator: COUNTER_DISCRIMINATOR,
+            version: COUNTER_VERSION,
+            count: old.count,
+            authority: old.authority,
+            is_frozen: old.is_frozen,
+            circuit_breaker: old.circuit_breaker,
+            pending_commitment: old.pending_commitment,
+            value_commitment: old.value_commitment,
+            metrics: old.metrics,
+            trigger: old.trigger,
+            trigger_fired: old.trigger_fired,
+            mirror_mint: old.mirror_mint,
+            mirror_synced_slot: old.mirror_synced_slot,
+            observations: old.observations,
+            gates: old.gates,
+            gate_last_slot: old.gate_last_slot,
+            payer: old.payer,
+            ttl_slots: old.ttl_slots,
+            last_active_slot: old.last_active_slot,
+            locked_amount: 0,
+            locked_until_slot: 0,
+            name: Label32::default(),
+            topics: Topics::default(),
+            step: 1,
+            import_source: None,
+            imported_slot: 0,
+            max_value: None,
+            cap_policy: CapPolicy::Reject,
+            min_observed_lamports: 0,
+            excess_destination: None,
+            close_at_zero: false,
+            is_paused: false,
+            min_compute_units: 0,
+            external_id: None,
+            snapshot_count: 0,
+            history: HistoryBuffer::default(),
+            delegates: Delegates::default(),
+            access_log: AccessLog::default(),
+            created_at: 0,
+            last_updated: 0,
+            unlock_timestamp: 0,
+            increment_fee: 0,
+            fee_recipient: Pubkey::default(),
+            admin_frozen: false,
+            unfreeze_requested_at: 0,
+            unfreeze_at: 0,
+            expires_at: 0,
+            merkle_root: [0; 32],
+            once_per_transaction: false,
+            max_uses: 0,
+            uses: 0,
+            is_finalized: false,
+            event_log: false,
+            log_head: 0,
+            token_treasury: None,
+        }
+    }
+}
+
+/// Fails for counters using state the unversioned layout cannot hold; those
+/// must be migrated first. Bookkeeping it has no room for, such as the
+/// timestamps, is dropped.
+impl TryFrom<CounterAccount> for CounterAccountV0 {
+    type Error = ProgramError;
+
+    fn try_from(counter: CounterAccount) -> Result<Self, Self::Error> {
+        if counter.locked_amount != 0
+            || !counter.name.is_empty()
+            || !counter.topics.is_empty()
+            || counter.step != 1
+            || counter.import_source.is_some()
+            || counter.max_value.is_some()
+            || counter.excess_destination.is_some()
+            || counter.close_at_zero
+            || counter.is_paused
+            || counter.min_compute_units != 0
+            || counter.external_id.is_some()
+            || counter.snapshot_count != 0
+            || !counter.history.entries.is_empty()
+            || !counter.delegates.is_empty()
+            || !counter.access_log.entries.is_empty()
+            || counter.unlock_timestamp != 0
+            || counter.increment_fee != 0
+            || counter.admin_frozen
+            || counter.expires_at != 0
+            || counter.merkle_root != [0; 32]
+            || counter.once_per_transaction
+            || counter.max_uses != 0
+            || counter.event_log
+            || counter.token_treasury.is_some()
+        {
+            msg!("Counter must be migrated to the current layout first");
+            return Err(CounterError::MigrationRequired.into());
+        }
+
+        Ok(Self {
+            discriminator: COUNTER_DISCRIMINATOR_V0,
+            count: counter.count,
+            authority: counter.authority,
+            is_frozen: counter.is_frozen,
+            circuit_breaker: counter.circuit_breaker,
+            pending_commitment: counter.pending_commitment,
+            value_commitment: counter.value_commitment,
+            metrics: counter.metrics,
+            trigger: counter.trigger,
+            trigger_fired: counter.trigger_fired,
+            mirror_mint: counter.mirror_mint,
+            mirror_synced_slot: counter.mirror_synced_slot,
+            observations: counter.observations,
+            gates: counter.gates,
+            gate_last_slot: counter.gate_last_slot,
+            payer: counter.payer,
+            ttl_slots: counter.ttl_slots,
+            last_active_slot: counter.last_active_slot,
+        })
+    }
+}
+
+pub const MAX_TOPICS: usize = 4;
+pub const MAX_TOPIC_LEN: usize = 32;
+
+pub type Topic = BoundedVec<u8, MAX_TOPIC_LEN>;
+pub type Topics = BoundedVec<Topic, MAX_TOPICS>;
+
+pub const MAX_DELEGATES: usize = 8;
+
+pub type Delegates = BoundedVec<Pubkey, MAX_DELEGATES>;
+
+pub const MAX_MULTISIG_SIGNERS: usize = 11;
+
+pub type MultisigSigners = BoundedVec<Pubkey, MAX_MULTISIG_SIGNERS>;
+
+/// Share of a reaped counter's rent paid to the reaper, in basis points.
+pub const REAP_REWARD_BPS: u64 = 1_000;
+
+pub const MAX_METRICS: usize = 8;
+pub const MAX_METRIC_NAME_LEN: usize = Label32::MAX_LEN;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Metric {
+    pub name: Label32,
+    pub value: u64,
+    /// Upper bound for `value`, zero when unbounded.
+    pub max_value: u64,
+}
+
+impl CounterAccount {
+    /// Encoded size of a counter with every `Option` empty and every
+    /// variable-length field empty, as `InitConfig::default()` creates it.
+    pub const LEN: usize = 8 // discriminator
+        + 1 // version
+        + 8 // count
+        + 32 // authority
+        + 1 // is_frozen
+        + 32 // circuit_breaker
+        + 32 // pending_commitment
+        + 32 // value_commitment
+        + 4 // metrics
+        + 1 // trigger
+        + 1 // trigger_fired
+        + 1 // mirror_mint
+        + 8 // mirror_synced_slot
+        + 8 // observations: index, len and no samples
+        + 1 + 4 + 32 + 8 + 32 + 8 // gates, with an empty allowlist
+        + 8 // gate_last_slot
+        + 32 // payer
+        + 8 // ttl_slots
+        + 8 // last_active_slot
+        + 8 // locked_amount
+        + 8 // locked_until_slot
+        + 4 // name
+        + 4 // topics
+        + 8 // step
+        + 1 // import_source
+        + 8 // imported_slot
+        + 1 // max_value
+        + 1 // cap_policy
+        + 8 // min_observed_lamports
+        + 1 // excess_destination
+        + 1 // close_at_zero
+        + 1 // is_paused
+        + 8 // min_compute_units
+        + 1 // external_id
+        + 8 // snapshot_count
+        + 8 // history: index, len and no entries
+        + 4 // delegates
+        + 8 // access_log: index, len and no entries
+        + 8 // created_at
+        + 8 // last_updated
+        + 8 // unlock_timestamp
+        + 8 // increment_fee
+        + 32 // fee_recipient
+        + 1 // admin_frozen
+        + 8 // unfreeze_requested_at
+        + 8 // unfreeze_at
+        + 8 // expires_at
+        + 32 // merkle_root
+        + 1 // once_per_transaction
+        + 8 // max_uses
+        + 8 // uses
+        + 1 // is_finalized
+        + 1 // event_log
+        + 4 // log_head
+        + 1; // token_treasury
+
+    /// Account size of the counter `InitializeCounter` creates from
+    /// `config`. Named counters need their name's length on top, and
+    /// counters created by id 32 bytes for `external_id`.
+    pub fn space_for(config: &InitConfig) -> usize {
+        let metrics: usize = config
+            .metrics
+            .iter()
+            .map(|metric| 4 + metric.name.len() + 16)
+            .sum();
+        let trigger = config.trigger.as_ref().map_or(0, |trigger| {
+            8 + 32 + 4 + trigger.accounts.len() * 34 + 4 + trigger.data.len()
+        });
+        let allowlist = config
+            .gates
+            .as_ref()
+            .map_or(0, |gates| gates.allowlist.len() * 32);
+
+        Self::LEN
+            + metrics
+            + trigger
+            + config.mirror_mint.map_or(0, |_| 32)
+            + config.observation_slots as usize * 24
+            + allowlist
+            + config.import_source.map_or(0, |_| 72)
+            + config.max_value.map_or(0, |_| 8)
+            + config.excess_destination.map_or(0, |_| 32)
+            + config.history_len as usize * 48
+            + config.access_log_len as usize * 41
+            + config.token_treasury.map_or(0, |_| 32)
+    }
+
+    /// Decodes counter account data in the current or the unversioned
+    /// layout, exactly as the program loads it. Needs no runtime, so RPC
+    /// clients and wasm front-ends can decode fetched accounts with it.
+    pub fn from_account_data(data: &[u8]) -> Result<Self, ProgramError> {
+        backend_for(data)?.load(data)
+    }
+
+    /// Encodes the counter in the current layout, as the program stores it.
+    pub fn to_account_data(&self) -> Vec<u8> {
+        borsh::to_vec(self).expect("serializing into a Vec cannot fail")
+    }
+
+    pub fn increment_mode(&self) -> IncrementMode {
+        if self.gates.flags & GATE_AUTHORITY != 0 {
+            IncrementMode::Permissioned
+        } else {
+            IncrementMode::Permissionless
+        }
+    }
+
+    pub fn set_increment_mode(&mut self, mode: IncrementMode) {
+        match mode {
+            IncrementMode::Permissioned => self.gates.flags |= GATE_AUTHORITY,
+            IncrementMode::Permissionless => self.gates.flags &= !GATE_AUTHORITY,
+        }
+    }
+
+    /// The spendable part of `count`.
+    pub fn liquid(&self) -> u64 {
+        self.count.saturating_sub(self.locked_amount)
+    }
+
+    /// The count after adding `amount`, held to `max_value` by the cap
+    /// policy.
+    pub fn capped_add(&self, amount: u64) -> Result<u64, ProgramError> {
+        let new_count = self.count.checked_add(amount);
+
+        let Some(max_value) = self.max_value else {
+            return new_count.ok_or_else(|| {
+                msg!("Counter cannot be incremented past u64::MAX");
+                CounterError::Overflow.into()
+            });
+        };
+
+        match new_count {
+            Some(new_count) if new_count <= max_value => Ok(new_count),
+            _ if self.cap_policy == CapPolicy::Saturate => Ok(max_value.max(self.count)),
+            _ => {
+                msg!("Counter is capped at {}", max_value);
+                Err(CounterError::CapExceeded.into())
+            }
+        }
+    }
+
+    /// Whether an admin freeze is in force at `now`: one lapses once the
+    /// grace period after an unfreeze request has passed.
+    pub fn is_admin_frozen(&self, now: i64) -> bool {
+        self.admin_frozen && (self.unfreeze_at == 0 || now < self.unfreeze_at)
+    }
+
+    /// Whether the counter has reached its `expires_at` at `now`.
+    pub fn is_past_expiry(&self, now: i64) -> bool {
+        self.expires_at != 0 && now >= self.expires_at
+    }
+
+    pub fn is_expired(&self, slot: u64) -> bool {
+        self.ttl_slots != 0 && slot >= self.last_active_slot.saturating_add(self.ttl_slots)
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.value_commitment != [0; 32]
+    }
+
+    /// Slots elapsed since the mirrored supply was last synced.
+    pub fn mirror_age(&self, current_slot: u64) -> u64 {
+        current_slot.saturating_sub(self.mirror_synced_slot)
+    }
+
+    pub fn metric_index(&self, name: &str) -> Option<usize> {
+        self.metrics.iter().position(|metric| metric.name == name)
+    }
+
+    /// Marks the trigger as fired and returns it if moving from `old_count`
+    /// to the current count crossed its threshold for the first time.
+    pub fn take_crossed_trigger(&mut self, old_count: u64) -> Option<TriggerConfig> {
+        let trigger = self.trigger.as_ref()?;
+
+        if self.trigger_fired || old_count >= trigger.threshold || self.count < trigger.threshold {
+            return None;
+        }
+
+        self.trigger_fired = true;
+        Some(trigger.clone())
+    }
+}
+
+/// Rate-of-change guard. A `max_delta` of zero leaves the breaker disabled.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    pub max_delta: u64,
+    pub window_slots: u64,
+    pub window_start_slot: u64,
+    pub window_start_value: u64,
+}
+
+impl CircuitBreaker {
+    pub fn is_enabled(&self) -> bool {
+        self.max_delta > 0
+    }
+
+    /// Moves the window forward if it has elapsed and reports whether changing
+    /// the counter from `current` to `new_value` at `slot` exceeds `max_delta`.
+    pub fn trips(&mut self, slot: u64, current: u64, new_value: u64) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        if slot >= self.window_start_slot.saturating_add(self.window_slots) {
+            self.window_start_slot = slot;
+            self.window_start_value = current;
+        }
+
+        new_value.abs_diff(self.window_start_value) > self.max_delta
+    }
+}
+
+pub const MAX_COMPOSITE_SOURCES: usize = 8;
+
+/// Weights are fixed point with this many units representing 1.0.
+pub const WEIGHT_SCALE: u64 = 10_000;
+
+/// Counter whose value is a weighted sum of other counters, recomputed by
+/// `RefreshComposite`.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct CompositeCounter {
+    /// Always `COMPOSITE_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub sources: Vec<WeightedSource>,
+    pub value: u64,
+    /// Slot of the last refresh, zero until the first one.
+    pub refreshed_slot: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightedSource {
+    pub counter: Pubkey,
+    pub weight: u64,
+}
+
+/// Names are used as a PDA seed, which caps them at 32 bytes.
+pub const MAX_COUNTER_NAME_LEN: usize = Label32::MAX_LEN;
+
+pub const MAX_TRIGGER_ACCOUNTS: usize = 8;
+pub const MAX_TRIGGER_DATA_LEN: usize = 64;
+
+/// Instruction template invoked when a counter crosses `threshold`. The
+/// counter's trigger authority PDA may appear in `accounts` as a signer.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TriggerConfig {
+    pub threshold: u64,
+    pub program_id: Pubkey,
+    pub accounts: Vec<TriggerAccount>,
+    pub data: Vec<u8>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// A program's grant to read a counter through `GetCount`, at
+/// `["view", counter, program]`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ViewKey {
+    /// Always `VIEW_KEY_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counter: Pubkey,
+    pub program: Pubkey,
+    /// Reads served under this grant; stamped on each attestation.
+    pub nonce: u64,
+}
+
+/// Return data of `GetCount`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountAttestation {
+    pub counter: Pubkey,
     pub count: u64,
-}
\ No newline at end of file
+    pub slot: u64,
+    pub nonce: u64,
+}
+
+/// What an off-chain attester signs about a counter on another cluster:
+/// its `CountAttestation` plus the wall-clock time it was taken, since slots
+/// on different clusters cannot be compared.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttestationSnapshot {
+    pub attestation: CountAttestation,
+    pub unix_timestamp: i64,
+}
+
+/// Where a cross-cluster mirror takes its value from.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSource {
+    /// Counter address on the source cluster.
+    pub counter: Pubkey,
+    /// Key whose Ed25519 signature snapshots must carry.
+    pub attester: Pubkey,
+    /// Oldest snapshot accepted, in seconds.
+    pub max_age_secs: i64,
+}
+
+pub const MAX_OBSERVATIONS: usize = 64;
+
+/// Running integral of the counter value over slots at a point in time.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+    pub slot: u64,
+    pub cumulative_value: u128,
+}
+
+impl Observation {
+    /// Projects this observation forward to `slot`, assuming `count` has been
+    /// held since it was written.
+    pub fn extrapolate(&self, count: u64, slot: u64) -> Observation {
+        let elapsed = slot.saturating_sub(self.slot);
+
+        Observation {
+            slot: self.slot.max(slot),
+            cumulative_value: self.cumulative_value + count as u128 * elapsed as u128,
+        }
+    }
+
+    /// Time-weighted average value between two observations, `None` if no
+    /// slots elapsed between them.
+    pub fn time_weighted_average(older: &Observation, newer: &Observation) -> Option<u64> {
+        let elapsed = newer
+            .slot
+            .checked_sub(older.slot)
+            .filter(|slots| *slots > 0)?;
+        let delta = newer.cumulative_value.checked_sub(older.cumulative_value)?;
+
+        u64::try_from(delta / elapsed as u128).ok()
+    }
+}
+
+/// Uniswap-style ring buffer of observations written when the count changes.
+/// `samples` is allocated at full capacity on initialization and stays empty
+/// (zero length) when observations are disabled.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ObservationBuffer {
+    /// Index of the most recent sample.
+    pub index: u16,
+    /// Number of samples written so far, capped at the capacity.
+    pub len: u16,
+    pub samples: Vec<Observation>,
+}
+
+impl ObservationBuffer {
+    pub fn new(capacity: u16, slot: u64) -> Self {
+        let mut samples = vec![Observation::default(); capacity as usize];
+        let len = if let Some(first) = samples.first_mut() {
+            first.slot = slot;
+            1
+        } else {
+            0
+        };
+
+        Self {
+            index: 0,
+            len,
+            samples,
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Observation> {
+        if self.len == 0 {
+            return None;
+        }
+        self.samples.get(self.index as usize)
+    }
+
+    /// Samples from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &Observation> {
+        let capacity = self.samples.len();
+        let start = (self.index as usize + capacity + 1 - self.len as usize) % capacity.max(1);
+
+        (0..self.len as usize).map(move |offset| &self.samples[(start + offset) % capacity])
+    }
+
+    /// Writes a sample at `slot` accounting for `count` having been held since
+    /// the latest one. At most one sample is written per slot.
+    pub fn record(&mut self, slot: u64, count: u64) {
+        let Some(latest) = self.latest().copied() else {
+            return;
+        };
+
+        if slot <= latest.slot {
+            return;
+        }
+
+        let capacity = self.samples.len();
+        self.index = ((self.index as usize + 1) % capacity) as u16;
+        self.samples[self.index as usize] = latest.extrapolate(count, slot);
+        self.len = (self.len as usize + 1).min(capacity) as u16;
+    }
+}
+
+pub const MAX_HISTORY: usize = 32;
+
+/// One applied increment, as recorded in a counter's history.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub slot: u64,
+    pub delta: u64,
+    /// Account in the actor position of the increment.
+    pub actor: Pubkey,
+}
+
+/// Ring buffer of the last increments, so "who bumped this and when" can be
+/// read from the account instead of scraped from transaction history.
+pub type HistoryBuffer = RingBuffer<HistoryEntry>;
+
+pub const MAX_ACCESS_LOG: usize = 16;
+
+/// One privileged operation, as recorded in a counter's access log.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AccessLogEntry {
+    pub slot: u64,
+    /// Account in the authority position of the instruction.
+    pub actor: Pubkey,
+    /// Tag of the instruction, see `dispatch::meta` for its name.
+    pub instruction: u8,
+}
+
+/// The last privileged operations on a counter, so disputes over who reset
+/// or froze it can be settled from its current state.
+pub type AccessLog = RingBuffer<AccessLogEntry>;
+
+/// Fixed-capacity log that overwrites its oldest entry once full. `entries`
+/// is allocated at full capacity on initialization, which sizes the account
+/// for it, and stays empty when the log is disabled.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct RingBuffer<T> {
+    /// Index of the most recent entry.
+    pub index: u16,
+    /// Number of entries written so far, capped at the capacity.
+    pub len: u16,
+    pub entries: Vec<T>,
+}
+
+impl<T: Default + Clone> RingBuffer<T> {
+    pub fn new(capacity: u16) -> Self {
+        Self {
+            index: 0,
+            len: 0,
+            entries: vec![T::default(); capacity as usize],
+        }
+    }
+
+    /// Entries from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let capacity = self.entries.len();
+        let start = (self.index as usize + capacity + 1 - self.len as usize) % capacity.max(1);
+
+        (0..self.len as usize).map(move |offset| &self.entries[(start + offset) % capacity])
+    }
+
+    /// Does nothing when the buffer is disabled.
+    pub fn record(&mut self, entry: T) {
+        let capacity = self.entries.len();
+        if capacity == 0 {
+            return;
+        }
+
+        if self.len > 0 {
+            self.index = ((self.index as usize + 1) % capacity) as u16;
+        }
+        self.entries[self.index as usize] = entry;
+        self.len = (self.len as usize + 1).min(capacity) as u16;
+    }
+}
+
+pub const MAX_ALLOWLIST: usize = 16;
+
+/// Which gates guard count mutations (`gate::GATE_*` bits) and their
+/// parameters. Parameters of disabled gates are ignored.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct GateConfig {
+    pub flags: u8,
+    pub allowlist: Vec<Pubkey>,
+    pub token_mint: Pubkey,
+    pub min_token_balance: u64,
+    pub cosigner: Pubkey,
+    /// Minimum slots between gated mutations, enforced by `GATE_COOLDOWN`.
+    pub cooldown_slots: u64,
+}
+
+pub const MAX_COUPON_CODE_LEN: usize = 64;
+
+/// Deep enough for an allowlist of any `u32`-indexed size.
+pub const MAX_MERKLE_PROOF_LEN: usize = 32;
+
+pub type MerkleProof = BoundedVec<[u8; 32], MAX_MERKLE_PROOF_LEN>;
+
+/// Merkle allowlist leaves one `ClaimBitmap` tracks.
+pub const CLAIMS_PER_BITMAP: u32 = 2048;
+
+pub type CouponCode = BoundedVec<u8, MAX_COUPON_CODE_LEN>;
+
+/// A single-use increment at `["coupon", counter, coupon_code_hash(code)]`,
+/// closed by the `RedeemCoupon` that spends it.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Coupon {
+    /// Always `COUPON_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counter: Pubkey,
+    pub amount: u64,
+    /// Paid the rent and gets it back on redemption.
+    pub payer: Pubkey,
+}
+
+/// Names are used as a PDA seed, which caps them at 32 bytes.
+pub const MAX_TEMPLATE_NAME_LEN: usize = Label32::MAX_LEN;
+
+/// A counter configuration bundle at `["template", admin, name]`, written by
+/// `SetTemplate` and applied by `InitializeFromTemplate`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    /// Always `TEMPLATE_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    /// Signs for changes and pays the rent.
+    pub admin: Pubkey,
+    pub name: Label32,
+    pub config: InitConfig,
+}
+
+/// `Registry::unfreeze_grace_secs` for new registries: three days.
+pub const DEFAULT_UNFREEZE_GRACE_SECS: i64 = 3 * 24 * 60 * 60;
+
+/// Program-wide totals at `["registry"]`. Only operations that were passed
+/// the registry are counted.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Registry {
+    /// Always `REGISTRY_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counters_created: u64,
+    pub counters_closed: u64,
+    /// Sum of every increment applied, including initial deltas.
+    pub total_incremented: u128,
+    /// Whoever paid for the registry; only they can change its settings.
+    pub admin: Pubkey,
+    /// Seconds after a `RequestUnfreeze` before an admin freeze lapses, the
+    /// admin's window to burn the counter instead.
+    pub unfreeze_grace_secs: i64,
+}
+
+impl Registry {
+    pub fn record_created(&mut self) {
+        self.counters_created = self.counters_created.saturating_add(1);
+    }
+
+    pub fn record_incremented(&mut self, amount: u64) {
+        self.total_incremented = self.total_incremented.saturating_add(amount as u128);
+    }
+
+    pub fn record_closed(&mut self) {
+        self.counters_closed = self.counters_closed.saturating_add(1);
+    }
+}
+
+/// Per-owner totals at `["owner_stats", owner]`, created the first time the
+/// owner creates a counter under the global config's quota.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OwnerStats {
+    /// Always `OWNER_STATS_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub owner: Pubkey,
+    /// Counters created while a quota was set. Closing a counter does not
+    /// give one back, so closing and recreating cannot get around the cap.
+    pub counters_created: u32,
+}
+
+pub const MAX_INDEXED_COUNTERS: usize = 128;
+
+pub type IndexedCounters = BoundedVec<Pubkey, MAX_INDEXED_COUNTERS>;
+
+/// The counters an owner has created, at `["index", owner]`. Sized to its
+/// list: creating a counter grows it by one address at the owner's expense,
+/// closing one shrinks it again.
+///
+/// Best effort: the program only sees the index when an instruction passes
+/// it, so a create without it leaves the counter out and a close without it
+/// leaves a stale address behind. Clients should check each listed account
+/// still exists.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OwnerIndex {
+    /// Always `OWNER_INDEX_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub owner: Pubkey,
+    pub counters: IndexedCounters,
+}
+
+/// A checkpoint of a counter's value at `["snapshot", counter, index]`, with
+/// `index` encoded little-endian. Written once by `Snapshot` and never
+/// modified.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Always `SNAPSHOT_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counter: Pubkey,
+    pub index: u64,
+    pub count: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+/// An M-of-N authority. A counter whose authority is a multisig's address
+/// accepts resets, closes and authority transfers once `threshold` of
+/// `signers` have signed the instruction.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Multisig {
+    /// Always `MULTISIG_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub threshold: u8,
+    /// Distinct keys, at least `threshold` of them.
+    pub signers: MultisigSigners,
+}
+
+/// A counter that may go negative, for net balances. Its own account kind:
+/// none of `CounterAccount`'s gates, caps or history apply, only the
+/// authority may change it.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedCounter {
+    /// Always `SIGNED_COUNTER_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub authority: Pubkey,
+    pub count: i64,
+}
+
+impl SignedCounter {
+    pub fn checked_add(&self, amount: u64) -> Result<i64, ProgramError> {
+        self.count.checked_add_unsigned(amount).ok_or_else(|| {
+            msg!("Signed counter cannot be incremented past i64::MAX");
+            CounterError::SignedOverflow.into()
+        })
+    }
+
+    pub fn checked_sub(&self, amount: u64) -> Result<i64, ProgramError> {
+        self.count.checked_sub_unsigned(amount).ok_or_else(|| {
+            msg!("Signed counter cannot be decremented past i64::MIN");
+            CounterError::SignedUnderflow.into()
+        })
+    }
+}
+
+/// Slots a `SlotCounter` may be created with.
+pub const MAX_COUNTER_SLOTS: usize = 32;
+
+/// Several independent tallies in one account, for related counts that do
+/// not each need their own rent. The number of slots is fixed at creation.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlotCounter {
+    /// Always `SLOT_COUNTER_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub authority: Pubkey,
+    pub slots: Vec<u64>,
+}
+
+impl SlotCounter {
+    pub fn slot(&self, index: u8) -> Result<u64, ProgramError> {
+        self.check_index(index)?;
+        Ok(self.slots[index as usize])
+    }
+
+    pub fn slot_mut(&mut self, index: u8) -> Result<&mut u64, ProgramError> {
+        self.check_index(index)?;
+        Ok(&mut self.slots[index as usize])
+    }
+
+    fn check_index(&self, index: u8) -> Result<(), ProgramError> {
+        if index as usize >= self.slots.len() {
+            msg!(
+                "Slot {} is out of range, the counter has {}",
+                index,
+                self.slots.len()
+            );
+            return Err(CounterError::SlotOutOfRange.into());
+        }
+        Ok(())
+    }
+}
+
+/// Which leaves of a counter's merkle allowlist have incremented it, for
+/// leaves `chunk * CLAIMS_PER_BITMAP` onwards, at
+/// `["claims", counter, chunk]` with `chunk` encoded little-endian. Created
+/// by the first claim in its range.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClaimBitmap {
+    /// Always `CLAIM_BITMAP_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counter: Pubkey,
+    pub chunk: u32,
+    pub bits: [u8; CLAIMS_PER_BITMAP as usize / 8],
+}
+
+impl ClaimBitmap {
+    pub fn new(counter: Pubkey, chunk: u32) -> Self {
+        Self {
+            discriminator: CLAIM_BITMAP_DISCRIMINATOR,
+            counter,
+            chunk,
+            bits: [0; CLAIMS_PER_BITMAP as usize / 8],
+        }
+    }
+
+    /// Whether leaf `index` has claimed. Indexes outside the bitmap's range
+    /// never have.
+    pub fn is_claimed(&self, index: u32) -> bool {
+        index / CLAIMS_PER_BITMAP == self.chunk && {
+            let bit = index % CLAIMS_PER_BITMAP;
+            self.bits[bit as usize / 8] & 1 << (bit % 8) != 0
+        }
+    }
+
+    /// Marks leaf `index` as claimed. Returns `false`, changing nothing, if it
+    /// already was or lies outside the bitmap's range.
+    pub fn claim(&mut self, index: u32) -> bool {
+        if index / CLAIMS_PER_BITMAP != self.chunk || self.is_claimed(index) {
+            return false;
+        }
+        let bit = index % CLAIMS_PER_BITMAP;
+        self.bits[bit as usize / 8] |= 1 << (bit % 8);
+        true
+    }
+}
+
+/// One user's share of a counter's increments, at `["user", counter, user]`.
+/// Created by the user's first increment that passes it; increments that do
+/// not pass it still count towards the counter but not the record.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserRecord {
+    /// Always `USER_RECORD_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counter: Pubkey,
+    pub user: Pubkey,
+    /// Sum of the increments recorded, as applied after any cap.
+    pub count: u64,
+    pub last_updated: i64,
+}
+
+impl UserRecord {
+    pub fn new(counter: Pubkey, user: Pubkey) -> Self {
+        Self {
+            discriminator: USER_RECORD_DISCRIMINATOR,
+            counter,
+            user,
+            count: 0,
+            last_updated: 0,
+        }
+    }
+}
+
+/// Program-wide parameters the upgrade authority tunes without redeploying.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct GlobalParams {
+    /// `max_value` for new counters whose `InitConfig` sets none, 0 for none.
+    pub default_max_value: u64,
+    /// Lamports each increment pays `fee_recipient`, on top of any counter
+    /// fee. 0 for none.
+    pub protocol_fee: u64,
+    pub fee_recipient: Pubkey,
+    /// Rejects every instruction but `SetGlobalConfig` while set.
+    pub paused: bool,
+    /// Counters each owner may create, 0 for no limit. Tracked in the
+    /// owner's `OwnerStats`.
+    pub max_counters_per_owner: u32,
+}
+
+/// The program's global config at `["config"]`. Handlers consult it only
+/// when it is passed among their accounts, like the registry.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GlobalConfig {
+    /// Always `GLOBAL_CONFIG_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub params: GlobalParams,
+}
+
+/// Records one `LogPage` holds.
+pub const LOG_PAGE_RECORDS: usize = 32;
+
+/// One increment of an event-log counter.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LogRecord {
+    pub slot: u64,
+    /// Account in the actor position of the increment.
+    pub actor: Pubkey,
+    /// Amount added, as applied after any cap.
+    pub amount: u64,
+    /// The count after the increment, so a replay can tell when something
+    /// other than a logged increment changed it.
+    pub count: u64,
+}
+
+/// Page `page` of an event-log counter's increments, at
+/// `["log", counter, page]` with `page` encoded little-endian. Pages fill in
+/// order and are never rewritten; the counter's `log_head` is the one being
+/// filled. Created full size by the first record that lands in it.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LogPage {
+    /// Always `LOG_PAGE_DISCRIMINATOR`.
+    pub discriminator: [u8; 8],
+    pub counter: Pubkey,
+    pub page: u32,
+    /// Records written, the rest of `records` is zeroed.
+    pub len: u16,
+    pub records: [LogRecord; LOG_PAGE_RECORDS],
+}
+
+impl LogPage {
+    pub fn new(counter: Pubkey, page: u32) -> Self {
+        Self {
+            discriminator: LOG_PAGE_DISCRIMINATOR,
+            counter,
+            page,
+            len: 0,
+            records: [LogRecord::default(); LOG_PAGE_RECORDS],
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len as usize >= LOG_PAGE_RECORDS
+    }
+
+    /// The records written so far, oldest first.
+    pub fn records(&self) -> &[LogRecord] {
+        &self.records[..(self.len as usize).min(LOG_PAGE_RECORDS)]
+    }
+
+    /// Appends `record`, which the caller has checked fits.
+    pub fn push(&mut self, record: LogRecord) {
+        self.records[self.len as usize] = record;
+        self.len += 1;
+    }
+}
@@ -0,0 +1,163 @@
+//! `solana-program-test` harness for crates building on the counter program.
+//! Enable the `test-utils` feature in `dev-dependencies`; the program runs
+//! natively in the test bank, so no `.so` build is needed.
+//!
+//! ```ignore
+//! let mut ctx = setup_program().await;
+//! let counter = create_counter(&mut ctx, 5).await.unwrap();
+//! assert_eq!(read_counter(&mut ctx, &counter).await.count, 5);
+//! ```
+//!
+//! Add other programs or accounts with `program_test` and
+//! `TestContext::start` instead of `setup_program`.
+
+use borsh::BorshDeserialize;
+use solana_program_test::{
+    BanksClientError, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext, processor,
+};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use crate::client::initialize_counter_with_config_ix;
+use crate::instruction::InitConfig;
+use crate::processor::process_instruction;
+use crate::state::CounterAccount;
+
+/// A started test bank with the counter program loaded at `program_id`.
+pub struct TestContext {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+}
+
+/// A `ProgramTest` with the counter program registered under `program_id`.
+pub fn program_test(program_id: Pubkey) -> ProgramTest {
+    ProgramTest::new(
+        "counter_program",
+        program_id,
+        processor!(process_instruction),
+    )
+}
+
+impl TestContext {
+    pub async fn start(program_test: ProgramTest, program_id: Pubkey) -> Self {
+        Self {
+            context: program_test.start_with_context().await,
+            program_id,
+        }
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+}
+
+/// Starts a bank with only the counter program, under a fresh program id.
+pub async fn setup_program() -> TestContext {
+    let program_id = Pubkey::new_unique();
+    TestContext::start(program_test(program_id), program_id).await
+}
+
+/// Sends `instructions` paid and signed by the context's payer, plus
+/// `signers`, then moves to a fresh blockhash so the same instructions can
+/// be sent again.
+pub async fn send(
+    ctx: &mut TestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let transaction = sign(ctx, instructions, signers);
+    let result = ctx
+        .context
+        .banks_client
+        .process_transaction(transaction)
+        .await;
+    refresh_blockhash(ctx).await?;
+    result
+}
+
+/// `send`, returning the compute units the transaction consumed. Programs
+/// are only fully metered when they run as SBF (`cargo test-sbf`); natively
+/// run ones are charged for their syscalls alone.
+pub async fn send_metered(
+    ctx: &mut TestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<u64, BanksClientError> {
+    let transaction = sign(ctx, instructions, signers);
+    let outcome = ctx
+        .context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await;
+    refresh_blockhash(ctx).await?;
+
+    let outcome = outcome?;
+    outcome.result?;
+    Ok(outcome
+        .metadata
+        .map_or(0, |metadata| metadata.compute_units_consumed))
+}
+
+fn sign(ctx: &TestContext, instructions: &[Instruction], signers: &[&Keypair]) -> Transaction {
+    let context = &ctx.context;
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+
+    Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    )
+}
+
+async fn refresh_blockhash(ctx: &mut TestContext) -> Result<(), BanksClientError> {
+    let context = &mut ctx.context;
+    context.last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await?;
+    Ok(())
+}
+
+/// Creates a counter at `initial` owned by the payer and returns its address.
+pub async fn create_counter(
+    ctx: &mut TestContext,
+    initial: u64,
+) -> Result<Pubkey, BanksClientError> {
+    create_counter_with_config(ctx, initial, InitConfig::default()).await
+}
+
+pub async fn create_counter_with_config(
+    ctx: &mut TestContext,
+    initial: u64,
+    config: InitConfig,
+) -> Result<Pubkey, BanksClientError> {
+    let counter = Keypair::new();
+    let instruction = initialize_counter_with_config_ix(
+        &ctx.program_id,
+        &counter.pubkey(),
+        &ctx.payer(),
+        initial,
+        config,
+    );
+    send(ctx, &[instruction], &[&counter]).await?;
+    Ok(counter.pubkey())
+}
+
+/// Panics when `counter` does not exist or is not a counter account, like a
+/// failed assertion would.
+pub async fn read_counter(ctx: &mut TestContext, counter: &Pubkey) -> CounterAccount {
+    let account = ctx
+        .context
+        .banks_client
+        .get_account(*counter)
+        .await
+        .expect("fetching the counter")
+        .unwrap_or_else(|| panic!("counter {} does not exist", counter));
+    CounterAccount::try_from_slice(&account.data).expect("decoding the counter")
+}
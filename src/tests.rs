@@ -1,12 +1,187 @@
 #[cfg(test)]
 mod test {
-    use std::vec;
-    use crate::{processor::process_instruction, state::CounterAccount};
-    use borsh::BorshDeserialize;
+    use crate::{
+        addresses::{
+            COUNTER_DISCRIMINATOR, VIEWER_SEED, derive_claim_bitmap_address,
+            derive_counter_address, derive_coupon_address, derive_external_counter_address,
+            derive_global_config_address, derive_log_page_address, derive_program_data_address,
+            derive_registry_address, derive_snapshot_address, derive_template_address,
+            derive_trigger_authority, derive_user_record_address, derive_view_key_address,
+            derive_viewer_authority,
+        },
+        bounded::Label32,
+        dispatch::{INSTRUCTIONS, meta},
+        ed25519::verify_instruction,
+        error::CounterError,
+        events::{CounterEvent, CounterIncremented, LoggedEvent, decode_fields, event_fields},
+        gate::{GATE_ALLOWLIST, GATE_COOLDOWN, GATE_TOKEN_BALANCE, cooldown_ends_at},
+        idl::idl_json,
+        instruction::{
+            CircuitBreakerConfig, CounterInstruction, InitConfig, MetricConfig, MetricRef,
+            commitment_hash, coupon_code_hash, external_id_hash,
+        },
+        interface::{self, CounterInterfaceInstruction, INCREMENT_DISCRIMINATOR},
+        merkle::MerkleTree,
+        processor::process_instruction,
+        spl::{MINT_LEN, TOKEN_ACCOUNT_LEN, TOKEN_PROGRAM_ID},
+        state::{
+            AccessLogEntry, AttestationSnapshot, COUNTER_VERSION, CapPolicy, CompositeCounter,
+            CountAttestation, CounterAccount, CounterAccountV0, CouponCode, GateConfig,
+            GlobalParams, HistoryEntry, ImportSource, IncrementMode, LOG_PAGE_RECORDS, LogPage,
+            MAX_DELEGATES, MAX_HISTORY, MerkleProof, MultisigSigners, Observation, Registry,
+            Snapshot, Topic, Topics, TriggerAccount, TriggerConfig, UserRecord, WeightedSource,
+        },
+    };
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use proptest::prelude::any;
     use solana_program_test::*;
     use solana_sdk::{
-        instruction::{AccountMeta, Instruction}, pubkey::Pubkey, signature::{Keypair, Signer}, system_program, transaction::Transaction
+        account::Account,
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        hash::Hash,
+        instruction::InstructionError,
+        instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_program,
+        transaction::Transaction,
+        transaction::TransactionError,
     };
+    use std::vec;
+
+    struct TestEnv {
+        context: ProgramTestContext,
+        banks_client: BanksClient,
+        payer: Keypair,
+        blockhash: Hash,
+        program_id: Pubkey,
+    }
+
+    impl TestEnv {
+        async fn start() -> Self {
+            Self::start_with(|_| {}).await
+        }
+
+        // lets a test register extra programs or accounts before the bank starts
+        async fn start_with(setup: impl FnOnce(&mut ProgramTest)) -> Self {
+            let program_id = Pubkey::new_unique();
+
+            let mut program_test = ProgramTest::new(
+                "counter_program",
+                program_id,
+                processor!(process_instruction),
+            );
+            setup(&mut program_test);
+
+            let context = program_test.start_with_context().await;
+            let banks_client = context.banks_client.clone();
+            let payer = context.payer.insecure_clone();
+            let blockhash = context.last_blockhash;
+
+            Self {
+                context,
+                banks_client,
+                payer,
+                blockhash,
+                program_id,
+            }
+        }
+
+        // moves the bank forward so slot-dependent logic sees time pass
+        async fn warp_slots(&mut self, slots: u64) {
+            let slot = self.banks_client.get_root_slot().await.unwrap();
+            self.context.warp_to_slot(slot + slots).unwrap();
+            self.blockhash = self
+                .banks_client
+                .get_new_latest_blockhash(&self.blockhash)
+                .await
+                .unwrap();
+        }
+
+        // sends a transaction paid by the payer and moves to a fresh blockhash so
+        // identical instructions can be resent
+        async fn send(
+            &mut self,
+            instructions: &[Instruction],
+            signers: &[&Keypair],
+        ) -> Result<(), BanksClientError> {
+            let mut all_signers = vec![&self.payer];
+            all_signers.extend_from_slice(signers);
+
+            let mut transaction =
+                Transaction::new_with_payer(instructions, Some(&self.payer.pubkey()));
+            transaction.sign(&all_signers, self.blockhash);
+
+            let result = self.banks_client.process_transaction(transaction).await;
+            self.blockhash = self
+                .banks_client
+                .get_new_latest_blockhash(&self.blockhash)
+                .await
+                .unwrap();
+            result
+        }
+
+        async fn initialize(&mut self, initial_value: u64, config: &InitConfig) -> Keypair {
+            let counter_keypair = Keypair::new();
+
+            let mut data = vec![0];
+            data.extend_from_slice(&initial_value.to_le_bytes());
+            config.serialize(&mut data).unwrap();
+
+            let instruction = Instruction::new_with_bytes(
+                self.program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter_keypair.pubkey(), true),
+                    AccountMeta::new(self.payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(self.global_config(), false),
+                ],
+            );
+
+            self.send(&[instruction], &[&counter_keypair])
+                .await
+                .unwrap();
+            counter_keypair
+        }
+
+        // every counter creation passes the global config PDA
+        fn global_config(&self) -> Pubkey {
+            derive_global_config_address(&self.program_id).0
+        }
+
+        // counter instruction signed by the payer, the default authority
+        fn counter_ix(&self, data: &[u8], counter: &Keypair) -> Instruction {
+            self.counter_ix_as(data, counter, &self.payer.pubkey())
+        }
+
+        fn counter_ix_as(&self, data: &[u8], counter: &Keypair, authority: &Pubkey) -> Instruction {
+            Instruction::new_with_bytes(
+                self.program_id,
+                data,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(*authority, true),
+                ],
+            )
+        }
+
+        async fn read_counter(&mut self, counter: &Keypair) -> CounterAccount {
+            self.read_counter_at(counter.pubkey()).await
+        }
+
+        async fn read_counter_at(&mut self, counter: Pubkey) -> CounterAccount {
+            let account = self
+                .banks_client
+                .get_account(counter)
+                .await
+                .unwrap()
+                .expect("counter account missing");
+            CounterAccount::try_from_slice(&account.data).unwrap()
+        }
+    }
 
     #[tokio::test]
     async fn test_counter_program() {
@@ -38,6 +213,7 @@ mod test {
                 AccountMeta::new(counter_keypair.pubkey(), true),
                 AccountMeta::new(payer.pubkey(), true),
                 AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(derive_global_config_address(&program_id).0, false),
             ],
         );
 
@@ -68,13 +244,16 @@ mod test {
         let increment_instruction = Instruction::new_with_bytes(
             program_id,
             &[1],
-            vec![AccountMeta::new(counter_keypair.pubkey(), true)],
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
         );
 
         let mut transaction =
             Transaction::new_with_payer(&[increment_instruction], Some(&payer.pubkey()));
 
-        transaction.sign(&[&payer, &counter_keypair], recent_blockhash);
+        transaction.sign(&[&payer], recent_blockhash);
         banks_client.process_transaction(transaction).await.unwrap();
 
         // check account data
@@ -91,4 +270,7169 @@ mod test {
             println!("Counter incremented successfullu to : {}", counter.count);
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_circuit_breaker_freezes_counter() {
+        let mut env = TestEnv::start().await;
+
+        // allow at most one unit of change per 1000 slots
+        let config = InitConfig {
+            circuit_breaker: Some(CircuitBreakerConfig {
+                max_delta: 1,
+                window_slots: 1000,
+            }),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // first increment stays within the window budget, second one trips it
+        for _ in 0..2 {
+            let increment = env.counter_ix(&[1], &counter);
+            env.send(&[increment], &[]).await.unwrap();
+        }
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 1);
+        assert!(state.is_frozen);
+
+        // frozen counters reject further increments
+        let increment = env.counter_ix(&[1], &counter);
+        assert!(env.send(&[increment], &[]).await.is_err());
+
+        // the counter keypair can clear the breaker
+        let reset = env.counter_ix(&[2], &counter);
+        env.send(&[reset], &[]).await.unwrap();
+
+        assert!(!env.read_counter(&counter).await.is_frozen);
+    }
+
+    #[tokio::test]
+    async fn test_commit_reveal_increment() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(10, &InitConfig::default()).await;
+
+        let salt = [7u8; 32];
+        let mut commit_data = vec![3];
+        commit_data.extend_from_slice(&commitment_hash(5, &salt));
+        let commit = env.counter_ix(&commit_data, &counter);
+        env.send(&[commit], &[]).await.unwrap();
+
+        // a reveal with the wrong salt does not match the commitment
+        let mut bad_reveal_data = vec![4];
+        bad_reveal_data.extend_from_slice(&5u64.to_le_bytes());
+        bad_reveal_data.extend_from_slice(&[8u8; 32]);
+        let bad_reveal = env.counter_ix(&bad_reveal_data, &counter);
+        assert!(env.send(&[bad_reveal], &[]).await.is_err());
+
+        let mut reveal_data = vec![4];
+        reveal_data.extend_from_slice(&5u64.to_le_bytes());
+        reveal_data.extend_from_slice(&salt);
+        let reveal = env.counter_ix(&reveal_data, &counter);
+        env.send(std::slice::from_ref(&reveal), &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 15);
+        assert_eq!(state.pending_commitment, [0; 32]);
+
+        // the commitment is consumed, so the same reveal cannot be replayed
+        assert!(env.send(&[reveal], &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commitment_mode_counter() {
+        let mut env = TestEnv::start().await;
+
+        let salt = [1u8; 32];
+        let config = InitConfig {
+            value_commitment: Some(commitment_hash(42, &salt)),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // plain increments are not allowed on committed counters
+        let increment = env.counter_ix(&[1], &counter);
+        assert!(env.send(&[increment], &[]).await.is_err());
+
+        let new_salt = [2u8; 32];
+        let transition = |value: u64, salt: &[u8; 32]| {
+            let mut data = vec![5];
+            data.extend_from_slice(&value.to_le_bytes());
+            data.extend_from_slice(salt);
+            data.extend_from_slice(&3u64.to_le_bytes());
+            data.extend_from_slice(&new_salt);
+            data
+        };
+
+        // a wrong preimage is rejected
+        let bad = env.counter_ix(&transition(41, &salt), &counter);
+        assert!(env.send(&[bad], &[]).await.is_err());
+
+        let good = env.counter_ix(&transition(42, &salt), &counter);
+        env.send(&[good], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 0);
+        assert_eq!(state.value_commitment, commitment_hash(45, &new_salt));
+    }
+
+    #[tokio::test]
+    async fn test_named_metrics() {
+        let mut env = TestEnv::start().await;
+
+        let config = InitConfig {
+            metrics: vec![
+                MetricConfig {
+                    name: Label32::new("logins").unwrap(),
+                    initial_value: 0,
+                    max_value: 0,
+                },
+                MetricConfig {
+                    name: Label32::new("purchases").unwrap(),
+                    initial_value: 0,
+                    max_value: 5,
+                },
+            ],
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        let increment_metric = |metric: MetricRef, amount: u64| {
+            let mut data = vec![6];
+            metric.serialize(&mut data).unwrap();
+            data.extend_from_slice(&amount.to_le_bytes());
+            data
+        };
+
+        let by_index = env.counter_ix(&increment_metric(MetricRef::Index(0), 2), &counter);
+        let by_name = env.counter_ix(
+            &increment_metric(MetricRef::Name(Label32::new("purchases").unwrap()), 4),
+            &counter,
+        );
+        env.send(&[by_index, by_name], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 0);
+        assert_eq!(state.metrics[0].value, 2);
+        assert_eq!(state.metrics[1].value, 4);
+
+        // the purchases metric is capped at 5
+        let over_cap = env.counter_ix(&increment_metric(MetricRef::Index(1), 2), &counter);
+        assert!(env.send(&[over_cap], &[]).await.is_err());
+
+        let unknown = env.counter_ix(
+            &increment_metric(MetricRef::Name(Label32::new("refunds").unwrap()), 1),
+            &counter,
+        );
+        assert!(env.send(&[unknown], &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_weighted_composite_refresh() {
+        let mut env = TestEnv::start().await;
+
+        let first = env.initialize(10, &InitConfig::default()).await;
+        let second = env.initialize(7, &InitConfig::default()).await;
+
+        // full weight for the first counter, half weight for the second
+        let sources = vec![
+            WeightedSource {
+                counter: first.pubkey(),
+                weight: 10_000,
+            },
+            WeightedSource {
+                counter: second.pubkey(),
+                weight: 5_000,
+            },
+        ];
+
+        let composite = Keypair::new();
+        let mut data = vec![7];
+        sources.serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(composite.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[initialize], &[&composite]).await.unwrap();
+
+        let refresh = |accounts: Vec<AccountMeta>| {
+            Instruction::new_with_bytes(env.program_id, &[8], accounts)
+        };
+
+        // sources passed out of order are rejected
+        let swapped = refresh(vec![
+            AccountMeta::new(composite.pubkey(), false),
+            AccountMeta::new_readonly(second.pubkey(), false),
+            AccountMeta::new_readonly(first.pubkey(), false),
+        ]);
+        let in_order = refresh(vec![
+            AccountMeta::new(composite.pubkey(), false),
+            AccountMeta::new_readonly(first.pubkey(), false),
+            AccountMeta::new_readonly(second.pubkey(), false),
+        ]);
+        assert!(env.send(&[swapped], &[]).await.is_err());
+        env.send(&[in_order], &[]).await.unwrap();
+
+        let account = env
+            .banks_client
+            .get_account(composite.pubkey())
+            .await
+            .unwrap()
+            .expect("composite account missing");
+        let state = CompositeCounter::try_from_slice(&account.data).unwrap();
+        assert_eq!(state.value, 13);
+        assert!(state.refreshed_slot > 0);
+
+        // a weighted sum past u128 fails rather than wrapping
+        let full = env.initialize(u64::MAX, &InitConfig::default()).await;
+        let sources = vec![
+            WeightedSource {
+                counter: full.pubkey(),
+                weight: u64::MAX,
+            };
+            2
+        ];
+        let composite = Keypair::new();
+        let mut data = vec![7];
+        sources.serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(composite.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[initialize], &[&composite]).await.unwrap();
+
+        let refresh = Instruction::new_with_bytes(
+            env.program_id,
+            &[8],
+            vec![
+                AccountMeta::new(composite.pubkey(), false),
+                AccountMeta::new_readonly(full.pubkey(), false),
+                AccountMeta::new_readonly(full.pubkey(), false),
+            ],
+        );
+        let err = env.send(&[refresh], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+    }
+
+    // stand-in target for trigger CPIs: requires its first account to sign and
+    // flags the record account it owns
+    fn trigger_target(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        _instruction_data: &[u8],
+    ) -> ProgramResult {
+        if !accounts[0].is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        accounts[1].data.borrow_mut()[0] = 1;
+        Ok(())
+    }
+
+    // reads a counter through its view key and records the attested count
+    // and nonce; accounts: counter, view key, viewer PDA, counter program,
+    // record
+    fn view_reader(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        _instruction_data: &[u8],
+    ) -> ProgramResult {
+        let (counter, view_key, viewer, counter_program, record) = (
+            &accounts[0],
+            &accounts[1],
+            &accounts[2],
+            &accounts[3],
+            &accounts[4],
+        );
+        let (_, bump) = derive_viewer_authority(program_id);
+
+        let get_count = Instruction::new_with_bytes(
+            *counter_program.key,
+            &[27],
+            vec![
+                AccountMeta::new_readonly(*counter.key, false),
+                AccountMeta::new(*view_key.key, false),
+                AccountMeta::new_readonly(*viewer.key, true),
+            ],
+        );
+        solana_program::program::invoke_signed(
+            &get_count,
+            &[counter.clone(), view_key.clone(), viewer.clone()],
+            &[&[VIEWER_SEED, &[bump]]],
+        )?;
+
+        let (_, data) = solana_program::program::get_return_data().unwrap();
+        let attestation = CountAttestation::try_from_slice(&data)?;
+        let mut record_data = record.data.borrow_mut();
+        record_data[..8].copy_from_slice(&attestation.count.to_le_bytes());
+        record_data[8..].copy_from_slice(&attestation.nonce.to_le_bytes());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_threshold_trigger_cpi() {
+        let target_id = Pubkey::new_unique();
+        let record = Pubkey::new_unique();
+
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_program("trigger_target", target_id, processor!(trigger_target));
+            program_test.add_account(
+                record,
+                Account {
+                    lamports: 1_000_000,
+                    data: vec![0],
+                    owner: target_id,
+                    ..Account::default()
+                },
+            );
+        })
+        .await;
+
+        let counter = Keypair::new();
+        let (trigger_authority, _) = derive_trigger_authority(&env.program_id, &counter.pubkey());
+
+        let config = InitConfig {
+            trigger: Some(TriggerConfig {
+                threshold: 2,
+                program_id: target_id,
+                accounts: vec![
+                    TriggerAccount {
+                        pubkey: trigger_authority,
+                        is_signer: true,
+                        is_writable: false,
+                    },
+                    TriggerAccount {
+                        pubkey: record,
+                        is_signer: false,
+                        is_writable: true,
+                    },
+                ],
+                data: vec![],
+            }),
+            ..InitConfig::default()
+        };
+
+        let mut data = vec![0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        config.serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(derive_global_config_address(&env.program_id).0, false),
+            ],
+        );
+        env.send(&[initialize], &[&counter]).await.unwrap();
+
+        // below the threshold no trigger accounts are needed
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+
+        let increment = Instruction::new_with_bytes(
+            env.program_id,
+            &[1],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(target_id, false),
+                AccountMeta::new_readonly(trigger_authority, false),
+                AccountMeta::new(record, false),
+            ],
+        );
+        env.send(&[increment], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 2);
+        assert!(state.trigger_fired);
+
+        let record_account = env.banks_client.get_account(record).await.unwrap().unwrap();
+        assert_eq!(record_account.data, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_decrement_counter_underflow() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(1, &InitConfig::default()).await;
+
+        let decrement = env.counter_ix(&[9], &counter);
+        env.send(&[decrement], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 0);
+
+        // going below zero fails and leaves the count untouched
+        let decrement = env.counter_ix(&[9], &counter);
+        let err = env.send(&[decrement], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_mint_supply_mirror() {
+        let mint = Pubkey::new_unique();
+
+        let mut mint_data = vec![0u8; MINT_LEN];
+        mint_data[36..44].copy_from_slice(&1_250u64.to_le_bytes());
+        mint_data[45] = 1;
+
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_account(
+                mint,
+                Account {
+                    lamports: 1_000_000,
+                    data: mint_data,
+                    owner: TOKEN_PROGRAM_ID,
+                    ..Account::default()
+                },
+            );
+        })
+        .await;
+
+        let config = InitConfig {
+            mirror_mint: Some(mint),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // mirrors only move through SyncMintSupply
+        let increment = env.counter_ix(&[1], &counter);
+        assert!(env.send(&[increment], &[]).await.is_err());
+
+        let sync = Instruction::new_with_bytes(
+            env.program_id,
+            &[10],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(mint, false),
+            ],
+        );
+        env.send(&[sync], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 1_250);
+        assert!(state.mirror_synced_slot > 0);
+    }
+
+    #[tokio::test]
+    async fn test_observation_buffer_twap() {
+        let mut env = TestEnv::start().await;
+
+        let config = InitConfig {
+            observation_slots: 4,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(10, &config).await;
+
+        for _ in 0..6 {
+            env.warp_slots(5).await;
+            let increment = env.counter_ix(&[1], &counter);
+            env.send(&[increment], &[]).await.unwrap();
+        }
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 16);
+        assert_eq!(state.observations.samples.len(), 4);
+        assert_eq!(state.observations.len, 4);
+
+        // samples are ordered and the average sits between the held values
+        let samples: Vec<Observation> = state.observations.iter().copied().collect();
+        assert!(samples.windows(2).all(|pair| pair[0].slot < pair[1].slot));
+
+        let oldest = samples[0];
+        let newest = samples[samples.len() - 1];
+        let average = Observation::time_weighted_average(&oldest, &newest).unwrap();
+        assert!((10..16).contains(&average));
+    }
+
+    #[tokio::test]
+    async fn test_increment_and_decrement_by_amount() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(5, &InitConfig::default()).await;
+
+        let by = |tag: u8, amount: u64| {
+            let mut data = vec![tag];
+            data.extend_from_slice(&amount.to_le_bytes());
+            data
+        };
+
+        let increment = env.counter_ix(&by(11, 100), &counter);
+        let decrement = env.counter_ix(&by(12, 30), &counter);
+        env.send(&[increment, decrement], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 75);
+
+        let underflow = env.counter_ix(&by(12, 76), &counter);
+        let err = env.send(&[underflow], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+
+        let overflow = env.counter_ix(&by(11, u64::MAX), &counter);
+        let err = env.send(&[overflow], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+
+        // a payload without the amount is malformed
+        let truncated = env.counter_ix(&[11, 1, 2], &counter);
+        let err = env.send(&[truncated], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_authority_gating_and_transfer() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+
+        let stranger = Keypair::new();
+
+        // anyone other than the authority is rejected
+        let increment = env.counter_ix_as(&[1], &counter, &stranger.pubkey());
+        let err = env.send(&[increment], &[&stranger]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        let mut transfer_data = vec![13];
+        transfer_data.extend_from_slice(stranger.pubkey().as_ref());
+        let transfer = env.counter_ix(&transfer_data, &counter);
+        env.send(&[transfer], &[]).await.unwrap();
+        assert_eq!(
+            env.read_counter(&counter).await.authority,
+            stranger.pubkey()
+        );
+
+        // the old authority lost its rights, the new one can increment
+        let increment = env.counter_ix(&[1], &counter);
+        assert!(env.send(&[increment], &[]).await.is_err());
+        let increment = env.counter_ix_as(&[1], &counter, &stranger.pubkey());
+        env.send(&[increment], &[&stranger]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+
+        let destination = Pubkey::new_unique();
+        let close = Instruction::new_with_bytes(
+            env.program_id,
+            &[14],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(stranger.pubkey(), true),
+                AccountMeta::new(destination, false),
+            ],
+        );
+        env.send(&[close], &[&stranger]).await.unwrap();
+
+        assert!(
+            env.banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let refunded = env.banks_client.get_balance(destination).await.unwrap();
+        assert!(refunded > 0);
+    }
+
+    #[tokio::test]
+    async fn test_token_gate_requires_holder_signature() {
+        use crate::gate::GATE_TOKEN_BALANCE;
+        use crate::spl::TOKEN_ACCOUNT_LEN;
+
+        let mint = Pubkey::new_unique();
+        let holder = Keypair::new();
+        let holder_tokens = Pubkey::new_unique();
+
+        // SPL Token `Account` layout: mint, owner, amount, ..., state
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(holder.pubkey().as_ref());
+        data[64..72].copy_from_slice(&10u64.to_le_bytes());
+        data[108] = 1;
+        let tokens = Account {
+            lamports: 10_000_000,
+            data,
+            owner: TOKEN_PROGRAM_ID,
+            ..Account::default()
+        };
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_account(holder_tokens, tokens);
+        })
+        .await;
+
+        let config = InitConfig {
+            gates: Some(GateConfig {
+                flags: GATE_TOKEN_BALANCE,
+                token_mint: mint,
+                min_token_balance: 10,
+                ..GateConfig::default()
+            }),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // naming a holder and their token account is not enough
+        let mut increment = env.counter_ix_as(&[1], &counter, &holder.pubkey());
+        increment.accounts[1].is_signer = false;
+        increment
+            .accounts
+            .push(AccountMeta::new_readonly(holder_tokens, false));
+        let err = env.send(&[increment.clone()], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        increment.accounts[1].is_signer = true;
+        env.send(&[increment], &[&holder]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_and_cooldown_gates() {
+        let mut env = TestEnv::start().await;
+
+        let member = Keypair::new();
+        let outsider = Keypair::new();
+        let config = InitConfig {
+            gates: Some(GateConfig {
+                flags: GATE_ALLOWLIST | GATE_COOLDOWN,
+                allowlist: vec![member.pubkey()],
+                cooldown_slots: 50,
+                ..GateConfig::default()
+            }),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // the authority gate is not enabled, so only the allowlist matters
+        let increment = env.counter_ix(&[1], &counter);
+        assert!(env.send(&[increment], &[]).await.is_err());
+        let increment = env.counter_ix_as(&[1], &counter, &outsider.pubkey());
+        assert!(env.send(&[increment], &[&outsider]).await.is_err());
+
+        let increment = env.counter_ix_as(&[1], &counter, &member.pubkey());
+        env.send(&[increment], &[&member]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(cooldown_ends_at(&state), Some(state.gate_last_slot + 50));
+
+        // a second increment inside the cooldown window is rejected
+        let increment = env.counter_ix_as(&[1], &counter, &member.pubkey());
+        let err = env.send(&[increment], &[&member]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::CooldownActive as u32)
+            )
+        );
+
+        env.warp_slots(60).await;
+        let increment = env.counter_ix_as(&[1], &counter, &member.pubkey());
+        env.send(&[increment], &[&member]).await.unwrap();
+
+        assert_eq!(env.read_counter(&counter).await.count, 2);
+
+        // a cooldown too long to add to the slot saturates instead of wrapping
+        let config = InitConfig {
+            gates: Some(GateConfig {
+                flags: GATE_COOLDOWN,
+                cooldown_slots: u64::MAX,
+                ..GateConfig::default()
+            }),
+            ..InitConfig::default()
+        };
+        let frozen = env.initialize(0, &config).await;
+        let increment = env.counter_ix(&[1], &frozen);
+        env.send(&[increment], &[]).await.unwrap();
+        let increment = env.counter_ix(&[1], &frozen);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::CooldownActive as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_account_contracts_are_enforced() {
+        let mut env = TestEnv::start().await;
+
+        // the third initialize account has to be the system program
+        let counter = Keypair::new();
+        let mut data = vec![0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            ],
+        );
+        let err = env.send(&[initialize], &[&counter]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::NotSystemProgram as u32)
+            )
+        );
+
+        // mutations need the counter writable and at least the actor account
+        let counter = env.initialize(0, &InitConfig::default()).await;
+        let read_only = Instruction::new_with_bytes(
+            env.program_id,
+            &[1],
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new_readonly(env.payer.pubkey(), true),
+            ],
+        );
+        let err = env.send(&[read_only], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+        );
+
+        let missing_actor = Instruction::new_with_bytes(
+            env.program_id,
+            &[1],
+            vec![AccountMeta::new(counter.pubkey(), false)],
+        );
+        let err = env.send(&[missing_actor], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pda_counter_per_owner() {
+        let mut env = TestEnv::start().await;
+        let (counter, _) = derive_counter_address(&env.program_id, &env.payer.pubkey(), "");
+
+        let mut data = vec![15];
+        data.extend_from_slice(&3u64.to_le_bytes());
+        String::new().serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(derive_global_config_address(&env.program_id).0, false),
+            ],
+        );
+        env.send(std::slice::from_ref(&initialize), &[])
+            .await
+            .unwrap();
+
+        let increment = Instruction::new_with_bytes(
+            env.program_id,
+            &[1],
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(env.payer.pubkey(), true),
+            ],
+        );
+        env.send(&[increment], &[]).await.unwrap();
+
+        let state = env.read_counter_at(counter).await;
+        assert_eq!(state.count, 4);
+        assert_eq!(state.authority, env.payer.pubkey());
+
+        // each owner gets exactly one counter at that address
+        assert!(env.send(&[initialize], &[]).await.is_err());
+
+        // addresses that are not the owner's PDA are refused
+        let other = Pubkey::new_unique();
+        let wrong = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(other, false),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[wrong], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidCounterAddress as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reset_and_set_counter() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(9, &InitConfig::default()).await;
+
+        let mut set_data = vec![17];
+        set_data.extend_from_slice(&500u64.to_le_bytes());
+        let set = env.counter_ix(&set_data, &counter);
+        env.send(&[set], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 500);
+
+        let reset = env.counter_ix(&[16], &counter);
+        env.send(&[reset], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 0);
+
+        // only the authority may set the value
+        let stranger = Keypair::new();
+        let set = env.counter_ix_as(&set_data, &counter, &stranger.pubkey());
+        assert!(env.send(&[set], &[&stranger]).await.is_err());
+        assert_eq!(env.read_counter(&counter).await.count, 0);
+    }
+
+    #[test]
+    fn test_dispatch_table_matches_instructions() {
+        for (index, entry) in INSTRUCTIONS.iter().enumerate() {
+            assert_eq!(entry.tag as usize, index);
+            let listed = idl_json().contains(&format!(r#""name":"{}""#, entry.name));
+            assert_eq!(listed, entry.is_enabled());
+        }
+        assert!(meta(INSTRUCTIONS.len() as u8).is_none());
+
+        // every tag in the table decodes, and the first tag past it does not
+        let mut set_data = vec![17];
+        set_data.extend_from_slice(&1u64.to_le_bytes());
+        assert!(CounterInstruction::unpack(&set_data).is_ok());
+        assert_eq!(
+            CounterInstruction::unpack(&[INSTRUCTIONS.len() as u8]).unwrap_err(),
+            CounterError::UnknownInstruction.into()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counter_discriminator_checks() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(5, &InitConfig::default()).await;
+        assert_eq!(
+            env.read_counter(&counter).await.discriminator,
+            COUNTER_DISCRIMINATOR
+        );
+
+        // a second initialize on the same account is refused
+        let mut data = vec![0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        let reinitialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[reinitialize], &[&counter]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::AlreadyInitialized as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 5);
+
+        // program-owned accounts that are not counters cannot be incremented
+        let zeroed = Keypair::new();
+        let foreign = Keypair::new();
+        for (account, data) in [(&zeroed, vec![0; 64]), (&foreign, vec![7; 64])] {
+            let account_data = Account {
+                lamports: 1_000_000,
+                data,
+                owner: env.program_id,
+                ..Account::default()
+            };
+            env.context
+                .set_account(&account.pubkey(), &account_data.into());
+        }
+
+        let increment = env.counter_ix(&[1], &zeroed);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::AccountNotInitialized as u32)
+            )
+        );
+
+        let increment = env.counter_ix(&[1], &foreign);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::WrongAccountType as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_devnet_tools_follow_the_feature() {
+        let devnet_tools = ["ForceSet", "FastForwardCooldown", "ResetStats"];
+        for name in devnet_tools {
+            let listed = idl_json().contains(&format!(r#""name":"{}""#, name));
+            assert_eq!(listed, cfg!(feature = "devnet-tools"));
+        }
+
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(3, &InitConfig::default()).await;
+
+        let mut force_data = vec![18];
+        force_data.extend_from_slice(&42u64.to_le_bytes());
+        let force = env.counter_ix(&force_data, &counter);
+        let result = env.send(&[force], &[]).await;
+
+        if cfg!(feature = "devnet-tools") {
+            result.unwrap();
+            assert_eq!(env.read_counter(&counter).await.count, 42);
+        } else {
+            assert_eq!(
+                result.unwrap_err().unwrap(),
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(CounterError::UnknownInstruction as u32)
+                )
+            );
+            assert_eq!(env.read_counter(&counter).await.count, 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reap_expired_counter() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            ttl_slots: 50,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // the reaper needs a rent-exempt balance to receive its share into
+        let reaper = Keypair::new();
+        let fund = solana_sdk::system_instruction::transfer(
+            &env.payer.pubkey(),
+            &reaper.pubkey(),
+            1_000_000_000,
+        );
+        env.send(&[fund], &[]).await.unwrap();
+
+        let reap = Instruction::new_with_bytes(
+            env.program_id,
+            &[21],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(env.payer.pubkey(), false),
+                AccountMeta::new(reaper.pubkey(), true),
+            ],
+        );
+
+        // activity pushes the expiry back
+        env.warp_slots(40).await;
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        env.warp_slots(40).await;
+        let err = env
+            .send(std::slice::from_ref(&reap), &[&reaper])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::NotExpired as u32)
+            )
+        );
+
+        env.warp_slots(20).await;
+        let rent = env
+            .banks_client
+            .get_balance(counter.pubkey())
+            .await
+            .unwrap();
+        env.send(&[reap], &[&reaper]).await.unwrap();
+
+        assert!(
+            env.banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let reaper_balance = env.banks_client.get_balance(reaper.pubkey()).await.unwrap();
+        assert_eq!(reaper_balance, 1_000_000_000 + rent / 10);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_counter() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(7, &InitConfig::default()).await;
+
+        // rewrite the counter in the unversioned layout it had before `version`
+        let current = env.read_counter(&counter).await;
+        let legacy_data = borsh::to_vec(&CounterAccountV0::try_from(current).unwrap()).unwrap();
+        let legacy_len = legacy_data.len();
+        let mut account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        account.data = legacy_data;
+        env.context.set_account(&counter.pubkey(), &account.into());
+
+        // legacy counters keep working in place
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.data.len(), legacy_len);
+        assert_eq!(
+            CounterAccountV0::try_from_slice(&account.data)
+                .unwrap()
+                .count,
+            8
+        );
+
+        let migrate = Instruction::new_with_bytes(
+            env.program_id,
+            &[22],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(std::slice::from_ref(&migrate), &[]).await.unwrap();
+
+        let migrated = env.read_counter(&counter).await;
+        assert_eq!(migrated.discriminator, COUNTER_DISCRIMINATOR);
+        assert_eq!(migrated.version, COUNTER_VERSION);
+        assert_eq!(migrated.count, 8);
+        assert_eq!(migrated.authority, env.payer.pubkey());
+
+        // migrating a current counter is a no-op
+        env.send(&[migrate], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 8);
+    }
+
+    #[test]
+    fn test_instruction_pack_round_trips() {
+        let instructions = vec![
+            CounterInstruction::InitializeCounter {
+                initial_value: 4,
+                config: Box::new(InitConfig {
+                    observation_slots: 8,
+                    ..InitConfig::default()
+                }),
+            },
+            CounterInstruction::IncrementCommitted {
+                value: 1,
+                salt: [2; 32],
+                amount: 3,
+                new_salt: [4; 32],
+            },
+            CounterInstruction::IncrementMetric {
+                metric: MetricRef::Name(Label32::new("hits").unwrap()),
+                amount: 5,
+            },
+            CounterInstruction::InitializeComposite {
+                sources: vec![WeightedSource {
+                    counter: Pubkey::new_unique(),
+                    weight: 10_000,
+                }],
+            },
+            CounterInstruction::TransferAuthority {
+                new_authority: Pubkey::new_unique(),
+            },
+            CounterInstruction::SetCounter { value: 9 },
+            CounterInstruction::MigrateAccount,
+            CounterInstruction::InitializeCounterPda {
+                initial_value: 1,
+                name: Label32::new("daily").unwrap(),
+                config: Box::default(),
+            },
+            CounterInstruction::SetTopics {
+                topics: Topics::new(vec![Topic::new(b"orders".to_vec()).unwrap()]).unwrap(),
+            },
+            CounterInstruction::InitializeAndIncrement {
+                initial_value: 2,
+                delta: 3,
+            },
+            CounterInstruction::SetStep { step: 25 },
+            CounterInstruction::MintCoupon {
+                code_hash: [7; 32],
+                amount: 2,
+            },
+            CounterInstruction::RedeemCoupon {
+                code: CouponCode::new(b"SPRING".to_vec()).unwrap(),
+            },
+            CounterInstruction::ImportAttestation {
+                snapshot: AttestationSnapshot {
+                    attestation: CountAttestation {
+                        counter: Pubkey::new_unique(),
+                        count: 8,
+                        slot: 90,
+                        nonce: 1,
+                    },
+                    unix_timestamp: 1_700_000_000,
+                },
+            },
+            CounterInstruction::IncrementMany,
+            CounterInstruction::SkimExcess,
+            CounterInstruction::SetTemplate {
+                name: Label32::new("standard").unwrap(),
+                config: Box::new(InitConfig {
+                    step: 5,
+                    ..InitConfig::default()
+                }),
+            },
+            CounterInstruction::InitializeFromTemplate { initial_value: 3 },
+            CounterInstruction::InitializeRegistry,
+            CounterInstruction::SetMode {
+                mode: IncrementMode::Permissionless,
+            },
+            CounterInstruction::Pause,
+            CounterInstruction::Resume,
+            CounterInstruction::InitializeCounterById {
+                initial_value: 6,
+                external_id: external_id_hash(b"order-1"),
+                config: Box::default(),
+            },
+            CounterInstruction::Snapshot,
+            CounterInstruction::AddDelegate {
+                delegate: Pubkey::new_unique(),
+            },
+            CounterInstruction::RemoveDelegate {
+                delegate: Pubkey::new_unique(),
+            },
+            CounterInstruction::CreateMultisig {
+                threshold: 2,
+                signers: MultisigSigners::new(vec![Pubkey::new_unique(), Pubkey::new_unique()])
+                    .unwrap(),
+            },
+            CounterInstruction::SetUnlockTime {
+                unlock_timestamp: Some(1_700_000_000),
+            },
+            CounterInstruction::SetUnlockTime {
+                unlock_timestamp: None,
+            },
+            CounterInstruction::SetOwnerQuota {
+                max_counters_per_owner: 3,
+            },
+            CounterInstruction::WithdrawFees,
+            CounterInstruction::Resize,
+            CounterInstruction::AdminFreeze,
+            CounterInstruction::RequestUnfreeze,
+            CounterInstruction::BurnCounter,
+            CounterInstruction::SetUnfreezeGracePeriod { grace_secs: 60 },
+            CounterInstruction::InitializeSignedCounter { initial_value: -7 },
+            CounterInstruction::IncrementSigned { amount: 2 },
+            CounterInstruction::DecrementSigned { amount: 9 },
+            CounterInstruction::InitializeSlotCounter { slots: 4 },
+            CounterInstruction::IncrementSlot { index: 3 },
+            CounterInstruction::DecrementSlot { index: 0 },
+            CounterInstruction::GetSlot { index: 2 },
+            CounterInstruction::ConditionalIncrement { expected: 41 },
+            CounterInstruction::TransferCount { amount: 6 },
+            CounterInstruction::CloseExpired,
+            CounterInstruction::IncrementWithProof {
+                index: 3,
+                proof: MerkleProof::try_from(vec![[4; 32], [5; 32]]).unwrap(),
+            },
+            CounterInstruction::InitializeGlobalConfig,
+            CounterInstruction::SetGlobalConfig {
+                params: GlobalParams {
+                    default_max_value: 100,
+                    protocol_fee: 7,
+                    fee_recipient: Pubkey::new_unique(),
+                    paused: true,
+                    max_counters_per_owner: 3,
+                },
+            },
+            CounterInstruction::ReadCount,
+        ];
+
+        let malformed = Err(ProgramError::InvalidInstructionData);
+        for instruction in instructions {
+            let data = instruction.pack();
+            assert_eq!(CounterInstruction::unpack(&data).unwrap().pack(), data);
+
+            // trailing bytes are rejected rather than ignored
+            let mut extended = data.clone();
+            extended.push(0);
+            let result = CounterInstruction::unpack(&extended).map(|_| ());
+            assert_eq!(result, malformed, "{:?}", instruction);
+
+            // and so is a payload cut short
+            if data.len() > 1 {
+                let result = CounterInstruction::unpack(&data[..data.len() - 1]).map(|_| ());
+                assert_eq!(result, malformed, "{:?}", instruction);
+            }
+        }
+
+        // an unknown tag is reported apart from a malformed payload
+        for data in [&[][..], &[INSTRUCTIONS.len() as u8], &[u8::MAX, 0, 0]] {
+            let result = CounterInstruction::unpack(data).map(|_| ());
+            assert_eq!(result, Err(CounterError::UnknownInstruction.into()));
+        }
+    }
+
+    #[test]
+    fn test_event_fields_round_trip() {
+        let counter = Pubkey::new_unique();
+        let topics = vec![b"orders".to_vec(), b"eu-west".to_vec()];
+        let event = CounterEvent::count_changed(4, 9).unwrap();
+        assert_eq!(
+            event,
+            CounterEvent::Incremented(CounterIncremented { old: 4, new: 9 })
+        );
+
+        let fields = event_fields(&counter, &topics, &event);
+        assert_eq!(fields.len(), 4);
+        assert_eq!(
+            decode_fields(&fields),
+            Some(LoggedEvent {
+                counter,
+                topics,
+                event,
+            })
+        );
+
+        // data logged by other programs is not mistaken for an event
+        assert_eq!(decode_fields(&[b"short".to_vec(), vec![9]]), None);
+        assert_eq!(CounterEvent::count_changed(3, 3), None);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_client_instruction_builders() {
+        use crate::client::{
+            deserialize_counter, increment_by_ix, initialize_counter_ix, parse_event_log,
+            set_counter_ix,
+        };
+        use base64::{Engine, engine::general_purpose::STANDARD};
+
+        let mut env = TestEnv::start().await;
+        let counter = Keypair::new();
+        let payer = env.payer.pubkey();
+
+        let initialize = initialize_counter_ix(&env.program_id, &counter.pubkey(), &payer, 2);
+        env.send(&[initialize], &[&counter]).await.unwrap();
+
+        let increment = increment_by_ix(&env.program_id, &counter.pubkey(), &payer, 5);
+        env.send(&[increment], &[]).await.unwrap();
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(deserialize_counter(&account.data).unwrap().count, 7);
+
+        let set = set_counter_ix(&env.program_id, &counter.pubkey(), &payer, 100);
+        env.send(&[set], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 100);
+
+        let event = CounterEvent::count_changed(7, 100).unwrap();
+        let line = format!(
+            "Program data: {} {}",
+            STANDARD.encode(counter.pubkey()),
+            STANDARD.encode(borsh::to_vec(&event).unwrap())
+        );
+        let logged = parse_event_log(&line).unwrap();
+        assert_eq!(logged.counter, counter.pubkey());
+        assert_eq!(logged.event, event);
+        assert!(parse_event_log("Program log: Counter set from 7 to 100").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lock_and_unlock_count() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(100, &InitConfig::default()).await;
+
+        let mut lock_data = vec![23];
+        lock_data.extend_from_slice(&60u64.to_le_bytes());
+        lock_data.extend_from_slice(&50u64.to_le_bytes());
+        let lock = env.counter_ix(&lock_data, &counter);
+        env.send(&[lock], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.locked_amount, 60);
+        assert_eq!(state.liquid(), 40);
+
+        // locked points cannot be spent
+        let mut spend = vec![12];
+        spend.extend_from_slice(&41u64.to_le_bytes());
+        let decrement = env.counter_ix(&spend, &counter);
+        let err = env.send(&[decrement], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InsufficientLiquid as u32)
+            )
+        );
+
+        let unlock = Instruction::new_with_bytes(
+            env.program_id,
+            &[24],
+            vec![AccountMeta::new(counter.pubkey(), false)],
+        );
+        let err = env
+            .send(std::slice::from_ref(&unlock), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::LockActive as u32)
+            )
+        );
+
+        env.warp_slots(60).await;
+        env.send(&[unlock], &[]).await.unwrap();
+        let decrement = env.counter_ix(&spend, &counter);
+        env.send(&[decrement], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.locked_amount, 0);
+        assert_eq!(state.count, 59);
+    }
+
+    #[tokio::test]
+    async fn test_view_key_grants_reads() {
+        let reader_id = Pubkey::new_unique();
+        let outsider_id = Pubkey::new_unique();
+        let record = Pubkey::new_unique();
+
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_program("view_reader", reader_id, processor!(view_reader));
+            program_test.add_program("view_outsider", outsider_id, processor!(view_reader));
+            program_test.add_account(
+                record,
+                Account {
+                    lamports: 1_000_000,
+                    data: vec![0; 16],
+                    owner: reader_id,
+                    ..Account::default()
+                },
+            );
+        })
+        .await;
+        let counter = env.initialize(12, &InitConfig::default()).await;
+
+        let (view_key, _) = derive_view_key_address(&env.program_id, &counter.pubkey(), &reader_id);
+        let mut grant_data = vec![25];
+        grant_data.extend_from_slice(reader_id.as_ref());
+        let grant = Instruction::new_with_bytes(
+            env.program_id,
+            &grant_data,
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new(view_key, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[grant], &[]).await.unwrap();
+
+        let counter_program = env.program_id;
+        let read_as = |program: Pubkey| {
+            Instruction::new_with_bytes(
+                program,
+                &[],
+                vec![
+                    AccountMeta::new_readonly(counter.pubkey(), false),
+                    AccountMeta::new(view_key, false),
+                    AccountMeta::new_readonly(derive_viewer_authority(&program).0, false),
+                    AccountMeta::new_readonly(counter_program, false),
+                    AccountMeta::new(record, false),
+                ],
+            )
+        };
+
+        env.send(&[read_as(reader_id)], &[]).await.unwrap();
+        env.send(&[read_as(reader_id)], &[]).await.unwrap();
+        let record_data = env
+            .banks_client
+            .get_account(record)
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        assert_eq!(record_data[..8], 12u64.to_le_bytes());
+        assert_eq!(record_data[8..], 2u64.to_le_bytes());
+
+        // a program without a grant cannot use another program's view key
+        let err = env.send(&[read_as(outsider_id)], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::ViewNotGranted as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_named_pda_counters() {
+        let mut env = TestEnv::start().await;
+        let owner = env.payer.pubkey();
+
+        let mut counters = Vec::new();
+        for (name, initial_value) in [("logins", 1u64), ("purchases", 20)] {
+            let (counter, _) = derive_counter_address(&env.program_id, &owner, name);
+
+            let mut data = vec![15];
+            data.extend_from_slice(&initial_value.to_le_bytes());
+            name.to_string().serialize(&mut data).unwrap();
+            let initialize = Instruction::new_with_bytes(
+                env.program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new(owner, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(
+                        derive_global_config_address(&env.program_id).0,
+                        false,
+                    ),
+                ],
+            );
+            env.send(&[initialize], &[]).await.unwrap();
+            counters.push((counter, name, initial_value));
+        }
+
+        assert_ne!(counters[0].0, counters[1].0);
+        for (counter, name, initial_value) in counters {
+            let state = env.read_counter_at(counter).await;
+            assert_eq!(state.name, name);
+            assert_eq!(state.count, initial_value);
+        }
+
+        // names longer than a seed are refused before they are decoded
+        let long_name = "x".repeat(33);
+        let mut data = vec![15];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        long_name.serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(owner, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[initialize], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_topics_tag_count_logs() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+
+        let topics = vec![b"orders".to_vec(), b"eu-west".to_vec()];
+        let mut data = vec![28];
+        topics.serialize(&mut data).unwrap();
+        let set_topics = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[set_topics], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.topics, topics);
+
+        // topics survive count changes on the resized account
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 1);
+        assert_eq!(state.topics, topics);
+
+        // more than MAX_TOPICS topics are refused
+        let mut data = vec![28];
+        vec![b"t".to_vec(); 5].serialize(&mut data).unwrap();
+        let too_many = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[too_many], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_increment() {
+        let mut env = TestEnv::start().await;
+
+        let counter = Keypair::new();
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+        let initialize = |initial_value: u64, delta: u64| {
+            let mut data = vec![29];
+            data.extend_from_slice(&initial_value.to_le_bytes());
+            data.extend_from_slice(&delta.to_le_bytes());
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(derive_global_config_address(&program_id).0, false),
+                ],
+            )
+        };
+
+        // an overflowing delta leaves no account behind
+        let overflow = initialize(u64::MAX, 1);
+        let err = env.send(&[overflow], &[&counter]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+        assert!(
+            env.banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let instruction = initialize(10, 5);
+        env.send(&[instruction], &[&counter]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 15);
+        assert_eq!(state.authority, payer);
+    }
+
+    #[tokio::test]
+    async fn test_configured_step() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            step: 5,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(10, &config).await;
+
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 15);
+
+        // explicit amounts ignore the step
+        let mut data = vec![11];
+        data.extend_from_slice(&2u64.to_le_bytes());
+        let increment_by = env.counter_ix(&data, &counter);
+        env.send(&[increment_by], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 17);
+
+        let mut data = vec![30];
+        data.extend_from_slice(&3u64.to_le_bytes());
+        let set_step = env.counter_ix(&data, &counter);
+        env.send(&[set_step], &[]).await.unwrap();
+
+        let decrement = env.counter_ix(&[9], &counter);
+        env.send(&[decrement], &[]).await.unwrap();
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.step, 3);
+        assert_eq!(state.count, 14);
+
+        // a zero step would make increments no-ops
+        let mut data = vec![30];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        let zero_step = env.counter_ix(&data, &counter);
+        let err = env.send(&[zero_step], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_coupons_redeem_exactly_once() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(1, &InitConfig::default()).await;
+        let payer = env.payer.pubkey();
+
+        let code = b"SPRING-24";
+        let (coupon, _) =
+            derive_coupon_address(&env.program_id, &counter.pubkey(), &coupon_code_hash(code));
+        let mut mint_data = vec![31];
+        mint_data.extend_from_slice(&coupon_code_hash(code));
+        mint_data.extend_from_slice(&3u64.to_le_bytes());
+        let mint = Instruction::new_with_bytes(
+            env.program_id,
+            &mint_data,
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[mint], &[]).await.unwrap();
+
+        let program_id = env.program_id;
+        let redeem = |code: &[u8]| {
+            let mut data = vec![32];
+            code.to_vec().serialize(&mut data).unwrap();
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(coupon, false),
+                    AccountMeta::new(payer, false),
+                ],
+            )
+        };
+
+        // a guessed code does not lead to the coupon
+        let err = env.send(&[redeem(b"SUMMER-24")], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
+        );
+
+        env.send(&[redeem(code)], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 4);
+        assert!(
+            env.banks_client
+                .get_account(coupon)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let err = env.send(&[redeem(code)], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidCoupon as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_import_attestation_mirror() {
+        use solana_sdk::{clock::Clock, sysvar};
+
+        let mut env = TestEnv::start().await;
+        let attester = Keypair::new();
+        let source = Pubkey::new_unique();
+        let config = InitConfig {
+            import_source: Some(ImportSource {
+                counter: source,
+                attester: attester.pubkey(),
+                max_age_secs: 60,
+            }),
+            ..InitConfig::default()
+        };
+        let mirror = env.initialize(0, &config).await;
+        let now = env
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap()
+            .unix_timestamp;
+
+        let program_id = env.program_id;
+        let import = |signer: &Keypair, count: u64, slot: u64, unix_timestamp: i64| {
+            let snapshot = AttestationSnapshot {
+                attestation: CountAttestation {
+                    counter: source,
+                    count,
+                    slot,
+                    nonce: 0,
+                },
+                unix_timestamp,
+            };
+            let message = borsh::to_vec(&snapshot).unwrap();
+            let signature = signer.sign_message(&message);
+
+            let mut data = vec![33];
+            snapshot.serialize(&mut data).unwrap();
+            [
+                verify_instruction(
+                    &signer.pubkey(),
+                    signature.as_ref().try_into().unwrap(),
+                    &message,
+                ),
+                Instruction::new_with_bytes(
+                    program_id,
+                    &data,
+                    vec![
+                        AccountMeta::new(mirror.pubkey(), false),
+                        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                    ],
+                ),
+            ]
+        };
+        let import_error = |code: CounterError| {
+            TransactionError::InstructionError(1, InstructionError::Custom(code as u32))
+        };
+
+        env.send(&import(&attester, 42, 1_000, now), &[])
+            .await
+            .unwrap();
+        let state = env.read_counter(&mirror).await;
+        assert_eq!(state.count, 42);
+        assert_eq!(state.imported_slot, 1_000);
+
+        // replays, old snapshots and other signers are rejected
+        let err = env
+            .send(&import(&attester, 40, 1_000, now), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), import_error(CounterError::StaleAttestation));
+
+        let err = env
+            .send(&import(&attester, 50, 2_000, now - 3_600), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), import_error(CounterError::StaleAttestation));
+
+        let err = env
+            .send(&import(&Keypair::new(), 50, 2_000, now), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), import_error(CounterError::InvalidAttestation));
+
+        // the mirror only moves through imports
+        let increment = env.counter_ix(&[1], &mirror);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::MirroredCounter as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_value_cap_policies() {
+        let mut env = TestEnv::start().await;
+        let capped = |cap_policy| InitConfig {
+            max_value: Some(10),
+            cap_policy,
+            ..InitConfig::default()
+        };
+        let rejecting = env.initialize(8, &capped(CapPolicy::Reject)).await;
+        let saturating = env.initialize(8, &capped(CapPolicy::Saturate)).await;
+
+        let mut increment_by_5 = vec![11];
+        increment_by_5.extend_from_slice(&5u64.to_le_bytes());
+
+        let increment = env.counter_ix(&increment_by_5, &rejecting);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::CapExceeded as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&rejecting).await.count, 8);
+
+        let increment = env.counter_ix(&increment_by_5, &saturating);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&saturating).await.count, 10);
+
+        // the cap also bounds SetCounter, whatever the policy
+        let mut set_data = vec![17];
+        set_data.extend_from_slice(&11u64.to_le_bytes());
+        let set = env.counter_ix(&set_data, &saturating);
+        let err = env.send(&[set], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::CapExceeded as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_increment_many() {
+        let mut env = TestEnv::start().await;
+        let mut counters = Vec::new();
+        for step in [1, 2, 5] {
+            let config = InitConfig {
+                step,
+                ..InitConfig::default()
+            };
+            counters.push(env.initialize(10, &config).await);
+        }
+
+        let mut accounts = vec![AccountMeta::new_readonly(env.payer.pubkey(), true)];
+        accounts.extend(
+            counters
+                .iter()
+                .map(|counter| AccountMeta::new(counter.pubkey(), false)),
+        );
+        let increment_many = Instruction::new_with_bytes(env.program_id, &[34], accounts.clone());
+        env.send(&[increment_many], &[]).await.unwrap();
+
+        for (counter, expected) in counters.iter().zip([11, 12, 15]) {
+            assert_eq!(env.read_counter(counter).await.count, expected);
+        }
+
+        // one foreign account fails the whole batch
+        accounts.push(AccountMeta::new(env.payer.pubkey(), false));
+        let increment_many = Instruction::new_with_bytes(env.program_id, &[34], accounts);
+        let err = env.send(&[increment_many], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+        assert_eq!(env.read_counter(&counters[0]).await.count, 11);
+    }
+
+    #[tokio::test]
+    async fn test_skim_excess_lamports() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+        let treasury = Pubkey::new_unique();
+
+        let deposit = env
+            .banks_client
+            .get_balance(counter.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(
+            env.read_counter(&counter).await.min_observed_lamports,
+            deposit
+        );
+
+        let donation = solana_sdk::system_instruction::transfer(
+            &env.payer.pubkey(),
+            &counter.pubkey(),
+            1_000_000,
+        );
+        env.send(&[donation], &[]).await.unwrap();
+
+        let program_id = env.program_id;
+        let skim = |authority: &Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &[35],
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(*authority, true),
+                    AccountMeta::new(treasury, false),
+                ],
+            )
+        };
+
+        let outsider = Keypair::new();
+        let instruction = skim(&outsider.pubkey());
+        let err = env.send(&[instruction], &[&outsider]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        let instruction = skim(&env.payer.pubkey());
+        env.send(&[instruction], &[]).await.unwrap();
+        assert_eq!(
+            env.banks_client.get_balance(treasury).await.unwrap(),
+            1_000_000
+        );
+        assert_eq!(
+            env.banks_client
+                .get_balance(counter.pubkey())
+                .await
+                .unwrap(),
+            deposit
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_close_routes_excess() {
+        let mut env = TestEnv::start().await;
+        let treasury = Pubkey::new_unique();
+        let config = InitConfig {
+            excess_destination: Some(treasury),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+        let closer = Pubkey::new_unique();
+
+        let deposit = env
+            .banks_client
+            .get_balance(counter.pubkey())
+            .await
+            .unwrap();
+        let donation = solana_sdk::system_instruction::transfer(
+            &env.payer.pubkey(),
+            &counter.pubkey(),
+            1_000_000,
+        );
+        env.send(&[donation], &[]).await.unwrap();
+
+        let program_id = env.program_id;
+        let authority = env.payer.pubkey();
+        let close = |excess: Option<Pubkey>| {
+            let mut accounts = vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(closer, false),
+            ];
+            accounts.extend(excess.map(|key| AccountMeta::new(key, false)));
+            Instruction::new_with_bytes(program_id, &[14], accounts)
+        };
+
+        // the donation may not follow the rent to the closer
+        for excess in [None, Some(closer)] {
+            let err = env.send(&[close(excess)], &[]).await.unwrap_err();
+            assert_eq!(
+                err.unwrap(),
+                TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+            );
+        }
+
+        // nor may a skim pick its own treasury
+        let skim = Instruction::new_with_bytes(
+            program_id,
+            &[35],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(closer, false),
+            ],
+        );
+        let err = env.send(&[skim], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        env.send(&[close(Some(treasury))], &[]).await.unwrap();
+        assert_eq!(
+            env.banks_client.get_balance(treasury).await.unwrap(),
+            1_000_000
+        );
+        assert_eq!(env.banks_client.get_balance(closer).await.unwrap(), deposit);
+    }
+
+    #[tokio::test]
+    async fn test_counter_templates() {
+        let mut env = TestEnv::start().await;
+        let admin = env.payer.pubkey();
+        let (template, _) = derive_template_address(&env.program_id, &admin, "faucet");
+
+        let program_id = env.program_id;
+        let set_template = |config: InitConfig| {
+            let instruction = CounterInstruction::SetTemplate {
+                name: Label32::new("faucet").unwrap(),
+                config: Box::new(config),
+            };
+            Instruction::new_with_bytes(
+                program_id,
+                &instruction.pack(),
+                vec![
+                    AccountMeta::new(template, false),
+                    AccountMeta::new(admin, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+        let from_template = |counter: &Pubkey, template: Pubkey| {
+            let instruction = CounterInstruction::InitializeFromTemplate { initial_value: 2 };
+            Instruction::new_with_bytes(
+                program_id,
+                &instruction.pack(),
+                vec![
+                    AccountMeta::new(*counter, true),
+                    AccountMeta::new(admin, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(template, false),
+                    AccountMeta::new_readonly(derive_global_config_address(&program_id).0, false),
+                ],
+            )
+        };
+
+        // templates are validated like initialize configs
+        let invalid = set_template(InitConfig {
+            max_value: Some(10),
+            value_commitment: Some([1; 32]),
+            ..InitConfig::default()
+        });
+        let err = env.send(&[invalid], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidConfig as u32)
+            )
+        );
+
+        let instruction = set_template(InitConfig {
+            step: 5,
+            max_value: Some(20),
+            cap_policy: CapPolicy::Saturate,
+            ..InitConfig::default()
+        });
+        env.send(&[instruction], &[]).await.unwrap();
+
+        let counter = Keypair::new();
+        let instruction = from_template(&counter.pubkey(), template);
+        env.send(&[instruction], &[&counter]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 2);
+        assert_eq!(state.step, 5);
+        assert_eq!(state.max_value, Some(20));
+        assert_eq!(state.cap_policy, CapPolicy::Saturate);
+
+        // replacing the template, with a larger config, affects new counters only
+        let instruction = set_template(InitConfig {
+            step: 2,
+            metrics: vec![MetricConfig {
+                name: Label32::new("claims").unwrap(),
+                initial_value: 0,
+                max_value: 0,
+            }],
+            ..InitConfig::default()
+        });
+        env.send(&[instruction], &[]).await.unwrap();
+
+        let second = Keypair::new();
+        let instruction = from_template(&second.pubkey(), template);
+        env.send(&[instruction], &[&second]).await.unwrap();
+
+        let state = env.read_counter(&second).await;
+        assert_eq!(state.step, 2);
+        assert_eq!(state.metrics.len(), 1);
+        assert_eq!(env.read_counter(&counter).await.step, 5);
+
+        // other program accounts are not templates
+        let third = Keypair::new();
+        let instruction = from_template(&third.pubkey(), counter.pubkey());
+        let err = env.send(&[instruction], &[&third]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::WrongAccountType as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_at_zero_ticket() {
+        let mut env = TestEnv::start().await;
+        let owner = Keypair::new();
+        let config = InitConfig {
+            authority: Some(owner.pubkey()),
+            close_at_zero: true,
+            ..InitConfig::default()
+        };
+        let ticket = env.initialize(2, &config).await;
+        let rent = env.banks_client.get_balance(ticket.pubkey()).await.unwrap();
+
+        let program_id = env.program_id;
+        let authority = owner.pubkey();
+        let decrement = |refund: bool| {
+            let mut accounts = vec![
+                AccountMeta::new(ticket.pubkey(), false),
+                AccountMeta::new_readonly(authority, true),
+            ];
+            if refund {
+                accounts.push(AccountMeta::new(authority, false));
+            }
+            Instruction::new_with_bytes(program_id, &[9], accounts)
+        };
+
+        // uses above zero leave the ticket open
+        env.send(&[decrement(false)], &[&owner]).await.unwrap();
+        assert_eq!(env.read_counter(&ticket).await.count, 1);
+
+        // the last use needs somewhere to send the rent
+        let err = env.send(&[decrement(false)], &[&owner]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        env.send(&[decrement(true)], &[&owner]).await.unwrap();
+        assert!(
+            env.banks_client
+                .get_account(ticket.pubkey())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(env.banks_client.get_balance(authority).await.unwrap(), rent);
+    }
+
+    #[test]
+    fn test_bounded_fields_reject_oversized_input() {
+        assert_eq!(
+            Label32::new("x".repeat(33)).unwrap_err(),
+            CounterError::LengthExceeded
+        );
+        let label = Label32::new("x".repeat(32)).unwrap();
+        let encoded = borsh::to_vec(&label).unwrap();
+        assert_eq!(encoded, borsh::to_vec(&"x".repeat(32)).unwrap());
+        assert_eq!(Label32::try_from_slice(&encoded).unwrap(), label);
+
+        // the length prefix is checked before anything is read or allocated
+        assert!(Label32::try_from_slice(&borsh::to_vec(&"x".repeat(33)).unwrap()).is_err());
+        assert!(Label32::try_from_slice(&u32::MAX.to_le_bytes()).is_err());
+        assert!(Topics::try_from_slice(&u32::MAX.to_le_bytes()).is_err());
+        assert!(CouponCode::try_from_slice(&borsh::to_vec(&vec![0u8; 65]).unwrap()).is_err());
+
+        let topics = vec![b"orders".to_vec(); 4];
+        let decoded = Topics::try_from_slice(&borsh::to_vec(&topics).unwrap()).unwrap();
+        assert_eq!(decoded, topics);
+        assert!(Topics::try_from_slice(&borsh::to_vec(&vec![b"t".to_vec(); 5]).unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registry_tracks_counters() {
+        let mut env = TestEnv::start().await;
+        let (registry, _) = derive_registry_address(&env.program_id);
+        let payer = env.payer.pubkey();
+
+        let create_registry = Instruction::new_with_bytes(
+            env.program_id,
+            &[38],
+            vec![
+                AccountMeta::new(registry, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(std::slice::from_ref(&create_registry), &[])
+            .await
+            .unwrap();
+
+        // the registry exists once per program
+        let err = env.send(&[create_registry], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::AlreadyInitialized as u32)
+            )
+        );
+
+        let counter = Keypair::new();
+        let mut data = vec![29];
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(derive_global_config_address(&env.program_id).0, false),
+                AccountMeta::new(registry, false),
+            ],
+        );
+        env.send(&[initialize], &[&counter]).await.unwrap();
+
+        let mut increment_by_5 = vec![11];
+        increment_by_5.extend_from_slice(&5u64.to_le_bytes());
+        let mut increment = env.counter_ix(&increment_by_5, &counter);
+        increment.accounts.push(AccountMeta::new(registry, false));
+        env.send(&[increment], &[]).await.unwrap();
+
+        // operations that leave the registry out are not counted
+        let increment = env.counter_ix(&increment_by_5, &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 13);
+
+        // batches count, and counters closing themselves at zero
+        let increment_many = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::IncrementMany.pack(),
+            vec![
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(registry, false),
+            ],
+        );
+        env.send(&[increment_many], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 14);
+
+        let config = InitConfig {
+            close_at_zero: true,
+            ..InitConfig::default()
+        };
+        let used_up = env.initialize(1, &config).await;
+        let mut decrement = env.counter_ix(&[9], &used_up);
+        decrement.accounts[1].is_writable = true;
+        decrement.accounts.push(AccountMeta::new(registry, false));
+        env.send(&[decrement], &[]).await.unwrap();
+
+        let close = Instruction::new_with_bytes(
+            env.program_id,
+            &[14],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(payer, false),
+                AccountMeta::new(registry, false),
+            ],
+        );
+        env.send(&[close], &[]).await.unwrap();
+
+        let account = env
+            .banks_client
+            .get_account(registry)
+            .await
+            .unwrap()
+            .unwrap();
+        let state = Registry::try_from_slice(&account.data).unwrap();
+        assert_eq!(state.counters_created, 1);
+        assert_eq!(state.counters_closed, 2);
+        assert_eq!(state.total_incremented, 8);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        use crate::client::{add_delegate_ix, set_topics_ix};
+        use crate::export::{ImportError, build_import_transaction, export_counter_json};
+
+        let mut env = TestEnv::start().await;
+        let payer = env.payer.pubkey();
+        let config = InitConfig {
+            circuit_breaker: Some(CircuitBreakerConfig {
+                max_delta: 50,
+                window_slots: 100,
+            }),
+            metrics: vec![MetricConfig {
+                name: Label32::new("logins").unwrap(),
+                initial_value: 3,
+                max_value: 10,
+            }],
+            gates: Some(GateConfig {
+                flags: GATE_COOLDOWN,
+                cooldown_slots: 5,
+                ..GateConfig::default()
+            }),
+            step: 2,
+            max_value: Some(1_000),
+            cap_policy: CapPolicy::Saturate,
+            close_at_zero: true,
+            ..InitConfig::default()
+        };
+        let original = env.initialize(7, &config).await;
+        let topics = Topics::new(vec![Topic::new(b"orders".to_vec()).unwrap()]).unwrap();
+        let set_topics = set_topics_ix(&env.program_id, &original.pubkey(), &payer, topics);
+        let delegate = Pubkey::new_unique();
+        let add_delegate = add_delegate_ix(&env.program_id, &original.pubkey(), &payer, &delegate);
+        env.send(&[set_topics, add_delegate], &[]).await.unwrap();
+
+        let exported = export_counter_json(&env.read_counter(&original).await);
+
+        let copy = Keypair::new();
+        let (address, message) =
+            build_import_transaction(&env.program_id, &exported, &copy.pubkey(), &payer).unwrap();
+        assert_eq!(address, copy.pubkey());
+
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction.sign(&[&env.payer, &copy], env.blockhash);
+        env.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        let reimported = export_counter_json(&env.read_counter(&copy).await);
+        assert_eq!(reimported, exported);
+
+        // exports from a newer format are refused rather than half-applied
+        let future = exported.replace("\"version\":1", "\"version\":2");
+        assert!(matches!(
+            build_import_transaction(&env.program_id, &future, &copy.pubkey(), &payer),
+            Err(ImportError::Version(2))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_increment_modes() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+        assert_eq!(
+            env.read_counter(&counter).await.increment_mode(),
+            IncrementMode::Permissioned
+        );
+
+        // permissioned counters only take the authority's signature
+        let stranger = Keypair::new();
+        let increment = env.counter_ix_as(&[1], &counter, &stranger.pubkey());
+        let err = env.send(&[increment], &[&stranger]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        // naming the authority is not enough without its signature
+        let owner = Keypair::new();
+        let config = InitConfig {
+            authority: Some(owner.pubkey()),
+            ..InitConfig::default()
+        };
+        let owned = env.initialize(0, &config).await;
+        let mut unsigned = env.counter_ix_as(&[1], &owned, &owner.pubkey());
+        unsigned.accounts[1].is_signer = false;
+        let err = env.send(&[unsigned], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&owned).await.count, 0);
+
+        let mut permissionless = vec![39];
+        IncrementMode::Permissionless
+            .serialize(&mut permissionless)
+            .unwrap();
+
+        // only the authority may change the mode
+        let set_mode = env.counter_ix_as(&permissionless, &counter, &stranger.pubkey());
+        assert!(env.send(&[set_mode], &[&stranger]).await.is_err());
+
+        let set_mode = env.counter_ix(&permissionless, &counter);
+        env.send(&[set_mode], &[]).await.unwrap();
+
+        let increment = env.counter_ix_as(&[1], &counter, &stranger.pubkey());
+        env.send(&[increment], &[&stranger]).await.unwrap();
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 1);
+        assert_eq!(state.increment_mode(), IncrementMode::Permissionless);
+
+        // counters can also start out permissionless
+        let config = InitConfig {
+            gates: Some(GateConfig::default()),
+            ..InitConfig::default()
+        };
+        let open = env.initialize(0, &config).await;
+        let increment = env.counter_ix_as(&[1], &open, &stranger.pubkey());
+        env.send(&[increment], &[&stranger]).await.unwrap();
+        assert_eq!(env.read_counter(&open).await.count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+        let paused = |index| {
+            TransactionError::InstructionError(
+                index,
+                InstructionError::Custom(CounterError::CounterPaused as u32),
+            )
+        };
+
+        // only the authority can pause
+        let stranger = Keypair::new();
+        let pause = env.counter_ix_as(&[40], &counter, &stranger.pubkey());
+        assert!(env.send(&[pause], &[&stranger]).await.is_err());
+
+        let pause = env.counter_ix(&[40], &counter);
+        env.send(&[pause], &[]).await.unwrap();
+        assert!(env.read_counter(&counter).await.is_paused);
+
+        let increment = env.counter_ix(&[1], &counter);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), paused(0));
+
+        // configuration changes are frozen too, not just the count
+        let mut set_step = vec![30];
+        set_step.extend_from_slice(&5u64.to_le_bytes());
+        let set_step = env.counter_ix(&set_step, &counter);
+        let err = env.send(&[set_step], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), paused(0));
+
+        let close = Instruction::new_with_bytes(
+            env.program_id,
+            &[14],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(env.payer.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), false),
+            ],
+        );
+        let err = env.send(&[close], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), paused(0));
+
+        let resume = env.counter_ix(&[41], &counter);
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[resume, increment], &[]).await.unwrap();
+        let state = env.read_counter(&counter).await;
+        assert!(!state.is_paused);
+        assert_eq!(state.count, 1);
+    }
+
+    #[cfg(not(feature = "test-sbf"))]
+    #[tokio::test]
+    async fn test_compute_floor_aborts_before_side_effects() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            min_compute_units: 10_000,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(3, &config).await;
+
+        // natively run programs see no compute left, so any floor trips
+        let increment = env.counter_ix(&[1], &counter);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InsufficientCompute as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 3);
+
+        // counters without a floor never query the meter
+        let unchecked = env.initialize(3, &InitConfig::default()).await;
+        let increment = env.counter_ix(&[1], &unchecked);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&unchecked).await.count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_counters_by_external_id() {
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let owner = env.payer.pubkey();
+        let external_id = external_id_hash(b"order-48151623");
+        let (counter, _) = derive_external_counter_address(&program_id, &owner, &external_id);
+
+        let initialize_at = |address: Pubkey| {
+            let mut data = vec![42];
+            data.extend_from_slice(&2u64.to_le_bytes());
+            data.extend_from_slice(&external_id);
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(address, false),
+                    AccountMeta::new(owner, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(derive_global_config_address(&program_id).0, false),
+                ],
+            )
+        };
+        env.send(&[initialize_at(counter)], &[]).await.unwrap();
+
+        let increment = Instruction::new_with_bytes(
+            program_id,
+            &[1],
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(owner, true),
+            ],
+        );
+        env.send(&[increment], &[]).await.unwrap();
+
+        let state = env.read_counter_at(counter).await;
+        assert_eq!(state.count, 3);
+        assert_eq!(state.external_id, Some(external_id));
+
+        // one counter per owner and ID
+        assert!(env.send(&[initialize_at(counter)], &[]).await.is_err());
+
+        // the ID must be the one the address was derived from
+        let (other, _) =
+            derive_external_counter_address(&program_id, &owner, &external_id_hash(b"order-2"));
+        let err = env.send(&[initialize_at(other)], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidCounterAddress as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_record_history() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(5, &InitConfig::default()).await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+        let snapshot_ix = |snapshot: Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &[43],
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(snapshot, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+
+        let mut snapshots = Vec::new();
+        for index in 0..2 {
+            let (snapshot, _) = derive_snapshot_address(&program_id, &counter.pubkey(), index);
+            let instructions = [snapshot_ix(snapshot), env.counter_ix(&[1], &counter)];
+            env.send(&instructions, &[]).await.unwrap();
+            snapshots.push(snapshot);
+        }
+        assert_eq!(env.read_counter(&counter).await.snapshot_count, 2);
+
+        for (index, snapshot) in snapshots.into_iter().enumerate() {
+            let account = env
+                .banks_client
+                .get_account(snapshot)
+                .await
+                .unwrap()
+                .unwrap();
+            let snapshot = Snapshot::try_from_slice(&account.data).unwrap();
+            assert_eq!(snapshot.counter, counter.pubkey());
+            assert_eq!(snapshot.index, index as u64);
+            assert_eq!(snapshot.count, 5 + index as u64);
+        }
+
+        // snapshots are only written at the next index
+        let (stale, _) = derive_snapshot_address(&program_id, &counter.pubkey(), 0);
+        assert!(env.send(&[snapshot_ix(stale)], &[]).await.is_err());
+        let (skipped, _) = derive_snapshot_address(&program_id, &counter.pubkey(), 3);
+        let err = env.send(&[snapshot_ix(skipped)], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_counter_batcher_flushes() {
+        use crate::batcher::{CounterBatcher, DeliveryMode};
+        use std::time::{Duration, Instant};
+
+        let mut env = TestEnv::start().await;
+        let first = env.initialize(0, &InitConfig::default()).await;
+        let second = env.initialize(0, &InitConfig::default()).await;
+
+        let mut batcher = CounterBatcher::new(
+            env.program_id,
+            env.payer.pubkey(),
+            DeliveryMode::ExactlyOnce,
+            Duration::from_secs(60),
+            3,
+        );
+        for _ in 0..100 {
+            batcher.record(first.pubkey(), 1).unwrap();
+        }
+        batcher.record(second.pubkey(), 7).unwrap();
+        assert!(!batcher.should_flush(Instant::now()));
+
+        let flushes = batcher.flush(env.blockhash);
+        assert_eq!(flushes.len(), 1);
+        assert_eq!(batcher.pending(&first.pubkey()), 0);
+        let flush = &flushes[0];
+
+        let transaction = Transaction::new(&[&env.payer], flush.message.clone(), env.blockhash);
+        env.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        // an unconfirmed flush is resent as the very same transaction, which
+        // the runtime recognises and does not apply again
+        let resend = batcher.fail(flush.key).unwrap();
+        assert_eq!(resend, flush.message);
+        let transaction = Transaction::new(&[&env.payer], resend, env.blockhash);
+        env.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+        batcher.confirm(flush.key);
+        assert_eq!(batcher.in_flight().count(), 0);
+
+        assert_eq!(env.read_counter(&first).await.count, 100);
+        assert_eq!(env.read_counter(&second).await.count, 7);
+
+        // at-least-once hands failed deltas straight back to the buffer
+        let mut batcher = CounterBatcher::new(
+            env.program_id,
+            env.payer.pubkey(),
+            DeliveryMode::AtLeastOnce,
+            Duration::ZERO,
+            3,
+        );
+        batcher.record(first.pubkey(), 5).unwrap();
+        assert!(batcher.should_flush(Instant::now()));
+        let flushes = batcher.flush(env.blockhash);
+        assert_eq!(batcher.fail(flushes[0].key), None);
+        assert_eq!(batcher.pending(&first.pubkey()), 5);
+    }
+
+    #[tokio::test]
+    async fn test_increment_history_ring_buffer() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            gates: Some(GateConfig::default()),
+            history_len: 2,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        // the buffer is allocated up front, empty
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.history.entries.len(), 2);
+        assert_eq!(state.history.iter().count(), 0);
+
+        let stranger = Keypair::new();
+        let increment_by = |amount: u64| {
+            let mut data = vec![11];
+            data.extend_from_slice(&amount.to_le_bytes());
+            data
+        };
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        let increment = env.counter_ix_as(&increment_by(5), &counter, &stranger.pubkey());
+        env.send(&[increment], &[&stranger]).await.unwrap();
+        let increment = env.counter_ix(&increment_by(3), &counter);
+        env.send(&[increment], &[]).await.unwrap();
+
+        // only the last two increments are kept, oldest first
+        let state = env.read_counter(&counter).await;
+        let history: Vec<(u64, Pubkey)> = state
+            .history
+            .iter()
+            .map(|entry: &HistoryEntry| (entry.delta, entry.actor))
+            .collect();
+        assert_eq!(
+            history,
+            vec![(5, stranger.pubkey()), (3, env.payer.pubkey())]
+        );
+
+        let config = InitConfig {
+            history_len: MAX_HISTORY as u16 + 1,
+            ..InitConfig::default()
+        };
+        let mut data = vec![0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        config.serialize(&mut data).unwrap();
+        let oversized = Keypair::new();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(oversized.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[initialize], &[&oversized]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delegates_increment_for_authority() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+        let delegate = Keypair::new();
+        let (program_id, payer) = (env.program_id, env.payer.pubkey());
+        let delegate_ix = |tag: u8, key: &Pubkey| {
+            let mut data = vec![tag];
+            data.extend_from_slice(key.as_ref());
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+        let add = delegate_ix(44, &delegate.pubkey());
+        let remove = delegate_ix(45, &delegate.pubkey());
+
+        env.send(std::slice::from_ref(&add), &[]).await.unwrap();
+        assert_eq!(
+            env.read_counter(&counter).await.delegates,
+            vec![delegate.pubkey()]
+        );
+
+        // adding the same key twice is refused
+        let err = env.send(&[add], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        let increment = env.counter_ix_as(&[1], &counter, &delegate.pubkey());
+        env.send(&[increment], &[&delegate]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+
+        // delegates only stand in for the authority gate, not for admin changes
+        let mut set_step = vec![30];
+        set_step.extend_from_slice(&5u64.to_le_bytes());
+        let set_step = env.counter_ix_as(&set_step, &counter, &delegate.pubkey());
+        assert!(env.send(&[set_step], &[&delegate]).await.is_err());
+
+        env.send(&[remove], &[]).await.unwrap();
+        let increment = env.counter_ix_as(&[1], &counter, &delegate.pubkey());
+        let err = env.send(&[increment], &[&delegate]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        // the list is bounded
+        let adds: Vec<Instruction> = (0..=MAX_DELEGATES)
+            .map(|_| delegate_ix(44, &Pubkey::new_unique()))
+            .collect();
+        let err = env.send(&adds, &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                MAX_DELEGATES as u8,
+                InstructionError::Custom(CounterError::LengthExceeded as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_log_records_privileged_operations() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            access_log_len: 3,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+
+        let mut set_step = vec![30];
+        set_step.extend_from_slice(&5u64.to_le_bytes());
+        let set_step = env.counter_ix(&set_step, &counter);
+        env.send(&[set_step], &[]).await.unwrap();
+
+        // plain increments and rejected attempts are not logged
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        let stranger = Keypair::new();
+        let pause = env.counter_ix_as(&[40], &counter, &stranger.pubkey());
+        assert!(env.send(&[pause], &[&stranger]).await.is_err());
+
+        for tag in [40, 41, 16] {
+            let ix = env.counter_ix(&[tag], &counter);
+            env.send(&[ix], &[]).await.unwrap();
+        }
+
+        // the oldest entry has been overwritten
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.count, 0);
+        let log: Vec<(Pubkey, u8)> = state
+            .access_log
+            .iter()
+            .map(|entry: &AccessLogEntry| (entry.actor, entry.instruction))
+            .collect();
+        let authority = env.payer.pubkey();
+        assert_eq!(log, vec![(authority, 40), (authority, 41), (authority, 16)]);
+    }
+
+    // minimal second implementer of the counter interface, keeping its
+    // count as the first 8 bytes of the counter account
+    fn toy_counter(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let counter = &accounts[0];
+        let count = interface::parse_value(&counter.data.borrow())?;
+
+        match CounterInterfaceInstruction::unpack(instruction_data)? {
+            Some(CounterInterfaceInstruction::Increment { amount }) => {
+                counter.data.borrow_mut()[..8].copy_from_slice(&(count + amount).to_le_bytes());
+            }
+            Some(CounterInterfaceInstruction::GetValue) => {
+                solana_program::program::set_return_data(&count.to_le_bytes())
+            }
+            None => return Err(ProgramError::InvalidInstructionData),
+        }
+        Ok(())
+    }
+
+    // reads any implementer's counter through the interface and records the
+    // value; accounts: counter, its program, record
+    fn interface_reader(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        _instruction_data: &[u8],
+    ) -> ProgramResult {
+        let value = interface::get_value(&accounts[0])?;
+        accounts[2].data.borrow_mut()[..8].copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_counter_interface_across_implementers() {
+        let toy_id = Pubkey::new_unique();
+        let reader_id = Pubkey::new_unique();
+        let toy_counter_key = Pubkey::new_unique();
+        let record = Pubkey::new_unique();
+
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_program("toy_counter", toy_id, processor!(toy_counter));
+            program_test.add_program("interface_reader", reader_id, processor!(interface_reader));
+            program_test.add_account(
+                toy_counter_key,
+                Account {
+                    lamports: 1_000_000,
+                    data: 40u64.to_le_bytes().to_vec(),
+                    owner: toy_id,
+                    ..Account::default()
+                },
+            );
+            program_test.add_account(
+                record,
+                Account {
+                    lamports: 1_000_000,
+                    data: vec![0; 8],
+                    owner: reader_id,
+                    ..Account::default()
+                },
+            );
+        })
+        .await;
+        let counter = env.initialize(1, &InitConfig::default()).await;
+        let payer = env.payer.pubkey();
+
+        // the same helpers drive both programs
+        for (program_id, counter) in [
+            (env.program_id, counter.pubkey()),
+            (toy_id, toy_counter_key),
+        ] {
+            let increment = interface::increment_ix(&program_id, &counter, &payer, 2, &[]);
+            env.send(&[increment], &[]).await.unwrap();
+        }
+        assert_eq!(env.read_counter(&counter).await.count, 3);
+        assert_eq!(
+            interface::read_count::<CounterAccount>(
+                &env.banks_client
+                    .get_account(counter.pubkey())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .data
+            ),
+            Ok(3)
+        );
+
+        for (program_id, counter, expected) in [
+            (env.program_id, counter.pubkey(), 3),
+            (toy_id, toy_counter_key, 42),
+        ] {
+            let read = Instruction::new_with_bytes(
+                reader_id,
+                &[],
+                vec![
+                    AccountMeta::new_readonly(counter, false),
+                    AccountMeta::new_readonly(program_id, false),
+                    AccountMeta::new(record, false),
+                ],
+            );
+            env.send(&[read], &[]).await.unwrap();
+            let account = env.banks_client.get_account(record).await.unwrap().unwrap();
+            assert_eq!(interface::parse_value(&account.data), Ok(expected));
+        }
+
+        // interface data with a bad payload is rejected, not read as a tag
+        let mut data = INCREMENT_DISCRIMINATOR.to_vec();
+        data.push(1);
+        let increment = env.counter_ix(&data, &counter);
+        assert!(env.send(&[increment], &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multisig_authority_approvals() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(7, &InitConfig::default()).await;
+        let owners = [Keypair::new(), Keypair::new(), Keypair::new()];
+        let program_id = env.program_id;
+
+        let create_multisig = |multisig: &Pubkey, payer: &Pubkey, threshold: u8| {
+            let signers = owners.iter().map(|owner| owner.pubkey()).collect();
+            let mut data = vec![46, threshold];
+            MultisigSigners::new(signers)
+                .unwrap()
+                .serialize(&mut data)
+                .unwrap();
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(*multisig, true),
+                    AccountMeta::new(*payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+        // the multisig stands in the authority slot without signing, its
+        // approving signers follow
+        let approved = |data: &[u8], multisig: &Pubkey, signers: &[&Keypair]| {
+            let mut accounts = vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(*multisig, false),
+            ];
+            accounts.extend(
+                signers
+                    .iter()
+                    .map(|signer| AccountMeta::new_readonly(signer.pubkey(), true)),
+            );
+            Instruction::new_with_bytes(program_id, data, accounts)
+        };
+        let payer = env.payer.pubkey();
+
+        // the threshold must be reachable
+        let unreachable = Keypair::new();
+        let create = create_multisig(&unreachable.pubkey(), &payer, 4);
+        let err = env.send(&[create], &[&unreachable]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        let multisig = Keypair::new();
+        let create = create_multisig(&multisig.pubkey(), &payer, 2);
+        env.send(&[create], &[&multisig]).await.unwrap();
+
+        let mut transfer_data = vec![13];
+        transfer_data.extend_from_slice(multisig.pubkey().as_ref());
+        let transfer = env.counter_ix(&transfer_data, &counter);
+        env.send(&[transfer], &[]).await.unwrap();
+
+        // one listed signer plus an unlisted one is not enough
+        let stranger = Keypair::new();
+        let reset = approved(&[16], &multisig.pubkey(), &[&owners[0], &stranger]);
+        let err = env
+            .send(&[reset], &[&owners[0], &stranger])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::MultisigThresholdNotMet as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 7);
+
+        let reset = approved(&[16], &multisig.pubkey(), &[&owners[0], &owners[2]]);
+        env.send(&[reset], &[&owners[0], &owners[2]]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 0);
+
+        // the multisig's own key approves nothing outside the three
+        // multisig instructions
+        let pause = approved(&[40], &multisig.pubkey(), &[&owners[0], &owners[1]]);
+        assert!(env.send(&[pause], &[&owners[0], &owners[1]]).await.is_err());
+
+        let mut transfer_data = vec![13];
+        transfer_data.extend_from_slice(payer.as_ref());
+        let transfer = approved(
+            &transfer_data,
+            &multisig.pubkey(),
+            &[&owners[1], &owners[2]],
+        );
+        env.send(&[transfer], &[&owners[1], &owners[2]])
+            .await
+            .unwrap();
+        assert_eq!(env.read_counter(&counter).await.authority, payer);
+    }
+
+    // mostly small values, sometimes close enough to u64::MAX to overflow
+    fn model_amount() -> impl proptest::strategy::Strategy<Value = u64> {
+        use proptest::prelude::prop_oneof;
+
+        prop_oneof![3 => 0..8u64, 1 => u64::MAX - 7..=u64::MAX]
+    }
+
+    // the instructions the model covers; actors are indexes into the test's
+    // keypairs
+    #[derive(Debug, Clone, Copy)]
+    enum ModelOp {
+        Increment,
+        Decrement,
+        IncrementBy(u64),
+        DecrementBy(u64),
+        SetStep(u64),
+        Reset,
+        Set(u64),
+        Pause,
+        Resume,
+        SetMode(IncrementMode),
+        TransferAuthority(usize),
+    }
+
+    impl ModelOp {
+        fn strategy(actors: usize) -> impl proptest::strategy::Strategy<Value = Self> {
+            use proptest::prelude::{Just, Strategy, prop_oneof};
+
+            prop_oneof![
+                Just(Self::Increment),
+                Just(Self::Decrement),
+                model_amount().prop_map(Self::IncrementBy),
+                model_amount().prop_map(Self::DecrementBy),
+                (0..4u64).prop_map(Self::SetStep),
+                Just(Self::Reset),
+                model_amount().prop_map(Self::Set),
+                Just(Self::Pause),
+                Just(Self::Resume),
+                Just(Self::SetMode(IncrementMode::Permissionless)),
+                Just(Self::SetMode(IncrementMode::Permissioned)),
+                (0..actors).prop_map(Self::TransferAuthority),
+            ]
+        }
+
+        fn pack(self, actors: &[Keypair]) -> Vec<u8> {
+            let instruction = match self {
+                Self::Increment => CounterInstruction::IncrementCounter,
+                Self::Decrement => CounterInstruction::DecrementCounter,
+                Self::IncrementBy(amount) => CounterInstruction::IncrementBy { amount },
+                Self::DecrementBy(amount) => CounterInstruction::DecrementBy { amount },
+                Self::SetStep(step) => CounterInstruction::SetStep { step },
+                Self::Reset => CounterInstruction::ResetCounter,
+                Self::Set(value) => CounterInstruction::SetCounter { value },
+                Self::Pause => CounterInstruction::Pause,
+                Self::Resume => CounterInstruction::Resume,
+                Self::SetMode(mode) => CounterInstruction::SetMode { mode },
+                Self::TransferAuthority(to) => CounterInstruction::TransferAuthority {
+                    new_authority: actors[to].pubkey(),
+                },
+            };
+            instruction.pack()
+        }
+    }
+
+    // pure reference model of the counter fields the ops touch, with the
+    // processor's check order
+    #[derive(Debug, Clone, PartialEq)]
+    struct CounterModel {
+        count: u64,
+        step: u64,
+        authority: usize,
+        paused: bool,
+        mode: IncrementMode,
+    }
+
+    impl CounterModel {
+        fn apply(&mut self, actor: usize, op: ModelOp) -> Result<(), CounterError> {
+            let authority = if actor == self.authority {
+                Ok(())
+            } else {
+                Err(CounterError::Unauthorized)
+            };
+            let gate = match self.mode {
+                IncrementMode::Permissionless => Ok(()),
+                IncrementMode::Permissioned => authority,
+            };
+            let not_paused = if self.paused {
+                Err(CounterError::CounterPaused)
+            } else {
+                Ok(())
+            };
+
+            match op {
+                ModelOp::Increment | ModelOp::IncrementBy(_) => {
+                    gate?;
+                    not_paused?;
+                    let amount = match op {
+                        ModelOp::IncrementBy(amount) => amount,
+                        _ => self.step,
+                    };
+                    self.count = self
+                        .count
+                        .checked_add(amount)
+                        .ok_or(CounterError::Overflow)?;
+                }
+                ModelOp::Decrement | ModelOp::DecrementBy(_) => {
+                    gate?;
+                    not_paused?;
+                    let amount = match op {
+                        ModelOp::DecrementBy(amount) => amount,
+                        _ => self.step,
+                    };
+                    self.count = self
+                        .count
+                        .checked_sub(amount)
+                        .ok_or(CounterError::Underflow)?;
+                }
+                ModelOp::SetStep(step) => {
+                    authority?;
+                    not_paused?;
+                    if step == 0 {
+                        return Err(CounterError::InvalidConfig);
+                    }
+                    self.step = step;
+                }
+                ModelOp::Reset | ModelOp::Set(_) => {
+                    authority?;
+                    not_paused?;
+                    self.count = match op {
+                        ModelOp::Set(value) => value,
+                        _ => 0,
+                    };
+                }
+                ModelOp::Pause | ModelOp::Resume => {
+                    authority?;
+                    self.paused = matches!(op, ModelOp::Pause);
+                }
+                ModelOp::SetMode(mode) => {
+                    authority?;
+                    not_paused?;
+                    self.mode = mode;
+                }
+                ModelOp::TransferAuthority(to) => {
+                    authority?;
+                    not_paused?;
+                    self.authority = to;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // a sync test driving its own runtime, since proptest's runner shrinks a
+    // failing sequence by calling back into the closure
+    #[test]
+    fn test_random_sequences_match_reference_model() {
+        use proptest::collection::vec;
+        use proptest::test_runner::{Config, TestRunner};
+        use std::cell::RefCell;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let env = RefCell::new(runtime.block_on(TestEnv::start()));
+        // the payer starts out as the authority
+        let actors = [env.borrow().payer.insecure_clone(), Keypair::new()];
+        let strategy = vec((0..actors.len(), ModelOp::strategy(actors.len())), 1..40);
+
+        let mut runner = TestRunner::new(Config {
+            cases: 8,
+            ..Config::default()
+        });
+        let result = runner.run(&strategy, |sequence| {
+            let mut env = env.borrow_mut();
+            runtime.block_on(async {
+                let counter = env.initialize(0, &InitConfig::default()).await;
+                let mut model = CounterModel {
+                    count: 0,
+                    step: 1,
+                    authority: 0,
+                    paused: false,
+                    mode: IncrementMode::Permissioned,
+                };
+
+                for (step, (actor, op)) in sequence.into_iter().enumerate() {
+                    let context = format!("step {step}: {op:?} by actor {actor}");
+
+                    let instruction =
+                        env.counter_ix_as(&op.pack(&actors), &counter, &actors[actor].pubkey());
+                    let signers: &[&Keypair] = if actor == 0 { &[] } else { &[&actors[actor]] };
+                    let result = env.send(&[instruction], signers).await;
+
+                    let expected = model.apply(actor, op);
+                    match (result, expected) {
+                        (Ok(()), Ok(())) => {}
+                        (Err(err), Err(expected)) => proptest::prop_assert_eq!(
+                            err.unwrap(),
+                            TransactionError::InstructionError(
+                                0,
+                                InstructionError::Custom(expected as u32)
+                            ),
+                            "{}",
+                            context
+                        ),
+                        (result, expected) => proptest::prop_assert!(
+                            false,
+                            "{}: processor gave {:?}, model {:?}",
+                            context,
+                            result,
+                            expected
+                        ),
+                    }
+
+                    let state = env.read_counter(&counter).await;
+                    let observed = CounterModel {
+                        count: state.count,
+                        step: state.step,
+                        authority: actors
+                            .iter()
+                            .position(|actor| actor.pubkey() == state.authority)
+                            .unwrap(),
+                        paused: state.is_paused,
+                        mode: state.increment_mode(),
+                    };
+                    proptest::prop_assert_eq!(observed, model.clone(), "{}", context);
+                }
+                Ok(())
+            })
+        });
+        if let Err(err) = result {
+            panic!("{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_counter_timestamps() {
+        use solana_sdk::clock::Clock;
+
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(0, &InitConfig::default()).await;
+        let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.created_at, clock.unix_timestamp);
+        assert_eq!(state.last_updated, clock.unix_timestamp);
+
+        let later = clock.unix_timestamp + 3_600;
+        env.context.set_sysvar(&Clock {
+            unix_timestamp: later,
+            ..clock
+        });
+
+        // configuration changes count as updates, not just the count
+        let mut set_step = vec![30];
+        set_step.extend_from_slice(&2u64.to_le_bytes());
+        let set_step = env.counter_ix(&set_step, &counter);
+        env.send(&[set_step], &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.created_at, clock.unix_timestamp);
+        assert_eq!(state.last_updated, later);
+
+        // a rejected mutation leaves the timestamp alone
+        env.context.set_sysvar(&Clock {
+            unix_timestamp: later + 60,
+            ..clock
+        });
+        let stranger = Keypair::new();
+        let increment = env.counter_ix_as(&[1], &counter, &stranger.pubkey());
+        assert!(env.send(&[increment], &[&stranger]).await.is_err());
+        assert_eq!(env.read_counter(&counter).await.last_updated, later);
+
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.last_updated, later + 60);
+    }
+
+    // every way of breaking `instruction`'s fixed accounts: dropping
+    // them, clearing signer and writable flags and swapping pairs, each with
+    // the exact error expected where there is one
+    fn account_permutations(
+        instruction: &Instruction,
+    ) -> Vec<(String, Instruction, Option<InstructionError>)> {
+        let fixed = meta(instruction.data[0]).unwrap().accounts.len();
+        let mut permutations = vec![];
+        let with_accounts = |accounts: Vec<AccountMeta>| Instruction {
+            accounts,
+            ..instruction.clone()
+        };
+
+        for len in 0..fixed {
+            permutations.push((
+                format!("only {len} accounts"),
+                with_accounts(instruction.accounts[..len].to_vec()),
+                Some(InstructionError::NotEnoughAccountKeys),
+            ));
+        }
+
+        for index in 0..fixed {
+            let mut accounts = instruction.accounts.clone();
+            if accounts[index].is_signer {
+                accounts[index].is_signer = false;
+                permutations.push((
+                    format!("account {index} not signing"),
+                    with_accounts(accounts),
+                    None,
+                ));
+            }
+
+            let mut accounts = instruction.accounts.clone();
+            if accounts[index].is_writable {
+                accounts[index].is_writable = false;
+                permutations.push((
+                    format!("account {index} read-only"),
+                    with_accounts(accounts),
+                    None,
+                ));
+            }
+
+            for other in index + 1..fixed {
+                let mut accounts = instruction.accounts.clone();
+                accounts.swap(index, other);
+                permutations.push((
+                    format!("accounts {index} and {other} swapped"),
+                    with_accounts(accounts),
+                    None,
+                ));
+            }
+        }
+
+        permutations
+    }
+
+    // sends every permutation of `instruction`, plus copies of its
+    // program-owned accounts under a foreign owner, and expects each to fail
+    // cleanly; then sends the original to show the failures were not for
+    // some unrelated reason. `preceding` go ahead of it in each transaction.
+    async fn assert_rejects_account_permutations(
+        env: &mut TestEnv,
+        name: &str,
+        preceding: &[Instruction],
+        instruction: Instruction,
+        keypairs: &[&Keypair],
+    ) {
+        let mut permutations = account_permutations(&instruction);
+
+        // a keypair, so the copy of a signing account can still sign
+        let mut impostors = vec![];
+        for (index, meta) in instruction.accounts.iter().enumerate() {
+            let Some(account) = env.banks_client.get_account(meta.pubkey).await.unwrap() else {
+                continue;
+            };
+            if account.owner != env.program_id {
+                continue;
+            }
+
+            let impostor = Keypair::new();
+            env.context.set_account(
+                &impostor.pubkey(),
+                &Account {
+                    owner: Pubkey::new_unique(),
+                    ..account
+                }
+                .into(),
+            );
+            let mut accounts = instruction.accounts.clone();
+            accounts[index].pubkey = impostor.pubkey();
+            impostors.push(impostor);
+            permutations.push((
+                format!("account {index} owned by another program"),
+                Instruction {
+                    accounts,
+                    ..instruction.clone()
+                },
+                None,
+            ));
+        }
+
+        let signers_of = |instruction: &Instruction| -> Vec<&Keypair> {
+            keypairs
+                .iter()
+                .copied()
+                .chain(&impostors)
+                .filter(|keypair| {
+                    instruction
+                        .accounts
+                        .iter()
+                        .any(|meta| meta.is_signer && meta.pubkey == keypair.pubkey())
+                })
+                .collect()
+        };
+
+        let index = preceding.len() as u8;
+        let transaction = |instruction: Instruction| {
+            let mut instructions = preceding.to_vec();
+            instructions.push(instruction);
+            instructions
+        };
+        for (permutation, instruction, expected) in permutations {
+            let signers = signers_of(&instruction);
+            let Err(err) = env.send(&transaction(instruction), &signers).await else {
+                panic!("{name}: {permutation} was accepted");
+            };
+            let TransactionError::InstructionError(failed, error) = err.unwrap() else {
+                panic!("{name}: {permutation} failed outside the instruction");
+            };
+            assert_eq!(failed, index, "{name}: {permutation} failed elsewhere");
+            assert_ne!(
+                error,
+                InstructionError::ProgramFailedToComplete,
+                "{name}: {permutation} aborted"
+            );
+            if let Some(expected) = expected {
+                assert_eq!(error, expected, "{name}: {permutation}");
+            }
+        }
+
+        let signers = signers_of(&instruction);
+        env.send(&transaction(instruction), &signers)
+            .await
+            .unwrap_or_else(|err| panic!("{name}: original failed with {err:?}"));
+    }
+
+    // forwards its accounts to the counter program whose id follows the tag
+    // byte in `instruction_data`, signing as its viewer PDA wherever that
+    // is passed, so `GetCount` can be sent with any account list. The
+    // program itself must be among the accounts for the invoke to resolve.
+    fn view_forwarder(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let (viewer, bump) = derive_viewer_authority(program_id);
+        let counter_program = Pubkey::try_from(&instruction_data[1..]).unwrap();
+        if !accounts
+            .iter()
+            .any(|account| account.key == &counter_program)
+        {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let metas = accounts
+            .iter()
+            .filter(|account| account.key != &counter_program)
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer || account.key == &viewer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let forwarded = Instruction::new_with_bytes(counter_program, &instruction_data[..1], metas);
+        solana_program::program::invoke_signed(&forwarded, accounts, &[&[VIEWER_SEED, &[bump]]])
+    }
+
+    // an instruction set up to succeed, for `assert_rejects_account_permutations`
+    struct PermutationCase {
+        instruction: Instruction,
+        // signers besides the owner and the fee payer
+        keypairs: Vec<Keypair>,
+        // sent ahead of the instruction in the same transaction
+        preceding: Vec<Instruction>,
+    }
+
+    impl From<Instruction> for PermutationCase {
+        fn from(instruction: Instruction) -> Self {
+            Self {
+                instruction,
+                keypairs: vec![],
+                preceding: vec![],
+            }
+        }
+    }
+
+    // instructions that take only a counter at 5 and its authority, signing
+    fn authority_instruction(name: &str) -> Option<CounterInstruction> {
+        Some(match name {
+            "IncrementCounter" => CounterInstruction::IncrementCounter,
+            "ResetCircuitBreaker" => CounterInstruction::ResetCircuitBreaker,
+            "CommitIncrement" => CounterInstruction::CommitIncrement { hash: [1; 32] },
+            "DecrementCounter" => CounterInstruction::DecrementCounter,
+            "IncrementBy" => CounterInstruction::IncrementBy { amount: 2 },
+            "DecrementBy" => CounterInstruction::DecrementBy { amount: 2 },
+            "TransferAuthority" => CounterInstruction::TransferAuthority {
+                new_authority: Pubkey::new_unique(),
+            },
+            "ResetCounter" => CounterInstruction::ResetCounter,
+            "SetCounter" => CounterInstruction::SetCounter { value: 3 },
+            #[cfg(feature = "devnet-tools")]
+            "ForceSet" => CounterInstruction::ForceSet { value: 3 },
+            #[cfg(feature = "devnet-tools")]
+            "FastForwardCooldown" => CounterInstruction::FastForwardCooldown,
+            #[cfg(feature = "devnet-tools")]
+            "ResetStats" => CounterInstruction::ResetStats,
+            "LockCount" => CounterInstruction::LockCount {
+                amount: 2,
+                duration_slots: 10,
+            },
+            "SetStep" => CounterInstruction::SetStep { step: 2 },
+            "SetMode" => CounterInstruction::SetMode {
+                mode: IncrementMode::Permissionless,
+            },
+            "Pause" => CounterInstruction::Pause,
+            "SetUnlockTime" => CounterInstruction::SetUnlockTime {
+                unlock_timestamp: None,
+            },
+            "ConditionalIncrement" => CounterInstruction::ConditionalIncrement { expected: 5 },
+            _ => return None,
+        })
+    }
+
+    // moves the clock on without a warp, which panics once `set_account` has
+    // been used on the bank
+    async fn advance_clock_slots(env: &mut TestEnv, slots: u64) {
+        use solana_sdk::clock::Clock;
+
+        let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+        env.context.set_sysvar(&Clock {
+            slot: clock.slot + slots,
+            ..clock
+        });
+    }
+
+    // sets up what the instruction called `name` needs, with `owner` as the
+    // authority and payer wherever there is one; `None` when there is no case
+    // for it yet. The registry and global config are the owner's and already
+    // exist, except for the instructions that create them.
+    async fn permutation_case(
+        env: &mut TestEnv,
+        owner: &Keypair,
+        forwarder: &Pubkey,
+        name: &str,
+    ) -> Option<PermutationCase> {
+        use crate::addresses::derive_fee_vault_address;
+        use solana_sdk::{clock::Clock, sysvar};
+
+        let program_id = env.program_id;
+        let authority = owner.pubkey();
+        let owned = |config: InitConfig| InitConfig {
+            authority: Some(authority),
+            ..config
+        };
+        let ix = |instruction: CounterInstruction, accounts: Vec<AccountMeta>| {
+            Instruction::new_with_bytes(program_id, &instruction.pack(), accounts)
+        };
+        let signing = AccountMeta::new_readonly(authority, true);
+        let paying = AccountMeta::new(authority, true);
+        let system = AccountMeta::new_readonly(system_program::id(), false);
+        let global_config = AccountMeta::new_readonly(env.global_config(), false);
+
+        // for instructions that send the rent back to the counter's payer,
+        // which must not be the fee payer
+        let initialize_paid_by_owner = |counter: &Keypair, config: InitConfig| {
+            ix(
+                CounterInstruction::InitializeCounter {
+                    initial_value: 5,
+                    config: Box::new(config),
+                },
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    paying.clone(),
+                    system.clone(),
+                    global_config.clone(),
+                ],
+            )
+        };
+
+        if let Some(instruction) = authority_instruction(name) {
+            let counter = env
+                .initialize(5, &owned(InitConfig::default()))
+                .await
+                .pubkey();
+            let accounts = vec![AccountMeta::new(counter, false), signing];
+            return Some(ix(instruction, accounts).into());
+        }
+
+        let program_data = derive_program_data_address(&program_id);
+        let (config, _) = derive_global_config_address(&program_id);
+        let initialize_global_config = ix(
+            CounterInstruction::InitializeGlobalConfig,
+            vec![
+                AccountMeta::new(config, false),
+                paying.clone(),
+                AccountMeta::new_readonly(program_data, false),
+                system.clone(),
+            ],
+        );
+        let set_global_config = |instruction: CounterInstruction| {
+            ix(
+                instruction,
+                vec![
+                    AccountMeta::new(config, false),
+                    signing.clone(),
+                    AccountMeta::new_readonly(program_data, false),
+                ],
+            )
+        };
+
+        let (registry, _) = derive_registry_address(&program_id);
+        let initialize_registry = ix(
+            CounterInstruction::InitializeRegistry,
+            vec![
+                AccountMeta::new(registry, false),
+                paying.clone(),
+                system.clone(),
+            ],
+        );
+        let admin_freeze = |counter: Pubkey| {
+            ix(
+                CounterInstruction::AdminFreeze,
+                vec![
+                    AccountMeta::new_readonly(registry, false),
+                    signing.clone(),
+                    AccountMeta::new(counter, false),
+                ],
+            )
+        };
+
+        let code = b"SPRING-24";
+        // named after the case, as each name can only be set once
+        let (template, _) = derive_template_address(&program_id, &authority, name);
+        let set_template = ix(
+            CounterInstruction::SetTemplate {
+                name: Label32::new(name).unwrap(),
+                config: Box::default(),
+            },
+            vec![
+                AccountMeta::new(template, false),
+                paying.clone(),
+                system.clone(),
+            ],
+        );
+
+        let case = match name {
+            "InitializeCounter" | "InitializeAndIncrement" => {
+                let instruction = if name == "InitializeCounter" {
+                    CounterInstruction::InitializeCounter {
+                        initial_value: 0,
+                        config: Box::default(),
+                    }
+                } else {
+                    CounterInstruction::InitializeAndIncrement {
+                        initial_value: 0,
+                        delta: 1,
+                    }
+                };
+                let counter = Keypair::new();
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    paying,
+                    system,
+                    global_config,
+                ];
+                PermutationCase {
+                    keypairs: vec![counter],
+                    ..ix(instruction, accounts).into()
+                }
+            }
+            "InitializeCounterPda" => {
+                let (counter, _) = derive_counter_address(&program_id, &authority, "orders");
+                let instruction = CounterInstruction::InitializeCounterPda {
+                    initial_value: 0,
+                    name: Label32::new("orders").unwrap(),
+                    config: Box::default(),
+                };
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    paying,
+                    system,
+                    global_config,
+                ];
+                ix(instruction, accounts).into()
+            }
+            "InitializeCounterById" => {
+                let external_id = external_id_hash(b"order-1");
+                let (counter, _) =
+                    derive_external_counter_address(&program_id, &authority, &external_id);
+                let instruction = CounterInstruction::InitializeCounterById {
+                    initial_value: 0,
+                    external_id,
+                    config: Box::default(),
+                };
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    paying,
+                    system,
+                    global_config,
+                ];
+                ix(instruction, accounts).into()
+            }
+            "InitializeFromTemplate" => {
+                env.send(&[set_template], &[owner]).await.unwrap();
+                let counter = Keypair::new();
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    paying,
+                    system,
+                    AccountMeta::new_readonly(template, false),
+                    global_config,
+                ];
+                let instruction = CounterInstruction::InitializeFromTemplate { initial_value: 0 };
+                PermutationCase {
+                    keypairs: vec![counter],
+                    ..ix(instruction, accounts).into()
+                }
+            }
+            "SetTemplate" => set_template.into(),
+            "RevealIncrement" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let salt = [7; 32];
+                let commit = ix(
+                    CounterInstruction::CommitIncrement {
+                        hash: commitment_hash(5, &salt),
+                    },
+                    vec![AccountMeta::new(counter, false), signing],
+                );
+                env.send(&[commit], &[owner]).await.unwrap();
+                let instruction = CounterInstruction::RevealIncrement { amount: 5, salt };
+                ix(instruction, vec![AccountMeta::new(counter, false)]).into()
+            }
+            "IncrementCommitted" => {
+                let salt = [1; 32];
+                let config = owned(InitConfig {
+                    value_commitment: Some(commitment_hash(0, &salt)),
+                    ..InitConfig::default()
+                });
+                let counter = env.initialize(0, &config).await.pubkey();
+                let instruction = CounterInstruction::IncrementCommitted {
+                    value: 0,
+                    salt,
+                    amount: 1,
+                    new_salt: [2; 32],
+                };
+                ix(instruction, vec![AccountMeta::new(counter, false), signing]).into()
+            }
+            "IncrementMetric" => {
+                let config = owned(InitConfig {
+                    metrics: vec![MetricConfig {
+                        name: Label32::new("logins").unwrap(),
+                        initial_value: 0,
+                        max_value: 0,
+                    }],
+                    ..InitConfig::default()
+                });
+                let counter = env.initialize(0, &config).await.pubkey();
+                let instruction = CounterInstruction::IncrementMetric {
+                    metric: MetricRef::Index(0),
+                    amount: 1,
+                };
+                ix(instruction, vec![AccountMeta::new(counter, false), signing]).into()
+            }
+            "InitializeComposite" | "RefreshComposite" => {
+                let source = env.initialize(5, &InitConfig::default()).await.pubkey();
+                let composite = Keypair::new();
+                let initialize = ix(
+                    CounterInstruction::InitializeComposite {
+                        sources: vec![WeightedSource {
+                            counter: source,
+                            weight: 1,
+                        }],
+                    },
+                    vec![AccountMeta::new(composite.pubkey(), true), paying, system],
+                );
+                if name == "InitializeComposite" {
+                    PermutationCase {
+                        keypairs: vec![composite],
+                        ..initialize.into()
+                    }
+                } else {
+                    env.send(&[initialize], &[owner, &composite]).await.unwrap();
+                    let accounts = vec![
+                        AccountMeta::new(composite.pubkey(), false),
+                        AccountMeta::new_readonly(source, false),
+                    ];
+                    ix(CounterInstruction::RefreshComposite, accounts).into()
+                }
+            }
+            "SyncMintSupply" => {
+                let mint = Pubkey::new_unique();
+                let mut mint_data = vec![0u8; MINT_LEN];
+                mint_data[36..44].copy_from_slice(&1_250u64.to_le_bytes());
+                mint_data[45] = 1;
+                let account = Account {
+                    lamports: 1_000_000,
+                    data: mint_data,
+                    owner: TOKEN_PROGRAM_ID,
+                    ..Account::default()
+                };
+                env.context.set_account(&mint, &account.into());
+                let config = InitConfig {
+                    mirror_mint: Some(mint),
+                    ..InitConfig::default()
+                };
+                let counter = env.initialize(0, &config).await.pubkey();
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(mint, false),
+                ];
+                ix(CounterInstruction::SyncMintSupply, accounts).into()
+            }
+            "CloseCounter" | "SkimExcess" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let instruction = if name == "CloseCounter" {
+                    CounterInstruction::CloseCounter
+                } else {
+                    let donation = solana_sdk::system_instruction::transfer(
+                        &env.payer.pubkey(),
+                        &counter,
+                        1_000_000,
+                    );
+                    env.send(&[donation], &[]).await.unwrap();
+                    CounterInstruction::SkimExcess
+                };
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    signing,
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                ];
+                ix(instruction, accounts).into()
+            }
+            "Reap" => {
+                // the rent goes back to the owner and the reward to a third
+                // party, so neither is writable only for paying the fee
+                let counter = Keypair::new();
+                let reaper = Keypair::new();
+                let config = owned(InitConfig {
+                    ttl_slots: 1,
+                    ..InitConfig::default()
+                });
+                let fund = solana_sdk::system_instruction::transfer(
+                    &env.payer.pubkey(),
+                    &reaper.pubkey(),
+                    1_000_000_000,
+                );
+                let initialize = initialize_paid_by_owner(&counter, config);
+                env.send(&[fund, initialize], &[owner, &counter])
+                    .await
+                    .unwrap();
+                advance_clock_slots(env, 2).await;
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(authority, false),
+                    AccountMeta::new(reaper.pubkey(), true),
+                ];
+                PermutationCase {
+                    keypairs: vec![reaper],
+                    ..ix(CounterInstruction::Reap, accounts).into()
+                }
+            }
+            "MigrateAccount" => {
+                let counter = env.initialize(5, &owned(InitConfig::default())).await;
+                let current = env.read_counter(&counter).await;
+                let counter = counter.pubkey();
+                let mut account = env
+                    .banks_client
+                    .get_account(counter)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                account.data =
+                    borsh::to_vec(&CounterAccountV0::try_from(current).unwrap()).unwrap();
+                env.context.set_account(&counter, &account.into());
+                let accounts = vec![AccountMeta::new(counter, false), paying, system];
+                ix(CounterInstruction::MigrateAccount, accounts).into()
+            }
+            "Resize" => {
+                let counter = Keypair::new();
+                let bare = Account {
+                    lamports: 1_000_000,
+                    data: 42u64.to_le_bytes().to_vec(),
+                    owner: program_id,
+                    ..Account::default()
+                };
+                env.context.set_account(&counter.pubkey(), &bare.into());
+                let accounts = vec![AccountMeta::new(counter.pubkey(), true), paying, system];
+                PermutationCase {
+                    keypairs: vec![counter],
+                    ..ix(CounterInstruction::Resize, accounts).into()
+                }
+            }
+            "UnlockCount" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let lock = ix(
+                    CounterInstruction::LockCount {
+                        amount: 2,
+                        duration_slots: 1,
+                    },
+                    vec![AccountMeta::new(counter, false), signing],
+                );
+                env.send(&[lock], &[owner]).await.unwrap();
+                advance_clock_slots(env, 2).await;
+                ix(
+                    CounterInstruction::UnlockCount,
+                    vec![AccountMeta::new(counter, false)],
+                )
+                .into()
+            }
+            "GrantViewKey" | "RevokeViewKey" | "GetCount" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let (view_key, _) = derive_view_key_address(&program_id, &counter, forwarder);
+                let grant = ix(
+                    CounterInstruction::GrantViewKey {
+                        program: *forwarder,
+                    },
+                    vec![
+                        AccountMeta::new_readonly(counter, false),
+                        paying.clone(),
+                        AccountMeta::new(view_key, false),
+                        system,
+                    ],
+                );
+                if name == "GrantViewKey" {
+                    grant.into()
+                } else if name == "RevokeViewKey" {
+                    env.send(&[grant], &[owner]).await.unwrap();
+                    let accounts = vec![
+                        AccountMeta::new_readonly(counter, false),
+                        paying,
+                        AccountMeta::new(view_key, false),
+                    ];
+                    ix(CounterInstruction::RevokeViewKey, accounts).into()
+                } else {
+                    env.send(&[grant], &[owner]).await.unwrap();
+                    let mut data = CounterInstruction::GetCount.pack();
+                    data.extend_from_slice(program_id.as_ref());
+                    let accounts = vec![
+                        AccountMeta::new_readonly(counter, false),
+                        AccountMeta::new(view_key, false),
+                        AccountMeta::new_readonly(derive_viewer_authority(forwarder).0, false),
+                        AccountMeta::new_readonly(program_id, false),
+                    ];
+                    Instruction::new_with_bytes(*forwarder, &data, accounts).into()
+                }
+            }
+            "SetTopics" | "AddDelegate" | "RemoveDelegate" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let accounts = vec![AccountMeta::new(counter, false), paying, system];
+                let delegate = Pubkey::new_unique();
+                let instruction = match name {
+                    "SetTopics" => CounterInstruction::SetTopics {
+                        topics: Topics::new(vec![Topic::new(b"orders".to_vec()).unwrap()]).unwrap(),
+                    },
+                    "AddDelegate" => CounterInstruction::AddDelegate { delegate },
+                    _ => {
+                        let add = ix(
+                            CounterInstruction::AddDelegate { delegate },
+                            accounts.clone(),
+                        );
+                        env.send(&[add], &[owner]).await.unwrap();
+                        CounterInstruction::RemoveDelegate { delegate }
+                    }
+                };
+                ix(instruction, accounts).into()
+            }
+            "Resume" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let accounts = vec![AccountMeta::new(counter, false), signing];
+                let pause = ix(CounterInstruction::Pause, accounts.clone());
+                env.send(&[pause], &[owner]).await.unwrap();
+                ix(CounterInstruction::Resume, accounts).into()
+            }
+            "Snapshot" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let (snapshot, _) = derive_snapshot_address(&program_id, &counter, 0);
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    paying,
+                    AccountMeta::new(snapshot, false),
+                    system,
+                ];
+                ix(CounterInstruction::Snapshot, accounts).into()
+            }
+            "MintCoupon" | "RedeemCoupon" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let (coupon, _) =
+                    derive_coupon_address(&program_id, &counter, &coupon_code_hash(code));
+                let mint = ix(
+                    CounterInstruction::MintCoupon {
+                        code_hash: coupon_code_hash(code),
+                        amount: 3,
+                    },
+                    vec![
+                        AccountMeta::new_readonly(counter, false),
+                        paying,
+                        AccountMeta::new(coupon, false),
+                        system,
+                    ],
+                );
+                if name == "MintCoupon" {
+                    mint.into()
+                } else {
+                    env.send(&[mint], &[owner]).await.unwrap();
+                    let instruction = CounterInstruction::RedeemCoupon {
+                        code: CouponCode::new(code.to_vec()).unwrap(),
+                    };
+                    let accounts = vec![
+                        AccountMeta::new(counter, false),
+                        AccountMeta::new(coupon, false),
+                        AccountMeta::new(authority, false),
+                    ];
+                    ix(instruction, accounts).into()
+                }
+            }
+            "ImportAttestation" => {
+                let attester = Keypair::new();
+                let source = Pubkey::new_unique();
+                let config = InitConfig {
+                    import_source: Some(ImportSource {
+                        counter: source,
+                        attester: attester.pubkey(),
+                        max_age_secs: 600,
+                    }),
+                    ..InitConfig::default()
+                };
+                let counter = env.initialize(0, &config).await.pubkey();
+                let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+                let snapshot = AttestationSnapshot {
+                    attestation: CountAttestation {
+                        counter: source,
+                        count: 42,
+                        slot: 1_000,
+                        nonce: 0,
+                    },
+                    unix_timestamp: clock.unix_timestamp,
+                };
+                let message = borsh::to_vec(&snapshot).unwrap();
+                let signature = attester.sign_message(&message);
+                let verify = verify_instruction(
+                    &attester.pubkey(),
+                    signature.as_ref().try_into().unwrap(),
+                    &message,
+                );
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                ];
+                PermutationCase {
+                    preceding: vec![verify],
+                    ..ix(CounterInstruction::ImportAttestation { snapshot }, accounts).into()
+                }
+            }
+            "IncrementMany" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let accounts = vec![signing, AccountMeta::new(counter, false)];
+                ix(CounterInstruction::IncrementMany, accounts).into()
+            }
+            "InitializeRegistry" => initialize_registry.into(),
+            "SetUnfreezeGracePeriod" => {
+                let instruction = CounterInstruction::SetUnfreezeGracePeriod { grace_secs: 600 };
+                ix(
+                    instruction,
+                    vec![AccountMeta::new(registry, false), signing],
+                )
+                .into()
+            }
+            "AdminFreeze" | "RequestUnfreeze" | "BurnCounter" => {
+                let counter = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let freeze = admin_freeze(counter);
+                if name == "AdminFreeze" {
+                    freeze.into()
+                } else if name == "RequestUnfreeze" {
+                    env.send(&[freeze], &[owner]).await.unwrap();
+                    let accounts = vec![
+                        AccountMeta::new(counter, false),
+                        signing,
+                        AccountMeta::new_readonly(registry, false),
+                    ];
+                    ix(CounterInstruction::RequestUnfreeze, accounts).into()
+                } else {
+                    env.send(&[freeze], &[owner]).await.unwrap();
+                    let accounts = vec![
+                        AccountMeta::new(registry, false),
+                        signing,
+                        AccountMeta::new(counter, false),
+                        AccountMeta::new(Pubkey::new_unique(), false),
+                    ];
+                    ix(CounterInstruction::BurnCounter, accounts).into()
+                }
+            }
+            "InitializeGlobalConfig" => initialize_global_config.into(),
+            "SetGlobalConfig" => set_global_config(CounterInstruction::SetGlobalConfig {
+                params: GlobalParams::default(),
+            })
+            .into(),
+            // any other quota would have every later creation pass the
+            // owner's stats
+            "SetOwnerQuota" => set_global_config(CounterInstruction::SetOwnerQuota {
+                max_counters_per_owner: 0,
+            })
+            .into(),
+            "CreateMultisig" => {
+                let multisig = Keypair::new();
+                let instruction = CounterInstruction::CreateMultisig {
+                    threshold: 1,
+                    signers: MultisigSigners::new(vec![authority]).unwrap(),
+                };
+                let accounts = vec![AccountMeta::new(multisig.pubkey(), true), paying, system];
+                PermutationCase {
+                    keypairs: vec![multisig],
+                    ..ix(instruction, accounts).into()
+                }
+            }
+            "WithdrawFees" => {
+                let counter = Keypair::new();
+                let (vault, _) = derive_fee_vault_address(&program_id, &counter.pubkey());
+                let config = owned(InitConfig {
+                    increment_fee: 1_000_000,
+                    ..InitConfig::default()
+                });
+                let initialize = ix(
+                    CounterInstruction::InitializeCounter {
+                        initial_value: 0,
+                        config: Box::new(config),
+                    },
+                    vec![
+                        AccountMeta::new(counter.pubkey(), true),
+                        paying.clone(),
+                        system.clone(),
+                        global_config,
+                        AccountMeta::new(vault, false),
+                    ],
+                );
+                let increment = ix(
+                    CounterInstruction::IncrementCounter,
+                    vec![
+                        AccountMeta::new(counter.pubkey(), false),
+                        paying,
+                        AccountMeta::new(vault, false),
+                        system,
+                    ],
+                );
+                env.send(&[initialize, increment], &[owner, &counter])
+                    .await
+                    .unwrap();
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    signing,
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                ];
+                ix(CounterInstruction::WithdrawFees, accounts).into()
+            }
+            "InitializeSignedCounter" | "InitializeSlotCounter" => {
+                let counter = Keypair::new();
+                let instruction = if name == "InitializeSignedCounter" {
+                    CounterInstruction::InitializeSignedCounter { initial_value: 0 }
+                } else {
+                    CounterInstruction::InitializeSlotCounter { slots: 2 }
+                };
+                let accounts = vec![AccountMeta::new(counter.pubkey(), true), paying, system];
+                PermutationCase {
+                    keypairs: vec![counter],
+                    ..ix(instruction, accounts).into()
+                }
+            }
+            "IncrementSigned" | "DecrementSigned" => {
+                let counter = Keypair::new();
+                let initialize = ix(
+                    CounterInstruction::InitializeSignedCounter { initial_value: 0 },
+                    vec![AccountMeta::new(counter.pubkey(), true), paying, system],
+                );
+                env.send(&[initialize], &[owner, &counter]).await.unwrap();
+                let instruction = if name == "IncrementSigned" {
+                    CounterInstruction::IncrementSigned { amount: 2 }
+                } else {
+                    CounterInstruction::DecrementSigned { amount: 2 }
+                };
+                ix(
+                    instruction,
+                    vec![AccountMeta::new(counter.pubkey(), false), signing],
+                )
+                .into()
+            }
+            "IncrementSlot" | "DecrementSlot" | "GetSlot" => {
+                let counter = Keypair::new();
+                let initialize = ix(
+                    CounterInstruction::InitializeSlotCounter { slots: 2 },
+                    vec![AccountMeta::new(counter.pubkey(), true), paying, system],
+                );
+                let accounts = vec![AccountMeta::new(counter.pubkey(), false), signing];
+                let increment = ix(
+                    CounterInstruction::IncrementSlot { index: 0 },
+                    accounts.clone(),
+                );
+                env.send(&[initialize, increment], &[owner, &counter])
+                    .await
+                    .unwrap();
+                match name {
+                    "IncrementSlot" => {
+                        ix(CounterInstruction::IncrementSlot { index: 0 }, accounts).into()
+                    }
+                    "DecrementSlot" => {
+                        ix(CounterInstruction::DecrementSlot { index: 0 }, accounts).into()
+                    }
+                    _ => ix(
+                        CounterInstruction::GetSlot { index: 0 },
+                        vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+                    )
+                    .into(),
+                }
+            }
+            "TransferCount" => {
+                let source = env
+                    .initialize(5, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let destination = env
+                    .initialize(0, &owned(InitConfig::default()))
+                    .await
+                    .pubkey();
+                let accounts = vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new(destination, false),
+                    signing,
+                ];
+                ix(CounterInstruction::TransferCount { amount: 1 }, accounts).into()
+            }
+            "CloseExpired" => {
+                let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+                let expires_at = clock.unix_timestamp + 600;
+                let config = owned(InitConfig {
+                    expires_at: Some(expires_at),
+                    ..InitConfig::default()
+                });
+                let counter = Keypair::new();
+                let initialize = initialize_paid_by_owner(&counter, config);
+                env.send(&[initialize], &[owner, &counter]).await.unwrap();
+                env.context.set_sysvar(&Clock {
+                    unix_timestamp: expires_at,
+                    ..clock
+                });
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(authority, false),
+                ];
+                ix(CounterInstruction::CloseExpired, accounts).into()
+            }
+            "IncrementWithProof" => {
+                let tree = MerkleTree::new(&[authority]);
+                let config = InitConfig {
+                    merkle_root: Some(tree.root()),
+                    ..InitConfig::default()
+                };
+                let counter = env.initialize(0, &config).await.pubkey();
+                let (claims, _) = derive_claim_bitmap_address(&program_id, &counter, 0);
+                let instruction = CounterInstruction::IncrementWithProof {
+                    index: 0,
+                    proof: MerkleProof::try_from(tree.proof(0)).unwrap(),
+                };
+                let accounts = vec![
+                    AccountMeta::new(counter, false),
+                    paying,
+                    AccountMeta::new(claims, false),
+                    system,
+                ];
+                ix(instruction, accounts).into()
+            }
+            "ReadCount" => {
+                let counter = env.initialize(5, &InitConfig::default()).await.pubkey();
+                let accounts = vec![AccountMeta::new_readonly(counter, false)];
+                ix(CounterInstruction::ReadCount, accounts).into()
+            }
+            _ => return None,
+        };
+        Some(case)
+    }
+
+    #[tokio::test]
+    async fn test_account_permutations_are_rejected() {
+        let forwarder = Pubkey::new_unique();
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_program("view_forwarder", forwarder, processor!(view_forwarder));
+        })
+        .await;
+
+        // an authority other than the fee payer, whose signature the runtime
+        // would otherwise supply to every instruction
+        let owner = Keypair::new();
+        let fund = solana_sdk::system_instruction::transfer(
+            &env.payer.pubkey(),
+            &owner.pubkey(),
+            10_000_000_000,
+        );
+        env.send(&[fund], &[]).await.unwrap();
+
+        // a native test program has no data account, so stand one in naming
+        // the owner as upgrade authority
+        let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        data.extend_from_slice(owner.pubkey().as_ref());
+        let program_data = Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: solana_sdk::bpf_loader_upgradeable::id(),
+            ..Account::default()
+        };
+        env.context.set_account(
+            &derive_program_data_address(&env.program_id),
+            &program_data.into(),
+        );
+
+        // the registry and global config can only be created once, and the
+        // instructions that manage them need them
+        let mut instructions: Vec<_> = INSTRUCTIONS
+            .iter()
+            .filter(|meta| meta.is_enabled())
+            .collect();
+        instructions.sort_by_key(|meta| {
+            !matches!(meta.name, "InitializeRegistry" | "InitializeGlobalConfig")
+        });
+
+        for meta in instructions {
+            let case = permutation_case(&mut env, &owner, &forwarder, meta.name)
+                .await
+                .unwrap_or_else(|| panic!("no account permutation case for {}", meta.name));
+            let mut keypairs = vec![&owner];
+            keypairs.extend(&case.keypairs);
+            assert_rejects_account_permutations(
+                &mut env,
+                meta.name,
+                &case.preceding,
+                case.instruction,
+                &keypairs,
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unlock_time_gates_mutations() {
+        use solana_sdk::clock::Clock;
+
+        let mut env = TestEnv::start().await;
+        let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+        let unlock_at = clock.unix_timestamp + 600;
+        let config = InitConfig {
+            unlock_timestamp: Some(unlock_at),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(5, &config).await;
+        let locked = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::CounterLocked as u32),
+        );
+
+        for data in [&[1][..], &[9][..]] {
+            let ix = env.counter_ix(data, &counter);
+            let err = env.send(&[ix], &[]).await.unwrap_err();
+            assert_eq!(err.unwrap(), locked);
+        }
+
+        // neither a revealed commitment nor a coupon gets around the lock
+        let salt = [7u8; 32];
+        let mut commit = vec![3];
+        commit.extend_from_slice(&commitment_hash(1, &salt));
+        let commit = env.counter_ix(&commit, &counter);
+        env.send(&[commit], &[]).await.unwrap();
+        let mut reveal = vec![4];
+        reveal.extend_from_slice(&1u64.to_le_bytes());
+        reveal.extend_from_slice(&salt);
+        let reveal = env.counter_ix(&reveal, &counter);
+        let err = env.send(&[reveal], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), locked);
+
+        let payer = env.payer.pubkey();
+        let code = CouponCode::new(b"EARLY".to_vec()).unwrap();
+        let (coupon, _) =
+            derive_coupon_address(&env.program_id, &counter.pubkey(), &coupon_code_hash(&code));
+        let mint = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::MintCoupon {
+                code_hash: coupon_code_hash(&code),
+                amount: 1,
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        );
+        env.send(&[mint], &[]).await.unwrap();
+        let redeem = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::RedeemCoupon { code }.pack(),
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new(payer, false),
+            ],
+        );
+        let err = env.send(&[redeem], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), locked);
+
+        // setting the count directly is not gated
+        let mut set = vec![17];
+        set.extend_from_slice(&8u64.to_le_bytes());
+        let set = env.counter_ix(&set, &counter);
+        env.send(&[set], &[]).await.unwrap();
+
+        env.context.set_sysvar(&Clock {
+            unix_timestamp: unlock_at,
+            ..clock
+        });
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 9);
+
+        // the authority can push the unlock time out again, then clear it
+        let set_unlock_time = |unlock_timestamp: Option<i64>| {
+            CounterInstruction::SetUnlockTime { unlock_timestamp }.pack()
+        };
+        let relock = env.counter_ix(&set_unlock_time(Some(unlock_at + 60)), &counter);
+        env.send(&[relock], &[]).await.unwrap();
+        let decrement = env.counter_ix(&[9], &counter);
+        let err = env.send(&[decrement], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), locked);
+
+        let clear = env.counter_ix(&set_unlock_time(None), &counter);
+        env.send(&[clear], &[]).await.unwrap();
+        let decrement = env.counter_ix(&[9], &counter);
+        env.send(&[decrement], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 8);
+        assert_eq!(env.read_counter(&counter).await.unlock_timestamp, 0);
+    }
+
+    // composes the counter program through `cpi`: tag 0 creates a counter
+    // whose authority is this program's `["authority"]` PDA, tag 1 increments
+    // it by signing as that PDA; accounts: counter program, counter, then
+    // payer, system program and global config for tag 0 or the PDA for tag 1
+    #[cfg(feature = "cpi")]
+    fn cpi_caller(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let (authority, bump) = Pubkey::find_program_address(&[b"authority"], program_id);
+
+        match instruction_data[0] {
+            0 => crate::cpi::initialize_counter(
+                &accounts[0],
+                &accounts[1],
+                &accounts[2],
+                &accounts[3],
+                &accounts[4],
+                1,
+                InitConfig {
+                    authority: Some(authority),
+                    ..InitConfig::default()
+                },
+                &[],
+            ),
+            _ => crate::cpi::increment_by(
+                &accounts[0],
+                &accounts[1],
+                &accounts[2],
+                instruction_data[1] as u64,
+                &[&[b"authority", &[bump]]],
+            ),
+        }
+    }
+
+    #[cfg(feature = "cpi")]
+    #[tokio::test]
+    async fn test_cpi_helpers_compose() {
+        let caller_id = Pubkey::new_unique();
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_program("cpi_caller", caller_id, processor!(cpi_caller));
+        })
+        .await;
+        let (authority, _) = Pubkey::find_program_address(&[b"authority"], &caller_id);
+
+        let counter = Keypair::new();
+        let initialize = Instruction::new_with_bytes(
+            caller_id,
+            &[0],
+            vec![
+                AccountMeta::new_readonly(env.program_id, false),
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(derive_global_config_address(&env.program_id).0, false),
+            ],
+        );
+        env.send(&[initialize], &[&counter]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.authority, authority);
+
+        let increment = Instruction::new_with_bytes(
+            caller_id,
+            &[1, 4],
+            vec![
+                AccountMeta::new_readonly(env.program_id, false),
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(authority, false),
+            ],
+        );
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 5);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_builders_follow_account_specs() {
+        use crate::client::{
+            close_counter_ix, create_multisig_ix, decrement_ix, get_count_ix, grant_view_key_ix,
+            increment_by_ix, increment_ix, initialize_and_increment_ix,
+            initialize_counter_by_id_ix, initialize_counter_ix, initialize_counter_pda_ix,
+            migrate_account_ix, mint_coupon_ix, reap_ix, reset_counter_ix, reveal_increment_ix,
+            revoke_view_key_ix, set_template_ix, set_unlock_time_ix, snapshot_ix, unlock_count_ix,
+        };
+
+        let program_id = Pubkey::new_unique();
+        let [counter, actor, other] = [(); 3].map(|_| Pubkey::new_unique());
+        let name = Label32::new("spec").unwrap();
+        let code = CouponCode::new(b"code".to_vec()).unwrap();
+
+        let instructions = [
+            initialize_counter_ix(&program_id, &counter, &actor, 0),
+            initialize_and_increment_ix(&program_id, &counter, &actor, 0, 1),
+            initialize_counter_pda_ix(&program_id, &actor, &name, 0, InitConfig::default()).1,
+            initialize_counter_by_id_ix(&program_id, &actor, &[7; 32], 0, InitConfig::default()).1,
+            create_multisig_ix(&program_id, &counter, &actor, 1, MultisigSigners::default()),
+            increment_ix(&program_id, &counter, &actor),
+            increment_by_ix(&program_id, &counter, &actor, 2),
+            decrement_ix(&program_id, &counter, &actor),
+            reveal_increment_ix(&program_id, &counter, 1, [0; 32]),
+            unlock_count_ix(&program_id, &counter),
+            reset_counter_ix(&program_id, &counter, &actor),
+            set_unlock_time_ix(&program_id, &counter, &actor, None),
+            close_counter_ix(&program_id, &counter, &actor, &other),
+            reap_ix(&program_id, &counter, &other, &actor),
+            migrate_account_ix(&program_id, &counter, &actor),
+            grant_view_key_ix(&program_id, &counter, &actor, &other),
+            revoke_view_key_ix(&program_id, &counter, &actor, &other),
+            get_count_ix(&program_id, &counter, &other),
+            mint_coupon_ix(&program_id, &counter, &actor, &code, 1).1,
+            set_template_ix(&program_id, &actor, &name, InitConfig::default()).1,
+            snapshot_ix(&program_id, &counter, &actor, 0).1,
+        ];
+
+        for instruction in instructions {
+            let meta = meta(instruction.data[0]).unwrap();
+            assert!(
+                instruction.accounts.len() >= meta.accounts.len(),
+                "{}",
+                meta.name
+            );
+            for (spec, account) in meta.accounts.iter().zip(&instruction.accounts) {
+                assert!(
+                    account.is_signer || !spec.signer,
+                    "{}: {}",
+                    meta.name,
+                    spec.name
+                );
+                assert!(
+                    account.is_writable || !spec.writable,
+                    "{}: {}",
+                    meta.name,
+                    spec.name
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_owner_quota_caps_creations() {
+        use crate::addresses::derive_owner_stats_address;
+        use crate::state::OwnerStats;
+
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+        let config = env.global_config();
+        let (owner_stats, _) = derive_owner_stats_address(&program_id, &payer);
+
+        // a native test program has no data account, so stand one in with
+        // the payer as upgrade authority
+        let program_data = derive_program_data_address(&program_id);
+        let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        data.extend_from_slice(payer.as_ref());
+        let account = Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: solana_sdk::bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        env.context.set_account(&program_data, &account.into());
+
+        let initialize = |counter: &Keypair, extra: Vec<AccountMeta>| {
+            let mut accounts = vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ];
+            accounts.extend(extra);
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::InitializeCounter {
+                    initial_value: 0,
+                    config: Box::default(),
+                }
+                .pack(),
+                accounts,
+            )
+        };
+        let with_config = |extra: &[AccountMeta]| {
+            let mut accounts = vec![AccountMeta::new_readonly(config, false)];
+            accounts.extend_from_slice(extra);
+            accounts
+        };
+
+        // leaving the config out would dodge any quota, so it is required
+        // even before the config exists
+        let counter = Keypair::new();
+        let err = env
+            .send(&[initialize(&counter, vec![])], &[&counter])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+        env.send(&[initialize(&counter, with_config(&[]))], &[&counter])
+            .await
+            .unwrap();
+
+        let create_config = Instruction::new_with_bytes(
+            program_id,
+            &CounterInstruction::InitializeGlobalConfig.pack(),
+            vec![
+                AccountMeta::new(config, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(program_data, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[create_config], &[]).await.unwrap();
+
+        let set_quota = |authority: Pubkey, max_counters_per_owner: u32| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::SetOwnerQuota {
+                    max_counters_per_owner,
+                }
+                .pack(),
+                vec![
+                    AccountMeta::new(config, false),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new_readonly(program_data, false),
+                ],
+            )
+        };
+
+        // only the upgrade authority sets the quota
+        let stranger = Keypair::new();
+        let err = env
+            .send(&[set_quota(stranger.pubkey(), 1)], &[&stranger])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        env.send(&[set_quota(payer, 2)], &[]).await.unwrap();
+
+        // with a quota set, the owner's stats account has to come along
+        let counter = Keypair::new();
+        let err = env
+            .send(&[initialize(&counter, with_config(&[]))], &[&counter])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        let stats = [AccountMeta::new(owner_stats, false)];
+        for _ in 0..2 {
+            let counter = Keypair::new();
+            env.send(&[initialize(&counter, with_config(&stats))], &[&counter])
+                .await
+                .unwrap();
+        }
+
+        let counter = Keypair::new();
+        let err = env
+            .send(&[initialize(&counter, with_config(&stats))], &[&counter])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::OwnerQuotaExceeded as u32)
+            )
+        );
+
+        let account = env
+            .banks_client
+            .get_account(owner_stats)
+            .await
+            .unwrap()
+            .unwrap();
+        let stats = OwnerStats::try_from_slice(&account.data).unwrap();
+        assert_eq!(stats.owner, payer);
+        assert_eq!(stats.counters_created, 2);
+
+        // lifting the quota lets the owner create again
+        env.send(&[set_quota(payer, 0)], &[]).await.unwrap();
+        env.send(&[initialize(&counter, with_config(&[]))], &[&counter])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_increment_fees_collect_and_withdraw() {
+        use crate::addresses::derive_fee_vault_address;
+
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+        const FEE: u64 = 1_000_000;
+
+        let counter = Keypair::new();
+        let (vault, _) = derive_fee_vault_address(&program_id, &counter.pubkey());
+        let config = InitConfig {
+            gates: Some(GateConfig::default()),
+            increment_fee: FEE,
+            ..InitConfig::default()
+        };
+        let initialize = |extra: Vec<AccountMeta>| {
+            let mut accounts = vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(derive_global_config_address(&program_id).0, false),
+            ];
+            accounts.extend(extra);
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::InitializeCounter {
+                    initial_value: 0,
+                    config: Box::new(config.clone()),
+                }
+                .pack(),
+                accounts,
+            )
+        };
+
+        // without a recipient the fee vault has to be created alongside
+        let err = env
+            .send(&[initialize(vec![])], &[&counter])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+        let with_vault = vec![AccountMeta::new(vault, false)];
+        env.send(&[initialize(with_vault)], &[&counter])
+            .await
+            .unwrap();
+        assert_eq!(env.read_counter(&counter).await.fee_recipient, vault);
+
+        let actor = Keypair::new();
+        let fund = solana_sdk::system_instruction::transfer(&payer, &actor.pubkey(), 1_000_000_000);
+        env.send(&[fund], &[]).await.unwrap();
+
+        let increment = |writable: bool| {
+            let actor_meta = if writable {
+                AccountMeta::new(actor.pubkey(), true)
+            } else {
+                AccountMeta::new_readonly(actor.pubkey(), true)
+            };
+            Instruction::new_with_bytes(
+                program_id,
+                &[1],
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    actor_meta,
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+
+        // the actor pays, so it has to be writable
+        let err = env.send(&[increment(false)], &[&actor]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+        );
+
+        let vault_before = env.banks_client.get_balance(vault).await.unwrap();
+        for _ in 0..3 {
+            env.send(&[increment(true)], &[&actor]).await.unwrap();
+        }
+        assert_eq!(env.read_counter(&counter).await.count, 3);
+        let vault_after = env.banks_client.get_balance(vault).await.unwrap();
+        assert_eq!(vault_after - vault_before, 3 * FEE);
+
+        // only the authority withdraws, and the vault keeps its rent
+        let destination = Pubkey::new_unique();
+        let withdraw = |authority: Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::WithdrawFees.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(destination, false),
+                ],
+            )
+        };
+        let err = env
+            .send(&[withdraw(actor.pubkey())], &[&actor])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        env.send(&[withdraw(payer)], &[]).await.unwrap();
+        let withdrawn = env.banks_client.get_balance(destination).await.unwrap();
+        assert_eq!(withdrawn, vault_after - vault_before);
+        assert_eq!(
+            env.banks_client.get_balance(vault).await.unwrap(),
+            vault_before
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resize_grows_bare_counters() {
+        let mut env = TestEnv::start().await;
+        let payer = env.payer.pubkey();
+
+        // a counter from before discriminators: just the count
+        let counter = Keypair::new();
+        let bare = Account {
+            lamports: 1_000_000,
+            data: 42u64.to_le_bytes().to_vec(),
+            owner: env.program_id,
+            ..Account::default()
+        };
+        env.context.set_account(&counter.pubkey(), &bare.into());
+
+        let program_id = env.program_id;
+        let resize = |payer: &Pubkey, counter_signs: bool| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::Resize.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), counter_signs),
+                    AccountMeta::new(*payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+
+        // a stranger paying for the resize cannot make itself the authority
+        let stranger = Keypair::new();
+        let fund =
+            solana_sdk::system_instruction::transfer(&payer, &stranger.pubkey(), 1_000_000_000);
+        env.send(&[fund], &[]).await.unwrap();
+        let takeover = resize(&stranger.pubkey(), false);
+        let err = env.send(&[takeover], &[&stranger]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+        );
+
+        let resize = resize(&payer, true);
+        env.send(std::slice::from_ref(&resize), &[&counter])
+            .await
+            .unwrap();
+
+        let resized = env.read_counter(&counter).await;
+        assert_eq!(resized.discriminator, COUNTER_DISCRIMINATOR);
+        assert_eq!(resized.version, COUNTER_VERSION);
+        assert_eq!(resized.count, 42);
+        assert_eq!(resized.authority, payer);
+        assert_ne!(resized.created_at, 0);
+
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        let rent = env.banks_client.get_rent().await.unwrap();
+        assert!(rent.is_exempt(account.lamports, account.data.len()));
+
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 43);
+
+        // resizing a current counter is a no-op
+        env.send(&[resize], &[&counter]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 43);
+    }
+
+    #[tokio::test]
+    async fn test_unfreeze_request_grace_period() {
+        use solana_sdk::clock::Clock;
+
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+        let (registry, _) = derive_registry_address(&program_id);
+
+        let create_registry = Instruction::new_with_bytes(
+            program_id,
+            &[38],
+            vec![
+                AccountMeta::new(registry, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        env.send(&[create_registry], &[]).await.unwrap();
+
+        let set_grace = Instruction::new_with_bytes(
+            program_id,
+            &CounterInstruction::SetUnfreezeGracePeriod { grace_secs: 600 }.pack(),
+            vec![
+                AccountMeta::new(registry, false),
+                AccountMeta::new_readonly(payer, true),
+            ],
+        );
+        env.send(&[set_grace], &[]).await.unwrap();
+
+        let admin_freeze = |admin: Pubkey, counter: Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::AdminFreeze.pack(),
+                vec![
+                    AccountMeta::new_readonly(registry, false),
+                    AccountMeta::new_readonly(admin, true),
+                    AccountMeta::new(counter, false),
+                ],
+            )
+        };
+        let request_unfreeze = |counter: Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::RequestUnfreeze.pack(),
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(payer, true),
+                    AccountMeta::new_readonly(registry, false),
+                ],
+            )
+        };
+        let destination = Pubkey::new_unique();
+        let burn = |counter: Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::BurnCounter.pack(),
+                vec![
+                    AccountMeta::new(registry, false),
+                    AccountMeta::new_readonly(payer, true),
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new(destination, false),
+                ],
+            )
+        };
+
+        let kept_keypair = env.initialize(5, &InitConfig::default()).await;
+        let kept = kept_keypair.pubkey();
+        let burned = env.initialize(5, &InitConfig::default()).await.pubkey();
+
+        // only the registry admin freezes
+        let stranger = Keypair::new();
+        let err = env
+            .send(&[admin_freeze(stranger.pubkey(), kept)], &[&stranger])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        // nothing to request while the counter is not frozen
+        assert!(env.send(&[request_unfreeze(kept)], &[]).await.is_err());
+
+        env.send(
+            &[admin_freeze(payer, kept), admin_freeze(payer, burned)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let increment = env.counter_ix(&[1], &kept_keypair);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::FrozenByAdmin as u32)
+            )
+        );
+
+        let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+        env.send(&[request_unfreeze(kept), request_unfreeze(burned)], &[])
+            .await
+            .unwrap();
+        let state = env.read_counter(&kept_keypair).await;
+        assert_eq!(state.unfreeze_requested_at, clock.unix_timestamp);
+        assert_eq!(state.unfreeze_at, clock.unix_timestamp + 600);
+
+        // one request per freeze
+        assert!(env.send(&[request_unfreeze(kept)], &[]).await.is_err());
+
+        // within the grace period the admin may still escalate
+        env.send(&[burn(burned)], &[]).await.unwrap();
+        assert!(
+            env.banks_client
+                .get_account(burned)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            env.banks_client
+                .get_account(destination)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // once it passes the freeze lapses on its own
+        env.context.set_sysvar(&Clock {
+            unix_timestamp: clock.unix_timestamp + 600,
+            ..clock
+        });
+        let increment = env.counter_ix(&[1], &kept_keypair);
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&kept_keypair).await.count, 6);
+
+        let err = env.send(&[burn(kept)], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+    }
+
+    // the keeper example's pass: decode whatever the program owns and reap
+    // the counters that ran out
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_keeper_helpers_reap_expired_counters() {
+        use crate::client::{ProgramAccount, decode_account, decode_counters, reap_expired_ixs};
+
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let expiring = env
+            .initialize(
+                0,
+                &InitConfig {
+                    ttl_slots: 10,
+                    ..InitConfig::default()
+                },
+            )
+            .await;
+        let lasting = env.initialize(0, &InitConfig::default()).await;
+        let (registry, create_registry) =
+            crate::client::initialize_registry_ix(&program_id, &env.payer.pubkey());
+        env.send(&[create_registry], &[]).await.unwrap();
+        env.warp_slots(20).await;
+
+        let mut accounts = vec![];
+        for address in [expiring.pubkey(), lasting.pubkey(), registry] {
+            let account = env
+                .banks_client
+                .get_account(address)
+                .await
+                .unwrap()
+                .unwrap();
+            accounts.push((address, account.data));
+        }
+        assert!(matches!(
+            decode_account(&accounts[2].1),
+            Some(ProgramAccount::Registry(_))
+        ));
+
+        let counters =
+            decode_counters(accounts.iter().map(|(address, data)| (*address, &data[..])));
+        assert_eq!(counters.len(), 2);
+
+        let slot = env.banks_client.get_root_slot().await.unwrap();
+        let reaps = reap_expired_ixs(&program_id, &env.payer.pubkey(), &counters, slot);
+        assert_eq!(reaps.len(), 1);
+        assert_eq!(reaps[0].accounts[0].pubkey, expiring.pubkey());
+
+        env.send(&reaps, &[]).await.unwrap();
+        assert!(
+            env.banks_client
+                .get_account(expiring.pubkey())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signed_counter_goes_negative() {
+        use crate::state::SignedCounter;
+
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+
+        let initialize = |counter: &Keypair, initial_value: i64| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::InitializeSignedCounter { initial_value }.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+        let change = |counter: &Keypair, authority: Pubkey, instruction: CounterInstruction| {
+            Instruction::new_with_bytes(
+                program_id,
+                &instruction.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(authority, true),
+                ],
+            )
+        };
+
+        let counter = Keypair::new();
+        env.send(&[initialize(&counter, 2)], &[&counter])
+            .await
+            .unwrap();
+
+        let decrement = change(
+            &counter,
+            payer,
+            CounterInstruction::DecrementSigned { amount: 5 },
+        );
+        let increment = change(
+            &counter,
+            payer,
+            CounterInstruction::IncrementSigned { amount: 1 },
+        );
+        env.send(&[decrement, increment], &[]).await.unwrap();
+
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        let state = SignedCounter::try_from_slice(&account.data).unwrap();
+        assert_eq!(state.count, -2);
+        assert_eq!(state.authority, payer);
+
+        // only the authority moves it
+        let stranger = Keypair::new();
+        let err = env
+            .send(
+                &[change(
+                    &counter,
+                    stranger.pubkey(),
+                    CounterInstruction::IncrementSigned { amount: 1 },
+                )],
+                &[&stranger],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        // both ends of the i64 range are checked
+        let low = Keypair::new();
+        env.send(&[initialize(&low, i64::MIN + 1)], &[&low])
+            .await
+            .unwrap();
+        let err = env
+            .send(
+                &[change(
+                    &low,
+                    payer,
+                    CounterInstruction::DecrementSigned { amount: 2 },
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::SignedUnderflow as u32)
+            )
+        );
+
+        let err = env
+            .send(
+                &[change(
+                    &low,
+                    payer,
+                    CounterInstruction::IncrementSigned { amount: u64::MAX },
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::SignedOverflow as u32)
+            )
+        );
+
+        // a regular counter is not a signed one
+        let unsigned = env.initialize(0, &InitConfig::default()).await;
+        let err = env
+            .send(
+                &[change(
+                    &unsigned,
+                    payer,
+                    CounterInstruction::DecrementSigned { amount: 1 },
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::WrongAccountType as u32)
+            )
+        );
+    }
+
+    // third-party implementations hard-code these; changing one is a new
+    // address scheme version, not an edit
+    #[test]
+    fn test_address_scheme_is_pinned() {
+        use crate::addresses::{ADDRESS_SCHEME_VERSION, derive_fee_vault_address};
+
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let counter = Pubkey::new_unique();
+        let derive = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &program_id);
+
+        assert_eq!(ADDRESS_SCHEME_VERSION, 1);
+        #[cfg(not(feature = "anchor-layout"))]
+        assert_eq!(COUNTER_DISCRIMINATOR, *b"counterv");
+        assert_eq!(
+            derive_counter_address(&program_id, &owner, "daily"),
+            derive(&[b"counter", owner.as_ref(), b"daily"])
+        );
+        assert_eq!(derive_registry_address(&program_id), derive(&[b"registry"]));
+        assert_eq!(
+            derive_fee_vault_address(&program_id, &counter),
+            derive(&[b"fee_vault", counter.as_ref()])
+        );
+        assert_eq!(
+            derive_snapshot_address(&program_id, &counter, 3),
+            derive(&[b"snapshot", counter.as_ref(), &3u64.to_le_bytes()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slot_counter_tallies() {
+        use crate::state::SlotCounter;
+
+        let mut env = TestEnv::start().await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+
+        let counter = Keypair::new();
+        let initialize = |slots: u8| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::InitializeSlotCounter { slots }.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+        let change = |instruction: CounterInstruction| {
+            Instruction::new_with_bytes(
+                program_id,
+                &instruction.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(payer, true),
+                ],
+            )
+        };
+
+        let err = env.send(&[initialize(0)], &[&counter]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidConfig as u32)
+            )
+        );
+        env.send(&[initialize(3)], &[&counter]).await.unwrap();
+
+        env.send(
+            &[
+                change(CounterInstruction::IncrementSlot { index: 0 }),
+                change(CounterInstruction::IncrementSlot { index: 2 }),
+                change(CounterInstruction::IncrementSlot { index: 2 }),
+                change(CounterInstruction::DecrementSlot { index: 2 }),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // sized for exactly the requested slots
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.data.len(), 8 + 32 + 4 + 3 * 8);
+        let state = SlotCounter::try_from_slice(&account.data).unwrap();
+        assert_eq!(state.slots, vec![1, 0, 1]);
+
+        let err = env
+            .send(
+                &[change(CounterInstruction::IncrementSlot { index: 3 })],
+                &[],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::SlotOutOfRange as u32)
+            )
+        );
+        let err = env
+            .send(
+                &[change(CounterInstruction::DecrementSlot { index: 1 })],
+                &[],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+
+        let get_slot = Instruction::new_with_bytes(
+            program_id,
+            &CounterInstruction::GetSlot { index: 2 }.pack(),
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[get_slot],
+            Some(&payer),
+            &[&env.payer],
+            env.blockhash,
+        );
+        let metadata = env
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap()
+            .metadata
+            .unwrap();
+        let return_data = metadata.return_data.unwrap();
+        assert_eq!(interface::parse_value(&return_data.data), Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_conditional_increment_rejects_stale_values() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(5, &InitConfig::default()).await;
+
+        let conditional = |env: &TestEnv, expected: u64| {
+            env.counter_ix(
+                &CounterInstruction::ConditionalIncrement { expected }.pack(),
+                &counter,
+            )
+        };
+
+        let stale = conditional(&env, 4);
+        let err = env.send(&[stale], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::StaleValue as u32)
+            )
+        );
+
+        let fresh = conditional(&env, 5);
+        env.send(&[fresh], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 6);
+
+        // two writers that both read 6: only one can win
+        let first = conditional(&env, 6);
+        let second = conditional(&env, 6);
+        let err = env.send(&[first, second], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(CounterError::StaleValue as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 6);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_with_trigger_accounts() {
+        use crate::client::{increment_ix, with_trigger_accounts};
+
+        let program_id = Pubkey::new_unique();
+        let counter = Pubkey::new_unique();
+        let actor = Pubkey::new_unique();
+        let target_id = Pubkey::new_unique();
+        let record = Pubkey::new_unique();
+        let (trigger_authority, _) = derive_trigger_authority(&program_id, &counter);
+
+        let trigger = TriggerConfig {
+            threshold: 10,
+            program_id: target_id,
+            accounts: vec![
+                TriggerAccount {
+                    pubkey: trigger_authority,
+                    is_signer: true,
+                    is_writable: false,
+                },
+                TriggerAccount {
+                    pubkey: record,
+                    is_signer: false,
+                    is_writable: true,
+                },
+            ],
+            data: vec![],
+        };
+
+        let instruction = with_trigger_accounts(
+            increment_ix(&program_id, &counter, &actor),
+            &counter,
+            &trigger,
+        );
+        assert_eq!(
+            instruction.accounts[2..],
+            [
+                AccountMeta::new_readonly(target_id, false),
+                AccountMeta::new_readonly(trigger_authority, false),
+                AccountMeta::new(record, false),
+            ]
+        );
+    }
+
+    #[cfg(feature = "anchor-layout")]
+    #[test]
+    fn test_anchor_discriminators_match_sighashes() {
+        use crate::addresses::{REGISTRY_DISCRIMINATOR, SLOT_COUNTER_DISCRIMINATOR};
+        use crate::anchor::INSTRUCTION_SIGHASHES;
+        use solana_program::hash::hashv;
+
+        let sighash = |preimage: &str| -> [u8; 8] {
+            hashv(&[preimage.as_bytes()]).to_bytes()[..8]
+                .try_into()
+                .unwrap()
+        };
+        let snake_case = |name: &str| {
+            let mut snake = String::new();
+            for (i, c) in name.chars().enumerate() {
+                if c.is_ascii_uppercase() && i > 0 {
+                    snake.push('_');
+                }
+                snake.push(c.to_ascii_lowercase());
+            }
+            snake
+        };
+
+        assert_eq!(INSTRUCTION_SIGHASHES.len(), INSTRUCTIONS.len());
+        for meta in INSTRUCTIONS {
+            assert_eq!(
+                INSTRUCTION_SIGHASHES[meta.tag as usize],
+                sighash(&format!("global:{}", snake_case(meta.name))),
+                "{}",
+                meta.name
+            );
+        }
+        assert_eq!(COUNTER_DISCRIMINATOR, sighash("account:CounterAccount"));
+        assert_eq!(REGISTRY_DISCRIMINATOR, sighash("account:Registry"));
+        assert_eq!(SLOT_COUNTER_DISCRIMINATOR, sighash("account:SlotCounter"));
+    }
+
+    #[cfg(feature = "anchor-layout")]
+    #[tokio::test]
+    async fn test_anchor_sighash_dispatch() {
+        use crate::anchor::to_anchor_data;
+
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(5, &InitConfig::default()).await;
+
+        let data = to_anchor_data(&CounterInstruction::IncrementBy { amount: 3 }.pack()).unwrap();
+        assert_ne!(data[0], 11);
+        let ix = env.counter_ix(&data, &counter);
+        env.send(&[ix], &[]).await.unwrap();
+
+        let account = env.read_counter(&counter).await;
+        assert_eq!(account.count, 8);
+        assert_eq!(account.discriminator, COUNTER_DISCRIMINATOR);
+    }
+
+    #[test]
+    fn test_idl_args_match_packed_lengths() {
+        use crate::idl::{INSTRUCTION_ARGS, instruction_args};
+
+        assert_eq!(INSTRUCTION_ARGS.len(), INSTRUCTIONS.len());
+
+        let delegate = Pubkey::new_unique();
+        let fixed = [
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::IncrementBy { amount: 5 },
+            CounterInstruction::RevealIncrement {
+                amount: 1,
+                salt: [7; 32],
+            },
+            CounterInstruction::LockCount {
+                amount: 2,
+                duration_slots: 10,
+            },
+            CounterInstruction::AddDelegate { delegate },
+            CounterInstruction::SetOwnerQuota {
+                max_counters_per_owner: 3,
+            },
+            CounterInstruction::InitializeSignedCounter { initial_value: -4 },
+            CounterInstruction::GetSlot { index: 1 },
+        ];
+        for instruction in fixed {
+            let data = instruction.pack();
+            let size: usize = instruction_args(data[0])
+                .iter()
+                .map(|arg| arg.ty.fixed_size().unwrap())
+                .sum();
+            assert_eq!(size, data.len() - 1, "{:?}", instruction);
+        }
+    }
+
+    #[cfg(not(any(feature = "devnet-tools", feature = "anchor-layout")))]
+    #[test]
+    fn test_shipped_idl_is_current() {
+        let shipped = include_str!("../idl/counter_program.json");
+        assert_eq!(
+            shipped.trim_end(),
+            idl_json(),
+            "regenerate with `cargo run --example idl > idl/counter_program.json`"
+        );
+    }
+
+    fn fuzzed_account_data() -> impl proptest::strategy::Strategy<Value = Vec<u8>> {
+        use proptest::collection::vec;
+        use proptest::prelude::{Just, Strategy, prop_oneof};
+
+        // random bytes rarely start with a discriminator, so lead with one
+        // most of the time to reach the decoders behind it
+        let prefix = prop_oneof![
+            Just(COUNTER_DISCRIMINATOR.to_vec()),
+            Just(crate::addresses::COUNTER_DISCRIMINATOR_V0.to_vec()),
+            vec(any::<u8>(), 0..8),
+        ];
+        (prefix, vec(any::<u8>(), 0..1024)).prop_map(|(mut data, tail)| {
+            data.extend(tail);
+            data
+        })
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_unpack_never_panics(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+            let _ = CounterInstruction::unpack(&data);
+            let _ = CounterInterfaceInstruction::unpack(&data);
+        }
+
+        #[test]
+        fn test_unpack_round_trips_whatever_it_accepts(
+            tag in 0..=INSTRUCTIONS.len() as u8,
+            rest in proptest::collection::vec(any::<u8>(), 0..256),
+        ) {
+            let mut data = vec![tag];
+            data.extend(rest);
+            if let Ok(instruction) = CounterInstruction::unpack(&data) {
+                let repacked = CounterInstruction::unpack(&instruction.pack()).unwrap();
+                proptest::prop_assert_eq!(format!("{:?}", repacked), format!("{:?}", instruction));
+            }
+        }
+
+        #[test]
+        fn test_account_decoders_never_panic(data in fuzzed_account_data()) {
+            use crate::state::{Multisig, SignedCounter, SlotCounter};
+
+            // `backend_for` logs, which needs the bank's invoke context, so
+            // the processor test below covers it
+            let _ = CounterAccount::deserialize(&mut data.as_slice());
+            let _ = CounterAccountV0::deserialize(&mut data.as_slice());
+            let _ = CompositeCounter::deserialize(&mut data.as_slice());
+            let _ = Registry::deserialize(&mut data.as_slice());
+            let _ = Snapshot::deserialize(&mut data.as_slice());
+            let _ = Multisig::deserialize(&mut data.as_slice());
+            let _ = SignedCounter::deserialize(&mut data.as_slice());
+            let _ = SlotCounter::deserialize(&mut data.as_slice());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processor_survives_fuzzed_input() {
+        use proptest::collection::vec;
+        use proptest::strategy::{Strategy, ValueTree};
+        use proptest::test_runner::TestRunner;
+        use std::time::Duration;
+
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(3, &InitConfig::default()).await;
+        let fuzzed = Pubkey::new_unique();
+
+        let strategy = (
+            0..=INSTRUCTIONS.len() as u8,
+            vec(any::<u8>(), 0..128),
+            fuzzed_account_data(),
+        );
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..64 {
+            let (tag, rest, account_data) = strategy.new_tree(&mut runner).unwrap().current();
+            let account = Account {
+                lamports: 1_000_000_000,
+                data: account_data,
+                owner: env.program_id,
+                executable: false,
+                rent_epoch: 0,
+            };
+            env.context.set_account(&fuzzed, &account.into());
+
+            let mut data = vec![tag];
+            data.extend(rest);
+            let instruction = Instruction::new_with_bytes(
+                env.program_id,
+                &data,
+                vec![
+                    AccountMeta::new(fuzzed, false),
+                    AccountMeta::new(env.payer.pubkey(), true),
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            );
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&env.payer.pubkey()),
+                &[&env.payer],
+                env.blockhash,
+            );
+
+            // a panicking native processor hangs the bank instead of failing
+            let result = tokio::time::timeout(
+                Duration::from_secs(30),
+                env.banks_client.process_transaction(transaction),
+            )
+            .await
+            .unwrap_or_else(|_| panic!("tag {} hung the processor", tag));
+            match result {
+                Ok(()) => {}
+                Err(BanksClientError::TransactionError(TransactionError::InstructionError(
+                    0,
+                    _,
+                ))) => {}
+                Err(err) => panic!("tag {} failed outside the program: {:?}", tag, err),
+            }
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_test_utils_harness() {
+        use crate::client::increment_by_ix;
+        use crate::test_utils::{create_counter, read_counter, send, setup_program};
+
+        let mut ctx = setup_program().await;
+        let counter = create_counter(&mut ctx, 5).await.unwrap();
+        assert_eq!(read_counter(&mut ctx, &counter).await.count, 5);
+
+        let increment = increment_by_ix(&ctx.program_id, &counter, &ctx.payer(), 2);
+        for _ in 0..2 {
+            send(&mut ctx, std::slice::from_ref(&increment), &[])
+                .await
+                .unwrap();
+        }
+        assert_eq!(read_counter(&mut ctx, &counter).await.count, 9);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_pre_funded_account() {
+        use solana_sdk::system_instruction::transfer;
+
+        let mut env = TestEnv::start().await;
+        let initialize_ix = |program_id: Pubkey, counter: &Pubkey, payer: &Pubkey| {
+            let mut data = vec![0];
+            data.extend_from_slice(&7u64.to_le_bytes());
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(*counter, true),
+                    AccountMeta::new(*payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(derive_global_config_address(&program_id).0, false),
+                ],
+            )
+        };
+        let payer = env.payer.pubkey();
+
+        // someone already sent lamports to the counter's address
+        let counter = Keypair::new();
+        let prefund = transfer(&payer, &counter.pubkey(), 1_000_000);
+        env.send(&[prefund], &[]).await.unwrap();
+        let initialize = initialize_ix(env.program_id, &counter.pubkey(), &payer);
+        env.send(&[initialize], &[&counter]).await.unwrap();
+
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        let rent = env.banks_client.get_rent().await.unwrap();
+        assert_eq!(account.owner, env.program_id);
+        assert_eq!(account.lamports, rent.minimum_balance(account.data.len()));
+        assert_eq!(env.read_counter(&counter).await.count, 7);
+
+        // a payer that cannot cover the rent is refused before any transfer
+        let poor = Keypair::new();
+        let fund = transfer(&payer, &poor.pubkey(), 1_000_000);
+        env.send(&[fund], &[]).await.unwrap();
+        let counter = Keypair::new();
+        let initialize = initialize_ix(env.program_id, &counter.pubkey(), &poor.pubkey());
+        let err = env
+            .send(&[initialize], &[&counter, &poor])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InsufficientRentFunds as u32)
+            )
+        );
+
+        // an address another program owns is not free to take
+        let foreign = Keypair::new();
+        let foreign_account = Account {
+            lamports: 1_000_000,
+            data: vec![],
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        env.context
+            .set_account(&foreign.pubkey(), &foreign_account.into());
+        let initialize = initialize_ix(env.program_id, &foreign.pubkey(), &payer);
+        let err = env.send(&[initialize], &[&foreign]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::AlreadyInitialized as u32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_owner_index_tracks_created_counters() {
+        use crate::addresses::derive_owner_index_address;
+        use crate::state::OwnerIndex;
+
+        let mut env = TestEnv::start().await;
+        let payer = env.payer.pubkey();
+        let (index, _) = derive_owner_index_address(&env.program_id, &payer);
+
+        let mut counters = Vec::new();
+        for _ in 0..2 {
+            let counter = Keypair::new();
+            let mut data = vec![0];
+            data.extend_from_slice(&0u64.to_le_bytes());
+            let initialize = Instruction::new_with_bytes(
+                env.program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(
+                        derive_global_config_address(&env.program_id).0,
+                        false,
+                    ),
+                    AccountMeta::new(index, false),
+                ],
+            );
+            env.send(&[initialize], &[&counter]).await.unwrap();
+            counters.push(counter.pubkey());
+        }
+
+        let account = env.banks_client.get_account(index).await.unwrap().unwrap();
+        let listed = OwnerIndex::try_from_slice(&account.data).unwrap();
+        assert_eq!(listed.owner, payer);
+        assert_eq!(listed.counters, counters);
+        let full_len = account.data.len();
+
+        let close = Instruction::new_with_bytes(
+            env.program_id,
+            &[14],
+            vec![
+                AccountMeta::new(counters[0], false),
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(payer, false),
+                AccountMeta::new(index, false),
+            ],
+        );
+        env.send(&[close], &[]).await.unwrap();
+
+        let account = env.banks_client.get_account(index).await.unwrap().unwrap();
+        let listed = OwnerIndex::try_from_slice(&account.data).unwrap();
+        assert_eq!(listed.counters, vec![counters[1]]);
+        assert_eq!(account.data.len(), full_len - 32);
+
+        // the index is best effort: a create without it goes unlisted and a
+        // close without it leaves the address behind
+        let unlisted = env.initialize(0, &InitConfig::default()).await;
+        let close = Instruction::new_with_bytes(
+            env.program_id,
+            &[14],
+            vec![
+                AccountMeta::new(counters[1], false),
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(payer, false),
+            ],
+        );
+        env.send(&[close], &[]).await.unwrap();
+
+        let account = env.banks_client.get_account(index).await.unwrap().unwrap();
+        let listed = OwnerIndex::try_from_slice(&account.data).unwrap();
+        assert!(!listed.counters.contains(&unlisted.pubkey()));
+        assert_eq!(listed.counters, vec![counters[1]]);
+        assert!(
+            env.banks_client
+                .get_account(counters[1])
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_count_between_counters() {
+        let mut env = TestEnv::start().await;
+        let source = env.initialize(10, &InitConfig::default()).await;
+        let destination = env.initialize(1, &InitConfig::default()).await;
+        let program_id = env.program_id;
+        let payer = env.payer.pubkey();
+        let transfer_ix = |amount: u64, authority: &Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::TransferCount { amount }.pack(),
+                vec![
+                    AccountMeta::new(source.pubkey(), false),
+                    AccountMeta::new(destination.pubkey(), false),
+                    AccountMeta::new_readonly(*authority, true),
+                ],
+            )
+        };
+
+        env.send(&[transfer_ix(4, &payer)], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&source).await.count, 6);
+        assert_eq!(env.read_counter(&destination).await.count, 5);
+
+        // more than the source holds moves nothing
+        let err = env.send(&[transfer_ix(7, &payer)], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&destination).await.count, 5);
+
+        // only the source's authority may move its count
+        let stranger = Keypair::new();
+        let err = env
+            .send(&[transfer_ix(1, &stranger.pubkey())], &[&stranger])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_plain_counter_scan_matches_borsh() {
+        use crate::client::{add_delegate_ix, set_topics_ix};
+        use crate::zero_copy::PlainCounter;
+
+        let mut env = TestEnv::start().await;
+        let payer = env.payer.pubkey();
+        let config = InitConfig {
+            metrics: vec![MetricConfig {
+                name: Label32::new("logins").unwrap(),
+                initial_value: 3,
+                max_value: 10,
+            }],
+            step: 4,
+            access_log_len: 2,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(7, &config).await;
+        let topics = Topics::new(vec![
+            Topic::new(b"orders".to_vec()).unwrap(),
+            Topic::new(b"eu".to_vec()).unwrap(),
+        ])
+        .unwrap();
+        let set_topics = set_topics_ix(&env.program_id, &counter.pubkey(), &payer, topics);
+        let add_delegate = add_delegate_ix(
+            &env.program_id,
+            &counter.pubkey(),
+            &payer,
+            &Pubkey::new_unique(),
+        );
+        env.send(&[set_topics, add_delegate], &[]).await.unwrap();
+
+        let mut data = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        let decoded = CounterAccount::try_from_slice(&data).unwrap();
+        let plain = PlainCounter::scan(&data).expect("a plain counter");
+        assert_eq!(plain.count, 7);
+        assert_eq!(plain.authority, payer);
+        assert!(plain.authority_gated);
+        assert_eq!(plain.step, 4);
+        assert_eq!(plain.min_observed_lamports, decoded.min_observed_lamports);
+        let topics: Vec<&[u8]> = decoded.topics.iter().map(|topic| &topic[..]).collect();
+        assert_eq!(plain.topics(&data), topics);
+
+        plain.write_increment(&mut data, 11, 40, 1_700_000_000);
+        let patched = CounterAccount::try_from_slice(&data).unwrap();
+        assert_eq!(patched.count, 11);
+        assert_eq!(patched.gate_last_slot, 40);
+        assert_eq!(patched.last_active_slot, 40);
+        assert_eq!(patched.last_updated, 1_700_000_000);
+        assert_eq!(patched.access_log, decoded.access_log);
+        assert_eq!(patched.delegates, decoded.delegates);
+
+        // anything the in-place path does not handle is left to Borsh
+        let history = env
+            .initialize(
+                0,
+                &InitConfig {
+                    history_len: 4,
+                    ..InitConfig::default()
+                },
+            )
+            .await;
+        let data = env
+            .banks_client
+            .get_account(history.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        assert_eq!(PlainCounter::scan(&data), None);
+        assert_eq!(PlainCounter::scan(&data[..data.len() - 1]), None);
+    }
+
+    // tests/compute_units.rs checks that the in-place path is also cheaper
+    #[tokio::test]
+    async fn test_in_place_increment_matches_full_path() {
+        let mut env = TestEnv::start().await;
+        let plain = env.initialize(5, &InitConfig::default()).await;
+        // an unlock time in the past changes nothing but rules out the
+        // in-place path
+        let full = env
+            .initialize(
+                5,
+                &InitConfig {
+                    unlock_timestamp: Some(1),
+                    ..InitConfig::default()
+                },
+            )
+            .await;
+
+        let increments = [env.counter_ix(&[1], &plain), env.counter_ix(&[1], &full)];
+        env.send(&increments, &[]).await.unwrap();
+
+        let plain_state = env.read_counter(&plain).await;
+        let mut full_state = env.read_counter(&full).await;
+        assert_eq!(plain_state.count, 6);
+        full_state.unlock_timestamp = 0;
+        full_state.created_at = plain_state.created_at;
+        assert_eq!(
+            borsh::to_vec(&plain_state).unwrap(),
+            borsh::to_vec(&full_state).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_counter_is_read_only_and_closable() {
+        use solana_sdk::clock::Clock;
+
+        let mut env = TestEnv::start().await;
+        let clock = env.banks_client.get_sysvar::<Clock>().await.unwrap();
+        let expires_at = clock.unix_timestamp + 600;
+        let config = InitConfig {
+            expires_at: Some(expires_at),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(5, &config).await;
+        assert_eq!(env.read_counter(&counter).await.expires_at, expires_at);
+
+        let payer = env.payer.pubkey();
+        let close_expired = |program_id: Pubkey, payer: &Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::CloseExpired.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(*payer, false),
+                ],
+            )
+        };
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+
+        let err = env
+            .send(&[close_expired(env.program_id, &payer)], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::NotExpired));
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+
+        env.context.set_sysvar(&Clock {
+            unix_timestamp: expires_at,
+            ..clock
+        });
+        let set_step = env.counter_ix(&CounterInstruction::SetStep { step: 2 }.pack(), &counter);
+        for data in [&[1][..], &[9][..]] {
+            let ix = env.counter_ix(data, &counter);
+            let err = env.send(&[ix], &[]).await.unwrap_err();
+            assert_eq!(err.unwrap(), custom(CounterError::CounterExpired));
+        }
+        let err = env.send(&[set_step], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::CounterExpired));
+
+        // anyone may close it, but only into the original payer
+        let err = env
+            .send(&[close_expired(env.program_id, &Pubkey::new_unique())], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+        let before = env.banks_client.get_balance(payer).await.unwrap();
+        env.send(&[close_expired(env.program_id, &payer)], &[])
+            .await
+            .unwrap();
+        assert!(env.banks_client.get_balance(payer).await.unwrap() > before);
+        assert!(
+            env.banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // an expiry must lie in the future
+        let mut data = vec![0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        InitConfig {
+            expires_at: Some(expires_at),
+            ..InitConfig::default()
+        }
+        .serialize(&mut data)
+        .unwrap();
+        let new_counter = Keypair::new();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(new_counter.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[initialize], &[&new_counter]).await.unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::InvalidConfig));
+    }
+
+    #[tokio::test]
+    async fn test_increment_with_proof_claims_once() {
+        let mut env = TestEnv::start().await;
+        let payer = env.payer.pubkey();
+        let allowlist = [Pubkey::new_unique(), payer, Pubkey::new_unique()];
+        let tree = MerkleTree::new(&allowlist);
+        let config = InitConfig {
+            merkle_root: Some(tree.root()),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+        assert_eq!(env.read_counter(&counter).await.merkle_root, tree.root());
+
+        let with_proof = |program_id: Pubkey, index: u32| {
+            let (claims, _) = derive_claim_bitmap_address(&program_id, &counter.pubkey(), 0);
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::IncrementWithProof {
+                    index,
+                    proof: MerkleProof::try_from(tree.proof(1)).unwrap(),
+                }
+                .pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(claims, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            )
+        };
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+
+        // the allowlist replaces the authority gate, even for the authority
+        let increment = env.counter_ix(&[1], &counter);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::Unauthorized));
+        let err = env
+            .send(&[with_proof(env.program_id, 0)], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::InvalidMerkleProof));
+
+        env.send(&[with_proof(env.program_id, 1)], &[])
+            .await
+            .unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+
+        let err = env
+            .send(&[with_proof(env.program_id, 1)], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::AlreadyClaimed));
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_user_record_tracks_contributions() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            gates: Some(GateConfig::default()),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(10, &config).await;
+        let payer = env.payer.pubkey();
+        let other = Keypair::new();
+        let record_ix = |program_id: Pubkey, user: &Pubkey, amount: u64| {
+            let (record, _) = derive_user_record_address(&program_id, &counter.pubkey(), user);
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::IncrementBy { amount }.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(*user, true),
+                    AccountMeta::new(record, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            )
+        };
+        let fund = solana_sdk::system_instruction::transfer(&payer, &other.pubkey(), 1_000_000_000);
+        env.send(&[fund], &[]).await.unwrap();
+
+        env.send(&[record_ix(env.program_id, &payer, 3)], &[])
+            .await
+            .unwrap();
+        env.send(&[record_ix(env.program_id, &payer, 4)], &[])
+            .await
+            .unwrap();
+        // increments that leave the record out are not credited to it
+        let increment = env.counter_ix(
+            &CounterInstruction::IncrementBy { amount: 5 }.pack(),
+            &counter,
+        );
+        env.send(&[increment], &[]).await.unwrap();
+        env.send(&[record_ix(env.program_id, &other.pubkey(), 2)], &[&other])
+            .await
+            .unwrap();
+
+        let mut records = Vec::new();
+        for user in [payer, other.pubkey()] {
+            let (record, _) = derive_user_record_address(&env.program_id, &counter.pubkey(), &user);
+            let account = env.banks_client.get_account(record).await.unwrap().unwrap();
+            records.push(UserRecord::try_from_slice(&account.data).unwrap());
+        }
+        assert_eq!(
+            (records[0].counter, records[0].user),
+            (counter.pubkey(), payer)
+        );
+        assert_eq!((records[0].count, records[1].count), (7, 2));
+        assert_eq!(env.read_counter(&counter).await.count, 24);
+    }
+
+    #[tokio::test]
+    async fn test_global_config_is_upgrade_authority_gated() {
+        let mut env = TestEnv::start().await;
+        let payer = env.payer.pubkey();
+        let authority = Keypair::new();
+        let fund =
+            solana_sdk::system_instruction::transfer(&payer, &authority.pubkey(), 1_000_000_000);
+        env.send(&[fund], &[]).await.unwrap();
+
+        // a native test program has no data account, so stand one in
+        let program_data = derive_program_data_address(&env.program_id);
+        let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        data.extend_from_slice(authority.pubkey().as_ref());
+        let account = Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: solana_sdk::bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        env.context.set_account(&program_data, &account.into());
+
+        let (config, _) = derive_global_config_address(&env.program_id);
+        let global_ix = |program_id: Pubkey, instruction: CounterInstruction, signer: &Pubkey| {
+            let mut accounts = vec![
+                AccountMeta::new(config, false),
+                AccountMeta::new(*signer, true),
+                AccountMeta::new_readonly(program_data, false),
+            ];
+            if matches!(instruction, CounterInstruction::InitializeGlobalConfig) {
+                accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+            }
+            Instruction::new_with_bytes(program_id, &instruction.pack(), accounts)
+        };
+        let set_params = |program_id: Pubkey, paused: bool| {
+            let params = GlobalParams {
+                default_max_value: 100,
+                protocol_fee: 5_000,
+                fee_recipient: authority.pubkey(),
+                paused,
+                max_counters_per_owner: 0,
+            };
+            global_ix(
+                program_id,
+                CounterInstruction::SetGlobalConfig { params },
+                &authority.pubkey(),
+            )
+        };
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+
+        let initialize = global_ix(
+            env.program_id,
+            CounterInstruction::InitializeGlobalConfig,
+            &payer,
+        );
+        let err = env.send(&[initialize], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::Unauthorized));
+        let initialize = global_ix(
+            env.program_id,
+            CounterInstruction::InitializeGlobalConfig,
+            &authority.pubkey(),
+        );
+        env.send(
+            &[initialize, set_params(env.program_id, false)],
+            &[&authority],
+        )
+        .await
+        .unwrap();
+
+        // new counters passing the config pick up its default cap
+        let counter = Keypair::new();
+        let mut data = vec![0];
+        data.extend_from_slice(&5u64.to_le_bytes());
+        InitConfig::default().serialize(&mut data).unwrap();
+        let create = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(config, false),
+            ],
+        );
+        env.send(&[create], &[&counter]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.max_value, Some(100));
+
+        // so do counters created already incremented, which respect the cap
+        let incremented = Keypair::new();
+        let program_id = env.program_id;
+        let create_incremented = |delta: u64| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::InitializeAndIncrement {
+                    initial_value: 95,
+                    delta,
+                }
+                .pack(),
+                vec![
+                    AccountMeta::new(incremented.pubkey(), true),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                    AccountMeta::new_readonly(config, false),
+                ],
+            )
+        };
+        let err = env
+            .send(&[create_incremented(10)], &[&incremented])
+            .await
+            .unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::CapExceeded));
+        env.send(&[create_incremented(5)], &[&incremented])
+            .await
+            .unwrap();
+        let state = env.read_counter(&incremented).await;
+        assert_eq!((state.count, state.max_value), (100, Some(100)));
+
+        let mut increment = env.counter_ix(&[1], &counter);
+        increment.accounts[1].is_writable = true;
+        increment.accounts.extend([
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(authority.pubkey(), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ]);
+        let before = env
+            .banks_client
+            .get_balance(authority.pubkey())
+            .await
+            .unwrap();
+        env.send(&[increment.clone()], &[]).await.unwrap();
+        let after = env
+            .banks_client
+            .get_balance(authority.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(after - before, 5_000);
+
+        // increments nobody pays the fee for refuse to run while it is charged
+        let increment_many = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::IncrementMany.pack(),
+            vec![
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(config, false),
+            ],
+        );
+        let err = env.send(&[increment_many], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        let code = CouponCode::new(b"FEE-FREE".to_vec()).unwrap();
+        let (coupon, _) =
+            derive_coupon_address(&env.program_id, &counter.pubkey(), &coupon_code_hash(&code));
+        let mint = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::MintCoupon {
+                code_hash: coupon_code_hash(&code),
+                amount: 1,
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        );
+        env.send(&[mint], &[]).await.unwrap();
+        let redeem = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::RedeemCoupon { code }.pack(),
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new(payer, false),
+                AccountMeta::new_readonly(config, false),
+            ],
+        );
+        let err = env.send(&[redeem], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 6);
+
+        // the pause holds for everything passing the config but lifting it
+        env.send(&[set_params(env.program_id, true)], &[&authority])
+            .await
+            .unwrap();
+        let err = env.send(&[increment.clone()], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), custom(CounterError::ProgramPaused));
+        env.send(&[set_params(env.program_id, false)], &[&authority])
+            .await
+            .unwrap();
+        env.send(&[increment], &[]).await.unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 7);
+    }
+
+    #[tokio::test]
+    async fn test_read_count_returns_borsh_count() {
+        let mut env = TestEnv::start().await;
+        let counter = env.initialize(41, &InitConfig::default()).await;
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+
+        // a read-only transaction, so simulating it is enough
+        let read = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::ReadCount.pack(),
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[read],
+            Some(&env.payer.pubkey()),
+            &[&env.payer],
+            env.blockhash,
+        );
+        let simulation = env
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .unwrap();
+        let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+        assert_eq!(return_data.program_id, env.program_id);
+        assert_eq!(u64::try_from_slice(&return_data.data).unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_once_per_transaction_rejects_repeat_increments() {
+        use solana_sdk::sysvar;
+
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            once_per_transaction: true,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+        let with_instructions_sysvar = |mut instruction: Instruction| {
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+            instruction
+        };
+        let duplicate = |index| {
+            TransactionError::InstructionError(
+                index,
+                InstructionError::Custom(CounterError::DuplicateIncrement as u32),
+            )
+        };
+
+        // the check needs the instructions sysvar
+        let increment = env.counter_ix(&[1], &counter);
+        let err = env
+            .send(std::slice::from_ref(&increment), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        let increment = with_instructions_sysvar(increment);
+        env.send(std::slice::from_ref(&increment), &[])
+            .await
+            .unwrap();
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+
+        // a second increment in the same transaction fails the first as well
+        let increment_by = with_instructions_sysvar(env.counter_ix(
+            &CounterInstruction::IncrementBy { amount: 2 }.pack(),
+            &counter,
+        ));
+        let err = env.send(&[increment, increment_by], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), duplicate(0));
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+
+        // and a batch could list the counter twice
+        let increment_many = Instruction::new_with_bytes(
+            env.program_id,
+            &[34],
+            vec![
+                AccountMeta::new_readonly(env.payer.pubkey(), true),
+                AccountMeta::new(counter.pubkey(), false),
+            ],
+        );
+        let err = env.send(&[increment_many], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), duplicate(0));
+
+        // and so could every other path raising the count
+        let payer = env.payer.pubkey();
+        let salt = [7u8; 32];
+        let mut commit = vec![3];
+        commit.extend_from_slice(&commitment_hash(1, &salt));
+        let commit = env.counter_ix(&commit, &counter);
+        env.send(&[commit], &[]).await.unwrap();
+        let code = CouponCode::new(b"ONCE".to_vec()).unwrap();
+        let (coupon, _) =
+            derive_coupon_address(&env.program_id, &counter.pubkey(), &coupon_code_hash(&code));
+        let mint = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::MintCoupon {
+                code_hash: coupon_code_hash(&code),
+                amount: 1,
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        );
+        env.send(&[mint], &[]).await.unwrap();
+        let source = env.initialize(3, &InitConfig::default()).await;
+
+        let mut reveal = vec![4];
+        reveal.extend_from_slice(&1u64.to_le_bytes());
+        reveal.extend_from_slice(&salt);
+        let others = [
+            env.counter_ix(&reveal, &counter),
+            env.counter_ix(
+                &CounterInstruction::IncrementCommitted {
+                    value: 0,
+                    salt,
+                    amount: 1,
+                    new_salt: salt,
+                }
+                .pack(),
+                &counter,
+            ),
+            env.counter_ix(
+                &CounterInstruction::IncrementMetric {
+                    metric: MetricRef::Index(0),
+                    amount: 1,
+                }
+                .pack(),
+                &counter,
+            ),
+            Instruction::new_with_bytes(
+                env.program_id,
+                &CounterInstruction::RedeemCoupon { code }.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(coupon, false),
+                    AccountMeta::new(payer, false),
+                ],
+            ),
+            Instruction::new_with_bytes(
+                env.program_id,
+                &CounterInstruction::TransferCount { amount: 1 }.pack(),
+                vec![
+                    AccountMeta::new(source.pubkey(), false),
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(payer, true),
+                ],
+            ),
+        ];
+        for instruction in others {
+            let err = env.send(&[instruction], &[]).await.unwrap_err();
+            assert_eq!(err.unwrap(), duplicate(0));
+        }
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+
+        // other counters may still be incremented repeatedly
+        let open = env.initialize(0, &InitConfig::default()).await;
+        let twice = [
+            env.counter_ix(&[1], &open),
+            env.counter_ix(&[11, 2, 0, 0, 0, 0, 0, 0, 0], &open),
+        ];
+        env.send(&twice, &[]).await.unwrap();
+        assert_eq!(env.read_counter(&open).await.count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_counter_finalizes_after_max_uses() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            max_uses: 2,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+        let finalized = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::CounterFinalized as u32),
+        );
+
+        // only increments are uses, setting the count higher is not
+        let mut set = vec![17];
+        set.extend_from_slice(&10u64.to_le_bytes());
+        let set = env.counter_ix(&set, &counter);
+        env.send(&[set], &[]).await.unwrap();
+        let state = env.read_counter(&counter).await;
+        assert_eq!((state.count, state.uses), (10, 0));
+
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+        let increment_by = env.counter_ix(
+            &CounterInstruction::IncrementBy { amount: 5 }.pack(),
+            &counter,
+        );
+        env.send(&[increment_by], &[]).await.unwrap();
+        let state = env.read_counter(&counter).await;
+        assert_eq!((state.count, state.uses), (16, 2));
+        assert!(state.is_finalized);
+
+        // every further mutation fails, configuration changes included
+        let increment = env.counter_ix(&[1], &counter);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), finalized);
+        let decrement = env.counter_ix(&[9], &counter);
+        let err = env.send(&[decrement], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), finalized);
+        let set_step = env.counter_ix(&CounterInstruction::SetStep { step: 3 }.pack(), &counter);
+        let err = env.send(&[set_step], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), finalized);
+        assert_eq!(env.read_counter(&counter).await.count, 16);
+
+        // but the authority can still close it
+        let close = Instruction::new_with_bytes(
+            env.program_id,
+            &[14],
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(env.payer.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), false),
+            ],
+        );
+        env.send(&[close], &[]).await.unwrap();
+        let account = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap();
+        assert!(account.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_space_for_matches_created_accounts() {
+        let mut env = TestEnv::start().await;
+        let rich = InitConfig {
+            metrics: vec![MetricConfig {
+                name: Label32::new("logins").unwrap(),
+                initial_value: 0,
+                max_value: 0,
+            }],
+            trigger: Some(TriggerConfig {
+                threshold: 10,
+                program_id: Pubkey::new_unique(),
+                accounts: vec![TriggerAccount {
+                    pubkey: Pubkey::new_unique(),
+                    is_signer: false,
+                    is_writable: true,
+                }],
+                data: vec![1, 2, 3],
+            }),
+            observation_slots: 4,
+            gates: Some(GateConfig {
+                flags: GATE_ALLOWLIST,
+                allowlist: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+                ..GateConfig::default()
+            }),
+            max_value: Some(100),
+            excess_destination: Some(Pubkey::new_unique()),
+            history_len: 3,
+            access_log_len: 2,
+            ..InitConfig::default()
+        };
+        let mirror = InitConfig {
+            mirror_mint: Some(Pubkey::new_unique()),
+            ..InitConfig::default()
+        };
+        let imported = InitConfig {
+            import_source: Some(ImportSource {
+                counter: Pubkey::new_unique(),
+                attester: Pubkey::new_unique(),
+                max_age_secs: 60,
+            }),
+            ..InitConfig::default()
+        };
+        assert_eq!(
+            CounterAccount::space_for(&InitConfig::default()),
+            CounterAccount::LEN
+        );
+
+        for config in [InitConfig::default(), rich, mirror, imported] {
+            let counter = env.initialize(0, &config).await;
+            let account = env
+                .banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(account.data.len(), CounterAccount::space_for(&config));
+            let state = env.read_counter(&counter).await;
+            assert_eq!(borsh::object_length(&state).unwrap(), account.data.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_log_appends_across_pages() {
+        let mut env = TestEnv::start().await;
+        let config = InitConfig {
+            event_log: true,
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(5, &config).await;
+        let payer = env.payer.pubkey();
+        let pages: Vec<Pubkey> = (0..2)
+            .map(|page| derive_log_page_address(&env.program_id, &counter.pubkey(), page).0)
+            .collect();
+        let logged_ix = |program_id: Pubkey, amount: u64| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::IncrementBy { amount }.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(pages[0], false),
+                    AccountMeta::new(pages[1], false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                ],
+            )
+        };
+
+        // the head page has to be passed
+        let increment = env.counter_ix(&[1], &counter);
+        let err = env.send(&[increment], &[]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        // one more record than a page holds spills onto the next page
+        let increments: Vec<Instruction> = (1..=LOG_PAGE_RECORDS as u64 + 1)
+            .map(|amount| logged_ix(env.program_id, amount))
+            .collect();
+        env.send(&increments, &[]).await.unwrap();
+
+        let state = env.read_counter(&counter).await;
+        assert_eq!(state.log_head, 1);
+        assert_eq!(state.count, 5 + (1..=33).sum::<u64>());
+
+        let mut decoded = Vec::new();
+        for page in &pages {
+            let account = env.banks_client.get_account(*page).await.unwrap().unwrap();
+            decoded.push(LogPage::try_from_slice(&account.data).unwrap());
+        }
+        assert_eq!(decoded[0].records().len(), LOG_PAGE_RECORDS);
+        assert_eq!(decoded[1].page, 1);
+        let last = decoded[1].records()[0];
+        assert_eq!(
+            (last.actor, last.amount, last.count),
+            (payer, 33, state.count)
+        );
+        let first = decoded[0].records()[0];
+        assert_eq!((first.amount, first.count), (1, 6));
+
+        // increments that take no log page cannot leave a gap in the log
+        let not_logged =
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys);
+        let salt = [7u8; 32];
+        let mut commit = vec![3];
+        commit.extend_from_slice(&commitment_hash(1, &salt));
+        let commit = env.counter_ix(&commit, &counter);
+        env.send(&[commit], &[]).await.unwrap();
+        let mut reveal = vec![4];
+        reveal.extend_from_slice(&1u64.to_le_bytes());
+        reveal.extend_from_slice(&salt);
+        let reveal = env.counter_ix(&reveal, &counter);
+        let err = env.send(&[reveal], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), not_logged);
+
+        let code = CouponCode::new(b"LOGGED".to_vec()).unwrap();
+        let (coupon, _) =
+            derive_coupon_address(&env.program_id, &counter.pubkey(), &coupon_code_hash(&code));
+        let mint = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::MintCoupon {
+                code_hash: coupon_code_hash(&code),
+                amount: 1,
+            }
+            .pack(),
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+        );
+        env.send(&[mint], &[]).await.unwrap();
+        let redeem = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::RedeemCoupon { code }.pack(),
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new(coupon, false),
+                AccountMeta::new(payer, false),
+            ],
+        );
+        let err = env.send(&[redeem], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), not_logged);
+
+        let source = env.initialize(3, &InitConfig::default()).await;
+        let transfer = Instruction::new_with_bytes(
+            env.program_id,
+            &CounterInstruction::TransferCount { amount: 1 }.pack(),
+            vec![
+                AccountMeta::new(source.pubkey(), false),
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(payer, true),
+            ],
+        );
+        let err = env.send(&[transfer], &[]).await.unwrap_err();
+        assert_eq!(err.unwrap(), not_logged);
+        assert_eq!(env.read_counter(&counter).await.count, state.count);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_replay_log_folds_pages() {
+        use crate::client::{ReplayError, replay_log};
+        use crate::state::LogRecord;
+
+        let counter = Pubkey::new_unique();
+        let record = |amount, count| LogRecord {
+            slot: 0,
+            actor: Pubkey::new_unique(),
+            amount,
+            count,
+        };
+        let mut first = LogPage::new(counter, 0);
+        first.push(record(2, 12));
+        first.push(record(3, 15));
+        let mut second = LogPage::new(counter, 1);
+        second.push(record(1, 16));
+
+        let pages = [first.clone(), second.clone()];
+        assert_eq!(replay_log(&counter, 10, &pages), Ok(16));
+        assert_eq!(replay_log(&counter, 10, &[]), Ok(10));
+
+        // something other than a logged increment moved the count
+        second.push(record(1, 20));
+        assert_eq!(
+            replay_log(&counter, 10, &[first.clone(), second]),
+            Err(ReplayError::Gap { page: 1, index: 1 })
+        );
+        assert_eq!(
+            replay_log(&counter, 10, &pages[1..]),
+            Err(ReplayError::Page(0))
+        );
+        assert_eq!(
+            replay_log(&Pubkey::new_unique(), 10, &[first]),
+            Err(ReplayError::Page(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_gate_charges_treasury() {
+        let mint = Pubkey::new_unique();
+        let holder = Keypair::new();
+        let holder_tokens = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+
+        // SPL Token `Account` layout: mint, owner, amount, ..., state
+        let token_account = |owner: &Pubkey, amount: u64| {
+            let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+            data[..32].copy_from_slice(mint.as_ref());
+            data[32..64].copy_from_slice(owner.as_ref());
+            data[64..72].copy_from_slice(&amount.to_le_bytes());
+            data[108] = 1;
+            Account {
+                lamports: 10_000_000,
+                data,
+                owner: TOKEN_PROGRAM_ID,
+                ..Account::default()
+            }
+        };
+        let mut env = TestEnv::start_with(|program_test| {
+            program_test.add_account(holder_tokens, token_account(&holder.pubkey(), 25));
+            program_test.add_account(treasury, token_account(&Pubkey::new_unique(), 0));
+        })
+        .await;
+        let balance = |account: Option<Account>| {
+            u64::from_le_bytes(account.unwrap().data[64..72].try_into().unwrap())
+        };
+
+        let gates = GateConfig {
+            flags: GATE_TOKEN_BALANCE,
+            token_mint: mint,
+            min_token_balance: 10,
+            ..GateConfig::default()
+        };
+
+        // a treasury without the token gate is a mistake
+        let counter = Keypair::new();
+        let config = InitConfig {
+            token_treasury: Some(treasury),
+            ..InitConfig::default()
+        };
+        let mut data = vec![0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        config.serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            env.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(env.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let err = env.send(&[initialize], &[&counter]).await.unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidConfig as u32)
+            )
+        );
+
+        let config = InitConfig {
+            gates: Some(gates),
+            token_treasury: Some(treasury),
+            ..InitConfig::default()
+        };
+        let counter = env.initialize(0, &config).await;
+        let base = env.counter_ix_as(&[1], &counter, &holder.pubkey());
+        let increment = |treasury: Pubkey| {
+            let mut increment = base.clone();
+            increment.accounts.extend([
+                AccountMeta::new(holder_tokens, false),
+                AccountMeta::new(treasury, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            ]);
+            increment
+        };
+
+        let err = env
+            .send(&[increment(Pubkey::new_unique())], &[&holder])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        // each increment moves the minimum balance to the treasury
+        env.send(&[increment(treasury)], &[&holder]).await.unwrap();
+        let holder_balance = env.banks_client.get_account(holder_tokens).await.unwrap();
+        assert_eq!(balance(holder_balance), 15);
+
+        // other gated instructions only check the balance
+        let mut decrement = env.counter_ix_as(&[9], &counter, &holder.pubkey());
+        decrement
+            .accounts
+            .push(AccountMeta::new(holder_tokens, false));
+        env.send(&[decrement], &[&holder]).await.unwrap();
+        let holder_balance = env.banks_client.get_account(holder_tokens).await.unwrap();
+        assert_eq!(balance(holder_balance), 15);
+        let treasury_balance = env.banks_client.get_account(treasury).await.unwrap();
+        assert_eq!(balance(treasury_balance), 10);
+
+        env.send(&[increment(treasury)], &[&holder]).await.unwrap();
+        let holder_balance = env.banks_client.get_account(holder_tokens).await.unwrap();
+        assert_eq!(balance(holder_balance), 5);
+        let treasury_balance = env.banks_client.get_account(treasury).await.unwrap();
+        assert_eq!(balance(treasury_balance), 20);
+
+        let err = env
+            .send(&[increment(treasury)], &[&holder])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InsufficientTokenBalance as u32)
+            )
+        );
+        assert_eq!(env.read_counter(&counter).await.count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_counter_account_data_round_trips() {
+        let mut env = TestEnv::start().await;
+
+        let counter = env.initialize(5, &InitConfig::default()).await;
+        let increment = env.counter_ix(&[1], &counter);
+        env.send(&[increment], &[]).await.unwrap();
+
+        let data = env
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        let decoded = CounterAccount::from_account_data(&data).unwrap();
+        assert_eq!(decoded.count, 6);
+        assert_eq!(decoded.to_account_data(), data);
+
+        // unversioned counters decode into the current layout
+        let legacy = borsh::to_vec(&CounterAccountV0::try_from(decoded).unwrap()).unwrap();
+        let upgraded = CounterAccount::from_account_data(&legacy).unwrap();
+        assert_eq!(upgraded.count, 6);
+        assert_eq!(upgraded.version, COUNTER_VERSION);
+    }
+}
@@ -0,0 +1,52 @@
+//! wasm-bindgen exports for browser front-ends, so they decode accounts and
+//! encode instructions with this crate's layouts instead of restating the
+//! Borsh schemas in TypeScript. Everything here is a pure function of its
+//! arguments, usable on simulated as well as fetched account data.
+
+use wasm_bindgen::prelude::*;
+
+use crate::export::export_counter_json;
+use crate::gate::cooldown_ends_at;
+use crate::instruction::CounterInstruction;
+use crate::state::CounterAccount;
+
+/// The counter's value and configuration as `export_counter_json` writes
+/// them.
+#[wasm_bindgen(js_name = decodeCounter)]
+pub fn decode_counter(data: &[u8]) -> Result<String, JsError> {
+    Ok(export_counter_json(&CounterAccount::from_account_data(
+        data,
+    )?))
+}
+
+#[wasm_bindgen(js_name = counterCount)]
+pub fn counter_count(data: &[u8]) -> Result<u64, JsError> {
+    Ok(CounterAccount::from_account_data(data)?.count)
+}
+
+/// See `gate::cooldown_ends_at`.
+#[wasm_bindgen(js_name = cooldownEndsAt)]
+pub fn counter_cooldown_ends_at(data: &[u8]) -> Result<Option<u64>, JsError> {
+    Ok(cooldown_ends_at(&CounterAccount::from_account_data(data)?))
+}
+
+/// Instruction data adding the counter's `step`.
+#[wasm_bindgen(js_name = encodeIncrement)]
+pub fn encode_increment() -> Vec<u8> {
+    CounterInstruction::IncrementCounter.pack()
+}
+
+#[wasm_bindgen(js_name = encodeIncrementBy)]
+pub fn encode_increment_by(amount: u64) -> Vec<u8> {
+    CounterInstruction::IncrementBy { amount }.pack()
+}
+
+#[wasm_bindgen(js_name = encodeDecrement)]
+pub fn encode_decrement() -> Vec<u8> {
+    CounterInstruction::DecrementCounter.pack()
+}
+
+#[wasm_bindgen(js_name = encodeDecrementBy)]
+pub fn encode_decrement_by(amount: u64) -> Vec<u8> {
+    CounterInstruction::DecrementBy { amount }.pack()
+}
@@ -0,0 +1,238 @@
+//! In-place access to Borsh-encoded `CounterAccount`s. Borsh stays the
+//! documented account layout; this walks the encoding to find where fields
+//! sit, so hot handlers can read and patch a few of them without decoding
+//! the whole account.
+//!
+//! Only counters with none of the optional machinery active are handled:
+//! anything `PlainCounter::scan` does not recognise goes through
+//! `load_counter`/`save_counter` as before.
+
+use solana_program::pubkey::Pubkey;
+
+use crate::addresses::COUNTER_DISCRIMINATOR;
+use crate::bounded::Label32;
+use crate::gate::GATE_AUTHORITY;
+use crate::state::{COUNTER_VERSION, MAX_DELEGATES, MAX_TOPIC_LEN, MAX_TOPICS};
+
+/// Offset of `count`, after the discriminator and version.
+pub const COUNT_OFFSET: usize = 9;
+
+const PUBKEY_LEN: usize = 32;
+/// `AccessLogEntry`: slot, actor and instruction tag.
+const ACCESS_LOG_ENTRY_LEN: usize = 8 + PUBKEY_LEN + 1;
+
+/// A current-layout counter that `IncrementCounter` can update by writing
+/// `count`, `gate_last_slot`, `last_active_slot` and `last_updated` alone:
+/// no breaker, commitment, trigger, mirror, cap, lock, pause, freeze, fee,
+/// compute floor, expiry, allowlist root, once-per-transaction guard, use
+/// limit, event log, observations or history, and no gate besides the
+/// authority gate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainCounter {
+    pub count: u64,
+    pub authority: Pubkey,
+    /// Whether the authority gate is on. It is the only gate that can be.
+    pub authority_gated: bool,
+    pub step: u64,
+    pub min_observed_lamports: u64,
+    /// Offset and length of each topic's bytes.
+    topics: Vec<(usize, usize)>,
+    gate_last_slot: usize,
+    last_active_slot: usize,
+    last_updated: usize,
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(drop)
+    }
+
+    /// Skips a field that must be `false`, `None`, zero or an empty `Vec`,
+    /// all of which encode as zero bytes.
+    fn zero(&mut self, len: usize) -> Option<()> {
+        self.take(len)?.iter().all(|&byte| byte == 0).then_some(())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<usize> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        usize::try_from(len).ok()
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    /// Skips a `bool` or a fieldless enum with two variants.
+    fn skip_flag(&mut self) -> Option<()> {
+        (self.u8()? <= 1).then_some(())
+    }
+
+    fn skip_vec(&mut self, element_len: usize) -> Option<()> {
+        let len = self.u32()?;
+        self.skip(len.checked_mul(element_len)?)
+    }
+
+    /// Skips a `BoundedVec` or `BoundedString`, which Borsh rejects when
+    /// longer than `max` or, for strings, not UTF-8.
+    fn skip_bounded(&mut self, element_len: usize, max: usize, utf8: bool) -> Option<()> {
+        let len = self.u32()?;
+        if len > max {
+            return None;
+        }
+        let bytes = self.take(len * element_len)?;
+        (!utf8 || std::str::from_utf8(bytes).is_ok()).then_some(())
+    }
+
+    fn skip_option(&mut self, len: usize) -> Option<()> {
+        match self.u8()? {
+            0 => Some(()),
+            1 => self.skip(len),
+            _ => None,
+        }
+    }
+}
+
+impl PlainCounter {
+    /// Reads the fields an increment needs, or `None` when `data` is not a
+    /// plain current-layout counter (or not valid Borsh at all).
+    pub fn scan(data: &[u8]) -> Option<Self> {
+        let mut cursor = Cursor { data, pos: 0 };
+
+        if cursor.take(COUNTER_DISCRIMINATOR.len())? != COUNTER_DISCRIMINATOR
+            || cursor.u8()? != COUNTER_VERSION
+        {
+            return None;
+        }
+        let count = cursor.u64()?;
+        let authority = Pubkey::new_from_array(cursor.take(PUBKEY_LEN)?.try_into().ok()?);
+        cursor.zero(1)?; // is_frozen
+        cursor.zero(8)?; // circuit_breaker.max_delta
+        cursor.skip(24)?; // rest of the breaker
+        cursor.skip(32)?; // pending_commitment
+        cursor.zero(32)?; // value_commitment
+
+        for _ in 0..cursor.u32()? {
+            cursor.skip_bounded(1, Label32::MAX_LEN, true)?; // metric name
+            cursor.skip(16)?; // value, max_value
+        }
+        cursor.zero(1)?; // trigger
+        cursor.skip_flag()?; // trigger_fired
+        cursor.zero(1)?; // mirror_mint
+        cursor.skip(8)?; // mirror_synced_slot
+        cursor.zero(8)?; // observations: index, len and no samples
+
+        let authority_gated = match cursor.u8()? {
+            0 => false,
+            GATE_AUTHORITY => true,
+            _ => return None,
+        };
+        cursor.skip_vec(PUBKEY_LEN)?; // allowlist
+        cursor.skip(PUBKEY_LEN + 8 + PUBKEY_LEN + 8)?; // other gate parameters
+        let gate_last_slot = cursor.pos;
+        cursor.skip(8)?;
+        cursor.skip(PUBKEY_LEN + 8)?; // payer, ttl_slots
+        let last_active_slot = cursor.pos;
+        cursor.skip(8)?;
+        cursor.zero(8)?; // locked_amount
+        cursor.skip(8)?; // locked_until_slot
+        cursor.skip_bounded(1, Label32::MAX_LEN, true)?; // name
+
+        let topic_count = cursor.u32()?;
+        if topic_count > MAX_TOPICS {
+            return None;
+        }
+        let mut topics = Vec::with_capacity(topic_count);
+        for _ in 0..topic_count {
+            let offset = cursor.pos + 4;
+            cursor.skip_bounded(1, MAX_TOPIC_LEN, false)?;
+            topics.push((offset, cursor.pos - offset));
+        }
+
+        let step = cursor.u64()?;
+        cursor.zero(1)?; // import_source
+        cursor.skip(8)?; // imported_slot
+        cursor.zero(1)?; // max_value
+        cursor.skip_flag()?; // cap_policy
+        let min_observed_lamports = cursor.u64()?;
+        cursor.skip_option(PUBKEY_LEN)?; // excess_destination
+        cursor.skip_flag()?; // close_at_zero
+        cursor.zero(1)?; // is_paused
+        cursor.zero(8)?; // min_compute_units
+        cursor.skip_option(32)?; // external_id
+        cursor.skip(8)?; // snapshot_count
+        cursor.zero(8)?; // history: index, len and no entries
+        cursor.skip_bounded(PUBKEY_LEN, MAX_DELEGATES, false)?; // delegates
+        cursor.skip(4)?; // access_log index, len
+        cursor.skip_vec(ACCESS_LOG_ENTRY_LEN)?;
+        cursor.skip(8)?; // created_at
+        let last_updated = cursor.pos;
+        cursor.skip(8)?;
+        cursor.zero(8)?; // unlock_timestamp
+        cursor.zero(8)?; // increment_fee
+        cursor.skip(PUBKEY_LEN)?; // fee_recipient
+        cursor.zero(1)?; // admin_frozen
+        cursor.skip(16)?; // unfreeze_requested_at, unfreeze_at
+        cursor.zero(8)?; // expires_at
+        cursor.zero(32)?; // merkle_root
+        cursor.zero(1)?; // once_per_transaction
+        cursor.zero(8)?; // max_uses
+        cursor.zero(8)?; // uses
+        cursor.zero(1)?; // is_finalized
+        cursor.zero(1)?; // event_log
+        cursor.zero(4)?; // log_head
+        cursor.zero(1)?; // token_treasury
+
+        // Borsh rejects trailing bytes, so the full path would too
+        if cursor.pos != data.len() {
+            return None;
+        }
+
+        Some(Self {
+            count,
+            authority,
+            authority_gated,
+            step,
+            min_observed_lamports,
+            topics,
+            gate_last_slot,
+            last_active_slot,
+            last_updated,
+        })
+    }
+
+    /// The counter's topics, borrowed from the `data` it was scanned from.
+    pub fn topics<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+        self.topics
+            .iter()
+            .map(|&(offset, len)| &data[offset..offset + len])
+            .collect()
+    }
+
+    /// Writes what an applied increment changes into the `data` the counter
+    /// was scanned from.
+    pub fn write_increment(&self, data: &mut [u8], count: u64, slot: u64, unix_timestamp: i64) {
+        put(data, COUNT_OFFSET, count.to_le_bytes());
+        put(data, self.gate_last_slot, slot.to_le_bytes());
+        put(data, self.last_active_slot, slot.to_le_bytes());
+        put(data, self.last_updated, unix_timestamp.to_le_bytes());
+    }
+}
+
+fn put(data: &mut [u8], offset: usize, bytes: [u8; 8]) {
+    data[offset..offset + 8].copy_from_slice(&bytes);
+}
@@ -0,0 +1,798 @@
+//! Compute units each instruction consumes, checked against a recorded
+//! baseline. Run natively, only syscalls are metered, so each mode keeps its
+//! own: `tests/compute_units.txt` for `cargo test --features test-utils` and
+//! `tests/compute_units.sbf.txt` for `cargo test-sbf --features test-utils`.
+//!
+//! Every instruction the build dispatches has a scenario. One fails the
+//! check when it uses more than `CU_THRESHOLD_PERCENT` (default 5) percent
+//! above its baseline, or has no baseline at all. After an intended change,
+//! run with `CU_RECORD=1 --all-features` to rewrite the baseline, devnet
+//! tools included.
+
+use std::{env, fs};
+
+use counter_program::addresses::{
+    VIEWER_SEED, derive_fee_vault_address, derive_program_data_address, derive_viewer_authority,
+};
+use counter_program::bounded::Label32;
+use counter_program::client::{
+    add_delegate_ix, admin_freeze_ix, burn_counter_ix, close_counter_ix, close_expired_ix,
+    commit_increment_ix, conditional_increment_ix, create_multisig_ix, decrement_by_ix,
+    decrement_ix, decrement_signed_ix, decrement_slot_ix, get_count_ix, get_slot_ix,
+    grant_view_key_ix, import_attestation_ix, increment_by_ix, increment_committed_ix,
+    increment_ix, increment_many_ix, increment_metric_ix, increment_signed_ix, increment_slot_ix,
+    increment_with_proof_ix, initialize_and_increment_ix, initialize_composite_ix,
+    initialize_counter_by_id_ix, initialize_counter_pda_ix, initialize_counter_with_config_ix,
+    initialize_from_template_ix, initialize_global_config_ix, initialize_registry_ix,
+    initialize_signed_counter_ix, initialize_slot_counter_ix, lock_count_ix, migrate_account_ix,
+    mint_coupon_ix, pause_ix, read_count_ix, reap_ix, redeem_coupon_ix, refresh_composite_ix,
+    remove_delegate_ix, request_unfreeze_ix, reset_circuit_breaker_ix, reset_counter_ix, resize_ix,
+    resume_ix, reveal_increment_ix, revoke_view_key_ix, set_counter_ix, set_global_config_ix,
+    set_mode_ix, set_owner_quota_ix, set_step_ix, set_template_ix, set_topics_ix,
+    set_unfreeze_grace_period_ix, set_unlock_time_ix, skim_excess_ix, snapshot_ix,
+    sync_mint_supply_ix, transfer_authority_ix, transfer_count_ix, unlock_count_ix, with_event_log,
+    with_increment_fee, withdraw_fees_ix,
+};
+use counter_program::dispatch::INSTRUCTIONS;
+use counter_program::ed25519::verify_instruction;
+#[cfg(feature = "devnet-tools")]
+use counter_program::instruction::CounterInstruction;
+use counter_program::instruction::{InitConfig, MetricConfig, MetricRef, commitment_hash};
+use counter_program::merkle::MerkleTree;
+use counter_program::spl::{MINT_LEN, TOKEN_PROGRAM_ID};
+use counter_program::state::{
+    AttestationSnapshot, CountAttestation, CounterAccountV0, CouponCode, GlobalParams,
+    ImportSource, IncrementMode, MerkleProof, MultisigSigners, Topic, Topics, WeightedSource,
+};
+use counter_program::test_utils::{
+    TestContext, create_counter, create_counter_with_config, program_test, read_counter, send,
+    send_metered,
+};
+use solana_program_test::{ProgramTestBanksClientExt, processor};
+use solana_sdk::{
+    account::Account,
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+const NATIVE_BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/compute_units.txt");
+const SBF_BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/compute_units.sbf.txt");
+const DEFAULT_THRESHOLD_PERCENT: u64 = 5;
+
+/// Whether `ProgramTest` loads the SBF build, which it does whenever
+/// `cargo test-sbf` points it at the build output. The `test-sbf` feature
+/// cannot tell, as `--all-features` turns it on for native runs too.
+fn runs_as_sbf() -> bool {
+    env::var_os("SBF_OUT_DIR").is_some() || env::var_os("BPF_OUT_DIR").is_some()
+}
+
+fn baseline_path() -> &'static str {
+    if runs_as_sbf() {
+        SBF_BASELINE_PATH
+    } else {
+        NATIVE_BASELINE_PATH
+    }
+}
+
+/// Instruction name and the compute units one run of it took.
+type Measurement = (&'static str, u64);
+
+/// Stands in for a program granted a view key: reads the counter with its
+/// viewer PDA signing. Accounts: counter, view key, viewer PDA, counter
+/// program.
+fn view_reader(program_id: &Pubkey, accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let get_count = get_count_ix(accounts[3].key, accounts[0].key, program_id);
+    let (_, bump) = derive_viewer_authority(program_id);
+    invoke_signed(&get_count, &accounts[..3], &[&[VIEWER_SEED, &[bump]]])
+}
+
+/// Sends `instructions` and records the units they took under `name`.
+async fn record(
+    ctx: &mut TestContext,
+    units: &mut Vec<Measurement>,
+    name: &'static str,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) {
+    let used = send_metered(ctx, instructions, signers)
+        .await
+        .unwrap_or_else(|err| panic!("{name} failed: {err}"));
+    units.push((name, used));
+}
+
+async fn warp_slots(ctx: &mut TestContext, slots: u64) {
+    let context = &mut ctx.context;
+    let slot = context.banks_client.get_root_slot().await.unwrap();
+    context.warp_to_slot(slot + slots).unwrap();
+    context.last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+}
+
+async fn funded_keypair(ctx: &mut TestContext) -> Keypair {
+    let keypair = Keypair::new();
+    let fund = system_instruction::transfer(&ctx.payer(), &keypair.pubkey(), 1_000_000_000);
+    send(ctx, &[fund], &[]).await.unwrap();
+    keypair
+}
+
+async fn measure(ctx: &mut TestContext, reader: &Pubkey) -> Vec<Measurement> {
+    let mut units = Vec::new();
+    measure_counter(ctx, &mut units, reader).await;
+    measure_increment_paths(ctx, &mut units).await;
+    measure_creations(ctx, &mut units).await;
+    measure_configured(ctx, &mut units).await;
+    measure_admin(ctx, &mut units).await;
+    units
+}
+
+/// Everything a plain counter's authority can do to it, ending in its close.
+async fn measure_counter(ctx: &mut TestContext, units: &mut Vec<Measurement>, reader: &Pubkey) {
+    let program_id = ctx.program_id;
+    let payer = ctx.payer();
+
+    let counter = Keypair::new();
+    let initialize = initialize_counter_with_config_ix(
+        &program_id,
+        &counter.pubkey(),
+        &payer,
+        0,
+        InitConfig::default(),
+    );
+    record(ctx, units, "InitializeCounter", &[initialize], &[&counter]).await;
+    let counter = counter.pubkey();
+
+    let salt = [7; 32];
+    let delegate = Pubkey::new_unique();
+    let topics = Topics::new(vec![Topic::new(b"metered".to_vec()).unwrap()]).unwrap();
+    let (_, snapshot) = snapshot_ix(&program_id, &counter, &payer, 0);
+    let simple = [
+        (
+            "IncrementCounter",
+            increment_ix(&program_id, &counter, &payer),
+        ),
+        (
+            "IncrementBy",
+            increment_by_ix(&program_id, &counter, &payer, 5),
+        ),
+        (
+            "DecrementCounter",
+            decrement_ix(&program_id, &counter, &payer),
+        ),
+        ("SetStep", set_step_ix(&program_id, &counter, &payer, 2)),
+        (
+            "ResetCounter",
+            reset_counter_ix(&program_id, &counter, &payer),
+        ),
+        (
+            "SetCounter",
+            set_counter_ix(&program_id, &counter, &payer, 10),
+        ),
+        (
+            "DecrementBy",
+            decrement_by_ix(&program_id, &counter, &payer, 2),
+        ),
+        (
+            "ConditionalIncrement",
+            conditional_increment_ix(&program_id, &counter, &payer, 8),
+        ),
+        (
+            "IncrementMany",
+            increment_many_ix(&program_id, &payer, &[counter]),
+        ),
+        ("ReadCount", read_count_ix(&program_id, &counter)),
+        (
+            "SetMode",
+            set_mode_ix(&program_id, &counter, &payer, IncrementMode::Permissioned),
+        ),
+        ("Pause", pause_ix(&program_id, &counter, &payer)),
+        ("Resume", resume_ix(&program_id, &counter, &payer)),
+        (
+            "AddDelegate",
+            add_delegate_ix(&program_id, &counter, &payer, &delegate),
+        ),
+        (
+            "RemoveDelegate",
+            remove_delegate_ix(&program_id, &counter, &payer, &delegate),
+        ),
+        (
+            "SetUnlockTime",
+            set_unlock_time_ix(&program_id, &counter, &payer, None),
+        ),
+        (
+            "SetTopics",
+            set_topics_ix(&program_id, &counter, &payer, topics),
+        ),
+        ("Snapshot", snapshot),
+        (
+            "CommitIncrement",
+            commit_increment_ix(&program_id, &counter, &payer, commitment_hash(5, &salt)),
+        ),
+        (
+            "RevealIncrement",
+            reveal_increment_ix(&program_id, &counter, 5, salt),
+        ),
+        (
+            "ResetCircuitBreaker",
+            reset_circuit_breaker_ix(&program_id, &counter, &payer),
+        ),
+        (
+            "LockCount",
+            lock_count_ix(&program_id, &counter, &payer, 1, 1),
+        ),
+    ];
+    for (name, instruction) in simple {
+        record(ctx, units, name, &[instruction], &[]).await;
+    }
+
+    warp_slots(ctx, 2).await;
+    let unlock = unlock_count_ix(&program_id, &counter);
+    record(ctx, units, "UnlockCount", &[unlock], &[]).await;
+
+    // only a program can sign as its viewer PDA, so the read goes through
+    // `view_reader`'s CPI
+    let grant = grant_view_key_ix(&program_id, &counter, &payer, reader);
+    record(ctx, units, "GrantViewKey", &[grant], &[]).await;
+    let get_count = get_count_ix(&program_id, &counter, reader);
+    let mut read = Instruction::new_with_bytes(*reader, &[], get_count.accounts);
+    read.accounts[2].is_signer = false;
+    read.accounts
+        .push(AccountMeta::new_readonly(program_id, false));
+    record(ctx, units, "GetCount", &[read], &[]).await;
+    let revoke = revoke_view_key_ix(&program_id, &counter, &payer, reader);
+    record(ctx, units, "RevokeViewKey", &[revoke], &[]).await;
+
+    let code = CouponCode::new(b"METERED".to_vec()).unwrap();
+    let (_, mint) = mint_coupon_ix(&program_id, &counter, &payer, &code, 1);
+    record(ctx, units, "MintCoupon", &[mint], &[]).await;
+    let redeem = redeem_coupon_ix(&program_id, &counter, &code, &payer);
+    record(ctx, units, "RedeemCoupon", &[redeem], &[]).await;
+
+    let donation = system_instruction::transfer(&payer, &counter, 1_000_000);
+    send(ctx, &[donation], &[]).await.unwrap();
+    let skim = skim_excess_ix(&program_id, &counter, &payer, &Pubkey::new_unique());
+    record(ctx, units, "SkimExcess", &[skim], &[]).await;
+
+    #[cfg(feature = "devnet-tools")]
+    {
+        let devnet_tools = [
+            ("ForceSet", CounterInstruction::ForceSet { value: 20 }),
+            (
+                "FastForwardCooldown",
+                CounterInstruction::FastForwardCooldown,
+            ),
+            ("ResetStats", CounterInstruction::ResetStats),
+        ];
+        for (name, instruction) in devnet_tools {
+            let instruction = Instruction::new_with_bytes(
+                program_id,
+                &instruction.pack(),
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(payer, true),
+                ],
+            );
+            record(ctx, units, name, &[instruction], &[]).await;
+        }
+    }
+
+    let other = create_counter(ctx, 0).await.unwrap();
+    let transfer = transfer_count_ix(&program_id, &counter, &other, &payer, 1);
+    record(ctx, units, "TransferCount", &[transfer], &[]).await;
+    let transfer = transfer_authority_ix(&program_id, &other, &payer, &Pubkey::new_unique());
+    record(ctx, units, "TransferAuthority", &[transfer], &[]).await;
+
+    let close = close_counter_ix(&program_id, &counter, &payer, &Pubkey::new_unique());
+    record(ctx, units, "CloseCounter", &[close], &[]).await;
+}
+
+/// The increment paths the handler may take, cheapest first.
+async fn measure_increment_paths(ctx: &mut TestContext, units: &mut Vec<Measurement>) {
+    let program_id = ctx.program_id;
+    let payer = ctx.payer();
+
+    // an unlock time in the past changes nothing but rules out the in-place
+    // path, which the plain counter took
+    let config = InitConfig {
+        unlock_timestamp: Some(1),
+        ..InitConfig::default()
+    };
+    let full = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let increment = increment_ix(&program_id, &full, &payer);
+    record(
+        ctx,
+        units,
+        "IncrementCounter (full path)",
+        &[increment],
+        &[],
+    )
+    .await;
+
+    // the optional machinery each increment may pay for
+    let config = InitConfig {
+        history_len: 8,
+        ..InitConfig::default()
+    };
+    let history = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let increment = increment_ix(&program_id, &history, &payer);
+    record(ctx, units, "IncrementCounter (history)", &[increment], &[]).await;
+
+    let config = InitConfig {
+        event_log: true,
+        ..InitConfig::default()
+    };
+    let logged = create_counter_with_config(ctx, 0, config).await.unwrap();
+    for name in [
+        "IncrementCounter (event log, new page)",
+        "IncrementCounter (event log)",
+    ] {
+        let increment = with_event_log(
+            increment_ix(&program_id, &logged, &payer),
+            &program_id,
+            &logged,
+            0,
+        );
+        record(ctx, units, name, &[increment], &[]).await;
+    }
+}
+
+/// Every other way to create an account, and the first use of each kind.
+async fn measure_creations(ctx: &mut TestContext, units: &mut Vec<Measurement>) {
+    let program_id = ctx.program_id;
+    let payer = ctx.payer();
+
+    let name = Label32::new("metered").unwrap();
+    let (_, initialize) =
+        initialize_counter_pda_ix(&program_id, &payer, &name, 0, InitConfig::default());
+    record(ctx, units, "InitializeCounterPda", &[initialize], &[]).await;
+    let (_, initialize) =
+        initialize_counter_by_id_ix(&program_id, &payer, &[7; 32], 0, InitConfig::default());
+    record(ctx, units, "InitializeCounterById", &[initialize], &[]).await;
+
+    let counter = Keypair::new();
+    let initialize = initialize_and_increment_ix(&program_id, &counter.pubkey(), &payer, 0, 1);
+    record(
+        ctx,
+        units,
+        "InitializeAndIncrement",
+        &[initialize],
+        &[&counter],
+    )
+    .await;
+
+    let (template, set_template) =
+        set_template_ix(&program_id, &payer, &name, InitConfig::default());
+    record(ctx, units, "SetTemplate", &[set_template], &[]).await;
+    let counter = Keypair::new();
+    let initialize =
+        initialize_from_template_ix(&program_id, &counter.pubkey(), &payer, &template, 0);
+    record(
+        ctx,
+        units,
+        "InitializeFromTemplate",
+        &[initialize],
+        &[&counter],
+    )
+    .await;
+
+    let source = counter.pubkey();
+    let composite = Keypair::new();
+    let sources = vec![WeightedSource {
+        counter: source,
+        weight: 1,
+    }];
+    let initialize = initialize_composite_ix(&program_id, &composite.pubkey(), &payer, sources);
+    record(
+        ctx,
+        units,
+        "InitializeComposite",
+        &[initialize],
+        &[&composite],
+    )
+    .await;
+    let refresh = refresh_composite_ix(&program_id, &composite.pubkey(), &[source]);
+    record(ctx, units, "RefreshComposite", &[refresh], &[]).await;
+
+    let multisig = Keypair::new();
+    let signers = MultisigSigners::new(vec![payer]).unwrap();
+    let create = create_multisig_ix(&program_id, &multisig.pubkey(), &payer, 1, signers);
+    record(ctx, units, "CreateMultisig", &[create], &[&multisig]).await;
+
+    let signed = Keypair::new();
+    let initialize = initialize_signed_counter_ix(&program_id, &signed.pubkey(), &payer, 0);
+    record(
+        ctx,
+        units,
+        "InitializeSignedCounter",
+        &[initialize],
+        &[&signed],
+    )
+    .await;
+    let signed = signed.pubkey();
+    let increment = increment_signed_ix(&program_id, &signed, &payer, 1);
+    record(ctx, units, "IncrementSigned", &[increment], &[]).await;
+    let decrement = decrement_signed_ix(&program_id, &signed, &payer, 2);
+    record(ctx, units, "DecrementSigned", &[decrement], &[]).await;
+
+    let slots = Keypair::new();
+    let initialize = initialize_slot_counter_ix(&program_id, &slots.pubkey(), &payer, 2);
+    record(
+        ctx,
+        units,
+        "InitializeSlotCounter",
+        &[initialize],
+        &[&slots],
+    )
+    .await;
+    let slots = slots.pubkey();
+    let increment = increment_slot_ix(&program_id, &slots, &payer, 0);
+    record(ctx, units, "IncrementSlot", &[increment], &[]).await;
+    let decrement = decrement_slot_ix(&program_id, &slots, &payer, 0);
+    record(ctx, units, "DecrementSlot", &[decrement], &[]).await;
+    let get = get_slot_ix(&program_id, &slots, 0);
+    record(ctx, units, "GetSlot", &[get], &[]).await;
+}
+
+/// Instructions that only apply to counters created with a matching config.
+async fn measure_configured(ctx: &mut TestContext, units: &mut Vec<Measurement>) {
+    let program_id = ctx.program_id;
+    let payer = ctx.payer();
+
+    // before any account is stood in: warping checks the bank's capitalization
+    let config = InitConfig {
+        ttl_slots: 1,
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let reaper = funded_keypair(ctx).await;
+    warp_slots(ctx, 2).await;
+    let reap = reap_ix(&program_id, &counter, &payer, &reaper.pubkey());
+    record(ctx, units, "Reap", &[reap], &[&reaper]).await;
+
+    let config = InitConfig {
+        metrics: vec![MetricConfig {
+            name: Label32::new("hits").unwrap(),
+            initial_value: 0,
+            max_value: 0,
+        }],
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let increment = increment_metric_ix(&program_id, &counter, &payer, MetricRef::Index(0), 1);
+    record(ctx, units, "IncrementMetric", &[increment], &[]).await;
+
+    let salt = [1; 32];
+    let config = InitConfig {
+        value_commitment: Some(commitment_hash(0, &salt)),
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let increment = increment_committed_ix(&program_id, &counter, &payer, 0, salt, 1, [2; 32]);
+    record(ctx, units, "IncrementCommitted", &[increment], &[]).await;
+
+    let mint = Pubkey::new_unique();
+    let mut mint_data = vec![0; MINT_LEN];
+    mint_data[36..44].copy_from_slice(&1_000u64.to_le_bytes());
+    mint_data[45] = 1;
+    let account = Account {
+        lamports: 1_000_000,
+        data: mint_data,
+        owner: TOKEN_PROGRAM_ID,
+        ..Account::default()
+    };
+    ctx.context.set_account(&mint, &account.into());
+    let config = InitConfig {
+        mirror_mint: Some(mint),
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let sync = sync_mint_supply_ix(&program_id, &counter, &mint);
+    record(ctx, units, "SyncMintSupply", &[sync], &[]).await;
+
+    let tree = MerkleTree::new(&[payer]);
+    let config = InitConfig {
+        merkle_root: Some(tree.root()),
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let proof = MerkleProof::new(tree.proof(0)).unwrap();
+    let increment = increment_with_proof_ix(&program_id, &counter, &payer, 0, proof);
+    record(ctx, units, "IncrementWithProof", &[increment], &[]).await;
+
+    let config = InitConfig {
+        increment_fee: 1_000_000,
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let (vault, _) = derive_fee_vault_address(&program_id, &counter);
+    let increment = with_increment_fee(increment_ix(&program_id, &counter, &payer), &vault);
+    send(ctx, &[increment], &[]).await.unwrap();
+    let withdraw = withdraw_fees_ix(&program_id, &counter, &payer, &Pubkey::new_unique());
+    record(ctx, units, "WithdrawFees", &[withdraw], &[]).await;
+
+    let attester = Keypair::new();
+    let source = Pubkey::new_unique();
+    let config = InitConfig {
+        import_source: Some(ImportSource {
+            counter: source,
+            attester: attester.pubkey(),
+            max_age_secs: 60,
+        }),
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    let clock = ctx
+        .context
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap();
+    let snapshot = AttestationSnapshot {
+        attestation: CountAttestation {
+            counter: source,
+            count: 1,
+            slot: 1,
+            nonce: 0,
+        },
+        unix_timestamp: clock.unix_timestamp,
+    };
+    let message = borsh::to_vec(&snapshot).unwrap();
+    let signature = attester.sign_message(&message);
+    let verify = verify_instruction(
+        &attester.pubkey(),
+        signature.as_ref().try_into().unwrap(),
+        &message,
+    );
+    let import = import_attestation_ix(&program_id, &counter, snapshot);
+    record(ctx, units, "ImportAttestation", &[verify, import], &[]).await;
+
+    // a legacy counter, rewritten in the layout from before `version`
+    let counter = create_counter(ctx, 0).await.unwrap();
+    let legacy = CounterAccountV0::try_from(read_counter(ctx, &counter).await).unwrap();
+    let mut account = ctx
+        .context
+        .banks_client
+        .get_account(counter)
+        .await
+        .unwrap()
+        .unwrap();
+    account.data = borsh::to_vec(&legacy).unwrap();
+    ctx.context.set_account(&counter, &account.into());
+    let migrate = migrate_account_ix(&program_id, &counter, &payer);
+    record(ctx, units, "MigrateAccount", &[migrate], &[]).await;
+
+    // a counter from before discriminators: just the count
+    let bare = Keypair::new();
+    let account = Account {
+        lamports: 1_000_000,
+        data: 0u64.to_le_bytes().to_vec(),
+        owner: program_id,
+        ..Account::default()
+    };
+    ctx.context.set_account(&bare.pubkey(), &account.into());
+    let resize = resize_ix(&program_id, &bare.pubkey(), &payer);
+    record(ctx, units, "Resize", &[resize], &[&bare]).await;
+
+    // last, as it moves the clock forward for the rest of the run
+    let clock = ctx
+        .context
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap();
+    let expires_at = clock.unix_timestamp + 600;
+    let config = InitConfig {
+        expires_at: Some(expires_at),
+        ..InitConfig::default()
+    };
+    let counter = create_counter_with_config(ctx, 0, config).await.unwrap();
+    ctx.context.set_sysvar(&Clock {
+        unix_timestamp: expires_at,
+        ..clock
+    });
+    let close = close_expired_ix(&program_id, &counter, &payer);
+    record(ctx, units, "CloseExpired", &[close], &[]).await;
+}
+
+/// The registry admin's and the upgrade authority's instructions.
+async fn measure_admin(ctx: &mut TestContext, units: &mut Vec<Measurement>) {
+    let program_id = ctx.program_id;
+    let payer = ctx.payer();
+
+    let (_, initialize) = initialize_registry_ix(&program_id, &payer);
+    record(ctx, units, "InitializeRegistry", &[initialize], &[]).await;
+    let set_grace = set_unfreeze_grace_period_ix(&program_id, &payer, 600);
+    record(ctx, units, "SetUnfreezeGracePeriod", &[set_grace], &[]).await;
+    let counter = create_counter(ctx, 0).await.unwrap();
+    let freeze = admin_freeze_ix(&program_id, &payer, &counter);
+    record(ctx, units, "AdminFreeze", &[freeze], &[]).await;
+    let request = request_unfreeze_ix(&program_id, &counter, &payer);
+    record(ctx, units, "RequestUnfreeze", &[request], &[]).await;
+    let burn = burn_counter_ix(&program_id, &payer, &counter, &Pubkey::new_unique());
+    record(ctx, units, "BurnCounter", &[burn], &[]).await;
+
+    // a native test program has no data account, so stand one in naming the
+    // payer as upgrade authority
+    let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    data.extend_from_slice(payer.as_ref());
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: bpf_loader_upgradeable::id(),
+        ..Account::default()
+    };
+    let program_data = derive_program_data_address(&program_id);
+    ctx.context.set_account(&program_data, &account.into());
+
+    let (_, initialize) = initialize_global_config_ix(&program_id, &payer);
+    record(ctx, units, "InitializeGlobalConfig", &[initialize], &[]).await;
+    let set = set_global_config_ix(&program_id, &payer, GlobalParams::default());
+    record(ctx, units, "SetGlobalConfig", &[set], &[]).await;
+    let set_quota = set_owner_quota_ix(&program_id, &payer, 0);
+    record(ctx, units, "SetOwnerQuota", &[set_quota], &[]).await;
+}
+
+/// Baseline lines are `<units> <instruction>`; blank lines and `#` comments
+/// are skipped.
+fn read_baseline() -> Vec<(String, u64)> {
+    let text = fs::read_to_string(baseline_path()).unwrap_or_default();
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (units, name) = line
+                .split_once(' ')
+                .unwrap_or_else(|| panic!("malformed baseline line {line:?}"));
+            let units = units
+                .parse()
+                .unwrap_or_else(|_| panic!("malformed baseline line {line:?}"));
+            (name.trim().to_string(), units)
+        })
+        .collect()
+}
+
+fn write_baseline(measured: &[Measurement]) {
+    let mode = if runs_as_sbf() {
+        "cargo test-sbf"
+    } else {
+        "cargo test"
+    };
+    let mut text = format!(
+        "# Compute units per instruction, measured under `{mode}`.\n\
+         # Rewrite with `CU_RECORD=1`; see tests/compute_units.rs.\n"
+    );
+    for (name, units) in measured {
+        text.push_str(&format!("{units} {name}\n"));
+    }
+    fs::write(baseline_path(), text).expect("writing the baseline");
+}
+
+/// Instructions the baseline has no entry for.
+fn unrecorded(measured: &[Measurement], baseline: &[(String, u64)]) -> Vec<&'static str> {
+    measured
+        .iter()
+        .map(|&(name, _)| name)
+        .filter(|name| {
+            !baseline
+                .iter()
+                .any(|(baseline_name, _)| baseline_name == name)
+        })
+        .collect()
+}
+
+/// Instructions above their baseline by more than `threshold_percent`.
+fn regressions(
+    measured: &[Measurement],
+    baseline: &[(String, u64)],
+    threshold_percent: u64,
+) -> Vec<String> {
+    measured
+        .iter()
+        .filter_map(|&(name, units)| {
+            let &(_, expected) = baseline
+                .iter()
+                .find(|(baseline_name, _)| baseline_name == name)?;
+            let allowed = expected.saturating_mul(100 + threshold_percent) / 100;
+            (units > allowed).then(|| format!("{name}: {units} CU, baseline {expected} CU"))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_compute_units_within_baseline() {
+    let program_id = Pubkey::new_unique();
+    let reader = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    program_test.add_program("view_reader", reader, processor!(view_reader));
+    let mut ctx = TestContext::start(program_test, program_id).await;
+    let measured = measure(&mut ctx, &reader).await;
+    for (name, units) in &measured {
+        println!("{name}: {units} CU");
+    }
+
+    // every instruction this build dispatches has a scenario, so it has a
+    // baseline too
+    let unmeasured: Vec<_> = INSTRUCTIONS
+        .iter()
+        .filter(|meta| meta.is_enabled())
+        .map(|meta| meta.name)
+        .filter(|name| !measured.iter().any(|(measured, _)| measured == name))
+        .collect();
+    assert!(
+        unmeasured.is_empty(),
+        "no compute unit scenario for {}",
+        unmeasured.join(", ")
+    );
+
+    // the in-place increment exists to be cheaper than the full handler,
+    // which only shows once the program itself is metered: natively both
+    // come down to the syscalls they make
+    if runs_as_sbf() {
+        let units = |scenario: &str| {
+            measured
+                .iter()
+                .find(|&&(name, _)| name == scenario)
+                .map(|&(_, units)| units)
+                .unwrap()
+        };
+        assert!(units("IncrementCounter") < units("IncrementCounter (full path)"));
+    }
+
+    if env::var_os("CU_RECORD").is_some() {
+        write_baseline(&measured);
+        return;
+    }
+
+    let threshold_percent = env::var("CU_THRESHOLD_PERCENT")
+        .map(|percent| {
+            percent
+                .parse()
+                .expect("CU_THRESHOLD_PERCENT is a whole number")
+        })
+        .unwrap_or(DEFAULT_THRESHOLD_PERCENT);
+    let baseline = read_baseline();
+    let missing = unrecorded(&measured, &baseline);
+    assert!(
+        missing.is_empty(),
+        "no baseline for {}, record one with CU_RECORD=1",
+        missing.join(", ")
+    );
+
+    let regressed = regressions(&measured, &baseline, threshold_percent);
+    assert!(
+        regressed.is_empty(),
+        "compute units regressed by more than {threshold_percent}%:\n{}",
+        regressed.join("\n")
+    );
+}
+
+#[test]
+fn test_regressions_respect_threshold() {
+    let baseline = vec![
+        ("IncrementCounter".to_string(), 1_000),
+        ("DecrementCounter".to_string(), 1_000),
+    ];
+    let measured = [
+        ("IncrementCounter", 1_050),
+        ("DecrementCounter", 1_051),
+        ("SetStep", 9_999),
+    ];
+
+    // at most 5% over passes, instructions without a baseline are reported
+    // separately
+    assert_eq!(
+        regressions(&measured, &baseline, 5),
+        ["DecrementCounter: 1051 CU, baseline 1000 CU"]
+    );
+    assert!(regressions(&measured, &baseline, 10).is_empty());
+    assert_eq!(unrecorded(&measured, &baseline), ["SetStep"]);
+}